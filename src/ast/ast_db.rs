@@ -664,7 +664,7 @@ pub async fn usages(ast_index: Arc<AMutex<AstDB>>, full_official_path: String, l
                     Ok(definition) => {
                         usages.push((Arc::new(definition), uline));
                     },
-                    Err(e) => println!("Failed to deserialize value for {}: {:?}", d_key, e),
+                    Err(e) => tracing::error!("Failed to deserialize value for {}: {:?}", d_key, e),
                 }
             }
         } else if parts.len() != 2  {
@@ -705,7 +705,7 @@ pub async fn definitions(ast_index: Arc<AMutex<AstDB>>, double_colon_path: &str)
             if let Ok(Some(d_value)) = db.get(d_key.as_bytes()) {
                 match serde_cbor::from_slice::<AstDefinition>(&d_value) {
                     Ok(definition) => defs.push(Arc::new(definition)),
-                    Err(e) => println!("Failed to deserialize value for {}: {:?}", d_key, e),
+                    Err(e) => tracing::error!("Failed to deserialize value for {}: {:?}", d_key, e),
                 }
             }
         }
@@ -713,6 +713,27 @@ pub async fn definitions(ast_index: Arc<AMutex<AstDB>>, double_colon_path: &str)
     defs
 }
 
+pub async fn subclasses(ast_index: Arc<AMutex<AstDB>>, klass: &str) -> Vec<Arc<AstDefinition>>
+{
+    // klass is like "cpp🔎Animal", see "Class hierarchy" records above
+    let db = ast_index.lock().await.sleddb.clone();
+    let t_prefix = format!("classes|{} ⚡ ", klass);
+    let mut defs = Vec::new();
+    let mut iter = db.scan_prefix(&t_prefix);
+    while let Some(Ok((key, _))) = iter.next() {
+        let key_string = String::from_utf8(key.to_vec()).unwrap();
+        let full_path = key_string[t_prefix.len()..].trim();
+        let d_key = format!("d|{}", full_path);
+        if let Ok(Some(d_value)) = db.get(d_key.as_bytes()) {
+            match serde_cbor::from_slice::<AstDefinition>(&d_value) {
+                Ok(definition) => defs.push(Arc::new(definition)),
+                Err(e) => tracing::error!("failed to deserialize value for {}: {:?}", d_key, e),
+            }
+        }
+    }
+    defs
+}
+
 #[allow(dead_code)]
 pub async fn type_hierarchy(ast_index: Arc<AMutex<AstDB>>, language: String, subtree_of: String) -> String
 {
@@ -829,30 +850,31 @@ pub async fn definition_paths_fuzzy(ast_index: Arc<AMutex<AstDB>>, pattern: &str
 #[allow(dead_code)]
 pub async fn dump_database(ast_index: Arc<AMutex<AstDB>>) -> usize
 {
+    // stderr, because stdout is the protocol in --lsp-stdin-stdout and --mcp-stdio modes
     let db = ast_index.lock().await.sleddb.clone();
-    println!("\nsled has {} records", db.len());
+    eprintln!("\nsled has {} records", db.len());
     let iter = db.iter();
     for item in iter {
         let (key, value) = item.unwrap();
         let key_string = String::from_utf8(key.to_vec()).unwrap();
         if key_string.starts_with("d|") {
             match serde_cbor::from_slice::<AstDefinition>(&value) {
-                Ok(definition) => println!("{} 👉 {:.50}", key_string, format!("{:?}", definition)),
-                Err(e) => println!("Failed to deserialize value at {}: {:?}", key_string, e),
+                Ok(definition) => eprintln!("{} 👉 {:.50}", key_string, format!("{:?}", definition)),
+                Err(e) => eprintln!("Failed to deserialize value at {}: {:?}", key_string, e),
             }
         } else if key_string.starts_with("classes|") {
             let value_string = String::from_utf8(value.to_vec()).unwrap();
-            println!("{} 👉 {:?}", key_string, value_string);
+            eprintln!("{} 👉 {:?}", key_string, value_string);
         } else if key_string.starts_with("counters|") {
             let counter_value: i32 = serde_cbor::from_slice(&value).unwrap();
-            println!("{}: {}", key_string, counter_value);
+            eprintln!("{}: {}", key_string, counter_value);
         } else if value.len() > 0 {
-            println!("{} ({} bytes)", key_string, value.len());
+            eprintln!("{} ({} bytes)", key_string, value.len());
         } else {
-            println!("{}", key_string);
+            eprintln!("{}", key_string);
         }
     }
-    println!("dump_database over");
+    eprintln!("dump_database over");
     db.len()
 }
 
@@ -909,6 +931,8 @@ mod tests {
         let expected_hierarchy = "Animal\n  Goat\n    CosmicGoat\nCosmicJustice\n  CosmicGoat\n";
         assert_eq!(hierarchy, expected_hierarchy, "Type hierarchy does not match expected output");
         println!("Type hierachy subtree_of=Animal:\n{}", type_hierarchy(ast_index.clone(), "cpp".to_string(), "cpp🔎Animal".to_string()).await);
+        let animal_subclasses = subclasses(ast_index.clone(), "cpp🔎Animal").await;
+        assert_eq!(animal_subclasses.iter().map(|x| x.name()).collect::<Vec<_>>(), vec!["Goat".to_string()]);
         dump_database(ast_index.clone()).await;
 
         // Goat::Goat() is a C++ constructor
@@ -0,0 +1,132 @@
+use std::path::{Path, PathBuf};
+use ropey::Rope;
+use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity, Position, Range};
+
+use crate::ast::treesitter::parsers::{get_ast_parser_by_filename, syntax_errors};
+use crate::tools::patch::ast_interaction::lint_and_get_problems;
+
+// Diagnostics for an open document: tree-sitter syntax errors (the same ones parse_anything puts
+// into AstErrorStats while indexing) and linter problems (the same ones the patch tool checks).
+
+const DIAGNOSTICS_SOURCE: &str = "refact";
+const DIAGNOSTICS_MAX: usize = 100;
+
+
+pub fn byte_column_to_utf16(rope: &Rope, row: usize, byte_column: usize) -> u32 {
+    if row >= rope.len_lines() {
+        return 0;
+    }
+    let line = rope.line(row).to_string();
+    let mut end = byte_column.min(line.len());
+    while !line.is_char_boundary(end) {
+        end -= 1;
+    }
+    line[..end].encode_utf16().count() as u32
+}
+
+fn whole_line_range(rope: &Rope, line0: usize) -> Range {
+    if line0 >= rope.len_lines() {
+        return Range::new(Position::new(line0 as u32, 0), Position::new(line0 as u32, 0));
+    }
+    let line = rope.line(line0).to_string();
+    let line = line.trim_end_matches(['\n', '\r']);
+    let indent = line.len() - line.trim_start().len();
+    Range::new(
+        Position::new(line0 as u32, line[..indent].encode_utf16().count() as u32),
+        Position::new(line0 as u32, line.encode_utf16().count() as u32),
+    )
+}
+
+pub fn syntax_error_diagnostics(path: &PathBuf, text: &Rope) -> Vec<Diagnostic> {
+    let mut parser = match get_ast_parser_by_filename(path) {
+        Ok((parser, _language_id)) => parser,
+        Err(_) => return vec![],  // no parser for this language, nothing to say
+    };
+    let code = text.to_string();
+    syntax_errors(&parser.parse_tree(&code), &code).into_iter().map(|e| {
+        let start = &e.range.start_point;
+        let end = &e.range.end_point;
+        Diagnostic {
+            range: Range::new(
+                Position::new(start.row as u32, byte_column_to_utf16(text, start.row, start.column)),
+                Position::new(end.row as u32, byte_column_to_utf16(text, end.row, end.column)),
+            ),
+            severity: Some(DiagnosticSeverity::ERROR),
+            source: Some(DIAGNOSTICS_SOURCE.to_string()),
+            message: e.message,
+            ..Default::default()
+        }
+    }).collect()
+}
+
+pub fn lint_diagnostics(path: &Path, text: &Rope) -> Vec<Diagnostic> {
+    lint_and_get_problems(path, text).into_iter().map(|p| Diagnostic {
+        range: whole_line_range(text, p.line.saturating_sub(1)),
+        severity: Some(DiagnosticSeverity::WARNING),
+        source: Some(DIAGNOSTICS_SOURCE.to_string()),
+        message: p.message,
+        ..Default::default()
+    }).collect()
+}
+
+pub fn document_diagnostics(path: &PathBuf, text: &Rope) -> Vec<Diagnostic> {
+    let mut diagnostics = syntax_error_diagnostics(path, text);
+    diagnostics.extend(lint_diagnostics(path, text));
+    diagnostics.truncate(DIAGNOSTICS_MAX);
+    diagnostics
+}
+
+// Why the patch tool rejected a patch: problems the patched text has and the current one doesn't. The lines are
+// those of the patched text, it never made it to the file.
+pub fn patch_rejection_diagnostics(path: &PathBuf, text_before: &Rope, text_after: &Rope) -> Vec<Diagnostic> {
+    let before: Vec<String> = document_diagnostics(path, text_before).into_iter().map(|d| d.message).collect();
+    document_diagnostics(path, text_after).into_iter()
+        .filter(|d| !before.contains(&d.message))
+        .map(|mut d| {
+            d.message = format!("patch rejected: {}", d.message);
+            d
+        })
+        .collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_document_diagnostics() {
+        let path = PathBuf::from("/tmp/diagnostics_test.py");
+        let good = Rope::from_str("def f(x):\n    return x\n");
+        assert!(document_diagnostics(&path, &good).is_empty());
+
+        let broken = Rope::from_str("def f(x):\n    return x\n\nprint(f(1)\n");
+        let diagnostics = document_diagnostics(&path, &broken);
+        assert!(!diagnostics.is_empty());
+        assert!(diagnostics.iter().all(|d| d.severity == Some(DiagnosticSeverity::ERROR)));
+        assert_eq!(diagnostics[0].range.start.line, 3);
+
+        let mixed = Rope::from_str("def f(x):\n    y = x\n\treturn y\n");
+        let lints = lint_diagnostics(&path, &mixed);
+        let mixed_lint = lints.iter().find(|d| d.message.starts_with("Mixed tabs")).unwrap();
+        assert_eq!(mixed_lint.range, Range::new(Position::new(2, 1), Position::new(2, 9)));
+    }
+
+    #[test]
+    fn test_patch_rejection_diagnostics() {
+        let path = PathBuf::from("/tmp/diagnostics_test.py");
+        let before = Rope::from_str("def f(x):\n    y = x\n\treturn y\n");
+        let after = Rope::from_str("def f(x):\n    y = x\n\treturn y\n\nprint(f(1)\n");
+        let diagnostics = patch_rejection_diagnostics(&path, &before, &after);
+        assert!(!diagnostics.is_empty());
+        assert!(diagnostics.iter().all(|d| d.message.starts_with("patch rejected: ") && !d.message.contains("Mixed tabs")));
+        assert_eq!(diagnostics[0].range.start.line, 4);
+        assert!(patch_rejection_diagnostics(&path, &before, &before).is_empty());
+    }
+
+    #[test]
+    fn test_no_diagnostics_for_unknown_language() {
+        let path = PathBuf::from("/tmp/notes.txt");
+        assert!(document_diagnostics(&path, &Rope::from_str("print(")).is_empty());
+    }
+}
@@ -6,6 +6,7 @@ use tokio::sync::RwLock as ARwLock;
 use tokio::task::JoinHandle;
 use tracing::info;
 use crate::files_in_workspace::Document;
+use crate::file_filter::{check_indexing_target, IndexingTarget};
 use crate::global_context::GlobalContext;
 
 use crate::ast::ast_structs::{AstDB, AstStatus, AstCounters, AstErrorStats};
@@ -76,6 +77,11 @@ async fn ast_indexer_thread(
 
             doc_remove(ast_index.clone(), &cpath).await;
 
+            if let Err(reason) = check_indexing_target(gcx.clone(), &doc.doc_path, IndexingTarget::Ast).await {
+                *stats_failure_reasons.entry(reason).or_insert(0) += 1;
+                continue;
+            }
+
             match crate::files_in_workspace::get_file_text_from_memory_or_disk(gcx.clone(), &doc.doc_path).await {
                 Ok(file_text) => {
                     doc.update_text(&file_text);
@@ -0,0 +1,407 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use ropey::Rope;
+use serde_json::json;
+use tokio::sync::{Mutex as AMutex, RwLock as ARwLock};
+use tower_lsp::lsp_types::{Location, Position, Range, SymbolInformation, SymbolKind, TypeHierarchyItem, Url};
+
+use crate::ast::ast_diagnostics::byte_column_to_utf16;
+use crate::ast::ast_db::{definition_paths_fuzzy, definitions, doc_defs, doc_usages, subclasses, usages};
+use crate::ast::ast_structs::{AstDB, AstDefinition, SymbolType};
+use crate::files_in_workspace::{get_file_text_from_memory_or_disk, lsp_position_to_char_idx};
+use crate::global_context::GlobalContext;
+
+// Navigation for LSP requests backed by the AST index, for languages where the IDE has no language server.
+// It's approximate: usages are known up to a line.
+
+const USAGES_LIMIT: usize = 1000;
+const WORKSPACE_SYMBOLS_TOP_N: usize = 100;
+
+
+pub async fn ast_index_maybe(gcx: Arc<ARwLock<GlobalContext>>) -> Option<Arc<AMutex<AstDB>>> {
+    let ast_service = gcx.read().await.ast_service.clone()?;
+    let ast_index = ast_service.lock().await.ast_index.clone();
+    Some(ast_index)
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+pub fn identifier_at_position(text: &str, position: &Position) -> Option<String> {
+    let rope = Rope::from_str(text);
+    let line_n = position.line as usize;
+    if line_n >= rope.len_lines() {
+        return None;
+    }
+    let line_start = rope.line_to_char(line_n);
+    let col = lsp_position_to_char_idx(&rope, line_n, position.character as usize) - line_start;
+    let line: Vec<char> = rope.line(line_n).chars().collect();
+    let mut start = col.min(line.len());
+    while start > 0 && is_identifier_char(line[start - 1]) {
+        start -= 1;
+    }
+    let mut end = col.min(line.len());
+    while end < line.len() && is_identifier_char(line[end]) {
+        end += 1;
+    }
+    if start == end {
+        return None;
+    }
+    Some(line[start..end].iter().collect())
+}
+
+fn symbol_kind(def: &AstDefinition) -> SymbolKind {
+    match def.symbol_type {
+        SymbolType::StructDeclaration => SymbolKind::CLASS,
+        SymbolType::TypeAlias => SymbolKind::TYPE_PARAMETER,
+        SymbolType::ClassFieldDeclaration => SymbolKind::FIELD,
+        SymbolType::ImportDeclaration => SymbolKind::MODULE,
+        SymbolType::FunctionDeclaration => SymbolKind::FUNCTION,
+        _ => SymbolKind::VARIABLE,
+    }
+}
+
+fn is_navigable(def: &AstDefinition) -> bool {
+    !matches!(def.symbol_type, SymbolType::CommentDefinition | SymbolType::ImportDeclaration | SymbolType::FunctionCall | SymbolType::VariableUsage)
+}
+
+// Tree-sitter columns are bytes, LSP wants UTF-16 code units, so ranges need the text of the file
+struct FileTexts {
+    gcx: Option<Arc<ARwLock<GlobalContext>>>,
+    texts: HashMap<String, Option<Rope>>,
+}
+
+impl FileTexts {
+    fn new(gcx: Arc<ARwLock<GlobalContext>>) -> Self {
+        FileTexts { gcx: Some(gcx), texts: HashMap::new() }
+    }
+
+    async fn get(&mut self, cpath: &str) -> Option<&Rope> {
+        if !self.texts.contains_key(cpath) {
+            let text = match &self.gcx {
+                Some(gcx) => get_file_text_from_memory_or_disk(gcx.clone(), &PathBuf::from(cpath)).await.ok(),
+                None => None,
+            };
+            self.texts.insert(cpath.to_string(), text.map(|x| Rope::from_str(&x)));
+        }
+        self.texts[cpath].as_ref()
+    }
+}
+
+fn ts_range_to_lsp(range: &tree_sitter::Range, text: Option<&Rope>) -> Range {
+    // without the text bytes are the best guess
+    let column = |row: usize, byte_column: usize| text.map(|rope| byte_column_to_utf16(rope, row, byte_column)).unwrap_or(byte_column as u32);
+    Range::new(
+        Position::new(range.start_point.row as u32, column(range.start_point.row, range.start_point.column)),
+        Position::new(range.end_point.row as u32, column(range.end_point.row, range.end_point.column)),
+    )
+}
+
+fn def_selection_range(def: &AstDefinition, text: Option<&Rope>) -> Range {
+    if def.declaration_range.end_byte > def.declaration_range.start_byte {
+        ts_range_to_lsp(&def.declaration_range, text)
+    } else {
+        ts_range_to_lsp(&def.full_range, text)
+    }
+}
+
+async fn def_location(def: &AstDefinition, file_texts: &mut FileTexts) -> Option<Location> {
+    let uri = Url::from_file_path(&def.cpath).ok()?;
+    Some(Location::new(uri, def_selection_range(def, file_texts.get(&def.cpath).await)))
+}
+
+#[allow(deprecated)]
+async fn def_symbol_information(def: &AstDefinition, file_texts: &mut FileTexts) -> Option<SymbolInformation> {
+    let container = def.official_path.iter().skip(1).take(def.official_path.len().saturating_sub(2)).cloned().collect::<Vec<_>>().join("::");
+    Some(SymbolInformation {
+        name: def.name(),
+        kind: symbol_kind(def),
+        tags: None,
+        deprecated: None,
+        location: def_location(def, file_texts).await?,
+        container_name: if container.is_empty() { None } else { Some(container) },
+    })
+}
+
+async fn def_type_hierarchy_item(def: &AstDefinition, file_texts: &mut FileTexts) -> Option<TypeHierarchyItem> {
+    let uri = Url::from_file_path(&def.cpath).ok()?;
+    let text = file_texts.get(&def.cpath).await;
+    Some(TypeHierarchyItem {
+        name: def.name(),
+        kind: symbol_kind(def),
+        tags: None,
+        detail: Some(def.path_drop0()),
+        uri,
+        range: ts_range_to_lsp(&def.full_range, text),
+        selection_range: def_selection_range(def, text),
+        data: Some(json!(def.path())),
+    })
+}
+
+async fn definitions_exact(ast_index: Arc<AMutex<AstDB>>, official_path: &str) -> Vec<Arc<AstDefinition>> {
+    definitions(ast_index, official_path).await.into_iter().filter(|x| x.path() == official_path).collect()
+}
+
+async fn definitions_at_position(
+    ast_index: Arc<AMutex<AstDB>>,
+    file_texts: &mut FileTexts,
+    cpath: &PathBuf,
+    position: &Position,
+) -> Vec<Arc<AstDefinition>> {
+    let cpath_str = cpath.to_string_lossy().to_string();
+    let text = match file_texts.get(&cpath_str).await {
+        Some(text) => text.to_string(),
+        None => return vec![],
+    };
+    let word = match identifier_at_position(&text, position) {
+        Some(word) => word,
+        None => return vec![],
+    };
+    let line = position.line as usize;
+
+    // cursor on a declaration in this very file
+    let this_doc_defs = doc_defs(ast_index.clone(), &cpath_str).await;
+    let declared_here: Vec<Arc<AstDefinition>> = this_doc_defs.into_iter()
+        .filter(|d| d.name() == word && is_navigable(d))
+        .filter(|d| d.declaration_range.start_point.row <= line && line <= d.declaration_range.end_point.row)
+        .collect();
+    if !declared_here.is_empty() {
+        return declared_here;
+    }
+
+    // resolved usage on the same line, usages are 1-based
+    let mut result = vec![];
+    for (uline, resolved_as) in doc_usages(ast_index.clone(), &cpath_str).await {
+        if uline.saturating_sub(1) == line && resolved_as.rsplit("::").next() == Some(word.as_str()) {
+            result.extend(definitions_exact(ast_index.clone(), &resolved_as).await);
+        }
+    }
+    if !result.is_empty() {
+        return result;
+    }
+
+    // guess by name
+    definitions(ast_index.clone(), &word).await.into_iter().filter(|d| is_navigable(d)).collect()
+}
+
+pub async fn goto_definition(
+    gcx: Arc<ARwLock<GlobalContext>>,
+    cpath: &PathBuf,
+    position: &Position,
+) -> Vec<Location> {
+    let Some(ast_index) = ast_index_maybe(gcx.clone()).await else { return vec![] };
+    _goto_definition(ast_index, &mut FileTexts::new(gcx.clone()), cpath, position).await
+}
+
+async fn _goto_definition(
+    ast_index: Arc<AMutex<AstDB>>,
+    file_texts: &mut FileTexts,
+    cpath: &PathBuf,
+    position: &Position,
+) -> Vec<Location> {
+    let mut locations = vec![];
+    for d in definitions_at_position(ast_index, file_texts, cpath, position).await {
+        locations.extend(def_location(&d, file_texts).await);
+    }
+    locations
+}
+
+fn word_range_in_line(line_text: &str, line_n: usize, word: &str) -> Range {
+    let found = line_text.match_indices(word).find(|(i, _)| {
+        let before_ok = line_text[..*i].chars().last().map(|c| !is_identifier_char(c)).unwrap_or(true);
+        let after_ok = line_text[i + word.len()..].chars().next().map(|c| !is_identifier_char(c)).unwrap_or(true);
+        before_ok && after_ok
+    });
+    match found {
+        Some((i, _)) => {
+            let col1 = line_text[..i].encode_utf16().count() as u32;
+            let col2 = col1 + word.encode_utf16().count() as u32;
+            Range::new(Position::new(line_n as u32, col1), Position::new(line_n as u32, col2))
+        }
+        None => Range::new(Position::new(line_n as u32, 0), Position::new(line_n as u32, 0)),
+    }
+}
+
+pub async fn references(
+    gcx: Arc<ARwLock<GlobalContext>>,
+    cpath: &PathBuf,
+    position: &Position,
+    include_declaration: bool,
+) -> Vec<Location> {
+    let Some(ast_index) = ast_index_maybe(gcx.clone()).await else { return vec![] };
+    _references(ast_index, &mut FileTexts::new(gcx.clone()), cpath, position, include_declaration).await
+}
+
+async fn _references(
+    ast_index: Arc<AMutex<AstDB>>,
+    file_texts: &mut FileTexts,
+    cpath: &PathBuf,
+    position: &Position,
+    include_declaration: bool,
+) -> Vec<Location> {
+    let targets = definitions_at_position(ast_index.clone(), file_texts, cpath, position).await;
+    let mut locations = vec![];
+    for target in targets.iter() {
+        if include_declaration {
+            locations.extend(def_location(target, file_texts).await);
+        }
+        let name = target.name();
+        for (used_at_def, uline) in usages(ast_index.clone(), target.path(), USAGES_LIMIT).await {
+            let Ok(uri) = Url::from_file_path(&used_at_def.cpath) else { continue };
+            let uline = uline.saturating_sub(1);  // usages are stored 1-based
+            let line_text = file_texts.get(&used_at_def.cpath).await
+                .and_then(|text| text.get_line(uline).map(|x| x.to_string().trim_end_matches(['\r', '\n']).to_string()))
+                .unwrap_or_default();
+            locations.push(Location::new(uri, word_range_in_line(&line_text, uline, &name)));
+        }
+    }
+    locations
+}
+
+pub async fn document_symbols(
+    gcx: Arc<ARwLock<GlobalContext>>,
+    cpath: &Path,
+) -> Vec<SymbolInformation> {
+    let Some(ast_index) = ast_index_maybe(gcx.clone()).await else { return vec![] };
+    let mut defs = doc_defs(ast_index, &cpath.to_string_lossy().to_string()).await;
+    defs.sort_by_key(|d| d.full_range.start_byte);
+    let mut file_texts = FileTexts::new(gcx.clone());
+    let mut result = vec![];
+    for d in defs.iter().filter(|d| is_navigable(d)) {
+        result.extend(def_symbol_information(d, &mut file_texts).await);
+    }
+    result
+}
+
+pub async fn workspace_symbols(
+    gcx: Arc<ARwLock<GlobalContext>>,
+    query: &str,
+) -> Vec<SymbolInformation> {
+    if query.is_empty() {
+        return vec![];
+    }
+    let Some(ast_index) = ast_index_maybe(gcx.clone()).await else { return vec![] };
+    let mut file_texts = FileTexts::new(gcx.clone());
+    let mut result = vec![];
+    for path in definition_paths_fuzzy(ast_index.clone(), query, WORKSPACE_SYMBOLS_TOP_N, WORKSPACE_SYMBOLS_TOP_N * 10).await {
+        for def in definitions(ast_index.clone(), &path).await {
+            if is_navigable(&def) {
+                result.extend(def_symbol_information(&def, &mut file_texts).await);
+            }
+        }
+    }
+    result
+}
+
+pub async fn prepare_type_hierarchy(
+    gcx: Arc<ARwLock<GlobalContext>>,
+    cpath: &PathBuf,
+    position: &Position,
+) -> Vec<TypeHierarchyItem> {
+    let Some(ast_index) = ast_index_maybe(gcx.clone()).await else { return vec![] };
+    let mut file_texts = FileTexts::new(gcx.clone());
+    let mut result = vec![];
+    for d in definitions_at_position(ast_index, &mut file_texts, cpath, position).await.iter().filter(|d| !d.this_is_a_class.is_empty()) {
+        result.extend(def_type_hierarchy_item(d, &mut file_texts).await);
+    }
+    result
+}
+
+async fn type_hierarchy_item_def(ast_index: Arc<AMutex<AstDB>>, item: &TypeHierarchyItem) -> Option<Arc<AstDefinition>> {
+    let official_path = item.data.as_ref()?.as_str()?;
+    definitions_exact(ast_index, official_path).await.into_iter().next()
+}
+
+pub async fn supertypes(
+    gcx: Arc<ARwLock<GlobalContext>>,
+    item: &TypeHierarchyItem,
+) -> Vec<TypeHierarchyItem> {
+    let Some(ast_index) = ast_index_maybe(gcx.clone()).await else { return vec![] };
+    let Some(def) = type_hierarchy_item_def(ast_index.clone(), item).await else { return vec![] };
+    let mut file_texts = FileTexts::new(gcx.clone());
+    let mut result = vec![];
+    for klass in def.this_class_derived_from.iter() {
+        // "cpp🔎Animal" => look up "Animal", keep only the ones that are this exact class
+        let class_name = klass.rsplit('🔎').next().unwrap_or(klass);
+        for parent in definitions(ast_index.clone(), class_name).await {
+            if &parent.this_is_a_class == klass {
+                result.extend(def_type_hierarchy_item(&parent, &mut file_texts).await);
+            }
+        }
+    }
+    result
+}
+
+pub async fn subtypes(
+    gcx: Arc<ARwLock<GlobalContext>>,
+    item: &TypeHierarchyItem,
+) -> Vec<TypeHierarchyItem> {
+    let Some(ast_index) = ast_index_maybe(gcx.clone()).await else { return vec![] };
+    let Some(def) = type_hierarchy_item_def(ast_index.clone(), item).await else { return vec![] };
+    let mut file_texts = FileTexts::new(gcx.clone());
+    let mut result = vec![];
+    for d in subclasses(ast_index, &def.this_is_a_class).await {
+        result.extend(def_type_hierarchy_item(&d, &mut file_texts).await);
+    }
+    result
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_identifier_at_position() {
+        let text = "fn main() {\n    let goat = Goat::new();\n}\n";
+        assert_eq!(identifier_at_position(text, &Position::new(1, 17)), Some("Goat".to_string()));
+        assert_eq!(identifier_at_position(text, &Position::new(1, 19)), Some("Goat".to_string()));
+        assert_eq!(identifier_at_position(text, &Position::new(1, 8)), Some("goat".to_string()));
+        assert_eq!(identifier_at_position(text, &Position::new(1, 2)), None);
+    }
+
+    #[test]
+    fn test_ts_range_to_lsp() {
+        // "é" is 2 bytes and 1 UTF-16 unit, "🐐" is 4 bytes and 2 units
+        let rope = Rope::from_str("fn a() {}\nlet é🐐 = Goat;\n");
+        let range = tree_sitter::Range {
+            start_byte: 0, end_byte: 0,
+            start_point: tree_sitter::Point { row: 1, column: 10 },
+            end_point: tree_sitter::Point { row: 1, column: 13 },
+        };
+        assert_eq!(ts_range_to_lsp(&range, Some(&rope)), Range::new(Position::new(1, 7), Position::new(1, 10)));
+        assert_eq!(ts_range_to_lsp(&range, None), Range::new(Position::new(1, 10), Position::new(1, 13)));
+    }
+
+    #[test]
+    fn test_word_range_in_line() {
+        let range = word_range_in_line("    goat.age = goat.age_max;", 3, "age");
+        assert_eq!(range, Range::new(Position::new(3, 9), Position::new(3, 12)));
+    }
+
+    #[tokio::test]
+    async fn test_goto_definition_and_references_lines() {
+        use crate::ast::ast_db::{ast_index_init, connect_usages, connect_usages_look_if_full_reset_needed, doc_add};
+        use crate::ast::ast_structs::AstErrorStats;
+        let tmp = tempfile::tempdir().unwrap();
+        let cpath = tmp.path().join("goats.py");
+        let cpath_str = cpath.to_string_lossy().to_string();
+        // Frog::Goat has the same name, guessing by name would find it too
+        let text = "class Goat:\n    def jump(self):\n        pass\n\n\nclass Frog:\n    def Goat(self):\n        pass\n\n\ndef main():\n    g = Goat()\n\n\ndef other():\n    h = Goat()\n";
+        let ast_index = ast_index_init("".to_string(), 10, false).await;
+        doc_add(ast_index.clone(), &cpath_str, &text.to_string(), &mut AstErrorStats::default()).await.unwrap();
+        let mut ucx = connect_usages_look_if_full_reset_needed(ast_index.clone()).await;
+        while connect_usages(ast_index.clone(), &mut ucx).await {}
+        let mut file_texts = FileTexts { gcx: None, texts: HashMap::from([(cpath_str.clone(), Some(Rope::from_str(text)))]) };
+
+        // the cursor on the `Goat()` in other()
+        let lines = |locations: Vec<Location>| locations.iter().map(|l| (l.range.start.line, l.range.start.character)).collect::<Vec<_>>();
+        let definitions = _goto_definition(ast_index.clone(), &mut file_texts, &cpath, &Position::new(15, 9)).await;
+        assert_eq!(lines(definitions), vec![(0, 0)]);
+        let mut references = lines(_references(ast_index.clone(), &mut file_texts, &cpath, &Position::new(15, 9), false).await);
+        references.sort();
+        assert_eq!(references, vec![(11, 8), (15, 8)]);
+    }
+}
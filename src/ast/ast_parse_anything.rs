@@ -3,8 +3,9 @@ use std::collections::HashMap;
 use indexmap::IndexMap;
 use uuid::Uuid;
 use crate::ast::ast_structs::{AstDefinition, AstUsage, AstErrorStats};
-use crate::ast::treesitter::parsers::get_ast_parser_by_filename;
+use crate::ast::treesitter::parsers::{get_ast_parser_by_filename, syntax_errors};
 use crate::ast::treesitter::structs::SymbolType;
+use crate::ast::treesitter::language_id::LanguageId;
 use crate::ast::treesitter::ast_instance_structs::{VariableUsage, VariableDefinition, AstSymbolInstance, FunctionDeclaration, StructDeclaration, FunctionCall, AstSymbolInstanceArc};
 use std::path::Path;
 use sha2::{Sha256, Digest};
@@ -70,6 +71,7 @@ fn _path_of_node(
         return path;
     }
     let mut current_guid = start_node_guid.unwrap();
+    let mut top_namespace = String::new();
     while current_guid != Uuid::nil() {
         if let Some(node_arc) = map.get(&current_guid) {
             let node = node_arc.read();
@@ -79,11 +81,16 @@ fn _path_of_node(
                 node.guid().to_string()
             };
             path.push(name_or_guid);
+            top_namespace = if node.is_declaration() && *node.language() == LanguageId::Go { node.namespace().to_string() } else { String::new() };
             current_guid = node.parent_guid().unwrap_or(Uuid::nil());
         } else {
             break;
         }
     }
+    // file::namespace::class::method, go declarations carry their package (and the receiver type declared in a sibling file) in namespace
+    for ns in top_namespace.rsplit("::").filter(|x| !x.is_empty()) {
+        path.push(ns.to_string());
+    }
     path.into_iter().rev().collect()
 }
 
@@ -340,10 +347,14 @@ pub fn parse_anything(
     let language = language_id.to_string();
     let file_global_path = vec!["file".to_string()];
 
-    let symbols = parser.parse(text, &path);
+    let tree = parser.parse_tree(text);
+    let symbols = parser.symbols_from_tree(&tree, text, &path);
     if symbols.len() > TOO_MANY_SYMBOLS_IN_FILE {
         return Err(format!("more than {} symbols, generated?", TOO_MANY_SYMBOLS_IN_FILE));
     }
+    for syntax_error in syntax_errors(&tree, text) {
+        errors.add_error("".to_string(), syntax_error.range.start_point.row + 1, &syntax_error.message);
+    }
     let symbols2 = symbols.clone();
 
     let mut pcx = ParseContext {
@@ -630,5 +641,16 @@ mod tests {
             "src/ast/alt_testsuite/cpp_goat_main.correct"
         );
     }
-}
 
+    #[test]
+    fn test_ast_parse_go_package_paths() {
+        let code = "package shapes\n\nfunc (p *Point) Move(dx int) {\n\tp.X += dx\n}\n\ntype Circle struct {\n\tR int\n}\n\nfunc (c Circle) Area() int {\n\treturn c.R * c.R\n}\n";
+        let mut errstats = AstErrorStats::default();
+        let (definitions, _language) = parse_anything("/tmp/shapes/point_move.go", code, &mut errstats).unwrap();
+        let paths: Vec<String> = definitions.values().map(|x| x.official_path.join("::")).collect();
+        // Point is declared in another file of the package, the path is the same as if it was here
+        assert!(paths.contains(&"shapes::Point::Move".to_string()), "{:?}", paths);
+        assert!(paths.contains(&"shapes::Circle::Area".to_string()), "{:?}", paths);
+        assert!(paths.contains(&"shapes::Circle".to_string()), "{:?}", paths);
+    }
+}
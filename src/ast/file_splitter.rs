@@ -76,6 +76,11 @@ pub fn lowlevel_file_markup(
     Ok(x)
 }
 
+fn symbol_path_for_vecdb(markup_symbol_path: &str) -> String {
+    // top level symbols have no parent in the file, lowlevel_file_markup calls it UNK
+    markup_symbol_path.strip_prefix("UNK::").unwrap_or(markup_symbol_path).to_string()
+}
+
 impl AstBasedFileSplitter {
     pub fn new(window_size: usize) -> Self {
         Self {
@@ -178,7 +183,7 @@ impl AstBasedFileSplitter {
                     if !children.is_empty() {
                         let skeleton_line = formatter.make_skeleton(&symbol, &doc_text, &guid_to_children, &guid_to_info);
                         let chunks_ = get_chunks(&skeleton_line, &symbol.file_path,
-                                                 &symbol.symbol_path,
+                                                 &symbol_path_for_vecdb(&symbol.symbol_path),
                                                  (symbol.full_range.start_point.row, symbol.full_range.end_point.row),
                                                  tokenizer.clone(), tokens_limit, LINES_OVERLAP, true);
                         chunks.extend(chunks_);
@@ -189,7 +194,7 @@ impl AstBasedFileSplitter {
             let (declaration, top_bottom_rows) = formatter.get_declaration_with_comments(&symbol, &doc_text, &guid_to_children, &guid_to_info);
             if !declaration.is_empty() {
                 let chunks_ = get_chunks(&declaration, &symbol.file_path,
-                                         &symbol.symbol_path, top_bottom_rows, tokenizer.clone(), tokens_limit, LINES_OVERLAP, true);
+                                         &symbol_path_for_vecdb(&symbol.symbol_path), top_bottom_rows, tokenizer.clone(), tokens_limit, LINES_OVERLAP, true);
                 chunks.extend(chunks_);
             }
         }
@@ -199,3 +204,40 @@ impl AstBasedFileSplitter {
         Ok(chunks)
     }
 }
+
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use ropey::Rope;
+    use super::*;
+
+    const PYTHON_CODE: &str = r#"import os
+
+class Frog:
+    def __init__(self, x):
+        self.x = x
+
+    def jump(self):
+        print("jump", self.x)
+
+
+def make_frog():
+    return Frog(os.getpid())
+"#;
+
+    #[test]
+    fn test_symbol_path_for_vecdb() {
+        let path = PathBuf::from("/tmp/frog.py");
+        let mut doc = Document::new(&path);
+        doc.doc_text = Some(Rope::from_str(PYTHON_CODE));
+        let (mut parser, _) = get_ast_parser_by_filename(&path).unwrap();
+        let symbols: Vec<SymbolInformation> = parser.parse(PYTHON_CODE, &path).into_iter().map(|s| s.read().symbol_info_struct()).collect();
+        let markup = lowlevel_file_markup(&doc, &symbols).unwrap();
+        let paths: Vec<String> = markup.symbols_sorted_by_path_len.iter().map(|s| symbol_path_for_vecdb(&s.symbol_path)).collect();
+        assert!(paths.contains(&"Frog".to_string()));
+        assert!(paths.contains(&"Frog::jump".to_string()));
+        assert!(paths.contains(&"make_frog".to_string()));
+        assert!(paths.iter().all(|p| !p.starts_with("UNK")));
+    }
+}
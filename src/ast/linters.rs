@@ -4,10 +4,17 @@ use crate::files_in_workspace::Document;
 use std::collections::HashMap;
 
 
-fn check_python_indentation(code: &str) -> Vec<String> {
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LintProblem {
+    pub line: usize,  // 1-based
+    pub message: String,
+}
+
+fn check_python_indentation(code: &str) -> Vec<LintProblem> {
     let mut indent_levels: HashMap<usize, usize> = HashMap::new(); // Tracks the frequency of indent levels
     let mut uses_tabs = false;
     let mut uses_spaces = false;
+    let mut mixed_at_line = 0;
     let mut last_indent_level = 0;
     let mut line_number = 0;
     let mut problems = Vec::new();
@@ -27,40 +34,54 @@ fn check_python_indentation(code: &str) -> Vec<String> {
         if line.starts_with(' ') {
             uses_spaces = true;
         }
+        if uses_tabs && uses_spaces && mixed_at_line == 0 {
+            mixed_at_line = line_number;
+        }
 
         *indent_levels.entry(indent_level).or_insert(0) += 1;
 
         if last_indent_level != 0 && indent_level != last_indent_level && indent_level > last_indent_level {
             let diff = indent_level - last_indent_level;
             if !indent_levels.contains_key(&diff) && diff % last_indent_level != 0 {
-                problems.push(format!("Inconsistent indentation at line {}: {}", line_number, line));
+                problems.push(LintProblem {
+                    line: line_number,
+                    message: format!("Inconsistent indentation at line {}: {}", line_number, line),
+                });
             }
         }
 
         last_indent_level = indent_level;
     }
 
-    if uses_tabs && uses_spaces {
-        problems.push("Mixed tabs and spaces detected".to_string());
+    if mixed_at_line != 0 {
+        problems.push(LintProblem {
+            line: mixed_at_line,
+            message: "Mixed tabs and spaces detected".to_string(),
+        });
     }
 
     problems
 }
 
 
-pub fn lint(doc: &Document) -> Result<(), Vec<String>> {
+pub fn lint_problems(doc: &Document) -> Vec<LintProblem> {
     let maybe_language_id = get_language_id_by_filename(&doc.doc_path);
     if let Some(language_id) = maybe_language_id {
         let code = doc.doc_text.as_ref().map(|x| x.to_string()).expect("Document text is not available");
         match language_id {
-            LanguageId::Python => {
-                let mut problems = vec![];
-                problems.extend(check_python_indentation(&code));
-                if problems.is_empty() { Ok(()) } else { Err(problems) }
-            }
-            _ => Ok(()),
+            LanguageId::Python => check_python_indentation(&code),
+            _ => vec![],
         }
     } else {
+        vec![]
+    }
+}
+
+pub fn lint(doc: &Document) -> Result<(), Vec<String>> {
+    let problems = lint_problems(doc);
+    if problems.is_empty() {
         Ok(())
+    } else {
+        Err(problems.into_iter().map(|x| x.message).collect())
     }
-}
\ No newline at end of file
+}
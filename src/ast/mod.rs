@@ -5,6 +5,8 @@ use tokenizers::Tokenizer;
 pub mod ast_structs;
 pub mod ast_db;
 pub mod ast_parse_anything;
+pub mod ast_navigation;
+pub mod ast_diagnostics;
 
 pub mod treesitter;
 pub mod ast_indexer_thread;
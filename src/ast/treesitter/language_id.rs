@@ -141,6 +141,8 @@ impl From<Language> for LanguageId {
             Self::JavaScript
         } else if value == tree_sitter_rust::language() {
             Self::Rust
+        } else if value == tree_sitter_go::language() {
+            Self::Go
         } else if value == tree_sitter_typescript::language_typescript() {
             Self::TypeScript
         } else if value == tree_sitter_typescript::language_tsx() {
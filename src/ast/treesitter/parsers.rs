@@ -15,7 +15,9 @@ mod utils;
 mod java;
 mod cpp;
 mod ts;
+mod tsx;
 mod js;
+mod go;
 
 
 #[derive(Debug, PartialEq, Eq)]
@@ -23,8 +25,20 @@ pub struct ParserError {
     pub message: String,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyntaxError {
+    pub range: tree_sitter::Range,
+    pub message: String,
+}
+
 pub trait AstLanguageParser: Send {
-    fn parse(&mut self, code: &str, path: &PathBuf) -> Vec<AstSymbolInstanceArc>;
+    fn parse_tree(&mut self, code: &str) -> tree_sitter::Tree;
+    fn symbols_from_tree(&mut self, tree: &tree_sitter::Tree, code: &str, path: &PathBuf) -> Vec<AstSymbolInstanceArc>;
+
+    fn parse(&mut self, code: &str, path: &PathBuf) -> Vec<AstSymbolInstanceArc> {
+        let tree = self.parse_tree(code);
+        self.symbols_from_tree(&tree, code, path)
+    }
 }
 
 fn internal_error<E: Display>(err: E) -> ParserError {
@@ -35,6 +49,41 @@ fn internal_error<E: Display>(err: E) -> ParserError {
     }
 }
 
+// ERROR and MISSING nodes of an already parsed tree, the same tree the symbols come from
+pub fn syntax_errors(tree: &tree_sitter::Tree, code: &str) -> Vec<SyntaxError> {
+    let root = tree.root_node();
+    let mut errors = vec![];
+    if !root.has_error() {
+        return errors;
+    }
+    let mut stack = vec![root];
+    while let Some(node) = stack.pop() {
+        if node.is_error() {
+            // report the first line only, ERROR nodes can swallow the rest of the file
+            let text = code.get(node.start_byte()..node.end_byte()).unwrap_or("");
+            let first_line = text.lines().map(|x| x.trim()).find(|x| !x.is_empty()).unwrap_or("");
+            let message = if first_line.is_empty() {
+                "syntax error".to_string()
+            } else {
+                format!("syntax error, unexpected `{}`", first_line.chars().take(40).collect::<String>())
+            };
+            errors.push(SyntaxError { range: node.range(), message });
+            continue;
+        }
+        if node.is_missing() {
+            errors.push(SyntaxError { range: node.range(), message: format!("syntax error, missing `{}`", node.kind()) });
+            continue;
+        }
+        if !node.has_error() {
+            continue;
+        }
+        for i in (0..node.child_count()).rev() {
+            stack.push(node.child(i).unwrap());
+        }
+    }
+    errors
+}
+
 pub(crate) fn get_ast_parser(language_id: LanguageId) -> Result<Box<dyn AstLanguageParser + 'static>, ParserError> {
     match language_id {
         LanguageId::Rust => {
@@ -57,10 +106,18 @@ pub(crate) fn get_ast_parser(language_id: LanguageId) -> Result<Box<dyn AstLangu
             let parser = ts::TSParser::new()?;
             Ok(Box::new(parser))
         }
+        LanguageId::TypeScriptReact => {
+            let parser = ts::TSParser::new_tsx()?;
+            Ok(Box::new(parser))
+        }
         LanguageId::JavaScript => {
             let parser = js::JSParser::new()?;
             Ok(Box::new(parser))
         }
+        LanguageId::Go => {
+            let parser = go::GoParser::new()?;
+            Ok(Box::new(parser))
+        }
         other => Err(ParserError {
             message: "Unsupported language id: ".to_string() + &other.to_string()
         }),
@@ -89,6 +146,7 @@ pub fn get_language_id_by_filename(filename: &PathBuf) -> Option<LanguageId> {
         "java" => Some(LanguageId::Java),
         "js" | "jsx" => Some(LanguageId::JavaScript),
         "rs" => Some(LanguageId::Rust),
+        "go" => Some(LanguageId::Go),
         "ts" => Some(LanguageId::TypeScript),
         "tsx" => Some(LanguageId::TypeScriptReact),
         _ => None
@@ -0,0 +1,788 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::string::ToString;
+use std::sync::Arc;
+use itertools::Itertools;
+use parking_lot::RwLock;
+
+use similar::DiffableStr;
+use tree_sitter::{Node, Parser, Range, Tree};
+use tree_sitter_go::language;
+use uuid::Uuid;
+
+use crate::ast::treesitter::ast_instance_structs::{AstSymbolInstanceArc, ClassFieldDeclaration, CommentDefinition, FunctionArg, FunctionCall, FunctionDeclaration, ImportDeclaration, ImportType, StructDeclaration, TypeAlias, TypeDef, VariableDefinition, VariableUsage};
+use crate::ast::treesitter::language_id::LanguageId;
+use crate::ast::treesitter::parsers::{AstLanguageParser, internal_error, ParserError};
+use crate::ast::treesitter::parsers::utils::{get_children_guids, get_guid};
+use crate::ast::treesitter::structs::SymbolType;
+
+
+pub(crate) struct GoParser {
+    pub parser: Parser,
+    // per-file state: `fmt` in `fmt.Println()` is a package, not a variable
+    package_names: HashSet<String>,
+    // method guid -> receiver type name, methods get attached to their type after the whole file is parsed
+    method_receivers: HashMap<Uuid, String>,
+    // from `package xxx`, files of one package share it, so it goes into the official path of top level declarations
+    package_name: String,
+}
+
+static GO_KEYWORDS: [&str; 25] = [
+    "break", "case", "chan", "const", "continue", "default", "defer", "else", "fallthrough",
+    "for", "func", "go", "goto", "if", "import", "interface", "map", "package", "range",
+    "return", "select", "struct", "switch", "type", "var"
+];
+
+static GO_PRIMITIVE_TYPES: [&str; 21] = [
+    "bool", "byte", "complex64", "complex128", "error", "float32", "float64", "int", "int8",
+    "int16", "int32", "int64", "rune", "string", "uint", "uint8", "uint16", "uint32", "uint64",
+    "uintptr", "any"
+];
+
+impl GoParser {
+    pub fn new() -> Result<GoParser, ParserError> {
+        let mut parser = Parser::new();
+        parser
+            .set_language(&language())
+            .map_err(internal_error)?;
+        Ok(GoParser { parser, package_names: Default::default(), method_receivers: Default::default(), package_name: Default::default() })
+    }
+
+    pub fn parse_type(parent: &Node, code: &str) -> Option<TypeDef> {
+        let kind = parent.kind();
+        let text = code.slice(parent.byte_range()).to_string();
+        match kind {
+            "type_identifier" | "identifier" => {
+                return Some(TypeDef {
+                    name: Some(text.clone()),
+                    inference_info: None,
+                    inference_info_guid: None,
+                    is_pod: GO_PRIMITIVE_TYPES.contains(&text.as_str()),
+                    namespace: "".to_string(),
+                    guid: None,
+                    nested_types: vec![],
+                });
+            }
+            "qualified_type" => {
+                let package = parent.child_by_field_name("package")?;
+                let name = parent.child_by_field_name("name")?;
+                return Some(TypeDef {
+                    name: Some(code.slice(name.byte_range()).to_string()),
+                    inference_info: None,
+                    inference_info_guid: None,
+                    is_pod: false,
+                    namespace: code.slice(package.byte_range()).to_string(),
+                    guid: None,
+                    nested_types: vec![],
+                });
+            }
+            "pointer_type" | "parenthesized_type" | "type_elem" | "type_constraint" => {
+                let inner = parent.named_child(0)?;
+                return GoParser::parse_type(&inner, code);
+            }
+            "slice_type" | "array_type" | "implicit_length_array_type" => {
+                let element = parent.child_by_field_name("element")?;
+                return GoParser::parse_type(&element, code);
+            }
+            "map_type" | "channel_type" => {
+                let mut nested_types = vec![];
+                for field in ["key", "value"] {
+                    if let Some(child) = parent.child_by_field_name(field) {
+                        if let Some(t) = GoParser::parse_type(&child, code) {
+                            nested_types.push(t);
+                        }
+                    }
+                }
+                return Some(TypeDef {
+                    name: Some(if kind == "map_type" { "map".to_string() } else { "chan".to_string() }),
+                    inference_info: None,
+                    inference_info_guid: None,
+                    is_pod: false,
+                    namespace: "".to_string(),
+                    guid: None,
+                    nested_types,
+                });
+            }
+            "generic_type" => {
+                let type_node = parent.child_by_field_name("type")?;
+                let mut dtype = GoParser::parse_type(&type_node, code)?;
+                if let Some(type_arguments) = parent.child_by_field_name("type_arguments") {
+                    for i in 0..type_arguments.named_child_count() {
+                        if let Some(t) = GoParser::parse_type(&type_arguments.named_child(i).unwrap(), code) {
+                            dtype.nested_types.push(t);
+                        }
+                    }
+                }
+                return Some(dtype);
+            }
+            &_ => {}
+        }
+        None
+    }
+
+    fn parse_parameters(parameters_node: &Node, code: &str) -> Vec<FunctionArg> {
+        let mut function_args = vec![];
+        for idx in 0..parameters_node.named_child_count() {
+            let child = parameters_node.named_child(idx).unwrap();
+            match child.kind() {
+                "parameter_declaration" | "variadic_parameter_declaration" => {
+                    let type_ = child.child_by_field_name("type").and_then(|x| GoParser::parse_type(&x, code));
+                    // func f(a, b int): one declaration, two names
+                    let mut cursor = child.walk();
+                    let names = child.children_by_field_name("name", &mut cursor).collect::<Vec<_>>();
+                    if names.is_empty() {
+                        function_args.push(FunctionArg { name: "".to_string(), type_: type_.clone() });
+                    }
+                    for name in names {
+                        function_args.push(FunctionArg {
+                            name: code.slice(name.byte_range()).to_string(),
+                            type_: type_.clone(),
+                        });
+                    }
+                }
+                _ => {}
+            }
+        }
+        function_args
+    }
+
+    pub fn parse_function_declaration(&mut self, parent: &Node, code: &str, path: &PathBuf, parent_guid: &Uuid, is_error: bool) -> Vec<AstSymbolInstanceArc> {
+        let mut symbols: Vec<AstSymbolInstanceArc> = Default::default();
+        let mut decl = FunctionDeclaration::default();
+        decl.ast_fields.language = LanguageId::Go;
+        decl.ast_fields.full_range = parent.range();
+        decl.ast_fields.file_path = path.clone();
+        decl.ast_fields.parent_guid = Some(*parent_guid);
+        decl.ast_fields.is_error = is_error;
+        decl.ast_fields.guid = get_guid();
+
+        symbols.extend(self.find_error_usages(parent, code, path, &decl.ast_fields.guid));
+
+        if let Some(name_node) = parent.child_by_field_name("name") {
+            decl.ast_fields.name = code.slice(name_node.byte_range()).to_string();
+        }
+        let mut decl_end = parent.child_by_field_name("name").map(|x| (x.end_byte(), x.end_position()));
+        if let Some(receiver_node) = parent.child_by_field_name("receiver") {
+            // func (p *Point) Move(): the receiver is the first argument, like self in python
+            let receiver_args = GoParser::parse_parameters(&receiver_node, code);
+            if let Some(receiver_type) = receiver_args.first().and_then(|x| x.type_.clone()).and_then(|x| x.name) {
+                self.method_receivers.insert(decl.ast_fields.guid, receiver_type);
+            }
+            decl.args.extend(receiver_args);
+        }
+        if let Some(parameters_node) = parent.child_by_field_name("parameters") {
+            symbols.extend(self.find_error_usages(&parameters_node, code, path, &decl.ast_fields.guid));
+            decl.args.extend(GoParser::parse_parameters(&parameters_node, code));
+            decl_end = Some((parameters_node.end_byte(), parameters_node.end_position()));
+        }
+        if let Some(type_parameters) = parent.child_by_field_name("type_parameters") {
+            for idx in 0..type_parameters.named_child_count() {
+                let child = type_parameters.named_child(idx).unwrap();
+                if let Some(name) = child.child_by_field_name("name") {
+                    decl.template_types.push(TypeDef {
+                        name: Some(code.slice(name.byte_range()).to_string()),
+                        ..Default::default()
+                    });
+                }
+            }
+        }
+        if let Some(result_node) = parent.child_by_field_name("result") {
+            symbols.extend(self.find_error_usages(&result_node, code, path, &decl.ast_fields.guid));
+            decl.return_type = if result_node.kind() == "parameter_list" {
+                // func f() (int, error): the first one is what callers usually care about
+                GoParser::parse_parameters(&result_node, code).into_iter().next().and_then(|x| x.type_)
+            } else {
+                GoParser::parse_type(&result_node, code)
+            };
+            decl_end = Some((result_node.end_byte(), result_node.end_position()));
+        }
+        decl.ast_fields.declaration_range = match decl_end {
+            Some((end_byte, end_point)) => Range {
+                start_byte: decl.ast_fields.full_range.start_byte,
+                end_byte,
+                start_point: decl.ast_fields.full_range.start_point,
+                end_point,
+            },
+            None => decl.ast_fields.full_range,
+        };
+        if let Some(body_node) = parent.child_by_field_name("body") {
+            decl.ast_fields.definition_range = body_node.range();
+            symbols.extend(self.parse_block(&body_node, code, path, &decl.ast_fields.guid, is_error));
+        }
+        decl.ast_fields.childs_guid = get_children_guids(&decl.ast_fields.guid, &symbols);
+        symbols.push(Arc::new(RwLock::new(Box::new(decl))));
+        symbols
+    }
+
+    pub fn parse_type_declaration(&mut self, parent: &Node, code: &str, path: &PathBuf, parent_guid: &Uuid, is_error: bool) -> Vec<AstSymbolInstanceArc> {
+        let mut symbols: Vec<AstSymbolInstanceArc> = Default::default();
+        symbols.extend(self.find_error_usages(parent, code, path, parent_guid));
+        let specs = (0..parent.named_child_count())
+            .map(|i| parent.named_child(i).unwrap())
+            .filter(|x| x.kind() == "type_spec" || x.kind() == "type_alias")
+            .collect::<Vec<_>>();
+        for spec in specs.iter() {
+            // `type Point struct {...}` should include the keyword, `type ( A int; B string )` can't
+            let full_range = if specs.len() == 1 { parent.range() } else { spec.range() };
+            let Some(name_node) = spec.child_by_field_name("name") else { continue };
+            let Some(type_node) = spec.child_by_field_name("type") else { continue };
+            match type_node.kind() {
+                "struct_type" | "interface_type" if spec.kind() == "type_spec" => {
+                    symbols.extend(self.parse_struct_declaration(spec, &name_node, &type_node, full_range, code, path, parent_guid, is_error));
+                }
+                _ => {
+                    let mut type_alias = TypeAlias::default();
+                    type_alias.ast_fields.name = code.slice(name_node.byte_range()).to_string();
+                    type_alias.ast_fields.language = LanguageId::Go;
+                    type_alias.ast_fields.full_range = full_range;
+                    type_alias.ast_fields.file_path = path.clone();
+                    type_alias.ast_fields.parent_guid = Some(*parent_guid);
+                    type_alias.ast_fields.guid = get_guid();
+                    type_alias.ast_fields.is_error = is_error;
+                    if let Some(dtype) = GoParser::parse_type(&type_node, code) {
+                        type_alias.types.push(dtype);
+                    }
+                    symbols.push(Arc::new(RwLock::new(Box::new(type_alias))));
+                }
+            }
+        }
+        symbols
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn parse_struct_declaration(&mut self, spec: &Node, name_node: &Node, type_node: &Node, full_range: Range, code: &str, path: &PathBuf, parent_guid: &Uuid, is_error: bool) -> Vec<AstSymbolInstanceArc> {
+        let mut symbols: Vec<AstSymbolInstanceArc> = Default::default();
+        let mut decl = StructDeclaration::default();
+        decl.ast_fields.language = LanguageId::Go;
+        decl.ast_fields.full_range = full_range;
+        decl.ast_fields.definition_range = type_node.range();
+        decl.ast_fields.file_path = path.clone();
+        decl.ast_fields.parent_guid = Some(*parent_guid);
+        decl.ast_fields.guid = get_guid();
+        decl.ast_fields.is_error = is_error;
+        decl.ast_fields.name = code.slice(name_node.byte_range()).to_string();
+        // declaration is `type Point struct`, the skeletonizer adds the braces
+        let keyword = type_node.child(0).unwrap_or(*type_node);
+        decl.ast_fields.declaration_range = Range {
+            start_byte: decl.ast_fields.full_range.start_byte,
+            end_byte: keyword.end_byte(),
+            start_point: decl.ast_fields.full_range.start_point,
+            end_point: keyword.end_position(),
+        };
+        if let Some(type_parameters) = spec.child_by_field_name("type_parameters") {
+            for idx in 0..type_parameters.named_child_count() {
+                let child = type_parameters.named_child(idx).unwrap();
+                if let Some(name) = child.child_by_field_name("name") {
+                    decl.template_types.push(TypeDef {
+                        name: Some(code.slice(name.byte_range()).to_string()),
+                        ..Default::default()
+                    });
+                }
+            }
+        }
+
+        symbols.extend(self.find_error_usages(type_node, code, path, &decl.ast_fields.guid));
+        match type_node.kind() {
+            "struct_type" => {
+                let Some(fields_node) = type_node.named_child(0) else {
+                    symbols.push(Arc::new(RwLock::new(Box::new(decl))));
+                    return symbols;
+                };
+                symbols.extend(self.find_error_usages(&fields_node, code, path, &decl.ast_fields.guid));
+                for idx in 0..fields_node.named_child_count() {
+                    let field_node = fields_node.named_child(idx).unwrap();
+                    match field_node.kind() {
+                        "field_declaration" => {
+                            let Some(field_type_node) = field_node.child_by_field_name("type") else { continue };
+                            let field_type = GoParser::parse_type(&field_type_node, code);
+                            let mut cursor = field_node.walk();
+                            let names = field_node.children_by_field_name("name", &mut cursor).collect::<Vec<_>>();
+                            if names.is_empty() {
+                                // embedded struct: its fields and methods are promoted, that's Go's inheritance
+                                if let Some(field_type) = field_type {
+                                    decl.inherited_types.push(field_type);
+                                }
+                                continue;
+                            }
+                            for name_node in names {
+                                let mut decl_ = ClassFieldDeclaration::default();
+                                decl_.ast_fields.full_range = field_node.range();
+                                decl_.ast_fields.declaration_range = field_node.range();
+                                decl_.ast_fields.file_path = path.clone();
+                                decl_.ast_fields.parent_guid = Some(decl.ast_fields.guid);
+                                decl_.ast_fields.guid = get_guid();
+                                decl_.ast_fields.name = code.slice(name_node.byte_range()).to_string();
+                                decl_.ast_fields.language = LanguageId::Go;
+                                decl_.ast_fields.is_error = is_error;
+                                if let Some(type_) = field_type.clone() {
+                                    decl_.type_ = type_;
+                                }
+                                symbols.push(Arc::new(RwLock::new(Box::new(decl_))));
+                            }
+                        }
+                        "comment" => {
+                            symbols.push(self.make_comment(&field_node, path, &decl.ast_fields.guid, is_error));
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            "interface_type" => {
+                for idx in 0..type_node.named_child_count() {
+                    let child = type_node.named_child(idx).unwrap();
+                    match child.kind() {
+                        "method_elem" => {
+                            symbols.extend(self.parse_function_declaration(&child, code, path, &decl.ast_fields.guid, is_error));
+                        }
+                        "type_elem" => {
+                            // embedded interface
+                            for i in 0..child.named_child_count() {
+                                if let Some(t) = GoParser::parse_type(&child.named_child(i).unwrap(), code) {
+                                    decl.inherited_types.push(t);
+                                }
+                            }
+                        }
+                        "comment" => {
+                            symbols.push(self.make_comment(&child, path, &decl.ast_fields.guid, is_error));
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+        decl.ast_fields.childs_guid = get_children_guids(&decl.ast_fields.guid, &symbols);
+        symbols.push(Arc::new(RwLock::new(Box::new(decl))));
+        symbols
+    }
+
+    fn parse_type_in_value(value_node: &Node, code: &str) -> TypeDef {
+        let mut dtype = TypeDef::default();
+        let mut node = *value_node;
+        // p := &Point{}
+        if node.kind() == "unary_expression" {
+            if let Some(operand) = node.child_by_field_name("operand") {
+                node = operand;
+            }
+        }
+        if node.kind() == "composite_literal" {
+            if let Some(type_node) = node.child_by_field_name("type") {
+                if let Some(t) = GoParser::parse_type(&type_node, code) {
+                    dtype = t;
+                }
+            }
+        }
+        dtype.inference_info = Some(code.slice(value_node.byte_range()).to_string());
+        if dtype.name.is_none() {
+            // int_literal, float_literal, interpreted_string_literal, raw_string_literal, rune_literal
+            dtype.is_pod = node.kind().ends_with("literal") && node.kind() != "func_literal";
+        }
+        dtype
+    }
+
+    pub fn parse_variable_definition(&mut self, parent: &Node, code: &str, path: &PathBuf, parent_guid: &Uuid, is_error: bool) -> Vec<AstSymbolInstanceArc> {
+        let mut symbols: Vec<AstSymbolInstanceArc> = vec![];
+        symbols.extend(self.find_error_usages(parent, code, path, parent_guid));
+
+        // var_spec and const_spec have `name` fields, short_var_declaration and range_clause have `left`
+        let mut names: Vec<Node> = vec![];
+        let mut cursor = parent.walk();
+        names.extend(parent.children_by_field_name("name", &mut cursor));
+        if let Some(left) = parent.child_by_field_name("left") {
+            for i in 0..left.named_child_count() {
+                let child = left.named_child(i).unwrap();
+                if child.kind() == "identifier" {
+                    names.push(child);
+                }
+            }
+        }
+        let declared_type = parent.child_by_field_name("type").and_then(|x| GoParser::parse_type(&x, code));
+        let values: Vec<Node> = match parent.child_by_field_name("value").or(parent.child_by_field_name("right")) {
+            Some(value) if value.kind() == "expression_list" => (0..value.named_child_count()).map(|i| value.named_child(i).unwrap()).collect(),
+            Some(value) => vec![value],
+            None => vec![],
+        };
+        let is_range = parent.kind() == "range_clause";
+
+        let mut first_guid: Option<Uuid> = None;
+        for (i, name_node) in names.iter().enumerate() {
+            let name = code.slice(name_node.byte_range()).to_string();
+            if name == "_" {
+                continue;
+            }
+            let mut decl = VariableDefinition::default();
+            decl.ast_fields.language = LanguageId::Go;
+            // `a, b := ...` declares several variables in one statement, tell them apart by their names
+            decl.ast_fields.full_range = if names.len() > 1 { name_node.range() } else { parent.range() };
+            decl.ast_fields.file_path = path.clone();
+            decl.ast_fields.parent_guid = Some(*parent_guid);
+            decl.ast_fields.guid = get_guid();
+            decl.ast_fields.is_error = is_error;
+            decl.ast_fields.name = name;
+            if let Some(declared_type) = &declared_type {
+                decl.type_ = declared_type.clone();
+            } else if !is_range && values.len() == names.len() {
+                decl.type_ = GoParser::parse_type_in_value(&values[i], code);
+            }
+            if first_guid.is_none() {
+                first_guid = Some(decl.ast_fields.guid);
+            }
+            symbols.push(Arc::new(RwLock::new(Box::new(decl))));
+        }
+        let usages_parent = first_guid.unwrap_or(*parent_guid);
+        for value in values.iter() {
+            symbols.extend(self.parse_usages(value, code, path, &usages_parent, is_error));
+        }
+        symbols
+    }
+
+    fn parse_import_spec(&mut self, spec: &Node, code: &str, path: &Path, parent_guid: &Uuid) -> Option<AstSymbolInstanceArc> {
+        let path_node = spec.child_by_field_name("path")?;
+        let import_path = code.slice(path_node.byte_range()).trim_matches(|c| c == '"' || c == '`').to_string();
+        let mut def = ImportDeclaration::default();
+        def.ast_fields.language = LanguageId::Go;
+        def.ast_fields.full_range = spec.range();
+        def.ast_fields.file_path = path.to_path_buf();
+        def.ast_fields.parent_guid = Some(*parent_guid);
+        def.ast_fields.guid = get_guid();
+        def.path_components = import_path.split('/').map(|s| s.to_string()).collect();
+        if let Some(first) = def.path_components.first() {
+            // "fmt", "net/http" are standard library, everything else starts with a domain name
+            if first.contains('.') {
+                def.import_type = ImportType::Library;
+            } else {
+                def.import_type = ImportType::System;
+            }
+        }
+        if let Some(name_node) = spec.child_by_field_name("name") {
+            def.alias = Some(code.slice(name_node.byte_range()).to_string());
+        }
+        let package_name = def.alias.clone().or(def.path_components.last().cloned()).unwrap_or_default();
+        self.package_names.insert(package_name);
+        Some(Arc::new(RwLock::new(Box::new(def))))
+    }
+
+    fn parse_import_declaration(&mut self, parent: &Node, code: &str, path: &Path, parent_guid: &Uuid) -> Vec<AstSymbolInstanceArc> {
+        let mut symbols: Vec<AstSymbolInstanceArc> = vec![];
+        for i in 0..parent.named_child_count() {
+            let child = parent.named_child(i).unwrap();
+            match child.kind() {
+                "import_spec" => {
+                    symbols.extend(self.parse_import_spec(&child, code, path, parent_guid));
+                }
+                "import_spec_list" => {
+                    for j in 0..child.named_child_count() {
+                        let spec = child.named_child(j).unwrap();
+                        if spec.kind() == "import_spec" {
+                            symbols.extend(self.parse_import_spec(&spec, code, path, parent_guid));
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        symbols
+    }
+
+    fn parse_selector(&mut self, parent: &Node, code: &str, path: &PathBuf, parent_guid: &Uuid, is_error: bool) -> (String, String, Option<Uuid>, Vec<AstSymbolInstanceArc>) {
+        // returns name, namespace, caller guid and the usages found on the left side
+        let mut symbols: Vec<AstSymbolInstanceArc> = vec![];
+        let name = parent.child_by_field_name("field").map(|x| code.slice(x.byte_range()).to_string()).unwrap_or_default();
+        let Some(operand) = parent.child_by_field_name("operand") else {
+            return (name, "".to_string(), None, symbols);
+        };
+        if operand.kind() == "identifier" {
+            let operand_name = code.slice(operand.byte_range()).to_string();
+            if self.package_names.contains(&operand_name) {
+                return (name, operand_name, None, symbols);
+            }
+        }
+        let usages = self.parse_usages(&operand, code, path, parent_guid, is_error);
+        // the last one is the outermost expression on the left, see rust parser
+        let caller_guid = usages.last().map(|x| *x.read().guid());
+        symbols.extend(usages);
+        (name, "".to_string(), caller_guid, symbols)
+    }
+
+    pub fn parse_call_expression(&mut self, parent: &Node, code: &str, path: &PathBuf, parent_guid: &Uuid, is_error: bool) -> Vec<AstSymbolInstanceArc> {
+        let mut symbols: Vec<AstSymbolInstanceArc> = Default::default();
+        let mut decl = FunctionCall::default();
+        decl.ast_fields.language = LanguageId::Go;
+        decl.ast_fields.full_range = parent.range();
+        decl.ast_fields.file_path = path.clone();
+        decl.ast_fields.parent_guid = Some(*parent_guid);
+        decl.ast_fields.guid = get_guid();
+        decl.ast_fields.is_error = is_error;
+
+        symbols.extend(self.find_error_usages(parent, code, path, parent_guid));
+
+        let mut arguments_node: Option<Node> = None;
+        match parent.kind() {
+            "call_expression" => {
+                if let Some(function_node) = parent.child_by_field_name("function") {
+                    match function_node.kind() {
+                        "identifier" => {
+                            decl.ast_fields.name = code.slice(function_node.byte_range()).to_string();
+                        }
+                        "selector_expression" => {
+                            let (name, namespace, caller_guid, usages) = self.parse_selector(&function_node, code, path, parent_guid, is_error);
+                            decl.ast_fields.name = name;
+                            decl.ast_fields.namespace = namespace;
+                            decl.ast_fields.caller_guid = caller_guid;
+                            symbols.extend(usages);
+                        }
+                        _ => {
+                            // func literal called in place, generic instantiation, etc
+                            symbols.extend(self.parse_usages(&function_node, code, path, parent_guid, is_error));
+                        }
+                    }
+                }
+                arguments_node = parent.child_by_field_name("arguments");
+            }
+            "composite_literal" => {
+                // Point{X: 1} works as a constructor call
+                // slices and maps are not interesting, []int{1, 2} is not a call of int
+                let type_node = parent.child_by_field_name("type")
+                    .filter(|x| ["type_identifier", "qualified_type", "generic_type"].contains(&x.kind()));
+                if let Some(dtype) = type_node.and_then(|x| GoParser::parse_type(&x, code)) {
+                    decl.ast_fields.name = dtype.name.unwrap_or_default();
+                    decl.ast_fields.namespace = dtype.namespace;
+                }
+                arguments_node = parent.child_by_field_name("body");
+            }
+            &_ => {}
+        }
+
+        if let Some(arguments_node) = arguments_node {
+            symbols.extend(self.find_error_usages(&arguments_node, code, path, parent_guid));
+            for idx in 0..arguments_node.named_child_count() {
+                let arg_node = arguments_node.named_child(idx).unwrap();
+                symbols.extend(self.parse_usages(&arg_node, code, path, &decl.ast_fields.guid, is_error));
+            }
+        }
+        if decl.ast_fields.name.is_empty() {
+            // anonymous struct literal or a call of an expression, keep the usages only
+            return symbols;
+        }
+        decl.ast_fields.childs_guid = get_children_guids(&decl.ast_fields.guid, &symbols);
+        symbols.push(Arc::new(RwLock::new(Box::new(decl))));
+        symbols
+    }
+
+    pub fn parse_usages(&mut self, parent: &Node, code: &str, path: &PathBuf, parent_guid: &Uuid, is_error: bool) -> Vec<AstSymbolInstanceArc> {
+        let mut symbols: Vec<AstSymbolInstanceArc> = vec![];
+        let kind = parent.kind();
+        match kind {
+            "identifier" => {
+                let mut usage = VariableUsage::default();
+                usage.ast_fields.name = code.slice(parent.byte_range()).to_string();
+                usage.ast_fields.language = LanguageId::Go;
+                usage.ast_fields.full_range = parent.range();
+                usage.ast_fields.file_path = path.clone();
+                usage.ast_fields.parent_guid = Some(*parent_guid);
+                usage.ast_fields.guid = get_guid();
+                usage.ast_fields.is_error = is_error;
+                symbols.push(Arc::new(RwLock::new(Box::new(usage))));
+            }
+            "selector_expression" => {
+                let (name, namespace, caller_guid, usages) = self.parse_selector(parent, code, path, parent_guid, is_error);
+                symbols.extend(usages);
+                let mut usage = VariableUsage::default();
+                usage.ast_fields.name = name;
+                usage.ast_fields.namespace = namespace;
+                usage.ast_fields.caller_guid = caller_guid;
+                usage.ast_fields.language = LanguageId::Go;
+                usage.ast_fields.full_range = parent.range();
+                usage.ast_fields.file_path = path.clone();
+                usage.ast_fields.parent_guid = Some(*parent_guid);
+                usage.ast_fields.guid = get_guid();
+                usage.ast_fields.is_error = is_error;
+                symbols.push(Arc::new(RwLock::new(Box::new(usage))));
+            }
+            "call_expression" | "composite_literal" => {
+                symbols.extend(self.parse_call_expression(parent, code, path, parent_guid, is_error));
+            }
+            "func_literal" => {
+                if let Some(body_node) = parent.child_by_field_name("body") {
+                    symbols.extend(self.parse_block(&body_node, code, path, parent_guid, is_error));
+                }
+            }
+            "keyed_element" => {
+                // Point{X: 1}: X is a field name, not a usage of a variable
+                if let Some(value) = parent.named_child(1) {
+                    symbols.extend(self.parse_usages(&value, code, path, parent_guid, is_error));
+                }
+            }
+            "ERROR" => {
+                symbols.extend(self.parse_error_usages(parent, code, path, parent_guid));
+            }
+            _ => {
+                // unary, binary, index, slice, type assertion, expression lists, literal values, etc
+                for idx in 0..parent.named_child_count() {
+                    let child = parent.named_child(idx).unwrap();
+                    symbols.extend(self.parse_usages(&child, code, path, parent_guid, is_error));
+                }
+            }
+        }
+        symbols
+    }
+
+    fn find_error_usages(&mut self, parent: &Node, code: &str, path: &PathBuf, parent_guid: &Uuid) -> Vec<AstSymbolInstanceArc> {
+        let mut symbols: Vec<AstSymbolInstanceArc> = Default::default();
+        for i in 0..parent.child_count() {
+            let child = parent.child(i).unwrap();
+            if child.kind() == "ERROR" {
+                symbols.extend(self.parse_error_usages(&child, code, path, parent_guid));
+            }
+        }
+        symbols
+    }
+
+    fn parse_error_usages(&mut self, parent: &Node, code: &str, path: &PathBuf, parent_guid: &Uuid) -> Vec<AstSymbolInstanceArc> {
+        let mut symbols: Vec<AstSymbolInstanceArc> = Default::default();
+        match parent.kind() {
+            "identifier" | "field_identifier" => {
+                let name = code.slice(parent.byte_range()).to_string();
+                if GO_KEYWORDS.contains(&name.as_str()) {
+                    return vec![];
+                }
+                let mut usage = VariableUsage::default();
+                usage.ast_fields.name = name;
+                usage.ast_fields.language = LanguageId::Go;
+                usage.ast_fields.full_range = parent.range();
+                usage.ast_fields.file_path = path.clone();
+                usage.ast_fields.parent_guid = Some(*parent_guid);
+                usage.ast_fields.guid = get_guid();
+                usage.ast_fields.is_error = true;
+                symbols.push(Arc::new(RwLock::new(Box::new(usage))));
+            }
+            &_ => {
+                for i in 0..parent.child_count() {
+                    let child = parent.child(i).unwrap();
+                    symbols.extend(self.parse_error_usages(&child, code, path, parent_guid));
+                }
+            }
+        }
+        symbols
+    }
+
+    fn make_comment(&mut self, node: &Node, path: &Path, parent_guid: &Uuid, is_error: bool) -> AstSymbolInstanceArc {
+        let mut def = CommentDefinition::default();
+        def.ast_fields.language = LanguageId::Go;
+        def.ast_fields.full_range = node.range();
+        def.ast_fields.file_path = path.to_path_buf();
+        def.ast_fields.guid = get_guid();
+        def.ast_fields.parent_guid = Some(*parent_guid);
+        def.ast_fields.is_error = is_error;
+        Arc::new(RwLock::new(Box::new(def)))
+    }
+
+    pub fn parse_block(&mut self, parent: &Node, code: &str, path: &PathBuf, parent_guid: &Uuid, is_error: bool) -> Vec<AstSymbolInstanceArc> {
+        let mut symbols: Vec<AstSymbolInstanceArc> = vec![];
+        for i in 0..parent.named_child_count() {
+            let child = parent.named_child(i).unwrap();
+            let kind = child.kind();
+            match kind {
+                "package_clause" => {
+                    if let Some(name_node) = (0..child.named_child_count()).filter_map(|x| child.named_child(x)).find(|x| x.kind() == "package_identifier") {
+                        self.package_name = code.slice(name_node.byte_range()).to_string();
+                    }
+                }
+                "import_declaration" => {
+                    symbols.extend(self.parse_import_declaration(&child, code, path, parent_guid));
+                }
+                "function_declaration" | "method_declaration" => {
+                    symbols.extend(self.parse_function_declaration(&child, code, path, parent_guid, is_error));
+                }
+                "type_declaration" => {
+                    symbols.extend(self.parse_type_declaration(&child, code, path, parent_guid, is_error));
+                }
+                "var_declaration" | "const_declaration" | "var_spec_list" => {
+                    symbols.extend(self.parse_block(&child, code, path, parent_guid, is_error));
+                }
+                "var_spec" | "const_spec" | "short_var_declaration" | "range_clause" => {
+                    symbols.extend(self.parse_variable_definition(&child, code, path, parent_guid, is_error));
+                }
+                "comment" => {
+                    symbols.push(self.make_comment(&child, path, parent_guid, is_error));
+                }
+                "ERROR" => {
+                    symbols.extend(self.parse_error_usages(&child, code, path, parent_guid));
+                }
+                "block" | "labeled_statement" => {
+                    symbols.extend(self.parse_block(&child, code, path, parent_guid, is_error));
+                }
+                &_ if kind.ends_with("_statement") || kind.ends_with("_clause") || kind.ends_with("_case") => {
+                    // if, for, switch, select, return, go, defer, assignment: statements inside, expressions in between
+                    symbols.extend(self.parse_block(&child, code, path, parent_guid, is_error));
+                }
+                &_ => {
+                    symbols.extend(self.parse_usages(&child, code, path, parent_guid, is_error));
+                }
+            }
+        }
+        symbols
+    }
+
+    fn attach_methods_to_types(&mut self, symbols: &mut Vec<AstSymbolInstanceArc>, root_guid: &Uuid) {
+        // methods are declared outside of the type, but official path should be Point::Move like in other languages
+        let mut type_guids: HashMap<String, Uuid> = HashMap::new();
+        for symbol in symbols.iter() {
+            let symbol = symbol.read();
+            if symbol.symbol_type() == SymbolType::StructDeclaration {
+                type_guids.entry(symbol.name().to_string()).or_insert(*symbol.guid());
+            }
+        }
+        let guid_to_symbol: HashMap<Uuid, AstSymbolInstanceArc> = symbols.iter().map(|x| (*x.read().guid(), x.clone())).collect();
+        let mut receivers_from_other_files: HashMap<Uuid, String> = HashMap::new();
+        for (method_guid, receiver_type) in self.method_receivers.iter() {
+            let Some(method) = guid_to_symbol.get(method_guid) else {
+                continue;
+            };
+            let Some(type_guid) = type_guids.get(receiver_type) else {
+                // the type is declared in another file of this package, package::Point::Move is the same path that file produces
+                receivers_from_other_files.insert(*method_guid, receiver_type.clone());
+                continue;
+            };
+            method.write().fields_mut().parent_guid = Some(*type_guid);
+            if let Some(type_symbol) = guid_to_symbol.get(type_guid) {
+                type_symbol.write().fields_mut().childs_guid.push(*method_guid);
+            }
+        }
+        for symbol in symbols.iter() {
+            let mut symbol = symbol.write();
+            if symbol.parent_guid().as_ref() != Some(root_guid) || !symbol.is_declaration() || symbol.symbol_type() == SymbolType::CommentDefinition {
+                continue;
+            }
+            let receiver_type = receivers_from_other_files.get(symbol.guid()).cloned().unwrap_or_default();
+            symbol.fields_mut().namespace = [self.package_name.clone(), receiver_type].iter().filter(|x| !x.is_empty()).join("::");
+        }
+        // parents go first, parse_anything builds official paths walking up the parents it has already seen
+        let depth = |symbol: &AstSymbolInstanceArc| {
+            let mut depth = 0;
+            let mut parent_guid = *symbol.read().parent_guid();
+            while let Some(parent) = parent_guid.and_then(|x| guid_to_symbol.get(&x)) {
+                depth += 1;
+                parent_guid = *parent.read().parent_guid();
+            }
+            depth
+        };
+        symbols.sort_by_cached_key(depth);
+    }
+}
+
+impl AstLanguageParser for GoParser {
+    fn parse_tree(&mut self, code: &str) -> Tree {
+        self.parser.parse(code, None).unwrap()
+    }
+
+    fn symbols_from_tree(&mut self, tree: &Tree, code: &str, path: &PathBuf) -> Vec<AstSymbolInstanceArc> {
+        self.package_names.clear();
+        self.method_receivers.clear();
+        self.package_name.clear();
+        let parent_guid = get_guid();
+        let mut symbols = self.parse_block(&tree.root_node(), code, path, &parent_guid, false);
+        self.attach_methods_to_types(&mut symbols, &parent_guid);
+        symbols
+    }
+}
@@ -5,7 +5,7 @@ use std::sync::Arc;
 use parking_lot::RwLock;
 
 use similar::DiffableStr;
-use tree_sitter::{Node, Parser, Range};
+use tree_sitter::{Node, Parser, Range, Tree};
 use tree_sitter_javascript::language;
 use uuid::Uuid;
 
@@ -788,8 +788,11 @@ impl JSParser {
 }
 
 impl AstLanguageParser for JSParser {
-    fn parse(&mut self, code: &str, path: &PathBuf) -> Vec<AstSymbolInstanceArc> {
-        let tree = self.parser.parse(code, None).unwrap();
+    fn parse_tree(&mut self, code: &str) -> Tree {
+        self.parser.parse(code, None).unwrap()
+    }
+
+    fn symbols_from_tree(&mut self, tree: &Tree, code: &str, path: &PathBuf) -> Vec<AstSymbolInstanceArc> {
         let symbols = self.parse_(&tree.root_node(), code, path);
         symbols
     }
@@ -7,7 +7,7 @@ use std::sync::Arc;
 use itertools::Itertools;
 use parking_lot::RwLock;
 use similar::DiffableStr;
-use tree_sitter::{Node, Parser, Point, Range};
+use tree_sitter::{Node, Parser, Point, Range, Tree};
 use tree_sitter_python::language;
 use uuid::Uuid;
 
@@ -941,8 +941,11 @@ impl SkeletonFormatter for PythonSkeletonFormatter {
 }
 
 impl AstLanguageParser for PythonParser {
-    fn parse(&mut self, code: &str, path: &PathBuf) -> Vec<AstSymbolInstanceArc> {
-        let tree = self.parser.parse(code, None).unwrap();
+    fn parse_tree(&mut self, code: &str) -> Tree {
+        self.parser.parse(code, None).unwrap()
+    }
+
+    fn symbols_from_tree(&mut self, tree: &Tree, code: &str, path: &PathBuf) -> Vec<AstSymbolInstanceArc> {
         let symbols = self.parse_(&tree.root_node(), code, path);
         symbols
     }
@@ -22,6 +22,8 @@ mod java;
 mod cpp;
 mod ts;
 mod js;
+mod go;
+mod tsx;
 
 pub(crate) fn print(symbols: &Vec<AstSymbolInstanceArc>, code: &str) {
     let guid_to_symbol_map = symbols.iter()
@@ -0,0 +1,81 @@
+package main
+
+import (
+	"fmt"
+	"strings"
+
+	zoo "github.com/example/zoo/animals"
+)
+
+const MaxAge = 100
+
+var (
+	registry = map[string]Animal{}
+	counter  int
+)
+
+// Animal is the base for everything in the zoo
+type Animal struct {
+	Name string
+	Age  int
+}
+
+func (a *Animal) Describe() string {
+	return fmt.Sprintf("%s is %d years old", a.Name, a.Age)
+}
+
+type Speaker interface {
+	Speak() string
+}
+
+type LoudSpeaker interface {
+	Speaker
+	Volume() int
+}
+
+type Goat struct {
+	Animal
+	*zoo.Keeper
+	Beard bool
+}
+
+func (g Goat) Speak() string {
+	return strings.ToUpper("meh")
+}
+
+type Stack[T any] struct {
+	items []T
+}
+
+func (s *Stack[T]) Push(item T) {
+	s.items = append(s.items, item)
+}
+
+type Age = int
+
+func NewGoat(name string, age Age) *Goat {
+	goat := &Goat{Animal: Animal{Name: name, Age: age}}
+	registry[name] = goat.Animal
+	counter++
+	return goat
+}
+
+func main() {
+	g := NewGoat("Billy", 3)
+	fmt.Println(g.Describe(), g.Speak())
+	for i, name := range []string{"a", "b"} {
+		if i > 0 {
+			fmt.Println(name)
+		}
+	}
+	handler := func(x int) int {
+		return x * 2
+	}
+	switch v := handler(counter); v {
+	case 0:
+		zoo.Feed(g.Name)
+	default:
+		fmt.Println(v)
+	}
+	defer g.Describe()
+}
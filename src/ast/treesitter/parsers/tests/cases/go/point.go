@@ -0,0 +1,28 @@
+package geometry
+
+import "math"
+
+// Point represents a point in 2D space
+type Point struct {
+	X float64
+	Y float64
+}
+
+// NewPoint creates a new Point with the given coordinates
+//
+// Example:
+//
+//	p := NewPoint(3.0, 4.0)
+func NewPoint(x, y float64) *Point {
+	return &Point{X: x, Y: y}
+}
+
+// Distance returns the distance from the origin
+func (p *Point) Distance() float64 {
+	return math.Sqrt(p.X*p.X + p.Y*p.Y)
+}
+
+// Shape is anything that has an area
+type Shape interface {
+	Area() float64
+}
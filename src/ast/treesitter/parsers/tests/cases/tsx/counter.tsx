@@ -0,0 +1,23 @@
+import React from "react";
+
+interface CounterProps {
+    start: number;
+}
+
+interface CounterState {
+    count: number;
+}
+
+export class Counter extends React.Component<CounterProps, CounterState> {
+    state: CounterState = { count: this.props.start };
+
+    increment = () => {
+        this.setState({ count: this.state.count + 1 });
+    };
+
+    render() {
+        return <button onClick={this.increment}>{this.state.count}</button>;
+    }
+}
+
+export const Label = ({ text }: { text: string }) => <span>{text}</span>;
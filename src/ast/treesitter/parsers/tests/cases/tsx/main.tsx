@@ -0,0 +1,55 @@
+import React, { useEffect, useState } from "react";
+import { Button } from "./components/Button";
+import * as Menu from "./components/Menu";
+
+interface TodoItem {
+    id: number;
+    title: string;
+    done: boolean;
+}
+
+type TodoListProps = {
+    items: TodoItem[];
+    onToggle: (id: number) => void;
+};
+
+// custom hook, a regular function as far as the AST is concerned
+function useTodos(initial: TodoItem[]) {
+    const [todos, setTodos] = useState<TodoItem[]>(initial);
+    const toggle = (id: number) => {
+        setTodos(todos.map(t => t.id === id ? { ...t, done: !t.done } : t));
+    };
+    return { todos, toggle };
+}
+
+const TodoList = ({ items, onToggle }: TodoListProps) => {
+    return (
+        <ul className="todo-list">
+            {items.map(item => (
+                <li key={item.id}>
+                    <input type="checkbox" checked={item.done} onChange={() => onToggle(item.id)} />
+                    {item.title}
+                </li>
+            ))}
+        </ul>
+    );
+};
+
+export default function App() {
+    const { todos, toggle } = useTodos([]);
+    const [title, setTitle] = useState("");
+
+    useEffect(() => {
+        document.title = `${todos.length} todos`;
+    }, [todos]);
+
+    return (
+        <div>
+            <Menu.Bar>
+                <Menu.Item label="All" />
+            </Menu.Bar>
+            <TodoList items={todos} onToggle={toggle} />
+            <Button onClick={() => setTitle("")}>Clear {title}</Button>
+        </div>
+    );
+}
@@ -0,0 +1,41 @@
+#[cfg(test)]
+mod tests {
+    use std::fs::canonicalize;
+    use std::path::PathBuf;
+
+    use crate::ast::treesitter::language_id::LanguageId;
+    use crate::ast::treesitter::parsers::AstLanguageParser;
+    use crate::ast::treesitter::parsers::go::GoParser;
+    use crate::ast::treesitter::parsers::tests::{base_declaration_formatter_test, base_parser_test, base_skeletonizer_test};
+
+    const MAIN_GO_CODE: &str = include_str!("cases/go/main.go");
+    const MAIN_GO_SYMBOLS: &str = include_str!("cases/go/main.go.json");
+
+    const POINT_GO_CODE: &str = include_str!("cases/go/point.go");
+    const POINT_GO_DECLS: &str = include_str!("cases/go/point.go.decl_json");
+    const POINT_GO_SKELETON: &str = include_str!("cases/go/point.go.skeleton");
+
+    #[test]
+    fn parser_test() {
+        let mut parser: Box<dyn AstLanguageParser> = Box::new(GoParser::new().expect("GoParser::new"));
+        let path = PathBuf::from("file:///main.go");
+        base_parser_test(&mut parser, &path, MAIN_GO_CODE, MAIN_GO_SYMBOLS);
+    }
+
+    #[test]
+    fn skeletonizer_test() {
+        let mut parser: Box<dyn AstLanguageParser> = Box::new(GoParser::new().expect("GoParser::new"));
+        let file = canonicalize(PathBuf::from(file!())).unwrap().parent().unwrap().join("cases/go/point.go");
+        assert!(file.exists());
+
+        base_skeletonizer_test(&LanguageId::Go, &mut parser, &file, POINT_GO_CODE, POINT_GO_SKELETON);
+    }
+
+    #[test]
+    fn declaration_formatter_test() {
+        let mut parser: Box<dyn AstLanguageParser> = Box::new(GoParser::new().expect("GoParser::new"));
+        let file = canonicalize(PathBuf::from(file!())).unwrap().parent().unwrap().join("cases/go/point.go");
+        assert!(file.exists());
+        base_declaration_formatter_test(&LanguageId::Go, &mut parser, &file, POINT_GO_CODE, POINT_GO_DECLS);
+    }
+}
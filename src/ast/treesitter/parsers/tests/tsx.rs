@@ -0,0 +1,66 @@
+#[cfg(test)]
+mod tests {
+    use std::fs::canonicalize;
+    use std::path::PathBuf;
+
+    use crate::ast::treesitter::language_id::LanguageId;
+    use crate::ast::treesitter::parsers::AstLanguageParser;
+    use crate::ast::treesitter::parsers::tests::{base_declaration_formatter_test, base_parser_test, base_skeletonizer_test};
+    use crate::ast::treesitter::parsers::ts::TSParser;
+    use crate::ast::treesitter::structs::SymbolType;
+
+    const MAIN_TSX_CODE: &str = include_str!("cases/tsx/main.tsx");
+    const MAIN_TSX_SYMBOLS: &str = include_str!("cases/tsx/main.tsx.json");
+
+    const COUNTER_TSX_CODE: &str = include_str!("cases/tsx/counter.tsx");
+    const COUNTER_TSX_SKELETON: &str = include_str!("cases/tsx/counter.tsx.skeleton");
+    const COUNTER_TSX_DECLS: &str = include_str!("cases/tsx/counter.tsx.decl_json");
+
+    #[test]
+    fn parser_test() {
+        let mut parser: Box<dyn AstLanguageParser> = Box::new(TSParser::new_tsx().expect("TSParser::new_tsx"));
+        let path = PathBuf::from("file:///main.tsx");
+        base_parser_test(&mut parser, &path, MAIN_TSX_CODE, MAIN_TSX_SYMBOLS);
+    }
+
+    #[test]
+    fn skeletonizer_test() {
+        let mut parser: Box<dyn AstLanguageParser> = Box::new(TSParser::new_tsx().expect("TSParser::new_tsx"));
+        let file = canonicalize(PathBuf::from(file!())).unwrap().parent().unwrap().join("cases/tsx/counter.tsx");
+        assert!(file.exists());
+
+        base_skeletonizer_test(&LanguageId::TypeScriptReact, &mut parser, &file, COUNTER_TSX_CODE, COUNTER_TSX_SKELETON);
+    }
+
+    #[test]
+    fn declaration_formatter_test() {
+        let mut parser: Box<dyn AstLanguageParser> = Box::new(TSParser::new_tsx().expect("TSParser::new_tsx"));
+        let file = canonicalize(PathBuf::from(file!())).unwrap().parent().unwrap().join("cases/tsx/counter.tsx");
+        assert!(file.exists());
+        base_declaration_formatter_test(&LanguageId::TypeScriptReact, &mut parser, &file, COUNTER_TSX_CODE, COUNTER_TSX_DECLS);
+    }
+
+    #[test]
+    fn react_rules_are_tsx_only_test() {
+        let code = "const Button = (props: Props) => props.label;\nconst [count, setCount] = useState(0);\n";
+        let declarations = |mut parser: TSParser, path: &str| {
+            let mut result: Vec<(SymbolType, String)> = parser.parse(code, &PathBuf::from(path)).iter()
+                .map(|x| (x.read().symbol_type(), x.read().name().to_string()))
+                .filter(|(t, _)| [SymbolType::VariableDefinition, SymbolType::FunctionDeclaration].contains(t))
+                .collect();
+            result.sort_by(|a, b| a.1.cmp(&b.1));
+            result
+        };
+        // plain typescript: the arrow function stays an anonymous function assigned to a variable
+        assert_eq!(declarations(TSParser::new().unwrap(), "file:///button.ts"), vec![
+            (SymbolType::FunctionDeclaration, "".to_string()),
+            (SymbolType::VariableDefinition, "Button".to_string()),
+            (SymbolType::VariableDefinition, "[count, setCount]".to_string()),
+        ]);
+        assert_eq!(declarations(TSParser::new_tsx().unwrap(), "file:///button.tsx"), vec![
+            (SymbolType::FunctionDeclaration, "Button".to_string()),
+            (SymbolType::VariableDefinition, "count".to_string()),
+            (SymbolType::VariableDefinition, "setCount".to_string()),
+        ]);
+    }
+}
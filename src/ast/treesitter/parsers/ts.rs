@@ -7,7 +7,7 @@ use itertools::Itertools;
 use parking_lot::RwLock;
 
 use similar::DiffableStr;
-use tree_sitter::{Node, Parser, Range};
+use tree_sitter::{Node, Parser, Range, Tree};
 use tree_sitter_typescript::language_typescript as language;
 use uuid::Uuid;
 
@@ -18,8 +18,11 @@ use crate::ast::treesitter::parsers::utils::{CandidateInfo, get_guid};
 
 pub(crate) struct TSParser {
     pub parser: Parser,
+    pub(super) language_id: LanguageId,
 }
 
+static LAMBDA_KINDS: [&str; 2] = ["function_expression", "arrow_function"];
+
 pub fn parse_type(parent: &Node, code: &str) -> Option<TypeDef> {
     let kind = parent.kind();
     let text = code.slice(parent.byte_range()).to_string();
@@ -130,13 +133,28 @@ pub fn parse_type(parent: &Node, code: &str) -> Option<TypeDef> {
     None
 }
 
+// identifiers declared by a destructuring pattern, or the name itself if it's not a pattern
+fn pattern_identifiers<'a>(pattern: &Node<'a>) -> Vec<Node<'a>> {
+    match pattern.kind() {
+        "identifier" | "shorthand_property_identifier_pattern" => vec![*pattern],
+        "array_pattern" | "object_pattern" | "rest_pattern" => {
+            (0..pattern.named_child_count())
+                .flat_map(|i| pattern_identifiers(&pattern.named_child(i).unwrap()))
+                .collect()
+        }
+        "pair_pattern" => pattern.child_by_field_name("value").map(|x| pattern_identifiers(&x)).unwrap_or_default(),
+        "assignment_pattern" | "object_assignment_pattern" => pattern.child_by_field_name("left").map(|x| pattern_identifiers(&x)).unwrap_or_default(),
+        _ => vec![],
+    }
+}
+
 impl TSParser {
     pub fn new() -> Result<Self, ParserError> {
         let mut parser = Parser::new();
         parser
             .set_language(&language())
             .map_err(internal_error)?;
-        Ok(Self { parser })
+        Ok(Self { parser, language_id: LanguageId::TypeScript })
     }
 
     pub fn parse_struct_declaration<'a>(
@@ -253,37 +271,87 @@ impl TSParser {
         let mut symbols: Vec<AstSymbolInstanceArc> = vec![];
         symbols.extend(self.find_error_usages(&info.node, code, &info.ast_fields.file_path, &info.parent_guid));
 
-        let mut decl = VariableDefinition::default();
-        decl.ast_fields = AstSymbolFields::from_fields(&info.ast_fields);
-        decl.ast_fields.full_range = info.node.range();
-        decl.ast_fields.declaration_range = info.node.range();
-        decl.ast_fields.definition_range = info.node.range();
-        decl.ast_fields.parent_guid = Some(info.parent_guid.clone());
-        decl.ast_fields.guid = get_guid();
-
-        if let Some(name) = info.node.child_by_field_name("name") {
-            decl.ast_fields.name = code.slice(name.byte_range()).to_string();
-        }
+        let is_tsx = self.language_id == LanguageId::TypeScriptReact;
+        let name_mb = info.node.child_by_field_name("name");
+        let value_mb = info.node.child_by_field_name("value");
+        if let (true, Some(name), Some(value)) = (is_tsx, name_mb, value_mb) {
+            if name.kind() == "identifier" && LAMBDA_KINDS.contains(&value.kind()) {
+                // the declaration starts with `const`, unless there are several declarators in one statement
+                let statement = match info.node.parent() {
+                    Some(parent) if parent.named_child_count() == 1 => parent,
+                    _ => info.node,
+                };
+                symbols.extend(self.parse_named_lambda(info, &name, &value, &statement, code, candidates));
+                return symbols;
+            }
+        }
+
+        // tsx: `const [count, setCount] = useState(0)` declares two variables, tell them apart by their names
+        let names: Vec<(String, Range)> = match name_mb {
+            Some(name) if is_tsx && name.kind() != "identifier" => {
+                pattern_identifiers(&name).iter().map(|x| (code.slice(x.byte_range()).to_string(), x.range())).collect()
+            }
+            Some(name) => vec![(code.slice(name.byte_range()).to_string(), info.node.range())],
+            None => vec![("".to_string(), info.node.range())],
+        };
+        let mut type_ = TypeDef::default();
         if let Some(type_node) = info.node.child_by_field_name("type") {
-            if let Some(type_) = parse_type(&type_node, code) {
-                decl.type_ = type_;
+            if let Some(dtype) = parse_type(&type_node, code) {
+                type_ = dtype;
             }
         }
-        if let Some(value) = info.node.child_by_field_name("value") {
-            decl.type_.inference_info = Some(code.slice(value.byte_range()).to_string());
+        if let Some(value) = value_mb {
+            type_.inference_info = Some(code.slice(value.byte_range()).to_string());
             candidates.push_back(CandidateInfo {
                 ast_fields: info.ast_fields.clone(),
                 node: value,
-                parent_guid: info.parent_guid.clone(),
+                parent_guid: info.parent_guid,
             });
         }
 
-        symbols.push(Arc::new(RwLock::new(Box::new(decl))));
+        for (name, range) in names {
+            let mut decl = VariableDefinition {
+                ast_fields: AstSymbolFields::from_fields(&info.ast_fields),
+                type_: type_.clone(),
+            };
+            decl.ast_fields.full_range = range;
+            decl.ast_fields.declaration_range = decl.ast_fields.full_range;
+            decl.ast_fields.definition_range = decl.ast_fields.full_range;
+            decl.ast_fields.parent_guid = Some(info.parent_guid);
+            decl.ast_fields.guid = get_guid();
+            decl.ast_fields.name = name;
+            symbols.push(Arc::new(RwLock::new(Box::new(decl))));
+        }
+        symbols
+    }
+
+    fn parse_named_lambda<'a>(&mut self, info: &CandidateInfo<'a>, name: &Node<'a>, value: &Node<'a>, statement: &Node<'a>, code: &str, candidates: &mut VecDeque<CandidateInfo<'a>>) -> Vec<AstSymbolInstanceArc> {
+        // tsx: `const Button = (props: Props) => ...` is a component named Button, not a variable
+        let symbols = self.parse_function_declaration(&CandidateInfo {
+            ast_fields: info.ast_fields.clone(),
+            node: *value,
+            parent_guid: info.parent_guid,
+        }, code, candidates);
+        if let Some(decl) = symbols.last() {
+            let mut decl = decl.write();
+            let fields = decl.fields_mut();
+            fields.name = code.slice(name.byte_range()).to_string();
+            for range in [&mut fields.full_range, &mut fields.declaration_range] {
+                range.start_byte = statement.start_byte();
+                range.start_point = statement.start_position();
+            }
+        }
         symbols
     }
 
-    fn parse_field_declaration<'a>(&mut self, info: &CandidateInfo<'a>, code: &str, _: &mut VecDeque<CandidateInfo<'a>>) -> Vec<AstSymbolInstanceArc> {
+    fn parse_field_declaration<'a>(&mut self, info: &CandidateInfo<'a>, code: &str, candidates: &mut VecDeque<CandidateInfo<'a>>) -> Vec<AstSymbolInstanceArc> {
         let mut symbols: Vec<AstSymbolInstanceArc> = vec![];
+        if let (Some(name), Some(value)) = (info.node.child_by_field_name("name"), info.node.child_by_field_name("value")) {
+            if self.language_id == LanguageId::TypeScriptReact && LAMBDA_KINDS.contains(&value.kind()) {
+                // tsx: `handleClick = () => {...}` in a class is a method
+                return self.parse_named_lambda(info, &name, &value, &info.node, code, candidates);
+            }
+        }
         let mut decl = ClassFieldDeclaration::default();
         decl.ast_fields = AstSymbolFields::from_fields(&info.ast_fields);
         decl.ast_fields.full_range = info.node.range();
@@ -550,7 +618,7 @@ impl TSParser {
             "identifier" /*| "field_identifier"*/ => {
                 let mut usage = VariableUsage::default();
                 usage.ast_fields.file_path = path.clone();
-                usage.ast_fields.language = LanguageId::TypeScript;
+                usage.ast_fields.language = self.language_id;
                 usage.ast_fields.is_error = true;
                 usage.ast_fields.name = code.slice(parent.byte_range()).to_string();
                 usage.ast_fields.full_range = parent.range();
@@ -564,7 +632,7 @@ impl TSParser {
             "member_expression" => {
                 let mut usage = VariableUsage::default();
                 usage.ast_fields.file_path = path.clone();
-                usage.ast_fields.language = LanguageId::TypeScript;
+                usage.ast_fields.language = self.language_id;
                 usage.ast_fields.is_error = true;
                 if let Some(property) = parent.child_by_field_name("property") {
                     usage.ast_fields.name = code.slice(property.byte_range()).to_string();
@@ -618,6 +686,10 @@ impl TSParser {
             "enum_declaration" => {
                 symbols.extend(self.parse_enum_declaration(info, code, candidates));
             }
+            "jsx_opening_element" | "jsx_self_closing_element" => {
+                symbols.extend(self.parse_jsx_element(info, code, candidates));
+            }
+            "jsx_closing_element" | "jsx_text" => {}
             "identifier" /*| "field_identifier"*/ => {
                 let mut usage = VariableUsage::default();
                 usage.ast_fields = AstSymbolFields::from_fields(&info.ast_fields);
@@ -781,7 +853,7 @@ impl TSParser {
         let mut ast_fields = AstSymbolFields::default();
         ast_fields.file_path = path.clone();
         ast_fields.is_error = false;
-        ast_fields.language = LanguageId::TypeScript;
+        ast_fields.language = self.language_id;
 
         let mut candidates = VecDeque::from(vec![CandidateInfo {
             ast_fields,
@@ -819,8 +891,11 @@ impl TSParser {
 }
 
 impl AstLanguageParser for TSParser {
-    fn parse(&mut self, code: &str, path: &PathBuf) -> Vec<AstSymbolInstanceArc> {
-        let tree = self.parser.parse(code, None).unwrap();
+    fn parse_tree(&mut self, code: &str) -> Tree {
+        self.parser.parse(code, None).unwrap()
+    }
+
+    fn symbols_from_tree(&mut self, tree: &Tree, code: &str, path: &PathBuf) -> Vec<AstSymbolInstanceArc> {
         let symbols = self.parse_(&tree.root_node(), code, path);
         symbols
     }
@@ -0,0 +1,96 @@
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+use similar::DiffableStr;
+use tree_sitter::{Node, Parser, Point, Range};
+use tree_sitter_typescript::language_tsx as language;
+
+use crate::ast::treesitter::ast_instance_structs::{AstSymbolFields, AstSymbolInstanceArc, FunctionCall};
+use crate::ast::treesitter::language_id::LanguageId;
+use crate::ast::treesitter::parsers::{internal_error, ParserError};
+use crate::ast::treesitter::parsers::ts::TSParser;
+use crate::ast::treesitter::parsers::utils::{CandidateInfo, get_guid};
+
+// The tsx grammar is the typescript one plus JSX, so TSParser does all the work, this file adds
+// JSX elements: `<Button onClick={f}/>` is a call of the Button component, `<div>` is plain html.
+
+fn is_component_name(name: &str) -> bool {
+    name.chars().next().map(|c| c.is_uppercase()).unwrap_or(false)
+}
+
+fn jsx_element_range(node: &Node) -> Range {
+    // the `<` token swallows the whitespace (newlines too) before it, start the element at `<` itself
+    let mut range = node.range();
+    if let Some(lt) = node.child(0).filter(|x| x.kind() == "<") {
+        range.start_byte = lt.end_byte() - 1;
+        range.start_point = Point { row: lt.end_position().row, column: lt.end_position().column - 1 };
+    }
+    range
+}
+
+impl TSParser {
+    pub fn new_tsx() -> Result<Self, ParserError> {
+        let mut parser = Parser::new();
+        parser
+            .set_language(&language())
+            .map_err(internal_error)?;
+        Ok(Self { parser, language_id: LanguageId::TypeScriptReact })
+    }
+
+    pub(super) fn parse_jsx_element<'a>(
+        &mut self,
+        info: &CandidateInfo<'a>,
+        code: &str,
+        candidates: &mut VecDeque<CandidateInfo<'a>>)
+        -> Vec<AstSymbolInstanceArc> {
+        let mut symbols: Vec<AstSymbolInstanceArc> = Default::default();
+        let name_mb = info.node.child_by_field_name("name");
+        if let Some(name) = name_mb {
+            let mut decl = FunctionCall {
+                ast_fields: AstSymbolFields::from_fields(&info.ast_fields),
+                ..Default::default()
+            };
+            decl.ast_fields.full_range = jsx_element_range(&info.node);
+            decl.ast_fields.parent_guid = Some(info.parent_guid);
+            decl.ast_fields.guid = get_guid();
+            decl.ast_fields.caller_guid = Some(get_guid());
+            match name.kind() {
+                "identifier" => {
+                    decl.ast_fields.name = code.slice(name.byte_range()).to_string();
+                }
+                "member_expression" => {
+                    // <Menu.Item>, Menu is usually a component too
+                    if let Some(property) = name.child_by_field_name("property") {
+                        decl.ast_fields.name = code.slice(property.byte_range()).to_string();
+                    }
+                    if let Some(object) = name.child_by_field_name("object") {
+                        candidates.push_back(CandidateInfo {
+                            ast_fields: decl.ast_fields.clone(),
+                            node: object,
+                            parent_guid: info.parent_guid,
+                        });
+                    }
+                }
+                _ => {}  // <svg:rect>
+            }
+            if is_component_name(&decl.ast_fields.name) {
+                symbols.push(Arc::new(RwLock::new(Box::new(decl))));
+            }
+        }
+
+        // attributes and type arguments, `{...}` expressions inside are ordinary code
+        for i in 0..info.node.child_count() {
+            let child = info.node.child(i).unwrap();
+            if Some(child) == name_mb {
+                continue;
+            }
+            candidates.push_back(CandidateInfo {
+                ast_fields: info.ast_fields.clone(),
+                node: child,
+                parent_guid: info.parent_guid,
+            });
+        }
+        symbols
+    }
+}
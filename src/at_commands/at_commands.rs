@@ -10,9 +10,12 @@ use tokio::sync::RwLock as ARwLock;
 use crate::tools::tools_description::Tool;
 use crate::call_validation::{ChatMessage, ContextFile, ContextEnum, SubchatParameters, PostprocessSettings};
 use crate::global_context::GlobalContext;
+use crate::privacy_secrets::SecretRedaction;
+use crate::audit_log::AuditFile;
 
 use crate::at_commands::at_search::AtSearch;
 use crate::at_commands::at_file::AtFile;
+use crate::at_commands::at_image::AtImage;
 use crate::at_commands::at_ast_definition::AtAstDefinition;
 use crate::at_commands::at_ast_reference::AtAstReference;
 use crate::at_commands::at_tree::AtTree;
@@ -36,6 +39,8 @@ pub struct AtCommandsContext {
     pub at_tools: IndexMap<String, Arc<AMutex<Box<dyn Tool + Send>>>>,
     pub subchat_tool_parameters: IndexMap<String, SubchatParameters>,
     pub postprocess_parameters: PostprocessSettings,
+    pub secrets_redacted: Vec<SecretRedaction>,  // report for this request, what was replaced in files and tool outputs
+    pub audit_files: Vec<AuditFile>,  // files that went into the prompt during this request, for the audit log
 
     pub subchat_tx: Arc<AMutex<mpsc::UnboundedSender<serde_json::Value>>>, // one and only supported format for now {"tool_call_id": xx, "subchat_id": xx, "add_message": {...}}
     pub subchat_rx: Arc<AMutex<mpsc::UnboundedReceiver<serde_json::Value>>>,
@@ -66,6 +71,8 @@ impl AtCommandsContext {
             at_tools: crate::tools::tools_description::tools_merged_and_filtered(global_context.clone()).await,
             subchat_tool_parameters: IndexMap::new(),
             postprocess_parameters: PostprocessSettings::new(),
+            secrets_redacted: vec![],
+            audit_files: vec![],
 
             subchat_tx: Arc::new(AMutex::new(tx)),
             subchat_rx: Arc::new(AMutex::new(rx)),
@@ -100,6 +107,7 @@ pub async fn at_commands_dict(gcx: Arc<ARwLock<GlobalContext>>) -> HashMap<Strin
         // ("@diff".to_string(), Arc::new(AMutex::new(Box::new(AtDiff::new()) as Box<dyn AtCommand + Send>))),
         // ("@diff-rev".to_string(), Arc::new(AMutex::new(Box::new(AtDiffRev::new()) as Box<dyn AtCommand + Send>))),
         ("@web".to_string(), Arc::new(AMutex::new(Box::new(AtWeb::new()) as Box<dyn AtCommand + Send>))),
+        ("@image".to_string(), Arc::new(AMutex::new(Box::new(AtImage::new()) as Box<dyn AtCommand + Send>))),
     ]);
 
     let (ast_on, vecdb_on) = {
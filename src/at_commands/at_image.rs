@@ -0,0 +1,154 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use async_trait::async_trait;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use tokio::sync::{Mutex as AMutex, RwLock as ARwLock};
+use tracing::info;
+
+use crate::at_commands::at_commands::{AtCommand, AtCommandsContext, AtParam};
+use crate::at_commands::execute_at::AtCommandMember;
+use crate::call_validation::{ChatContent, ChatImageUrl, ChatMessage, ContextEnum, MultimodalElement};
+use crate::files_correction::{canonical_path, get_project_dirs};
+use crate::global_context::GlobalContext;
+use crate::privacy::{check_file_privacy, load_privacy_if_needed, FilePrivacyLevel};
+
+
+// images are large, a bigger file most likely is a mistake
+const IMAGE_MAX_SIZE_BYTES: u64 = 20 * 1024 * 1024;
+
+
+pub struct AtImage {
+    pub params: Vec<Arc<AMutex<dyn AtParam>>>,
+}
+
+impl AtImage {
+    pub fn new() -> Self {
+        AtImage {
+            params: vec![],
+        }
+    }
+}
+
+pub fn image_mime_type_from_path(path: &Path) -> Option<&'static str> {
+    let ext = path.extension()?.to_string_lossy().to_lowercase();
+    match ext.as_str() {
+        "png" => Some("image/png"),
+        "jpg" | "jpeg" => Some("image/jpeg"),
+        "gif" => Some("image/gif"),
+        "webp" => Some("image/webp"),
+        _ => None,
+    }
+}
+
+async fn resolve_image_path(
+    gcx: Arc<ARwLock<GlobalContext>>,
+    path_str: &str,
+) -> Result<PathBuf, String> {
+    // image files are not indexed, so no fuzzy correction here: absolute path or relative to one of the project dirs
+    let path = PathBuf::from(path_str);
+    let project_dirs = get_project_dirs(gcx.clone()).await;
+    if path.is_absolute() {
+        let path = canonical_path(&path_str.to_string());
+        if !project_dirs.iter().any(|x| path.starts_with(x)) {
+            return Err(format!("Path {:?} is outside of project directories:\n{:?}", path, project_dirs));
+        }
+        if !path.is_file() {
+            return Err(format!("The path {:?} does not exist.", path));
+        }
+        return Ok(path);
+    }
+    let options = project_dirs.iter().map(|x| canonical_path(&x.join(&path).to_string_lossy().to_string())).filter(|x| x.is_file()).collect::<Vec<_>>();
+    match options.len() {
+        0 => Err(format!("The path {:?} does not exist in any of the project directories.", path)),
+        1 => Ok(options[0].clone()),
+        _ => Err(format!("The path {:?} is ambiguous, it might be:\n{}", path, options.iter().map(|x| x.to_string_lossy().to_string()).collect::<Vec<_>>().join("\n"))),
+    }
+}
+
+pub async fn image_message_from_file(
+    gcx: Arc<ARwLock<GlobalContext>>,
+    path_str: &str,
+) -> Result<ChatMessage, String> {
+    let path = resolve_image_path(gcx.clone(), path_str).await?;
+    // same rule as for text files in @file
+    check_file_privacy(load_privacy_if_needed(gcx.clone()).await, &path, &FilePrivacyLevel::AllowToSendAnywhere)
+        .map_err(|e| format!("{:?} is not allowed to be sent: {}", path, e))?;
+    let mime_type = image_mime_type_from_path(&path)
+        .ok_or(format!("{:?} is not a supported image, use png, jpeg, gif or webp", path))?;
+    let size = tokio::fs::metadata(&path).await.map_err(|e| format!("{:?}: {}", path, e))?.len();
+    if size > IMAGE_MAX_SIZE_BYTES {
+        return Err(format!("{:?} is too large: {} bytes, the limit is {} bytes", path, size, IMAGE_MAX_SIZE_BYTES));
+    }
+    let bytes = tokio::fs::read(&path).await.map_err(|e| format!("{:?}: {}", path, e))?;
+    let url = format!("data:{};base64,{}", mime_type, STANDARD.encode(&bytes));
+    Ok(ChatMessage {
+        role: "user".to_string(),
+        content: ChatContent::Multimodal(vec![
+            MultimodalElement::Text { text: format!("image {}", path_str) },
+            MultimodalElement::ImageUrl { image_url: ChatImageUrl { url, detail: None } },
+        ]),
+        ..Default::default()
+    })
+}
+
+#[async_trait]
+impl AtCommand for AtImage {
+    fn params(&self) -> &Vec<Arc<AMutex<dyn AtParam>>> {
+        &self.params
+    }
+
+    async fn at_execute(
+        &self,
+        ccx: Arc<AMutex<AtCommandsContext>>,
+        cmd: &mut AtCommandMember,
+        args: &mut Vec<AtCommandMember>,
+    ) -> Result<(Vec<ContextEnum>, String), String> {
+        let arg0 = match args.iter().find(|x| !x.text.trim().is_empty()) {
+            Some(x) => x.clone(),
+            None => {
+                cmd.ok = false; cmd.reason = Some("no image provided".to_string());
+                args.clear();
+                return Err("Cannot execute @image: no image provided".to_string());
+            }
+        };
+        args.clear();
+        args.push(arg0.clone());
+
+        let (gcx, is_preview) = {
+            let ccx_lock = ccx.lock().await;
+            (ccx_lock.global_context.clone(), ccx_lock.is_preview)
+        };
+        let message = match image_message_from_file(gcx.clone(), &arg0.text).await {
+            Ok(message) => message,
+            Err(e) => {
+                cmd.ok = false; cmd.reason = Some(e.clone());
+                return Err(e);
+            }
+        };
+        if is_preview {
+            // don't send megabytes of base64 into the preview
+            return Ok((vec![], arg0.text.clone()));
+        }
+
+        info!("executed @image {}", arg0.text);
+        Ok((vec![ContextEnum::ChatMessage(message)], format!("[see image {} above]", arg0.text)))
+    }
+
+    fn depends_on(&self) -> Vec<String> {
+        vec![]
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_image_mime_type_from_path() {
+        assert_eq!(image_mime_type_from_path(Path::new("/tmp/screenshot.PNG")), Some("image/png"));
+        assert_eq!(image_mime_type_from_path(Path::new("photo.jpeg")), Some("image/jpeg"));
+        assert_eq!(image_mime_type_from_path(Path::new("main.rs")), None);
+    }
+}
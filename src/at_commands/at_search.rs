@@ -9,7 +9,7 @@ use crate::at_commands::execute_at::AtCommandMember;
 use crate::call_validation::{ContextEnum, ContextFile};
 use crate::caps::get_custom_embedding_api_key;
 use crate::vecdb;
-use crate::vecdb::vdb_structs::VecdbSearch;
+use crate::vecdb::vdb_structs::{lexical_weight_default, VecdbSearch};
 
 
 pub fn text_on_clip(query: &String, from_tool_call: bool) -> String {
@@ -51,7 +51,8 @@ fn results2message(results: &Vec<vecdb::vdb_structs::VecdbRecord>) -> Vec<Contex
             file_content: "".to_string(),
             line1: r.start_line as usize + 1,
             line2: r.end_line as usize + 1,
-            symbols: vec![],
+            // postprocessing highlights the whole symbol if it finds it, otherwise just the lines
+            symbols: if r.symbol_path.is_empty() { vec![] } else { vec![r.symbol_path.clone()] },
             gradient_type: -1,
             usefulness,
         });
@@ -62,7 +63,7 @@ fn results2message(results: &Vec<vecdb::vdb_structs::VecdbRecord>) -> Vec<Contex
 pub async fn execute_at_search(
     ccx: Arc<AMutex<AtCommandsContext>>,
     query: &String,
-    vecdb_scope_filter_mb: Option<String>,
+    scope_mb: Option<String>,
 ) -> Result<Vec<ContextFile>, String> {
     let (gcx, top_n) = {
         let ccx_locked = ccx.lock().await;
@@ -80,7 +81,7 @@ pub async fn execute_at_search(
         Some(ref db) => {
             let top_n_twice_as_big = top_n * 2;  // top_n will be cut at postprocessing stage, and we really care about top_n files, not pieces
             // TODO: this code sucks, release lock, don't hold anything during the search
-            let search_result = db.vecdb_search(query.clone(), top_n_twice_as_big, vecdb_scope_filter_mb, &api_key).await?;
+            let search_result = db.vecdb_search(query.clone(), top_n_twice_as_big, scope_mb, lexical_weight_default(query), &api_key).await?;
             let results = search_result.results.clone();
             return Ok(results2message(&results));
         }
@@ -9,6 +9,8 @@ use crate::at_commands::at_commands::{AtCommandsContext, AtParam, filter_only_co
 use crate::call_validation::{ChatMessage, ContextEnum};
 use crate::postprocessing::pp_context_files::postprocess_context_files;
 use crate::postprocessing::pp_plain_text::postprocess_plain_text;
+use crate::privacy_secrets::{merge_redactions, redact_context_files};
+use crate::audit_log::{audit_files_from_context_files, merge_audit_files};
 use crate::scratchpads::scratchpad_utils::{HasRagResults, count_tokens, max_tokens_for_rag_chat};
 
 pub const MIN_RAG_CONTEXT_LIMIT: usize = 256;
@@ -40,7 +42,7 @@ pub async fn run_at_commands(
         let message = original_messages.get(user_msg_starts - 1).unwrap().clone();
         if message.role == "user" {
             user_msg_starts -= 1;
-            if message.content.contains("@") {
+            if message.content.content_text_only().contains("@") {
                 messages_with_at += 1;
             }
         } else {
@@ -56,7 +58,7 @@ pub async fn run_at_commands(
     for msg_idx in user_msg_starts..original_messages.len() {
         let msg = original_messages[msg_idx].clone();
         let role = msg.role.clone();
-        let mut content = msg.content.clone();
+        let mut content = msg.content.content_text_only();
         let content_n_tokens = count_tokens(&tokenizer.read().unwrap(), &content);
 
         let mut context_limit = reserve_for_context / messages_with_at.max(1);
@@ -118,7 +120,7 @@ pub async fn run_at_commands(
             if pp_skeleton {
                 pp_settings.take_floor = 50.0;
             }
-            let post_processed = postprocess_context_files(
+            let mut post_processed = postprocess_context_files(
                 gcx.clone(),
                 &mut context_file_pp,
                 tokenizer.clone(),
@@ -126,6 +128,12 @@ pub async fn run_at_commands(
                 false,
                 &pp_settings,
             ).await;
+            let redacted = redact_context_files(&mut post_processed);
+            {
+                let mut ccx_locked = ccx.lock().await;
+                merge_redactions(&mut ccx_locked.secrets_redacted, redacted);
+                merge_audit_files(&mut ccx_locked.audit_files, audit_files_from_context_files(&post_processed));
+            }
             if !post_processed.is_empty() {
                 // OUTPUT: files after all custom messages and plain text
                 let json_vec = post_processed.iter().map(|p| { json!(p)}).collect::<Vec<Value>>();
@@ -141,9 +149,13 @@ pub async fn run_at_commands(
             info!("postprocess_plain_text_messages+postprocess_at_results2 {:.3}s", t0.elapsed().as_secs_f32());
         }
 
-        if content.trim().len() > 0 {
+        if content.trim().len() > 0 || !msg.content.images().is_empty() {
             // stream back to the user, with at-commands replaced
-            let msg = ChatMessage::new(role.clone(), content);
+            let msg = ChatMessage {
+                role: role.clone(),
+                content: msg.content.with_text_replaced(content),
+                ..Default::default()
+            };
             rebuilt_messages.push(msg.clone());
             stream_back_to_user.push_in_json(json!(msg));
         }
@@ -3,6 +3,7 @@ pub mod at_ast_definition;
 pub mod at_ast_reference;
 pub mod at_commands;
 pub mod at_file;
+pub mod at_image;
 pub mod at_search;
 pub mod at_web;
 pub mod at_tree;
@@ -0,0 +1,310 @@
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex as StdMutex};
+use std::sync::mpsc::Sender;
+use std::thread::JoinHandle;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+
+use crate::call_validation::{ChatContent, ChatMessage, ContextFile};
+
+
+// Append-only record of everything sent to model and embedding servers: who, where and which files.
+// Forwarders and embedding fetchers don't see GlobalContext, so the writer is set up once at startup.
+// Rotated files are kept unless --audit-log-keep says otherwise.
+
+const AUDIT_LOG_FILE: &str = "outbound.jsonl";
+const AUDIT_LOG_ROTATE_BYTES: u64 = 10 * 1024 * 1024;
+
+lazy_static! {
+    static ref AUDIT_LOG: StdMutex<Option<(Arc<AuditLog>, Sender<AuditRecord>)>> = StdMutex::new(None);
+    static ref AUDIT_LOG_WRITER: StdMutex<Option<JoinHandle<()>>> = StdMutex::new(None);
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct AuditFile {
+    pub file_name: String,
+    pub line1: usize,
+    pub line2: usize,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct AuditRecord {
+    pub ts: String,
+    pub scope: String,  // "chat", "completion", "embeddings", ...
+    pub endpoint: String,
+    pub model: String,
+    pub files: Vec<AuditFile>,
+    pub prompt_chars: usize,
+    pub prompt_tokens: Option<usize>,  // as reported by the server, if it does
+    pub completion_tokens: Option<usize>,
+    pub success: bool,
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct AuditLogFilter {
+    #[serde(default)]
+    pub since: String,  // RFC 3339, compared as text
+    #[serde(default)]
+    pub until: String,
+    #[serde(default)]
+    pub scope: String,
+    #[serde(default)]
+    pub endpoint: String,  // substring
+    #[serde(default)]
+    pub model: String,
+    #[serde(default)]
+    pub file: String,  // substring of any file name
+    #[serde(default)]
+    pub limit: usize,  // most recent N, zero means all
+}
+
+impl AuditRecord {
+    pub fn new(scope: &str, endpoint: &str, model: &str, files: Vec<AuditFile>, prompt_chars: usize) -> Self {
+        AuditRecord {
+            ts: chrono::Utc::now().to_rfc3339(),
+            scope: scope.to_string(),
+            endpoint: endpoint.to_string(),
+            model: model.to_string(),
+            files,
+            prompt_chars,
+            prompt_tokens: None,
+            completion_tokens: None,
+            success: false,
+        }
+    }
+
+    pub fn with_usage(mut self, response: &serde_json::Value) -> Self {
+        let get = |v: &serde_json::Value| v.as_u64().map(|x| x as usize);
+        if let Some(usage) = response.get("usage") {
+            self.prompt_tokens = get(&usage["prompt_tokens"]).or(self.prompt_tokens);
+            self.completion_tokens = get(&usage["completion_tokens"]).or(self.completion_tokens);
+        }
+        self.prompt_tokens = get(&response["metering_prompt_tokens_n"]).or(self.prompt_tokens);
+        self.completion_tokens = get(&response["metering_generated_tokens_n"]).or(self.completion_tokens);
+        self
+    }
+
+    fn matches(&self, filter: &AuditLogFilter) -> bool {
+        (filter.since.is_empty() || self.ts.as_str() >= filter.since.as_str())
+            && (filter.until.is_empty() || self.ts.as_str() <= filter.until.as_str())
+            && (filter.scope.is_empty() || self.scope == filter.scope)
+            && (filter.endpoint.is_empty() || self.endpoint.contains(&filter.endpoint))
+            && (filter.model.is_empty() || self.model == filter.model)
+            && (filter.file.is_empty() || self.files.iter().any(|f| f.file_name.contains(&filter.file)))
+    }
+}
+
+pub fn audit_files_from_context_files(context_files: &[ContextFile]) -> Vec<AuditFile> {
+    context_files.iter().map(|cf| AuditFile {
+        file_name: cf.file_name.clone(),
+        line1: cf.line1,
+        line2: cf.line2,
+    }).collect()
+}
+
+pub fn audit_files_from_messages(messages: &[ChatMessage]) -> Vec<AuditFile> {
+    // files attached earlier in the chat go out again with every request
+    let mut files = vec![];
+    for msg in messages.iter().filter(|m| m.role == "context_file") {
+        if let ChatContent::SimpleText(text) = &msg.content {
+            if let Ok(context_files) = serde_json::from_str::<Vec<ContextFile>>(text) {
+                files.extend(audit_files_from_context_files(&context_files));
+            }
+        }
+    }
+    files
+}
+
+pub fn merge_audit_files(into: &mut Vec<AuditFile>, more: Vec<AuditFile>) {
+    for f in more {
+        if !into.contains(&f) {
+            into.push(f);
+        }
+    }
+}
+
+pub struct AuditLog {
+    dir: PathBuf,
+    keep_rotated: usize,  // zero keeps all the rotated files
+}
+
+impl AuditLog {
+    pub fn new(dir: PathBuf, keep_rotated: usize) -> Self {
+        AuditLog { dir, keep_rotated }
+    }
+
+    fn current_path(&self) -> PathBuf {
+        self.dir.join(AUDIT_LOG_FILE)
+    }
+
+    fn rotated_paths(&self) -> Vec<PathBuf> {
+        // outbound.<utc timestamp>.jsonl, sorted oldest first
+        let mut paths: Vec<PathBuf> = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries.filter_map(|e| e.ok()).map(|e| e.path()).filter(|p| {
+                let name = p.file_name().unwrap_or_default().to_string_lossy();
+                name.starts_with("outbound.") && name.ends_with(".jsonl") && name != AUDIT_LOG_FILE
+            }).collect(),
+            Err(_) => vec![],
+        };
+        paths.sort();
+        paths
+    }
+
+    fn rotate_if_needed(&self) -> std::io::Result<()> {
+        let current = self.current_path();
+        match fs::metadata(&current) {
+            Ok(meta) if meta.len() >= AUDIT_LOG_ROTATE_BYTES => {},
+            _ => return Ok(()),
+        }
+        let ts = chrono::Utc::now().format("%Y%m%dT%H%M%S%.9fZ");
+        fs::rename(&current, self.dir.join(format!("outbound.{}.jsonl", ts)))?;
+        if self.keep_rotated > 0 {
+            let rotated = self.rotated_paths();
+            for old in rotated.iter().take(rotated.len().saturating_sub(self.keep_rotated)) {
+                fs::remove_file(old)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write(&self, record: &AuditRecord) {
+        let result = self.rotate_if_needed().and_then(|_| {
+            let mut f = OpenOptions::new().create(true).append(true).open(self.current_path())?;
+            writeln!(f, "{}", serde_json::to_string(record).unwrap())
+        });
+        if let Err(e) = result {
+            error!("audit log: cannot write to {}: {}", self.dir.display(), e);
+        }
+    }
+
+    pub fn read(&self, filter: &AuditLogFilter) -> Result<Vec<AuditRecord>, String> {
+        let mut records = vec![];
+        let mut paths = self.rotated_paths();
+        paths.push(self.current_path());
+        for path in paths {
+            let file = match fs::File::open(&path) {
+                Ok(file) => file,
+                Err(_) => continue,
+            };
+            for line in BufReader::new(file).lines().map_while(Result::ok) {
+                match serde_json::from_str::<AuditRecord>(&line) {
+                    Ok(record) if record.matches(filter) => records.push(record),
+                    Ok(_) => {},
+                    Err(e) => error!("audit log: bad line in {}: {}", path.display(), e),
+                }
+            }
+        }
+        if filter.limit > 0 && records.len() > filter.limit {
+            records.drain(..records.len() - filter.limit);
+        }
+        Ok(records)
+    }
+}
+
+pub fn audit_log_init(dir: PathBuf, keep_rotated: usize) {
+    if let Err(e) = fs::create_dir_all(&dir) {
+        error!("audit log: cannot create {}: {}", dir.display(), e);
+        return;
+    }
+    info!("audit log: {}", dir.join(AUDIT_LOG_FILE).display());
+    let audit_log = Arc::new(AuditLog::new(dir, keep_rotated));
+    // one writer thread keeps the records in order and the disk off the async workers
+    let (tx, rx) = std::sync::mpsc::channel::<AuditRecord>();
+    let writer = audit_log.clone();
+    let handle = std::thread::spawn(move || {
+        for record in rx {
+            writer.write(&record);
+        }
+    });
+    *AUDIT_LOG.lock().unwrap() = Some((audit_log, tx));
+    *AUDIT_LOG_WRITER.lock().unwrap() = Some(handle);
+}
+
+pub fn audit_log_shutdown() {
+    // dropping the sender ends the writer loop after the queued records are written
+    AUDIT_LOG.lock().unwrap().take();
+    if let Some(handle) = AUDIT_LOG_WRITER.lock().unwrap().take() {
+        if handle.join().is_err() {
+            error!("audit log: writer thread panicked, last records may be lost");
+        }
+    }
+}
+
+pub fn audit_log_write(record: &AuditRecord) {
+    if let Some((_, tx)) = AUDIT_LOG.lock().unwrap().as_ref() {
+        let _ = tx.send(record.clone());
+    }
+}
+
+pub fn audit_log_read(filter: &AuditLogFilter) -> Result<Vec<AuditRecord>, String> {
+    let audit_log = AUDIT_LOG.lock().unwrap().as_ref().map(|(audit_log, _)| audit_log.clone())
+        .ok_or("audit log is not initialized".to_string())?;
+    audit_log.read(filter)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grow_to_rotation_size(audit_log: &AuditLog) {
+        // sparse padding, read() skips it as one bad line
+        OpenOptions::new().write(true).open(audit_log.current_path()).unwrap().set_len(AUDIT_LOG_ROTATE_BYTES).unwrap();
+    }
+
+    #[test]
+    fn test_audit_log_rotation_and_filter() {
+        let dir = tempfile::tempdir().unwrap();
+        let audit_log = AuditLog::new(dir.path().to_path_buf(), 0);
+        let mut record = AuditRecord::new("chat", "https://api.openai.com/v1/chat/completions", "gpt-4o", vec![
+            AuditFile { file_name: "/home/user/proj/src/main.rs".to_string(), line1: 1, line2: 50 },
+        ], 1000).with_usage(&serde_json::json!({"usage": {"prompt_tokens": 300, "completion_tokens": 20}}));
+        record.success = true;
+        assert!(record.ts.ends_with("+00:00"), "{}", record.ts);
+        audit_log.write(&record);
+        audit_log.write(&AuditRecord::new("embeddings", "http://10.0.0.5:8008/v1/embeddings", "thenlper/gte-base", vec![], 200));
+
+        let all = audit_log.read(&AuditLogFilter::default()).unwrap();
+        assert_eq!(all.len(), 2);
+        assert_eq!(all[0].prompt_tokens, Some(300));
+        assert_eq!(all[0].completion_tokens, Some(20));
+
+        let by_file = audit_log.read(&AuditLogFilter { file: "main.rs".to_string(), ..Default::default() }).unwrap();
+        assert_eq!(by_file.len(), 1);
+        assert_eq!(by_file[0].scope, "chat");
+        let by_endpoint = audit_log.read(&AuditLogFilter { endpoint: "10.0.0.5".to_string(), ..Default::default() }).unwrap();
+        assert_eq!(by_endpoint.len(), 1);
+        assert_eq!(by_endpoint[0].model, "thenlper/gte-base");
+        let last = audit_log.read(&AuditLogFilter { limit: 1, ..Default::default() }).unwrap();
+        assert_eq!(last[0].scope, "embeddings");
+
+        // rotated files stay by default and are still searchable
+        for _ in 0..3 {
+            grow_to_rotation_size(&audit_log);
+            audit_log.write(&record);
+        }
+        assert_eq!(audit_log.rotated_paths().len(), 3);
+        assert_eq!(fs::read_to_string(audit_log.current_path()).unwrap().lines().count(), 1);
+        assert_eq!(audit_log.read(&AuditLogFilter::default()).unwrap().len(), 5);
+
+        let keep_two = AuditLog::new(dir.path().to_path_buf(), 2);
+        grow_to_rotation_size(&keep_two);
+        keep_two.write(&record);
+        assert_eq!(keep_two.rotated_paths().len(), 2);
+    }
+
+    #[test]
+    fn test_audit_log_shutdown_writes_queued_records() {
+        let dir = tempfile::tempdir().unwrap();
+        audit_log_init(dir.path().to_path_buf(), 0);
+        for _ in 0..100 {
+            audit_log_write(&AuditRecord::new("chat", "http://127.0.0.1:8080/v1/chat/completions", "llama", vec![], 10));
+        }
+        audit_log_shutdown();
+        assert_eq!(fs::read_to_string(dir.path().join(AUDIT_LOG_FILE)).unwrap().lines().count(), 100);
+        assert!(audit_log_read(&AuditLogFilter::default()).is_err());
+    }
+}
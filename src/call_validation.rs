@@ -244,11 +244,77 @@ pub struct ChatUsage {
     pub total_tokens: usize,   // TODO: remove (can produce self-contradictory data when prompt+completion != total)
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ChatImageUrl {
+    pub url: String,  // "data:image/png;base64,..." or http(s) link
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MultimodalElement {
+    Text { text: String },
+    ImageUrl { image_url: ChatImageUrl },
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum ChatContent {
+    SimpleText(String),
+    Multimodal(Vec<MultimodalElement>),  // openai-style content array
+}
+
+impl Default for ChatContent {
+    fn default() -> Self {
+        ChatContent::SimpleText(String::new())
+    }
+}
+
+impl ChatContent {
+    pub fn content_text_only(&self) -> String {
+        match self {
+            ChatContent::SimpleText(text) => text.clone(),
+            ChatContent::Multimodal(elements) => elements.iter().filter_map(|el| match el {
+                MultimodalElement::Text { text } => Some(text.clone()),
+                MultimodalElement::ImageUrl { .. } => None,
+            }).collect::<Vec<_>>().join("\n\n"),
+        }
+    }
+
+    pub fn images(&self) -> Vec<&ChatImageUrl> {
+        match self {
+            ChatContent::SimpleText(_) => vec![],
+            ChatContent::Multimodal(elements) => elements.iter().filter_map(|el| match el {
+                MultimodalElement::ImageUrl { image_url } => Some(image_url),
+                MultimodalElement::Text { .. } => None,
+            }).collect(),
+        }
+    }
+
+    // text goes first, the images follow
+    pub fn with_text_replaced(&self, text: String) -> ChatContent {
+        let images = self.images();
+        if images.is_empty() {
+            return ChatContent::SimpleText(text);
+        }
+        let mut elements = vec![MultimodalElement::Text { text }];
+        elements.extend(images.into_iter().map(|image_url| MultimodalElement::ImageUrl { image_url: image_url.clone() }));
+        ChatContent::Multimodal(elements)
+    }
+}
+
+impl From<String> for ChatContent {
+    fn from(text: String) -> Self {
+        ChatContent::SimpleText(text)
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct ChatMessage {
     pub role: String,
     #[serde(default, deserialize_with="deserialize_content")]
-    pub content: String,
+    pub content: ChatContent,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub tool_calls: Option<Vec<ChatToolCall>>,
     #[serde(default)]
@@ -261,7 +327,7 @@ pub struct ChatMessage {
 pub struct RealChatMessage {
     pub role: String,
     #[serde(default, deserialize_with="deserialize_content")]
-    pub content: String,
+    pub content: ChatContent,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub tool_calls: Option<Vec<ChatToolCall>>,
     #[serde(default)]
@@ -270,7 +336,7 @@ pub struct RealChatMessage {
 
 impl ChatMessage {
     pub fn new(role: String, content: String) -> Self {
-        ChatMessage { role, content, ..Default::default()}
+        ChatMessage { role, content: ChatContent::SimpleText(content), ..Default::default()}
     }
     pub fn into_real(&self) -> RealChatMessage {
         RealChatMessage {
@@ -283,11 +349,11 @@ impl ChatMessage {
 }
 
 // this converts null to empty string
-fn deserialize_content<'de, D>(deserializer: D) -> Result<String, D::Error>
+fn deserialize_content<'de, D>(deserializer: D) -> Result<ChatContent, D::Error>
 where
     D: Deserializer<'de>,
 {
-    Option::<String>::deserialize(deserializer).map(|opt| opt.unwrap_or_default())
+    Option::<ChatContent>::deserialize(deserializer).map(|opt| opt.unwrap_or_default())
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -14,10 +14,14 @@ use tracing::{error, info, warn};
 use crate::custom_error::ScratchError;
 use crate::global_context::{try_load_caps_quickly_if_not_present, GlobalContext};
 use crate::known_models::KNOWN_MODELS;
+use crate::forward_to_llama_cpp_endpoint::discover_llama_cpp_models;
+use crate::forward_to_ollama_endpoint::{discover_ollama_models, LocalModelInfo};
 
 
 const CAPS_FILENAME: &str = "refact-caps";
 const CAPS_FILENAME_FALLBACK: &str = "coding_assistant_caps.json";
+const DISCOVERED_MODELS_FALLBACK_TOKENIZER: &str = "Xenova/gpt-4o";
+const DISCOVERED_MODELS_MAX_N_CTX: usize = 32768;
 
 
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
@@ -91,6 +95,8 @@ pub struct CodeAssistantCaps {
     pub chat_apikey: String,
     #[serde(default)]
     pub embedding_apikey: String,
+    #[serde(default)]
+    pub vecdb_remote_apikey: String,
 
     #[serde(default)]
     #[serde(alias = "chat_endpoint")]
@@ -135,6 +141,10 @@ pub struct CodeAssistantCaps {
     #[serde(default)]
     pub embedding_n_ctx: usize,
     #[serde(default)]
+    pub vecdb_remote_endpoint: String,  // shared index server, for example https://index.example.com/v1/vdb-search
+    #[serde(default)]
+    pub vecdb_remote_root: String,  // where that server has the workspace
+    #[serde(default)]
     pub running_models: Vec<String>,
     #[serde(default)]
     pub caps_version: i64,  // need to reload if it increases on server, that happens when server configuration changes
@@ -143,6 +153,11 @@ pub struct CodeAssistantCaps {
 
     #[serde(default)]
     pub customization: String,  // on self-hosting server, allows to customize yaml_configs & friends for all engineers
+
+    #[serde(default)]
+    pub models_discovery_style: String,  // "ollama" or "llama.cpp", fills code_completion_models and code_chat_models from what the local server has
+    #[serde(default)]
+    pub models_discovery_endpoint: String,  // base url of that server, default is the standard local port
 }
 
 fn load_caps_from_buf(
@@ -283,6 +298,19 @@ pub async fn get_custom_embedding_api_key(gcx: Arc<ARwLock<GlobalContext>>) -> R
     Ok(api_key)
 }
 
+pub async fn get_vecdb_remote_api_key(gcx: Arc<ARwLock<GlobalContext>>) -> Result<String, ScratchError> {
+    let cmdline_apikey = gcx.read().await.cmdline.vecdb_remote_apikey.clone();
+    if !cmdline_apikey.is_empty() {
+        return Ok(cmdline_apikey);
+    }
+    let caps = try_load_caps_quickly_if_not_present(
+        gcx.clone(), 0,
+    ).await?;
+
+    let api_key = get_api_key_macro!(gcx, caps, vecdb_remote_apikey);
+    Ok(api_key)
+}
+
 #[allow(dead_code)]
 async fn get_custom_completion_api_key(gcx: Arc<ARwLock<GlobalContext>>) -> Result<String, ScratchError> {
     let caps = try_load_caps_quickly_if_not_present(
@@ -387,11 +415,149 @@ pub async fn load_caps(
     let mut caps_url = cmdline.address_url.clone();
     let buf: String;
     if caps_url.to_lowercase() == "refact" || caps_url.starts_with("http") {
-        (buf, caps_url) = load_caps_buf_from_url(cmdline, gcx).await?
+        (buf, caps_url) = load_caps_buf_from_url(cmdline, gcx.clone()).await?
     } else {
-        (buf, caps_url) = load_caps_buf_from_file(cmdline, gcx).await?
+        (buf, caps_url) = load_caps_buf_from_file(cmdline, gcx.clone()).await?
     }
-    load_caps_from_buf(&buf, &caps_url)
+    let caps = load_caps_from_buf(&buf, &caps_url)?;
+    let (discovery_style, discovery_endpoint) = {
+        let caps_locked = caps.read().unwrap();
+        (caps_locked.models_discovery_style.clone(), caps_locked.models_discovery_endpoint.clone())
+    };
+    if !discovery_style.is_empty() {
+        let http_client = gcx.read().await.http_client.clone();
+        // the local server may be not started yet, caps from the file are still good
+        match discover_local_models(&http_client, &discovery_style, &discovery_endpoint).await {
+            Ok(discovered) => {
+                let r0: ModelsOnly = serde_json::from_str(KNOWN_MODELS).map_err(|e| format!("failed to parse KNOWN_MODELS: {}", e))?;
+                apply_discovered_models(&mut caps.write().unwrap(), &discovered, &r0);
+            }
+            Err(e) => warn!("{}, continue with the models from caps only", e),
+        }
+    }
+    Ok(caps)
+}
+
+async fn discover_local_models(
+    http_client: &reqwest::Client,
+    discovery_style: &str,
+    discovery_endpoint: &str,
+) -> Result<Vec<LocalModelInfo>, String> {
+    match discovery_style {
+        "ollama" => {
+            let endpoint = if discovery_endpoint.is_empty() { "http://127.0.0.1:11434" } else { discovery_endpoint };
+            discover_ollama_models(http_client, endpoint).await
+        }
+        "llama.cpp" => {
+            let endpoint = if discovery_endpoint.is_empty() { "http://127.0.0.1:8080" } else { discovery_endpoint };
+            discover_llama_cpp_models(http_client, endpoint).await
+        }
+        _ => Err(format!("unknown models_discovery_style \"{}\", should be \"ollama\" or \"llama.cpp\"", discovery_style)),
+    }
+    .map_err(|e| format!("local models discovery failed: {}", e))
+}
+
+fn apply_discovered_models(
+    r1: &mut CodeAssistantCaps,
+    discovered: &[LocalModelInfo],
+    r0: &ModelsOnly,
+) {
+    fn find_known<'a>(models: &'a HashMap<String, ModelRecord>, candidates: &[String]) -> Option<(&'a String, &'a ModelRecord)> {
+        let exact = models.iter().find(|(rec_name, rec)| {
+            candidates.iter().any(|c| *rec_name == c || rec.similar_models.contains(c))
+        });
+        if exact.is_some() {
+            return exact;
+        }
+        // gguf files are named like "starcoder2-3b-Q4_K_M.gguf", compare without separators and quantization
+        let wanted: Vec<String> = candidates.iter().map(|c| normalized_model_name(c)).filter(|c| c.len() >= 4).collect();
+        let mut sorted: Vec<(&'a String, &'a ModelRecord)> = models.iter().collect();
+        sorted.sort_by(|a, b| a.0.cmp(b.0));
+        sorted.into_iter().find(|(rec_name, rec)| {
+            std::iter::once(*rec_name).chain(rec.similar_models.iter()).any(|known| {
+                // "bigcode/starcoder2-3b" is also known as just "starcoder2-3b"
+                let mut variants = vec![normalized_model_name(known)];
+                variants.extend(known.match_indices('/').map(|(i, _)| normalized_model_name(&known[i + 1..])));
+                variants.iter().any(|v| wanted.contains(v))
+            })
+        })
+    }
+
+    // llama.cpp style posts a rendered prompt to /completion, it can't carry PASSTHROUGH messages
+    let chat_style = if !r1.chat_endpoint_style.is_empty() { &r1.chat_endpoint_style } else { &r1.endpoint_style };
+    let passthrough_ok = chat_style != "llama.cpp";
+    let mut discovered_sorted = discovered.to_vec();
+    discovered_sorted.sort_by(|a, b| a.name.cmp(&b.name));
+    for m in discovered_sorted.iter() {
+        // ollama calls models like "starcoder2:3b", known models are mostly "starcoder2/3b" or "deepseek-coder/1.3b/base"
+        let slashed = m.name.replace(":", "/");
+        let candidates = [m.name.clone(), slashed.clone(), format!("{}/base", slashed)];
+        let stripped = strip_model_from_finetune(&m.name);
+
+        if m.supports_completion && !r1.code_completion_models.contains_key(&m.name) {
+            if let Some((known_name, rec)) = find_known(&r0.code_completion_models, &candidates) {
+                r1.code_completion_models.insert(m.name.clone(), rec.clone());
+                if !r1.tokenizer_rewrite_path.contains_key(&stripped) {
+                    let tokenizer = candidates.iter().find_map(|c| r0.tokenizer_rewrite_path.get(c)).unwrap_or(known_name);
+                    r1.tokenizer_rewrite_path.insert(stripped.clone(), tokenizer.clone());
+                }
+                if r1.code_completion_default_model.is_empty() {
+                    r1.code_completion_default_model = m.name.clone();
+                }
+            } else {
+                // completion scratchpads need the model's FIM tokens, only known models have them
+                info!("local model {:?} is not in known models, it can't be used for code completion", m.name);
+            }
+        }
+
+        if m.supports_chat && !r1.code_chat_models.contains_key(&m.name) {
+            let rec = match find_known(&r0.code_chat_models, &candidates) {
+                Some((_, rec)) if passthrough_ok || rec.default_scratchpad != "PASSTHROUGH" => Some(rec.clone()),
+                _ if !passthrough_ok => {
+                    info!("local model {:?} needs chat_endpoint_style \"openai\" with /v1/chat/completions to be used for chat", m.name);
+                    None
+                }
+                _ => {
+                    // the server applies its own chat template, tokenizer is only used to count tokens, approximate is fine
+                    if !r1.tokenizer_rewrite_path.contains_key(&stripped) {
+                        r1.tokenizer_rewrite_path.insert(stripped.clone(), DISCOVERED_MODELS_FALLBACK_TOKENIZER.to_string());
+                    }
+                    Some(ModelRecord {
+                        n_ctx: if m.n_ctx > 0 { m.n_ctx.min(DISCOVERED_MODELS_MAX_N_CTX) } else { default_code_completion_n_ctx() },
+                        supports_scratchpads: HashMap::from([("PASSTHROUGH".to_string(), serde_json::json!({}))]),
+                        default_scratchpad: "PASSTHROUGH".to_string(),
+                        similar_models: vec![],
+                        supports_tools: m.supports_tools,
+                    })
+                }
+            };
+            if let Some(rec) = rec {
+                r1.code_chat_models.insert(m.name.clone(), rec);
+                if r1.code_chat_default_model.is_empty() {
+                    r1.code_chat_default_model = m.name.clone();
+                }
+            }
+        }
+
+        if !r1.running_models.contains(&m.name) {
+            r1.running_models.push(m.name.clone());
+        }
+    }
+    info!("caps after local discovery: {} completion models, {} chat models", r1.code_completion_models.len(), r1.code_chat_models.len());
+}
+
+fn normalized_model_name(name: &str) -> String {
+    // "deepseek-coder-1.3b-base.Q4_K_M.gguf" -> "deepseekcoder13bbase"
+    let lower = name.to_lowercase();
+    let parts: Vec<&str> = lower.trim_end_matches(".gguf").split(|c: char| !c.is_ascii_alphanumeric()).filter(|p| !p.is_empty()).collect();
+    let is_quantization = |p: &str| {
+        let digits = p.trim_start_matches('i').trim_start_matches('q');
+        (digits.len() < p.len() && digits.starts_with(|c: char| c.is_ascii_digit())) || ["f16", "f32", "bf16", "fp16"].contains(&p)
+    };
+    // q4_k_m and q8_0 are split into several parts, everything short after the quantization goes too
+    let quantization_at = (0..parts.len()).rev()
+        .find(|i| *i > 0 && is_quantization(parts[*i]) && parts[*i + 1..].iter().all(|p| p.len() <= 2));
+    parts[..quantization_at.unwrap_or(parts.len())].concat()
 }
 
 pub fn strip_model_from_finetune(model: &String) -> String {
@@ -476,7 +642,8 @@ pub fn which_model_to_use<'a>(
     if user_wants_model != "" {
         take_this_one = user_wants_model;
     }
-    if let Some(model_rec) = models.get(&strip_model_from_finetune(&take_this_one.to_string())) {
+    // exact name first, local servers like ollama have "model:tag" names that are not finetunes
+    if let Some(model_rec) = models.get(take_this_one).or_else(|| models.get(&strip_model_from_finetune(&take_this_one.to_string()))) {
         return Ok((take_this_one.to_string(), model_rec));
     } else {
         return Err(format!(
@@ -549,6 +716,11 @@ embedding_apikey: "$OPENAI_API_KEY"
 embedding_model: text-embedding-3-small
 embedding_size: 1536
 
+# chat_endpoint: "https://api.anthropic.com/v1/messages"
+# chat_endpoint_style: "anthropic"
+# chat_apikey: "$ANTHROPIC_API_KEY"
+# chat_model: claude-3-5-sonnet-20240620
+
 # completion_endpoint: "https://api-inference.huggingface.co/models/$MODEL"
 # completion_endpoint_style: "hf"
 # completion_apikey: "hf_..."    # or use $HF_TOKEN if you have it in global environment variables
@@ -564,3 +736,35 @@ running_models:   # all models mentioned in *_model are automatically running, b
 # telemetry_basic_dest: <your-telemetry-address>             # default: https://www.smallcloud.ai/v1/telemetry-basic
 # telemetry_basic_retrieve_my_own: <your-telemetry-address>  # default: https://www.smallcloud.ai/v1/telemetry-retrieve-my-own-stats
 "#;
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_discovered_models() {
+        let r0: ModelsOnly = serde_json::from_str(KNOWN_MODELS).unwrap();
+        let mut caps = CodeAssistantCaps::default();
+        let discovered = vec![
+            LocalModelInfo { name: "starcoder2-3b-Q4_K_M".to_string(), n_ctx: 16384, supports_tools: false, supports_chat: false, supports_completion: true },
+            LocalModelInfo { name: "my-finetune-q8_0".to_string(), n_ctx: 0, supports_tools: true, supports_chat: true, supports_completion: true },
+        ];
+        apply_discovered_models(&mut caps, &discovered, &r0);
+        assert!(caps.code_completion_models.contains_key("starcoder2-3b-Q4_K_M"));
+        assert!(!caps.code_chat_models.contains_key("starcoder2-3b-Q4_K_M"));
+        assert!(!caps.code_completion_models.contains_key("my-finetune-q8_0"));
+        assert!(caps.code_chat_models["my-finetune-q8_0"].supports_tools);
+
+        let mut caps_llama_cpp = CodeAssistantCaps { endpoint_style: "llama.cpp".to_string(), ..Default::default() };
+        apply_discovered_models(&mut caps_llama_cpp, &discovered, &r0);
+        assert!(caps_llama_cpp.code_chat_models.is_empty());
+        assert!(caps_llama_cpp.code_chat_default_model.is_empty());
+        caps_llama_cpp.chat_endpoint_style = "openai".to_string();
+        apply_discovered_models(&mut caps_llama_cpp, &discovered, &r0);
+        assert!(caps_llama_cpp.code_chat_models.contains_key("my-finetune-q8_0"));
+
+        assert_eq!(normalized_model_name("deepseek-coder-1.3b-base.Q4_K_M.gguf"), "deepseekcoder13bbase");
+        assert_eq!(normalized_model_name("CodeLlama-7b-hf"), "codellama7bhf");
+    }
+}
@@ -1,10 +1,24 @@
+use std::collections::HashMap;
 use std::fs;
 #[cfg(not(windows))]
 use std::os::unix::fs::PermissionsExt;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+use ignore::gitignore::Gitignore;
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock as ARwLock;
+use tokio::time::Duration;
+use tracing::{error, info};
+
+use crate::ast::treesitter::parsers::get_language_id_by_filename;
+use crate::global_context::GlobalContext;
 
 const LARGE_FILE_SIZE_THRESHOLD: u64 = 180*1024; // 180k files (180k is ~0.2% of all files on our dataset)
 const SMALL_FILE_SIZE_THRESHOLD: u64 = 5;        // 5 Bytes
+const SKIPPED_EXAMPLES_MAX: usize = 20;
+const INDEXING_TOO_OLD: Duration = Duration::from_secs(3);
+pub const INDEXING_YAML: &str = "indexing.yaml";  // in the cache dir, and optionally in <workspace>/.refact/ to override
 
 const SOURCE_FILE_EXTENSIONS: &[&str] = &[
     "c", "cpp", "cc", "h", "hpp", "cs", "java", "py", "rb", "go", "rs", "swift",
@@ -16,6 +30,9 @@ const SOURCE_FILE_EXTENSIONS: &[&str] = &[
     "cmake", "gradle",
 ];
 
+pub const GITIGNORE_FILE: &str = ".gitignore";
+pub const REFACTIGNORE_FILE: &str = ".refactignore";  // same syntax, excludes from AST and vecdb only
+
 pub(crate) const BLACKLISTED_DIRS: &[&str] = &[
     "target", "node_modules", "vendor", "build", "dist",
     "bin", "pkg", "lib", "lib64", "obj",
@@ -23,7 +40,254 @@ pub(crate) const BLACKLISTED_DIRS: &[&str] = &[
     "coverage", "backup", "__pycache__",
 ];
 
-pub fn is_valid_file(path: &PathBuf) -> Result<(), Box<dyn std::error::Error>> {
+fn default_extensions() -> Vec<String> {
+    SOURCE_FILE_EXTENSIONS.iter().map(|x| x.to_string()).collect()
+}
+
+fn default_min_file_size() -> u64 {
+    SMALL_FILE_SIZE_THRESHOLD
+}
+
+fn default_max_file_size() -> u64 {
+    LARGE_FILE_SIZE_THRESHOLD
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct IndexingSettings {
+    #[serde(default = "default_extensions")]
+    pub extensions: Vec<String>,
+    #[serde(default)]
+    pub additional_extensions: Vec<String>,
+    #[serde(default = "default_min_file_size")]
+    pub min_file_size: u64,
+    #[serde(default)]
+    pub ast: IndexingTargetSettings,
+    #[serde(default)]
+    pub vecdb: IndexingTargetSettings,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct IndexingTargetSettings {
+    #[serde(default = "default_max_file_size")]
+    pub max_file_size: u64,
+    #[serde(default)]
+    pub disabled_languages: Vec<String>,  // "cpp", "python", ... or a file extension for the rest
+}
+
+impl Default for IndexingSettings {
+    fn default() -> Self {
+        IndexingSettings {
+            extensions: default_extensions(),
+            additional_extensions: vec![],
+            min_file_size: default_min_file_size(),
+            ast: IndexingTargetSettings::default(),
+            vecdb: IndexingTargetSettings::default(),
+        }
+    }
+}
+
+impl Default for IndexingTargetSettings {
+    fn default() -> Self {
+        IndexingTargetSettings {
+            max_file_size: default_max_file_size(),
+            disabled_languages: vec![],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IndexingTarget {
+    Ast,
+    Vecdb,
+}
+
+fn file_language(path: &PathBuf) -> String {
+    match get_language_id_by_filename(path) {
+        Some(language_id) => language_id.to_string(),
+        None => path.extension().map(|x| x.to_string_lossy().to_lowercase()).unwrap_or_default(),
+    }
+}
+
+impl IndexingSettings {
+    fn target(&self, target: IndexingTarget) -> &IndexingTargetSettings {
+        match target {
+            IndexingTarget::Ast => &self.ast,
+            IndexingTarget::Vecdb => &self.vecdb,
+        }
+    }
+
+    fn has_extension(&self, extension: &str) -> bool {
+        self.extensions.iter().chain(self.additional_extensions.iter()).any(|x| x == extension)
+    }
+
+    // The checks that is_valid_file() can't do, because they differ between AST and vecdb
+    pub fn skip_reason(&self, path: &PathBuf, target: IndexingTarget) -> Option<String> {
+        let target_settings = self.target(target);
+        let language = file_language(path);
+        if target_settings.disabled_languages.contains(&language) {
+            return Some(format!("Language {} is disabled for {:?}", language, target));
+        }
+        let file_size = fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+        if file_size > target_settings.max_file_size {
+            return Some(format!("File size is too large for {:?}", target));
+        }
+        None
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct IndexingConfig {
+    pub global: IndexingSettings,
+    pub per_workspace: Vec<(PathBuf, IndexingSettings)>,
+    pub loaded_ts: u64,
+}
+
+impl IndexingConfig {
+    pub fn for_path(&self, path: &Path) -> &IndexingSettings {
+        self.per_workspace.iter()
+            .filter(|(folder, _)| path.starts_with(folder))
+            .max_by_key(|(folder, _)| folder.as_os_str().len())
+            .map(|(_, settings)| settings)
+            .unwrap_or(&self.global)
+    }
+}
+
+fn merge_yaml(base: &mut serde_yaml::Value, over: serde_yaml::Value) {
+    match (base, over) {
+        (serde_yaml::Value::Mapping(base_map), serde_yaml::Value::Mapping(over_map)) => {
+            for (k, v) in over_map {
+                match base_map.get_mut(&k) {
+                    Some(base_v) => merge_yaml(base_v, v),
+                    None => { base_map.insert(k, v); }
+                }
+            }
+        }
+        (base, over) => *base = over,
+    }
+}
+
+async fn read_indexing_yaml(path: &Path) -> Option<serde_yaml::Value> {
+    let content = tokio::fs::read_to_string(path).await.ok()?;
+    match serde_yaml::from_str::<serde_yaml::Value>(&content) {
+        Ok(serde_yaml::Value::Null) => None,
+        Ok(value) => Some(value),
+        Err(e) => {
+            error!("parsing {} failed\n{}", path.display(), e);
+            None
+        }
+    }
+}
+
+fn indexing_settings_from_yaml(value: serde_yaml::Value, path: &Path) -> IndexingSettings {
+    serde_yaml::from_value(value).unwrap_or_else(|e| {
+        error!("{}: {}, using defaults", path.display(), e);
+        IndexingSettings::default()
+    })
+}
+
+pub async fn load_indexing_config_if_needed(gcx: Arc<ARwLock<GlobalContext>>) -> Arc<IndexingConfig>
+{
+    let current_time = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs();
+    let (global_path, workspace_folders) = {
+        let gcx_locked = gcx.read().await;
+        let should_reload = gcx_locked.indexing_config.loaded_ts + INDEXING_TOO_OLD.as_secs() <= current_time;
+        if !should_reload {
+            return gcx_locked.indexing_config.clone();
+        }
+        let workspace_folders = gcx_locked.documents_state.workspace_folders.lock().unwrap().clone();
+        (gcx_locked.cache_dir.join(INDEXING_YAML), workspace_folders)
+    };
+
+    let global_yaml = read_indexing_yaml(&global_path).await.unwrap_or(serde_yaml::Value::Mapping(Default::default()));
+    let mut config = IndexingConfig {
+        global: indexing_settings_from_yaml(global_yaml.clone(), &global_path),
+        per_workspace: vec![],
+        loaded_ts: current_time,
+    };
+    for folder in workspace_folders {
+        let path = folder.join(".refact").join(INDEXING_YAML);
+        if let Some(over) = read_indexing_yaml(&path).await {
+            let mut merged = global_yaml.clone();
+            merge_yaml(&mut merged, over);
+            config.per_workspace.push((folder, indexing_settings_from_yaml(merged, &path)));
+        }
+    }
+
+    let mut gcx_locked = gcx.write().await;
+    gcx_locked.indexing_config = Arc::new(config);
+    gcx_locked.indexing_config.clone()
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct SkippedReason {
+    pub count: usize,
+    pub examples: Vec<String>,
+}
+
+pub fn skipped_add(reasons: &mut HashMap<String, SkippedReason>, reason: &str, path: &Path) {
+    let r = reasons.entry(reason.to_string()).or_default();
+    r.count += 1;
+    if r.examples.len() < SKIPPED_EXAMPLES_MAX {
+        r.examples.push(path.to_string_lossy().to_string());
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct IndexingSkipped {
+    pub workspace: HashMap<String, SkippedReason>,  // didn't make it into workspace files, replaced on every full scan
+    pub ast: HashMap<PathBuf, String>,  // currently skipped, a file leaves the map when it gets indexed again
+    pub vecdb: HashMap<PathBuf, String>,
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct SkippedFilesReport {
+    pub workspace: HashMap<String, SkippedReason>,
+    pub ast: HashMap<String, SkippedReason>,
+    pub vecdb: HashMap<String, SkippedReason>,
+}
+
+impl IndexingSkipped {
+    pub fn report(&self) -> SkippedFilesReport {
+        let group = |skipped: &HashMap<PathBuf, String>| {
+            let mut reasons = HashMap::new();
+            let mut sorted: Vec<_> = skipped.iter().collect();
+            sorted.sort();
+            for (path, reason) in sorted {
+                skipped_add(&mut reasons, reason, path);
+            }
+            reasons
+        };
+        SkippedFilesReport {
+            workspace: self.workspace.clone(),
+            ast: group(&self.ast),
+            vecdb: group(&self.vecdb),
+        }
+    }
+}
+
+pub async fn check_indexing_target(gcx: Arc<ARwLock<GlobalContext>>, path: &PathBuf, target: IndexingTarget) -> Result<(), String>
+{
+    let indexing_config = load_indexing_config_if_needed(gcx.clone()).await;
+    let reason = indexing_config.for_path(path).skip_reason(path, target);
+    let skipped_arc = gcx.read().await.documents_state.indexing_skipped.clone();
+    let mut skipped = skipped_arc.lock().unwrap();
+    let skipped_for_target = match target {
+        IndexingTarget::Ast => &mut skipped.ast,
+        IndexingTarget::Vecdb => &mut skipped.vecdb,
+    };
+    match reason {
+        Some(reason) => {
+            skipped_for_target.insert(path.clone(), reason.clone());
+            Err(reason)
+        }
+        None => {
+            skipped_for_target.remove(path);
+            Ok(())
+        }
+    }
+}
+
+pub fn is_valid_file(path: &PathBuf, settings: &IndexingSettings) -> Result<(), Box<dyn std::error::Error>> {
     if !path.is_file() {
         return Err("Path is not a file".into());
     }
@@ -37,7 +301,7 @@ pub fn is_valid_file(path: &PathBuf) -> Result<(), Box<dyn std::error::Error>> {
     }
 
     if let Some(extension) = path.extension() {
-        if !SOURCE_FILE_EXTENSIONS.contains(&extension.to_str().unwrap_or_default()) {
+        if !settings.has_extension(extension.to_str().unwrap_or_default()) {
             return Err(format!("Unsupported file extension {:?}", extension).into());
         }
     } else {
@@ -46,10 +310,10 @@ pub fn is_valid_file(path: &PathBuf) -> Result<(), Box<dyn std::error::Error>> {
 
     if let Ok(metadata) = fs::metadata(path) {
         let file_size = metadata.len();
-        if file_size < SMALL_FILE_SIZE_THRESHOLD {
+        if file_size < settings.min_file_size {
             return Err("File size is too small".into());
         }
-        if file_size > LARGE_FILE_SIZE_THRESHOLD {
+        if file_size > settings.ast.max_file_size.max(settings.vecdb.max_file_size) {
             return Err("File size is too large".into());
         }
         #[cfg(not(windows))]
@@ -83,3 +347,191 @@ pub fn is_this_inside_blacklisted_dir(path: &PathBuf) -> bool {
     false
 }
 
+
+pub struct IgnoreFiles {
+    // Nested ignore files with gitignore semantics: the deepest file that has an opinion about the path wins,
+    // inside one file the last matching line wins, "!pattern" brings a path back.
+    filename: &'static str,
+    roots: Vec<PathBuf>,
+    per_dir: HashMap<PathBuf, Option<Gitignore>>,
+}
+
+impl IgnoreFiles {
+    pub fn new(filename: &'static str, roots: &[PathBuf]) -> Self {
+        IgnoreFiles {
+            filename,
+            roots: roots.to_vec(),
+            per_dir: HashMap::new(),
+        }
+    }
+
+    fn matcher_for_dir(&mut self, dir: &Path) -> Option<&Gitignore> {
+        let filename = self.filename;
+        self.per_dir.entry(dir.to_path_buf()).or_insert_with(|| {
+            let ignore_file = dir.join(filename);
+            if !ignore_file.is_file() {
+                return None;
+            }
+            let (gitignore, err) = Gitignore::new(&ignore_file);
+            if let Some(err) = err {
+                info!("problem in {}: {}", ignore_file.display(), err);
+            }
+            Some(gitignore)
+        }).as_ref()
+    }
+
+    fn forget(&mut self, ignore_file: &Path) -> bool {
+        if ignore_file.file_name() != Some(std::ffi::OsStr::new(self.filename)) {
+            return false;
+        }
+        if let Some(dir) = ignore_file.parent() {
+            self.per_dir.remove(dir);
+        }
+        true
+    }
+
+    fn top_dir_for(&self, path: &Path) -> Option<PathBuf> {
+        // workspace folder, otherwise the repository the file is in
+        if let Some(root) = self.roots.iter().filter(|r| path.starts_with(r)).max_by_key(|r| r.as_os_str().len()) {
+            return Some(root.clone());
+        }
+        path.ancestors().skip(1).find(|dir| dir.join(".git").exists()).map(|dir| dir.to_path_buf())
+    }
+
+    pub fn is_ignored(&mut self, path: &Path, is_dir: bool) -> bool {
+        let top_dir = self.top_dir_for(path);
+        let dirs: Vec<PathBuf> = match &top_dir {
+            Some(top_dir) => path.ancestors().skip(1).take_while(|dir| dir.starts_with(top_dir)).map(|dir| dir.to_path_buf()).collect(),
+            None => path.parent().map(|dir| vec![dir.to_path_buf()]).unwrap_or_default(),
+        };
+        for dir in dirs {  // deepest first
+            if let Some(matcher) = self.matcher_for_dir(&dir) {
+                let m = matcher.matched_path_or_any_parents(path, is_dir);
+                if m.is_ignore() {
+                    return true;
+                }
+                if m.is_whitelist() {
+                    return false;
+                }
+            }
+        }
+        false
+    }
+}
+
+pub struct IndexingIgnore {
+    pub gitignore: IgnoreFiles,
+    pub refactignore: IgnoreFiles,
+}
+
+impl IndexingIgnore {
+    pub fn new(workspace_folders: &[PathBuf]) -> Self {
+        IndexingIgnore {
+            gitignore: IgnoreFiles::new(GITIGNORE_FILE, workspace_folders),
+            refactignore: IgnoreFiles::new(REFACTIGNORE_FILE, workspace_folders),
+        }
+    }
+
+    pub fn is_gitignored(&mut self, path: &Path, is_dir: bool) -> bool {
+        self.gitignore.is_ignored(path, is_dir)
+    }
+
+    pub fn is_excluded_from_indexing(&mut self, path: &Path) -> bool {
+        self.gitignore.is_ignored(path, false) || self.refactignore.is_ignored(path, false)
+    }
+
+    pub fn roots(&self) -> &[PathBuf] {
+        &self.gitignore.roots
+    }
+
+    pub fn ignore_file_changed(&mut self, path: &Path) -> bool {
+        // the next lookup reads the changed file again
+        self.gitignore.forget(path) || self.refactignore.forget(path)
+    }
+}
+
+pub async fn is_excluded_from_indexing(gcx: Arc<ARwLock<GlobalContext>>, path: &Path) -> bool
+{
+    // one matcher for the workspace, built again when workspace folders change
+    let (workspace_folders, ignore_arc) = {
+        let gcx_locked = gcx.read().await;
+        let workspace_folders = gcx_locked.documents_state.workspace_folders.lock().unwrap().clone();
+        (workspace_folders, gcx_locked.documents_state.indexing_ignore.clone())
+    };
+    let mut ignore = ignore_arc.lock().unwrap();
+    if ignore.roots() != workspace_folders.as_slice() {
+        *ignore = IndexingIgnore::new(&workspace_folders);
+    }
+    ignore.is_excluded_from_indexing(path)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nested_ignore_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path().to_path_buf();
+        fs::create_dir_all(root.join("src/generated")).unwrap();
+        fs::create_dir_all(root.join("tests/fixtures")).unwrap();
+        fs::write(root.join(".gitignore"), "*.log\n/build/\ngenerated/\n").unwrap();
+        fs::write(root.join("src/.gitignore"), "!debug.log\n").unwrap();
+        fs::write(root.join(".refactignore"), "tests/fixtures/\n*.pb.go\n").unwrap();
+
+        let mut ignore = IndexingIgnore::new(std::slice::from_ref(&root));
+        assert!(ignore.is_gitignored(&root.join("server.log"), false));
+        assert!(!ignore.is_gitignored(&root.join("src/debug.log"), false));
+        assert!(ignore.is_gitignored(&root.join("src/other.log"), false));
+        assert!(ignore.is_gitignored(&root.join("build"), true));
+        assert!(!ignore.is_gitignored(&root.join("src/build"), true));  // anchored to the root
+        assert!(ignore.is_gitignored(&root.join("src/generated/api.rs"), false));
+        assert!(!ignore.is_gitignored(&root.join("src/main.rs"), false));
+
+        assert!(ignore.is_excluded_from_indexing(&root.join("tests/fixtures/big.json")));
+        assert!(ignore.is_excluded_from_indexing(&root.join("src/api.pb.go")));
+        assert!(ignore.is_excluded_from_indexing(&root.join("src/generated/api.rs")));
+        assert!(!ignore.is_gitignored(&root.join("tests/fixtures/big.json"), false));  // still visible, just not indexed
+        assert!(!ignore.is_excluded_from_indexing(&root.join("tests/test_main.rs")));
+
+        // cached until told that an ignore file changed
+        fs::write(root.join("tests/.refactignore"), "test_*.rs\n").unwrap();
+        assert!(!ignore.is_excluded_from_indexing(&root.join("tests/test_main.rs")));
+        assert!(!ignore.ignore_file_changed(&root.join("tests/test_main.rs")));
+        assert!(ignore.ignore_file_changed(&root.join("tests/.refactignore")));
+        assert!(ignore.is_excluded_from_indexing(&root.join("tests/test_main.rs")));
+        fs::write(root.join(".gitignore"), "/build/\n").unwrap();
+        assert!(ignore.is_gitignored(&root.join("server.log"), false));
+        assert!(ignore.ignore_file_changed(&root.join(".gitignore")));
+        assert!(!ignore.is_gitignored(&root.join("server.log"), false));
+    }
+
+    #[test]
+    fn test_indexing_settings() {
+        let tmp = tempfile::Builder::new().prefix("indexing").tempdir().unwrap();  // is_valid_file() rejects dirs starting with a dot
+        let root = tmp.path().to_path_buf();
+        fs::write(root.join("main.py"), "print('hello')\n".repeat(10)).unwrap();
+        fs::write(root.join("api.proto"), "syntax = \"proto3\";\n").unwrap();
+
+        let mut global: serde_yaml::Value = serde_yaml::from_str(crate::indexing_compiled_in::COMPILED_IN_INITIAL_INDEXING_YAML).unwrap();
+        merge_yaml(&mut global, serde_yaml::from_str("additional_extensions: [\"proto\"]\nast:\n  disabled_languages: [\"python\"]\nvecdb:\n  max_file_size: 100\n").unwrap());
+        let settings = indexing_settings_from_yaml(global, &root);
+        assert_eq!(settings.ast.max_file_size, 184320);  // untouched keys stay as they were
+        assert_eq!(settings.min_file_size, 5);
+
+        assert!(is_valid_file(&root.join("api.proto"), &settings).is_ok());
+        assert!(is_valid_file(&root.join("api.proto"), &IndexingSettings::default()).is_err());
+        assert_eq!(settings.skip_reason(&root.join("main.py"), IndexingTarget::Ast), Some("Language python is disabled for Ast".to_string()));
+        assert_eq!(settings.skip_reason(&root.join("main.py"), IndexingTarget::Vecdb), Some("File size is too large for Vecdb".to_string()));
+        assert_eq!(settings.skip_reason(&root.join("api.proto"), IndexingTarget::Ast), None);
+
+        let config = IndexingConfig {
+            global: IndexingSettings::default(),
+            per_workspace: vec![(root.clone(), settings)],
+            loaded_ts: 0,
+        };
+        assert_eq!(config.for_path(&root.join("api.proto")).additional_extensions, vec!["proto".to_string()]);
+        assert!(config.for_path(Path::new("/somewhere/else.rs")).additional_extensions.is_empty());
+    }
+}
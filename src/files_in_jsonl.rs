@@ -10,6 +10,7 @@ use tokio::io::AsyncBufReadExt;
 use tokio::io::BufReader;
 use tokio::sync::RwLock as ARwLock;
 
+use crate::file_filter::IndexingIgnore;
 use crate::files_in_workspace::Document;
 use crate::global_context::GlobalContext;
 use crate::ast::ast_indexer_thread::ast_indexer_enqueue_files;
@@ -24,9 +25,15 @@ pub async fn enqueue_all_docs_from_jsonl(
     if paths.is_empty() {
         return;
     }
-    let mut docs: Vec<Document> = vec![];
-    for d in paths.iter() {
-        docs.push(Document { doc_path: d.clone(), doc_text: None });
+    let workspace_folders = gcx.read().await.documents_state.workspace_folders.lock().unwrap().clone();
+    let mut ignore = IndexingIgnore::new(&workspace_folders);
+    let paths: Vec<PathBuf> = paths.into_iter().filter(|p| !ignore.is_gitignored(p, false)).collect();
+    let docs: Vec<Document> = paths.iter()
+        .filter(|p| !ignore.is_excluded_from_indexing(p))
+        .map(|p| Document { doc_path: p.clone(), doc_text: None })
+        .collect();
+    if docs.len() != paths.len() {
+        info!("jsonl: {} files excluded from indexing by .refactignore", paths.len() - docs.len());
     }
     let (vec_db_module, ast_service) = {
         let gcx_locked = gcx.write().await;
@@ -3,7 +3,7 @@ use std::fs;
 use std::hash::Hash;
 use std::path::PathBuf;
 use std::sync::{Arc, Weak, Mutex as StdMutex};
-use std::time::Instant;
+use std::time::{Duration, Instant};
 use notify::{Config, Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
 use notify::event::{CreateKind, DataChange, ModifyKind, RemoveKind};
 use ropey::Rope;
@@ -14,7 +14,7 @@ use tracing::info;
 
 use crate::global_context::GlobalContext;
 use crate::telemetry;
-use crate::file_filter::{is_this_inside_blacklisted_dir, is_valid_file, BLACKLISTED_DIRS};
+use crate::file_filter::{is_excluded_from_indexing, is_this_inside_blacklisted_dir, is_valid_file, load_indexing_config_if_needed, skipped_add, IndexingConfig, IndexingIgnore, IndexingSkipped, SkippedReason, BLACKLISTED_DIRS};
 use crate::ast::ast_indexer_thread::ast_indexer_enqueue_files;
 use crate::privacy::{check_file_privacy, load_privacy_if_needed, PrivacySettings, FilePrivacyLevel};
 
@@ -98,6 +98,109 @@ impl Document {
     }
 }
 
+// Edit as it comes from LSP incremental sync: positions are (line, character) where character counts
+// UTF-16 code units, no range means the whole text is replaced
+#[derive(Debug, Clone)]
+pub struct DocumentEdit {
+    pub range: Option<((usize, usize), (usize, usize))>,
+    pub text: String,
+}
+
+pub fn lsp_position_to_char_idx(rope: &Rope, line: usize, character_utf16: usize) -> usize {
+    if line >= rope.len_lines() {
+        return rope.len_chars();
+    }
+    let mut char_idx = rope.line_to_char(line);
+    let mut utf16_seen = 0;
+    for c in rope.line(line).chars() {
+        // position past the end of line means the end of line, not the next line
+        if utf16_seen >= character_utf16 || c == '\n' || c == '\r' {
+            break;
+        }
+        utf16_seen += c.len_utf16();
+        char_idx += 1;
+    }
+    char_idx
+}
+
+pub fn apply_edits_to_rope(rope: &mut Rope, edits: &[DocumentEdit]) {
+    for edit in edits {
+        match edit.range {
+            Some(((line1, char1), (line2, char2))) => {
+                let start = lsp_position_to_char_idx(rope, line1, char1);
+                let end = lsp_position_to_char_idx(rope, line2, char2).max(start);
+                rope.remove(start..end);
+                rope.insert(start, &edit.text);
+            }
+            None => {
+                *rope = Rope::from_str(&edit.text);
+            }
+        }
+    }
+}
+
+// Typing sends didChange for every key, indexing waits until the document is quiet for this long
+const DID_CHANGE_REINDEX_DEBOUNCE: Duration = Duration::from_millis(1000);
+
+pub async fn on_did_change_incremental(
+    gcx: Arc<ARwLock<GlobalContext>>,
+    path: &PathBuf,
+    edits: &[DocumentEdit],
+) -> Result<String, String> {
+    let (doc_arc, dirty_arc, mark_dirty) = get_or_create_document(gcx.clone(), path).await;
+    let text = {
+        // notifications can be handled concurrently, read-edit-write under one lock so no edit is lost
+        let mut doc = doc_arc.write().await;
+        let mut rope = if edits.first().map(|x| x.range.is_none()).unwrap_or(false) {
+            Rope::new()
+        } else {
+            match &doc.doc_text {
+                Some(text) => text.clone(),
+                None => read_file_from_disk_without_privacy_check(path).await
+                    .map_err(|e| format!("cannot apply edits, no text in memory and on disk: {}", e))?,
+            }
+        };
+        apply_edits_to_rope(&mut rope, edits);
+        let text = rope.to_string();
+        doc.doc_text = Some(rope);
+        text
+    };
+    if mark_dirty {
+        (*dirty_arc.lock().await) = true;
+    }
+
+    let (generation, pending_reindex) = {
+        let mut gcx_locked = gcx.write().await;
+        gcx_locked.documents_state.active_file_path = Some(path.clone());
+        let pending_reindex = gcx_locked.documents_state.pending_reindex.clone();
+        let mut pending_locked = pending_reindex.lock().unwrap();
+        let generation = pending_locked.entry(path.clone()).or_insert(0);
+        *generation += 1;
+        (*generation, pending_reindex.clone())
+    };
+    let gcx_weak = Arc::downgrade(&gcx);
+    let path = path.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(DID_CHANGE_REINDEX_DEBOUNCE).await;
+        {
+            let mut pending_locked = pending_reindex.lock().unwrap();
+            if pending_locked.get(&path) != Some(&generation) {
+                return;  // more edits came, the last one will reindex
+            }
+            pending_locked.remove(&path);
+        }
+        let gcx = match gcx_weak.upgrade() {
+            Some(gcx) => gcx,
+            None => return,
+        };
+        let text_maybe = doc_arc.read().await.doc_text.as_ref().map(|x| x.to_string());
+        if let Some(text) = text_maybe {
+            reindex_changed_document(gcx, &path, &text).await;
+        }
+    });
+    Ok(text)
+}
+
 pub struct DocumentsState {
     pub workspace_folders: Arc<StdMutex<Vec<PathBuf>>>,
     pub workspace_files: Arc<StdMutex<Vec<PathBuf>>>,
@@ -111,6 +214,21 @@ pub struct DocumentsState {
     pub cache_shortened: Arc<HashSet<String>>,
     pub fs_watcher: Arc<ARwLock<RecommendedWatcher>>,
     pub diffs_applied_state: HashMap<u64, Vec<bool>>,
+    pub indexing_skipped: Arc<StdMutex<IndexingSkipped>>,
+    pub pending_reindex: Arc<StdMutex<HashMap<PathBuf, u64>>>,  // didChange counter per document, for the debounce
+    pub indexing_ignore: Arc<StdMutex<IndexingIgnore>>,  // parsed .gitignore and .refactignore files, see is_excluded_from_indexing()
+}
+
+async fn get_or_create_document(
+    gcx: Arc<ARwLock<GlobalContext>>,
+    path: &PathBuf,
+) -> (Arc<ARwLock<Document>>, Arc<AMutex<bool>>, bool) {
+    let mut cx = gcx.write().await;
+    let cache_dirty = cx.documents_state.cache_dirty.clone();
+    match cx.documents_state.memory_document_map.entry(path.clone()) {
+        std::collections::hash_map::Entry::Occupied(e) => (e.get().clone(), cache_dirty, false),
+        std::collections::hash_map::Entry::Vacant(e) => (e.insert(Arc::new(ARwLock::new(Document::new(path)))).clone(), cache_dirty, true),
+    }
 }
 
 async fn overwrite_or_create_document(
@@ -136,7 +254,7 @@ impl DocumentsState {
     ) -> Self {
         let watcher = RecommendedWatcher::new(|_|{}, Default::default()).unwrap();
         Self {
-            workspace_folders: Arc::new(StdMutex::new(workspace_dirs)),
+            workspace_folders: Arc::new(StdMutex::new(workspace_dirs.clone())),
             workspace_files: Arc::new(StdMutex::new(Vec::new())),
             active_file_path: None,
             jsonl_files: Arc::new(StdMutex::new(Vec::new())),
@@ -146,6 +264,9 @@ impl DocumentsState {
             cache_shortened: Arc::new(HashSet::<String>::new()),
             fs_watcher: Arc::new(ARwLock::new(watcher)),
             diffs_applied_state: HashMap::new(),
+            indexing_skipped: Arc::new(StdMutex::new(IndexingSkipped::default())),
+            pending_reindex: Arc::new(StdMutex::new(HashMap::new())),
+            indexing_ignore: Arc::new(StdMutex::new(IndexingIgnore::new(&workspace_dirs))),
         }
     }
 
@@ -322,21 +443,32 @@ pub async fn detect_vcs_for_a_file_path(file_path: &PathBuf) -> Option<(PathBuf,
 //         .unwrap_or(false)
 // }
 
-async fn ls_files_under_version_control_recursive(path: PathBuf) -> Vec<PathBuf> {
+async fn ls_files_under_version_control_recursive(
+    path: PathBuf,
+    indexing_config: &IndexingConfig,
+    rejected_reasons: &mut HashMap<String, SkippedReason>,
+) -> Vec<PathBuf> {
     let mut paths: Vec<PathBuf> = vec![];
+    let mut ignore = IndexingIgnore::new(std::slice::from_ref(&path));
+    let settings = indexing_config.for_path(&path);
     let mut candidates: Vec<PathBuf> = vec![path];
-    let mut rejected_reasons: HashMap<String, usize> = HashMap::new();
     let mut blacklisted_dirs_cnt: usize = 0;
+    let mut gitignored_cnt: usize = 0;
     while !candidates.is_empty() {
         let local_path = candidates.pop().unwrap();
+        if ignore.is_gitignored(&local_path, local_path.is_dir()) {
+            gitignored_cnt += 1;
+            skipped_add(rejected_reasons, "Excluded by .gitignore", &local_path);
+            continue;
+        }
         if local_path.is_file() {
-            let maybe_valid = is_valid_file(&local_path);
+            let maybe_valid = is_valid_file(&local_path, settings);
             match maybe_valid {
                 Ok(_) => {
                     paths.push(local_path.clone());
                 }
                 Err(e) => {
-                    rejected_reasons.entry(e.to_string()).and_modify(|x| *x += 1).or_insert(1);
+                    skipped_add(rejected_reasons, &e.to_string(), &local_path);
                     continue;
                 }
             }
@@ -344,18 +476,24 @@ async fn ls_files_under_version_control_recursive(path: PathBuf) -> Vec<PathBuf>
         if local_path.is_dir() {
             if BLACKLISTED_DIRS.contains(&local_path.file_name().unwrap().to_str().unwrap()) {
                 blacklisted_dirs_cnt += 1;
+                skipped_add(rejected_reasons, "Blacklisted directory", &local_path);
                 continue;
             }
             let maybe_files = ls_files_under_version_control(&local_path).await;
             if let Some(v) = maybe_files {
                 for x in v.iter() {
-                    let maybe_valid = is_valid_file(x);
+                    if ignore.is_gitignored(x, false) {
+                        gitignored_cnt += 1;
+                        skipped_add(rejected_reasons, "Excluded by .gitignore", x);
+                        continue;
+                    }
+                    let maybe_valid = is_valid_file(x, settings);
                     match maybe_valid {
                         Ok(_) => {
                             paths.push(x.clone());
                         }
                         Err(e) => {
-                            rejected_reasons.entry(e.to_string()).and_modify(|x| *x += 1).or_insert(1);
+                            skipped_add(rejected_reasons, &e.to_string(), x);
                         }
                     }
                 }
@@ -371,23 +509,40 @@ async fn ls_files_under_version_control_recursive(path: PathBuf) -> Vec<PathBuf>
         }
     }
     info!("rejected files reasons:");
-    for (reason, count) in &rejected_reasons {
-        info!("    {:>6} {}", count, reason);
+    for (reason, skipped) in rejected_reasons.iter() {
+        info!("    {:>6} {}", skipped.count, reason);
     }
     if rejected_reasons.is_empty() {
         info!("    no bad files at all");
     }
-    info!("also the loop bumped into {} blacklisted dirs", blacklisted_dirs_cnt);
+    info!("also the loop bumped into {} blacklisted dirs, {} paths excluded by .gitignore", blacklisted_dirs_cnt, gitignored_cnt);
     paths
 }
 
-pub async fn retrieve_files_in_workspace_folders(proj_folders: Vec<PathBuf>) -> Vec<PathBuf> {
+fn documents_to_index(workspace_folders: &[PathBuf], paths: &[PathBuf]) -> Vec<Document> {
+    // .refactignore hides files from AST and vecdb, @file and the tools still can see them
+    let mut ignore = IndexingIgnore::new(workspace_folders);
+    let documents: Vec<Document> = paths.iter()
+        .filter(|p| !ignore.is_excluded_from_indexing(p))
+        .map(|p| Document { doc_path: p.clone(), doc_text: None })
+        .collect();
+    if documents.len() != paths.len() {
+        info!("{} files excluded from indexing by .refactignore", paths.len() - documents.len());
+    }
+    documents
+}
+
+pub async fn retrieve_files_in_workspace_folders(
+    proj_folders: Vec<PathBuf>,
+    indexing_config: &IndexingConfig,
+) -> (Vec<PathBuf>, HashMap<String, SkippedReason>) {
     let mut all_files: Vec<PathBuf> = Vec::new();
+    let mut rejected_reasons: HashMap<String, SkippedReason> = HashMap::new();
     for proj_folder in proj_folders {
-        let files = ls_files_under_version_control_recursive(proj_folder.clone()).await;
+        let files = ls_files_under_version_control_recursive(proj_folder.clone(), indexing_config, &mut rejected_reasons).await;
         all_files.extend(files);
     }
-    all_files
+    (all_files, rejected_reasons)
 }
 
 async fn enqueue_some_docs(
@@ -423,13 +578,11 @@ pub async fn enqueue_all_files_from_workspace_folders(
     let folders: Vec<PathBuf> = gcx.read().await.documents_state.workspace_folders.lock().unwrap().clone();
 
     info!("enqueue_all_files_from_workspace_folders started files search with {} folders", folders.len());
-    let paths = retrieve_files_in_workspace_folders(folders).await;
+    let indexing_config = load_indexing_config_if_needed(gcx.clone()).await;
+    let (paths, rejected_reasons) = retrieve_files_in_workspace_folders(folders.clone(), &indexing_config).await;
     info!("enqueue_all_files_from_workspace_folders found {} files => workspace_files", paths.len());
 
-    let mut documents: Vec<Document> = vec![];
-    for d in paths.iter() {
-        documents.push(Document { doc_path: d.clone(), doc_text: None });
-    }
+    let documents = documents_to_index(&folders, &paths);
 
     let (vec_db_module, ast_service, removed_old) = {
         let cx = gcx.write().await;
@@ -438,6 +591,7 @@ pub async fn enqueue_all_files_from_workspace_folders(
         let mut old_workspace_files = Vec::new();
         std::mem::swap(&mut *workspace_files, &mut old_workspace_files);
         workspace_files.extend(paths);
+        cx.documents_state.indexing_skipped.lock().unwrap().workspace = rejected_reasons;
         (cx.vec_db.clone(), cx.ast_service.clone(), old_workspace_files)
     };
     info!("detected {} deleted files", removed_old.len());
@@ -449,6 +603,9 @@ pub async fn enqueue_all_files_from_workspace_folders(
     }
 
     if let Some(ref mut db) = *vec_db_module.lock().await {
+        if !documents.is_empty() {
+            db.remove_files_not_in_workspace(&documents).await;
+        }
         db.vectorizer_enqueue_files(&documents, force).await;
     }
     if let Some(ast) = ast_service {
@@ -521,17 +678,33 @@ pub async fn on_did_change(
     }
 
     gcx.write().await.documents_state.active_file_path = Some(path.clone());
+    let doc_path = doc_arc.read().await.doc_path.clone();
+    reindex_changed_document(gcx.clone(), &doc_path, text).await;
+    info!("on_did_change {}, total time {:.3}s", crate::nicer_logs::last_n_chars(&path.to_string_lossy().to_string(), 30), t0.elapsed().as_secs_f32());
+}
 
+async fn reindex_changed_document(
+    gcx: Arc<ARwLock<GlobalContext>>,
+    path: &PathBuf,
+    text: &String,
+) {
     let mut go_ahead = true;
     {
-        let is_it_good = is_valid_file(path);
+        let indexing_config = load_indexing_config_if_needed(gcx.clone()).await;
+        let is_it_good = is_valid_file(path, indexing_config.for_path(path));
         if is_it_good.is_err() {
             info!("{:?} ignoring changes: {}", path, is_it_good.err().unwrap());
             go_ahead = false;
         }
     }
+    if go_ahead {
+        if is_excluded_from_indexing(gcx.clone(), path).await {
+            info!("{:?} ignoring changes: excluded by .gitignore or .refactignore", path);
+            go_ahead = false;
+        }
+    }
 
-    let doc = Document { doc_path: doc_arc.read().await.doc_path.clone(), doc_text: None };
+    let doc = Document { doc_path: path.clone(), doc_text: None };
     if go_ahead {
         enqueue_some_docs(gcx.clone(), &vec![doc], false).await;
     }
@@ -541,8 +714,6 @@ pub async fn on_did_change(
         &path.to_string_lossy().to_string(),
         text,
     ).await;
-
-    info!("on_did_change {}, total time {:.3}s", crate::nicer_logs::last_n_chars(&path.to_string_lossy().to_string(), 30), t0.elapsed().as_secs_f32());
 }
 
 pub async fn on_did_delete(gcx: Arc<ARwLock<GlobalContext>>, path: &PathBuf)
@@ -574,8 +745,9 @@ pub async fn add_folder(gcx: Arc<ARwLock<GlobalContext>>, path: &PathBuf)
         documents_state.workspace_folders.lock().unwrap().push(path.clone());
         let _ = documents_state.fs_watcher.write().await.watch(&path.clone(), RecursiveMode::Recursive);
     }
-    let paths = retrieve_files_in_workspace_folders(vec![path.clone()]).await;
-    let docs: Vec<Document> = paths.into_iter().map(|p| Document { doc_path: p, doc_text: None }).collect();
+    let indexing_config = load_indexing_config_if_needed(gcx.clone()).await;
+    let (paths, _) = retrieve_files_in_workspace_folders(vec![path.clone()], &indexing_config).await;
+    let docs = documents_to_index(std::slice::from_ref(path), &paths);
     enqueue_some_docs(gcx, &docs, false).await;
 }
 
@@ -592,15 +764,25 @@ pub async fn remove_folder(gcx: Arc<ARwLock<GlobalContext>>, path: &PathBuf)
 pub async fn file_watcher_event(event: Event, gcx_weak: Weak<ARwLock<GlobalContext>>)
 {
     async fn on_create_modify(gcx_weak: Weak<ARwLock<GlobalContext>>, event: Event) {
+        let (gcx, indexing_config) = match gcx_weak.upgrade() {
+            Some(gcx) => {
+                let indexing_config = load_indexing_config_if_needed(gcx.clone()).await;
+                (gcx, indexing_config)
+            }
+            None => return,
+        };
         let mut docs = vec![];
         for p in &event.paths {
             if is_this_inside_blacklisted_dir(&p) {  // important to filter BEFORE canonical_path
                 continue;
             }
+            if is_excluded_from_indexing(gcx.clone(), p).await {
+                continue;
+            }
 
             let mut go_ahead = true;
             {
-                let is_it_good = is_valid_file(p);
+                let is_it_good = is_valid_file(p, indexing_config.for_path(p));
                 if is_it_good.is_err() {
                     // info!("{:?} ignoring changes: {}", p, is_it_good.err().unwrap());
                     go_ahead = false;
@@ -616,9 +798,7 @@ pub async fn file_watcher_event(event: Event, gcx_weak: Weak<ARwLock<GlobalConte
             return;
         }
         // info!("EventKind::Create/Modify {} paths", event.paths.len());
-        if let Some(gcx) = gcx_weak.clone().upgrade() {
-            enqueue_some_docs(gcx, &docs, false).await;
-        }
+        enqueue_some_docs(gcx, &docs, false).await;
     }
 
     async fn on_remove(gcx_weak: Weak<ARwLock<GlobalContext>>, event: Event) {
@@ -644,6 +824,17 @@ pub async fn file_watcher_event(event: Event, gcx_weak: Weak<ARwLock<GlobalConte
         }
     }
 
+    if let Some(gcx) = gcx_weak.upgrade() {
+        // created, edited, renamed or deleted .gitignore / .refactignore
+        let ignore_arc = gcx.read().await.documents_state.indexing_ignore.clone();
+        let mut ignore = ignore_arc.lock().unwrap();
+        for p in &event.paths {
+            if ignore.ignore_file_changed(p) {
+                info!("{} changed, indexing will use the new rules", p.display());
+            }
+        }
+    }
+
     match event.kind {
         EventKind::Any => {},
         EventKind::Access(_) => {},
@@ -654,3 +845,40 @@ pub async fn file_watcher_event(event: Event, gcx_weak: Weak<ARwLock<GlobalConte
         _ => {}
     }
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edit(line1: usize, char1: usize, line2: usize, char2: usize, text: &str) -> DocumentEdit {
+        DocumentEdit { range: Some(((line1, char1), (line2, char2))), text: text.to_string() }
+    }
+
+    #[test]
+    fn test_apply_edits_to_rope() {
+        let mut rope = Rope::from_str("fn main() {\n    println!(\"hi\");\n}\n");
+        apply_edits_to_rope(&mut rope, &[
+            edit(1, 14, 1, 16, "hello"),
+            edit(2, 1, 2, 1, " // end"),
+            edit(0, 0, 0, 0, "// start\n"),
+        ]);
+        assert_eq!(rope.to_string(), "// start\nfn main() {\n    println!(\"hello\");\n} // end\n");
+        apply_edits_to_rope(&mut rope, &[DocumentEdit { range: None, text: "x".to_string() }]);
+        assert_eq!(rope.to_string(), "x");
+    }
+
+    #[test]
+    fn test_apply_edits_utf16_positions() {
+        // the emoji is 2 UTF-16 code units but 1 char in the rope, "ü" is 1 and 1
+        let mut rope = Rope::from_str("a😀bü\r\nsecond\n");
+        apply_edits_to_rope(&mut rope, &[edit(0, 3, 0, 4, "B")]);
+        assert_eq!(rope.to_string(), "a😀Bü\r\nsecond\n");
+        // character past the end of line stops before the line break
+        apply_edits_to_rope(&mut rope, &[edit(0, 100, 1, 0, "")]);
+        assert_eq!(rope.to_string(), "a😀Büsecond\n");
+        // line past the end of document appends
+        apply_edits_to_rope(&mut rope, &[edit(10, 0, 10, 0, "tail")]);
+        assert_eq!(rope.to_string(), "a😀Büsecond\ntail");
+    }
+}
@@ -0,0 +1,454 @@
+use std::collections::HashMap;
+
+use reqwest::header::CONTENT_TYPE;
+use reqwest::header::HeaderMap;
+use reqwest::header::HeaderName;
+use reqwest::header::HeaderValue;
+use reqwest_eventsource::EventSource;
+use serde_json::json;
+use tracing::info;
+
+use crate::call_validation::SamplingParameters;
+use crate::scratchpads::multimodality::split_data_url;
+
+
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+const ANTHROPIC_DEFAULT_MAX_TOKENS: usize = 4096;
+
+
+fn anthropic_headers(bearer: &str) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(CONTENT_TYPE, HeaderValue::from_str("application/json").unwrap());
+    headers.insert(HeaderName::from_static("anthropic-version"), HeaderValue::from_static(ANTHROPIC_VERSION));
+    if !bearer.is_empty() {
+        headers.insert(HeaderName::from_static("x-api-key"), HeaderValue::from_str(bearer).unwrap());
+    }
+    headers
+}
+
+fn anthropic_request_json(
+    model_name: &str,
+    prompt: &str,
+    sampling_parameters: &SamplingParameters,
+    stream: bool,
+) -> Result<serde_json::Value, String> {
+    // Messages API has no plain text completions, only chat scratchpads in passthrough mode can use this endpoint style
+    if !prompt.starts_with("PASSTHROUGH ") {
+        return Err("anthropic endpoint style supports only passthrough chat models".to_string());
+    }
+    let big_json: serde_json::Value = serde_json::from_str(&prompt[12..])
+        .map_err(|e| format!("cannot parse passthrough prompt: {}", e))?;
+    let empty = vec![];
+    let (system, messages) = openai_messages_to_anthropic(big_json["messages"].as_array().unwrap_or(&empty));
+
+    let max_tokens = if sampling_parameters.max_new_tokens > 0 { sampling_parameters.max_new_tokens } else { ANTHROPIC_DEFAULT_MAX_TOKENS };
+    let mut data = json!({
+        "model": model_name,
+        "stream": stream,
+        "max_tokens": max_tokens,
+        "messages": messages,
+    });
+    if !system.is_empty() {
+        data["system"] = json!(system);
+    }
+    if let Some(temperature) = sampling_parameters.temperature {
+        data["temperature"] = json!(temperature);
+    }
+    let stop_sequences: Vec<&String> = sampling_parameters.stop.iter().filter(|s| !s.trim().is_empty()).collect();
+    if !stop_sequences.is_empty() {
+        data["stop_sequences"] = json!(stop_sequences);
+    }
+    if let Some(tools) = big_json.get("tools").and_then(|x| x.as_array()) {
+        let tool_choice = big_json.get("tool_choice").and_then(|x| x.as_str()).unwrap_or("auto");
+        if !tools.is_empty() && tool_choice != "none" {
+            data["tools"] = json!(tools.iter().map(openai_tool_to_anthropic).collect::<Vec<_>>());
+            data["tool_choice"] = match tool_choice {
+                "required" => json!({"type": "any"}),
+                _ => json!({"type": "auto"}),
+            };
+        }
+    }
+    Ok(data)
+}
+
+fn openai_tool_to_anthropic(tool: &serde_json::Value) -> serde_json::Value {
+    let function = tool.get("function").unwrap_or(tool);
+    json!({
+        "name": function["name"],
+        "description": function.get("description").cloned().unwrap_or(json!("")),
+        "input_schema": function.get("parameters").cloned().unwrap_or(json!({"type": "object", "properties": {}})),
+    })
+}
+
+fn openai_content_text(content: &serde_json::Value) -> String {
+    match content.as_array() {
+        Some(elements) => elements.iter()
+            .filter_map(|el| el["text"].as_str())
+            .collect::<Vec<_>>()
+            .join("\n\n"),
+        None => content.as_str().unwrap_or("").to_string(),
+    }
+}
+
+fn openai_content_to_anthropic_blocks(content: &serde_json::Value) -> Vec<serde_json::Value> {
+    let mut blocks = vec![];
+    let Some(elements) = content.as_array() else {
+        let text = content.as_str().unwrap_or("");
+        if !text.is_empty() {
+            blocks.push(json!({"type": "text", "text": text}));
+        }
+        return blocks;
+    };
+    for el in elements {
+        match el["type"].as_str().unwrap_or("") {
+            "text" => blocks.push(json!({"type": "text", "text": el["text"]})),
+            "image_url" => {
+                let url = el["image_url"]["url"].as_str().unwrap_or("");
+                if let Some((media_type, data)) = split_data_url(url) {
+                    blocks.push(json!({"type": "image", "source": {"type": "base64", "media_type": media_type, "data": data}}));
+                } else {
+                    blocks.push(json!({"type": "image", "source": {"type": "url", "url": url}}));
+                }
+            }
+            _ => {}
+        }
+    }
+    blocks
+}
+
+pub fn openai_messages_to_anthropic(messages: &[serde_json::Value]) -> (String, Vec<serde_json::Value>) {
+    let mut system_parts: Vec<String> = vec![];
+    let mut result: Vec<serde_json::Value> = vec![];
+    for msg in messages.iter() {
+        let role = msg["role"].as_str().unwrap_or("");
+        let content = openai_content_text(&msg["content"]);
+        let (anthropic_role, mut blocks) = match role {
+            "system" => {
+                if !content.is_empty() {
+                    system_parts.push(content);
+                }
+                continue;
+            }
+            "assistant" => {
+                let mut blocks = vec![];
+                if !content.is_empty() {
+                    blocks.push(json!({"type": "text", "text": content}));
+                }
+                for tool_call in msg["tool_calls"].as_array().unwrap_or(&vec![]) {
+                    let arguments = tool_call["function"]["arguments"].as_str().unwrap_or("");
+                    let input = serde_json::from_str::<serde_json::Value>(arguments).unwrap_or(json!({}));
+                    blocks.push(json!({
+                        "type": "tool_use",
+                        "id": tool_call["id"],
+                        "name": tool_call["function"]["name"],
+                        "input": input,
+                    }));
+                }
+                ("assistant", blocks)
+            }
+            "tool" => {
+                ("user", vec![json!({
+                    "type": "tool_result",
+                    "tool_use_id": msg["tool_call_id"],
+                    "content": content,
+                })])
+            }
+            _ => ("user", openai_content_to_anthropic_blocks(&msg["content"])),
+        };
+        if blocks.is_empty() {
+            continue;
+        }
+        // Messages API requires user and assistant to alternate, merge neighbours with the same role
+        if let Some(last) = result.last_mut() {
+            if last["role"] == anthropic_role {
+                last["content"].as_array_mut().unwrap().append(&mut blocks);
+                continue;
+            }
+        }
+        result.push(json!({"role": anthropic_role, "content": blocks}));
+    }
+    (system_parts.join("\n\n"), result)
+}
+
+fn anthropic_stop_reason_to_openai(stop_reason: &str) -> &'static str {
+    match stop_reason {
+        "tool_use" => "tool_calls",
+        "max_tokens" => "length",
+        _ => "stop",
+    }
+}
+
+fn anthropic_usage_to_openai(usage: &serde_json::Value) -> serde_json::Value {
+    let prompt_tokens = usage["input_tokens"].as_u64().unwrap_or(0);
+    let completion_tokens = usage["output_tokens"].as_u64().unwrap_or(0);
+    json!({
+        "prompt_tokens": prompt_tokens,
+        "completion_tokens": completion_tokens,
+        "total_tokens": prompt_tokens + completion_tokens,
+    })
+}
+
+pub fn anthropic_response_to_openai(response: &serde_json::Value) -> serde_json::Value {
+    if response.get("error").is_some() {
+        return response.clone();
+    }
+    let mut text = String::new();
+    let mut tool_calls = vec![];
+    for block in response["content"].as_array().unwrap_or(&vec![]) {
+        match block["type"].as_str().unwrap_or("") {
+            "text" => text.push_str(block["text"].as_str().unwrap_or("")),
+            "tool_use" => tool_calls.push(json!({
+                "id": block["id"],
+                "type": "function",
+                "function": {
+                    "name": block["name"],
+                    "arguments": block["input"].to_string(),
+                },
+            })),
+            _ => {}
+        }
+    }
+    json!({
+        "id": response["id"],
+        "object": "chat.completion",
+        "model": response["model"],
+        "choices": [{
+            "index": 0,
+            "message": {
+                "role": "assistant",
+                "content": text,
+                "tool_calls": if tool_calls.is_empty() { serde_json::Value::Null } else { json!(tool_calls) },
+            },
+            "finish_reason": anthropic_stop_reason_to_openai(response["stop_reason"].as_str().unwrap_or("")),
+        }],
+        "usage": anthropic_usage_to_openai(&response["usage"]),
+    })
+}
+
+#[derive(Default)]
+pub struct AnthropicStreamConverter {
+    pub message_stopped: bool,
+    block_to_tool_index: HashMap<u64, usize>,
+    input_usage: serde_json::Value,
+}
+
+impl AnthropicStreamConverter {
+    pub fn new() -> Self {
+        AnthropicStreamConverter::default()
+    }
+
+    fn chunk(&self, delta: serde_json::Value, finish_reason: serde_json::Value) -> serde_json::Value {
+        json!({
+            "choices": [{
+                "index": 0,
+                "delta": delta,
+                "finish_reason": finish_reason,
+            }],
+            "object": "chat.completion.chunk",
+        })
+    }
+
+    // Converts one Messages API SSE event into an openai-style chunk, None for events that don't carry anything useful
+    pub fn event_to_openai_chunk(&mut self, event: &serde_json::Value) -> Option<serde_json::Value> {
+        match event["type"].as_str().unwrap_or("") {
+            "message_start" => {
+                self.input_usage = event["message"]["usage"].clone();
+                Some(self.chunk(json!({"role": "assistant", "content": ""}), serde_json::Value::Null))
+            }
+            "content_block_start" => {
+                let block = &event["content_block"];
+                if block["type"] != "tool_use" {
+                    return None;
+                }
+                let tool_index = self.block_to_tool_index.len();
+                self.block_to_tool_index.insert(event["index"].as_u64().unwrap_or(0), tool_index);
+                Some(self.chunk(json!({
+                    "tool_calls": [{
+                        "index": tool_index,
+                        "id": block["id"],
+                        "type": "function",
+                        "function": {"name": block["name"], "arguments": ""},
+                    }],
+                }), serde_json::Value::Null))
+            }
+            "content_block_delta" => {
+                let delta = &event["delta"];
+                match delta["type"].as_str().unwrap_or("") {
+                    "text_delta" => Some(self.chunk(json!({"content": delta["text"]}), serde_json::Value::Null)),
+                    "input_json_delta" => {
+                        let tool_index = *self.block_to_tool_index.get(&event["index"].as_u64().unwrap_or(0))?;
+                        Some(self.chunk(json!({
+                            "tool_calls": [{
+                                "index": tool_index,
+                                "function": {"arguments": delta["partial_json"]},
+                            }],
+                        }), serde_json::Value::Null))
+                    }
+                    _ => None,
+                }
+            }
+            "message_delta" => {
+                let stop_reason = event["delta"]["stop_reason"].as_str()?;
+                let mut usage = self.input_usage.clone();
+                if usage.is_null() {
+                    usage = json!({});
+                }
+                usage["output_tokens"] = event["usage"]["output_tokens"].clone();
+                let mut chunk = self.chunk(json!({}), json!(anthropic_stop_reason_to_openai(stop_reason)));
+                chunk["usage"] = anthropic_usage_to_openai(&usage);
+                Some(chunk)
+            }
+            "message_stop" => {
+                self.message_stopped = true;
+                None
+            }
+            "error" => Some(json!({"error": event["error"]})),
+            _ => None,  // ping, content_block_stop
+        }
+    }
+}
+
+pub async fn forward_to_anthropic_style_endpoint(
+    save_url: &mut String,
+    bearer: String,
+    model_name: &str,
+    prompt: &str,
+    client: &reqwest::Client,
+    endpoint_chat_passthrough: &str,
+    sampling_parameters: &SamplingParameters,
+) -> Result<serde_json::Value, String> {
+    let url = endpoint_chat_passthrough.to_string();
+    save_url.clone_from(&url);
+    let data = anthropic_request_json(model_name, prompt, sampling_parameters, false)?;
+    let req = client.post(&url)
+        .headers(anthropic_headers(&bearer))
+        .body(data.to_string())
+        .send()
+        .await;
+    let resp = req.map_err(|e| format!("{}", e))?;
+    let status_code = resp.status().as_u16();
+    let response_txt = resp.text().await.map_err(|e|
+        format!("reading from socket {}: {}", url, e)
+    )?;
+    // like openai, 400 comes with a json error inside, it's reported at the level higher
+    if status_code != 200 && status_code != 400 {
+        return Err(format!("{} status={} text {}", url, status_code, response_txt));
+    }
+    if status_code != 200 {
+        info!("forward_to_anthropic_style_endpoint: {} {}\n{}", url, status_code, response_txt);
+    }
+    let parsed_json: serde_json::Value = match serde_json::from_str(&response_txt) {
+        Ok(json) => json,
+        Err(e) => return Err(format!("Failed to parse JSON response: {}\n{}", e, response_txt)),
+    };
+    Ok(anthropic_response_to_openai(&parsed_json))
+}
+
+pub async fn forward_to_anthropic_style_endpoint_streaming(
+    save_url: &mut String,
+    bearer: String,
+    model_name: &str,
+    prompt: &str,
+    client: &reqwest::Client,
+    endpoint_chat_passthrough: &str,
+    sampling_parameters: &SamplingParameters,
+) -> Result<EventSource, String> {
+    let url = endpoint_chat_passthrough.to_string();
+    save_url.clone_from(&url);
+    let data = anthropic_request_json(model_name, prompt, sampling_parameters, true)?;
+    let builder = client.post(&url)
+        .headers(anthropic_headers(&bearer))
+        .body(data.to_string());
+    let event_source: EventSource = EventSource::new(builder).map_err(|e|
+        format!("can't stream from {}: {}", url, e)
+    )?;
+    Ok(event_source)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_messages_to_anthropic() {
+        let messages = vec![
+            json!({"role": "system", "content": "You are a helpful assistant."}),
+            json!({"role": "user", "content": "What is in main.rs?"}),
+            json!({"role": "assistant", "content": "", "tool_calls": [
+                {"id": "call_1", "type": "function", "function": {"name": "cat", "arguments": "{\"paths\": \"main.rs\"}"}}
+            ]}),
+            json!({"role": "tool", "tool_call_id": "call_1", "content": "fn main() {}"}),
+            json!({"role": "user", "content": "Explain it"}),
+        ];
+        let (system, converted) = openai_messages_to_anthropic(&messages);
+        assert_eq!(system, "You are a helpful assistant.");
+        assert_eq!(converted.len(), 3);
+        assert_eq!(converted[1]["content"][0]["type"], "tool_use");
+        assert_eq!(converted[1]["content"][0]["input"]["paths"], "main.rs");
+        // tool result and the next user message are merged into one user turn
+        assert_eq!(converted[2]["role"], "user");
+        assert_eq!(converted[2]["content"][0]["type"], "tool_result");
+        assert_eq!(converted[2]["content"][0]["tool_use_id"], "call_1");
+        assert_eq!(converted[2]["content"][1]["text"], "Explain it");
+    }
+
+    #[test]
+    fn test_multimodal_messages_to_anthropic() {
+        let messages = vec![
+            json!({"role": "user", "content": [
+                {"type": "text", "text": "What is on the screenshot?"},
+                {"type": "image_url", "image_url": {"url": "data:image/png;base64,iVBORw0KGgo="}},
+            ]}),
+        ];
+        let (_, converted) = openai_messages_to_anthropic(&messages);
+        assert_eq!(converted[0]["content"][0]["text"], "What is on the screenshot?");
+        assert_eq!(converted[0]["content"][1]["type"], "image");
+        assert_eq!(converted[0]["content"][1]["source"]["media_type"], "image/png");
+        assert_eq!(converted[0]["content"][1]["source"]["data"], "iVBORw0KGgo=");
+    }
+
+    #[test]
+    fn test_response_to_openai() {
+        let response = json!({
+            "id": "msg_1",
+            "model": "claude-3-5-sonnet-20240620",
+            "content": [
+                {"type": "text", "text": "Let me look."},
+                {"type": "tool_use", "id": "toolu_1", "name": "cat", "input": {"paths": "main.rs"}},
+            ],
+            "stop_reason": "tool_use",
+            "usage": {"input_tokens": 10, "output_tokens": 5},
+        });
+        let converted = anthropic_response_to_openai(&response);
+        let choice0 = &converted["choices"][0];
+        assert_eq!(choice0["message"]["content"], "Let me look.");
+        assert_eq!(choice0["message"]["tool_calls"][0]["function"]["name"], "cat");
+        assert_eq!(choice0["finish_reason"], "tool_calls");
+        assert_eq!(converted["usage"]["total_tokens"], 15);
+    }
+
+    #[test]
+    fn test_stream_converter() {
+        let mut converter = AnthropicStreamConverter::new();
+        let events = [
+            json!({"type": "message_start", "message": {"usage": {"input_tokens": 7, "output_tokens": 1}}}),
+            json!({"type": "content_block_start", "index": 0, "content_block": {"type": "text", "text": ""}}),
+            json!({"type": "content_block_delta", "index": 0, "delta": {"type": "text_delta", "text": "Hi"}}),
+            json!({"type": "content_block_stop", "index": 0}),
+            json!({"type": "content_block_start", "index": 1, "content_block": {"type": "tool_use", "id": "toolu_1", "name": "tree", "input": {}}}),
+            json!({"type": "content_block_delta", "index": 1, "delta": {"type": "input_json_delta", "partial_json": "{}"}}),
+            json!({"type": "message_delta", "delta": {"stop_reason": "tool_use"}, "usage": {"output_tokens": 3}}),
+            json!({"type": "message_stop"}),
+        ];
+        let chunks: Vec<serde_json::Value> = events.iter().filter_map(|e| converter.event_to_openai_chunk(e)).collect();
+        assert_eq!(chunks.len(), 5);
+        assert_eq!(chunks[1]["choices"][0]["delta"]["content"], "Hi");
+        assert_eq!(chunks[2]["choices"][0]["delta"]["tool_calls"][0]["index"], 0);
+        assert_eq!(chunks[2]["choices"][0]["delta"]["tool_calls"][0]["id"], "toolu_1");
+        assert_eq!(chunks[3]["choices"][0]["delta"]["tool_calls"][0]["function"]["arguments"], "{}");
+        assert_eq!(chunks[4]["choices"][0]["finish_reason"], "tool_calls");
+        assert_eq!(chunks[4]["usage"]["total_tokens"], 10);
+        assert!(converter.message_stopped);
+    }
+}
@@ -0,0 +1,176 @@
+use std::path::Path;
+
+use reqwest::header::AUTHORIZATION;
+use reqwest::header::CONTENT_TYPE;
+use reqwest::header::HeaderMap;
+use reqwest::header::HeaderValue;
+use reqwest_eventsource::EventSource;
+use serde_json::json;
+use tracing::info;
+
+use crate::call_validation::SamplingParameters;
+use crate::forward_to_ollama_endpoint::LocalModelInfo;
+
+
+fn llama_cpp_headers(bearer: &str) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(CONTENT_TYPE, HeaderValue::from_str("application/json").unwrap());
+    if !bearer.is_empty() {
+        headers.insert(AUTHORIZATION, HeaderValue::from_str(format!("Bearer {}", bearer).as_str()).unwrap());
+    }
+    headers
+}
+
+fn llama_cpp_request_json(
+    prompt: &str,
+    sampling_parameters: &SamplingParameters,
+    stream: bool,
+) -> Result<serde_json::Value, String> {
+    if prompt.starts_with("PASSTHROUGH ") {
+        return Err("llama.cpp endpoint style is for /completion only, use openai style with /v1/chat/completions for chat".to_string());
+    }
+    let mut data = json!({
+        "prompt": prompt,
+        "stream": stream,
+        "stop": sampling_parameters.stop,
+        "cache_prompt": true,
+    });
+    if sampling_parameters.max_new_tokens > 0 {
+        data["n_predict"] = json!(sampling_parameters.max_new_tokens);
+    }
+    if let Some(temperature) = sampling_parameters.temperature {
+        data["temperature"] = json!(temperature);
+    }
+    if let Some(top_p) = sampling_parameters.top_p {
+        data["top_p"] = json!(top_p);
+    }
+    Ok(data)
+}
+
+// Works both for the whole /completion response and for a single streaming chunk
+pub fn llama_cpp_chunk_to_openai(chunk: &serde_json::Value) -> serde_json::Value {
+    if chunk.get("error").is_some() {
+        return chunk.clone();
+    }
+    let finish_reason = if chunk["stop"] != true {
+        serde_json::Value::Null
+    } else if chunk["stopped_limit"] == true {
+        json!("length")
+    } else {
+        json!("stop")
+    };
+    let mut result = json!({
+        "choices": [{
+            "index": 0,
+            "text": chunk["content"].as_str().unwrap_or(""),
+            "finish_reason": finish_reason,
+        }],
+    });
+    if chunk["stop"] == true {
+        let prompt_tokens = chunk["tokens_evaluated"].as_u64().unwrap_or(0);
+        let completion_tokens = chunk["tokens_predicted"].as_u64().unwrap_or(0);
+        result["usage"] = json!({
+            "prompt_tokens": prompt_tokens,
+            "completion_tokens": completion_tokens,
+            "total_tokens": prompt_tokens + completion_tokens,
+        });
+    }
+    result
+}
+
+pub async fn forward_to_llama_cpp_style_endpoint(
+    save_url: &mut String,
+    bearer: String,
+    model_name: &str,
+    prompt: &str,
+    client: &reqwest::Client,
+    endpoint_template: &str,
+    sampling_parameters: &SamplingParameters,
+) -> Result<serde_json::Value, String> {
+    let url = endpoint_template.replace("$MODEL", model_name);
+    save_url.clone_from(&url);
+    let data = llama_cpp_request_json(prompt, sampling_parameters, false)?;
+    let req = client.post(&url)
+        .headers(llama_cpp_headers(&bearer))
+        .body(data.to_string())
+        .send()
+        .await;
+    let resp = req.map_err(|e| format!("{}", e))?;
+    let status_code = resp.status().as_u16();
+    let response_txt = resp.text().await.map_err(|e|
+        format!("reading from socket {}: {}", url, e)
+    )?;
+    if status_code != 200 {
+        return Err(format!("{} status={} text {}", url, status_code, response_txt));
+    }
+    let parsed_json: serde_json::Value = match serde_json::from_str(&response_txt) {
+        Ok(json) => json,
+        Err(e) => return Err(format!("Failed to parse JSON response: {}\n{}", e, response_txt)),
+    };
+    Ok(llama_cpp_chunk_to_openai(&parsed_json))
+}
+
+pub async fn forward_to_llama_cpp_style_endpoint_streaming(
+    save_url: &mut String,
+    bearer: String,
+    model_name: &str,
+    prompt: &str,
+    client: &reqwest::Client,
+    endpoint_template: &str,
+    sampling_parameters: &SamplingParameters,
+) -> Result<EventSource, String> {
+    let url = endpoint_template.replace("$MODEL", model_name);
+    save_url.clone_from(&url);
+    let data = llama_cpp_request_json(prompt, sampling_parameters, true)?;
+    let builder = client.post(&url)
+        .headers(llama_cpp_headers(&bearer))
+        .body(data.to_string());
+    let event_source: EventSource = EventSource::new(builder).map_err(|e|
+        format!("can't stream from {}: {}", url, e)
+    )?;
+    Ok(event_source)
+}
+
+pub async fn discover_llama_cpp_models(
+    client: &reqwest::Client,
+    base_url: &str,
+) -> Result<Vec<LocalModelInfo>, String> {
+    // llama.cpp server runs exactly one model, /props tells its context size and whether it has a chat template
+    let base_url = base_url.trim_end_matches('/');
+    let props_url = format!("{}/props", base_url);
+    let props: serde_json::Value = client.get(&props_url).send().await
+        .map_err(|e| format!("{}: {}", props_url, e))?
+        .json().await
+        .map_err(|e| format!("{}: {}", props_url, e))?;
+    let model_path = props["model_path"].as_str()
+        .or_else(|| props["default_generation_settings"]["model"].as_str())
+        .ok_or(format!("{}: no model_path in response", props_url))?;
+    let name = Path::new(model_path).file_stem().map(|x| x.to_string_lossy().to_string()).unwrap_or(model_path.to_string());
+    let info = LocalModelInfo {
+        name,
+        n_ctx: props["default_generation_settings"]["n_ctx"].as_u64().unwrap_or(0) as usize,
+        supports_tools: false,
+        supports_chat: props["chat_template"].as_str().map(|x| !x.is_empty()).unwrap_or(false),
+        supports_completion: true,
+    };
+    info!("llama.cpp at {} runs {:?}", base_url, info.name);
+    Ok(vec![info])
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_llama_cpp_chunk_to_openai() {
+        let chunk = llama_cpp_chunk_to_openai(&json!({"content": "def", "stop": false}));
+        assert_eq!(chunk["choices"][0]["text"], "def");
+        assert!(chunk["choices"][0]["finish_reason"].is_null());
+        let last = llama_cpp_chunk_to_openai(&json!({
+            "content": "", "stop": true, "stopped_limit": true, "tokens_evaluated": 100, "tokens_predicted": 20
+        }));
+        assert_eq!(last["choices"][0]["finish_reason"], "length");
+        assert_eq!(last["usage"]["total_tokens"], 120);
+    }
+}
@@ -0,0 +1,430 @@
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use async_stream::stream;
+use futures::{Stream, StreamExt};
+use reqwest::header::AUTHORIZATION;
+use reqwest::header::CONTENT_TYPE;
+use reqwest::header::HeaderMap;
+use reqwest::header::HeaderValue;
+use reqwest_eventsource::Event;
+use serde_json::json;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::call_validation::SamplingParameters;
+use crate::scratchpads::multimodality::split_data_url;
+
+
+// Ollama streams newline-delimited json, not SSE. This gives the same events as EventSource does, so restream
+// handles both the same way: a message per line, StreamEnded when the server closes the connection.
+pub struct OllamaEventSource {
+    lines: Pin<Box<dyn Stream<Item = Result<Event, reqwest_eventsource::Error>> + Send>>,
+    is_closed: bool,
+}
+
+impl OllamaEventSource {
+    pub fn close(&mut self) {
+        self.is_closed = true;
+    }
+}
+
+impl Stream for OllamaEventSource {
+    type Item = Result<Event, reqwest_eventsource::Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if self.is_closed {
+            return Poll::Ready(None);
+        }
+        self.lines.as_mut().poll_next(cx)
+    }
+}
+
+fn line_event(line: &[u8]) -> Option<Event> {
+    let data = String::from_utf8_lossy(line).trim().to_string();
+    if data.is_empty() {
+        return None;
+    }
+    Some(Event::Message(eventsource_stream::Event { data, ..Default::default() }))
+}
+
+
+fn ollama_headers(bearer: &str) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(CONTENT_TYPE, HeaderValue::from_str("application/json").unwrap());
+    if !bearer.is_empty() {
+        headers.insert(AUTHORIZATION, HeaderValue::from_str(format!("Bearer {}", bearer).as_str()).unwrap());
+    }
+    headers
+}
+
+fn ollama_request_json(
+    model_name: &str,
+    prompt: &str,
+    sampling_parameters: &SamplingParameters,
+    stream: bool,
+) -> Result<serde_json::Value, String> {
+    let mut options = json!({
+        "stop": sampling_parameters.stop,
+    });
+    if sampling_parameters.max_new_tokens > 0 {
+        // zero would mean nothing to generate, leave the model default instead
+        options["num_predict"] = json!(sampling_parameters.max_new_tokens);
+    }
+    if let Some(temperature) = sampling_parameters.temperature {
+        options["temperature"] = json!(temperature);
+    }
+    if let Some(top_p) = sampling_parameters.top_p {
+        options["top_p"] = json!(top_p);
+    }
+    let mut data = json!({
+        "model": model_name,
+        "stream": stream,
+        "options": options,
+    });
+    if let Some(messages_str) = prompt.strip_prefix("PASSTHROUGH ") {
+        let big_json: serde_json::Value = serde_json::from_str(messages_str)
+            .map_err(|e| format!("cannot parse passthrough prompt: {}", e))?;
+        let empty = vec![];
+        data["messages"] = json!(big_json["messages"].as_array().unwrap_or(&empty).iter().map(openai_message_to_ollama).collect::<Vec<_>>());
+        if let Some(tools) = big_json.get("tools").and_then(|x| x.as_array()) {
+            if !tools.is_empty() && big_json["tool_choice"] != "none" {
+                data["tools"] = json!(tools);
+            }
+        }
+    } else {
+        // scratchpads already formatted the prompt, don't let ollama apply the model template on top
+        data["prompt"] = json!(prompt);
+        data["raw"] = json!(true);
+    }
+    Ok(data)
+}
+
+fn openai_message_to_ollama(msg: &serde_json::Value) -> serde_json::Value {
+    let mut result = json!({
+        "role": msg["role"],
+        "content": msg["content"].as_str().unwrap_or(""),
+    });
+    // multimodal content: ollama takes text as a string and images as a list of bare base64
+    if let Some(elements) = msg["content"].as_array() {
+        let text = elements.iter().filter_map(|el| el["text"].as_str()).collect::<Vec<_>>().join("\n\n");
+        let images = elements.iter()
+            .filter_map(|el| el["image_url"]["url"].as_str())
+            .filter_map(|url| split_data_url(url).map(|(_, data)| data.to_string()))
+            .collect::<Vec<_>>();
+        result["content"] = json!(text);
+        if !images.is_empty() {
+            result["images"] = json!(images);
+        }
+    }
+    // ollama wants arguments as an object, openai style has them as a string
+    if let Some(tool_calls) = msg["tool_calls"].as_array() {
+        result["tool_calls"] = json!(tool_calls.iter().map(|tool_call| {
+            let arguments = tool_call["function"]["arguments"].as_str().unwrap_or("");
+            json!({
+                "function": {
+                    "name": tool_call["function"]["name"],
+                    "arguments": serde_json::from_str::<serde_json::Value>(arguments).unwrap_or(json!({})),
+                }
+            })
+        }).collect::<Vec<_>>());
+    }
+    result
+}
+
+fn ollama_tool_calls_to_openai(tool_calls: &serde_json::Value, first_index: usize) -> Vec<serde_json::Value> {
+    tool_calls.as_array().unwrap_or(&vec![]).iter().enumerate().map(|(i, tool_call)| {
+        let arguments = &tool_call["function"]["arguments"];
+        json!({
+            "index": first_index + i,
+            "id": format!("call_{}", Uuid::new_v4().simple()),
+            "type": "function",
+            "function": {
+                "name": tool_call["function"]["name"],
+                "arguments": if let Some(s) = arguments.as_str() { s.to_string() } else { arguments.to_string() },
+            },
+        })
+    }).collect()
+}
+
+fn ollama_finish_reason(response: &serde_json::Value, had_tool_calls: bool) -> serde_json::Value {
+    if response["done"] != true {
+        return serde_json::Value::Null;
+    }
+    if had_tool_calls {
+        json!("tool_calls")
+    } else if response["done_reason"] == "length" {
+        json!("length")
+    } else {
+        json!("stop")
+    }
+}
+
+fn ollama_usage(response: &serde_json::Value) -> serde_json::Value {
+    let prompt_tokens = response["prompt_eval_count"].as_u64().unwrap_or(0);
+    let completion_tokens = response["eval_count"].as_u64().unwrap_or(0);
+    json!({
+        "prompt_tokens": prompt_tokens,
+        "completion_tokens": completion_tokens,
+        "total_tokens": prompt_tokens + completion_tokens,
+    })
+}
+
+pub fn ollama_response_to_openai(response: &serde_json::Value) -> serde_json::Value {
+    if response.get("error").is_some() {
+        return response.clone();
+    }
+    let choice0 = if let Some(message) = response.get("message") {
+        let tool_calls = ollama_tool_calls_to_openai(&message["tool_calls"], 0);
+        json!({
+            "index": 0,
+            "message": {
+                "role": "assistant",
+                "content": message["content"].as_str().unwrap_or(""),
+                "tool_calls": if tool_calls.is_empty() { serde_json::Value::Null } else { json!(tool_calls) },
+            },
+            "finish_reason": ollama_finish_reason(response, !tool_calls.is_empty()),
+        })
+    } else {
+        json!({
+            "index": 0,
+            "text": response["response"].as_str().unwrap_or(""),
+            "finish_reason": ollama_finish_reason(response, false),
+        })
+    };
+    json!({
+        "model": response["model"],
+        "choices": [choice0],
+        "usage": ollama_usage(response),
+    })
+}
+
+#[derive(Default)]
+pub struct OllamaStreamConverter {
+    tool_calls_n: usize,
+}
+
+impl OllamaStreamConverter {
+    pub fn new() -> Self {
+        OllamaStreamConverter::default()
+    }
+
+    // One json line from /api/generate or /api/chat into an openai-style chunk
+    pub fn chunk_to_openai(&mut self, chunk: &serde_json::Value) -> serde_json::Value {
+        if chunk.get("error").is_some() {
+            return chunk.clone();
+        }
+        let mut result = if let Some(message) = chunk.get("message") {
+            let tool_calls = ollama_tool_calls_to_openai(&message["tool_calls"], self.tool_calls_n);
+            self.tool_calls_n += tool_calls.len();
+            json!({
+                "choices": [{
+                    "index": 0,
+                    "delta": {
+                        "role": "assistant",
+                        "content": message["content"].as_str().unwrap_or(""),
+                        "tool_calls": if tool_calls.is_empty() { serde_json::Value::Null } else { json!(tool_calls) },
+                    },
+                    "finish_reason": ollama_finish_reason(chunk, self.tool_calls_n > 0),
+                }],
+                "object": "chat.completion.chunk",
+            })
+        } else {
+            json!({
+                "choices": [{
+                    "index": 0,
+                    "text": chunk["response"].as_str().unwrap_or(""),
+                    "finish_reason": ollama_finish_reason(chunk, false),
+                }],
+            })
+        };
+        if chunk["done"] == true {
+            result["usage"] = ollama_usage(chunk);
+        }
+        result
+    }
+}
+
+pub async fn forward_to_ollama_style_endpoint(
+    save_url: &mut String,
+    bearer: String,
+    model_name: &str,
+    prompt: &str,
+    client: &reqwest::Client,
+    endpoint_template: &str,
+    endpoint_chat_passthrough: &str,
+    sampling_parameters: &SamplingParameters,
+) -> Result<serde_json::Value, String> {
+    let is_passthrough = prompt.starts_with("PASSTHROUGH ");
+    let url = if !is_passthrough { endpoint_template.replace("$MODEL", model_name) } else { endpoint_chat_passthrough.to_string() };
+    save_url.clone_from(&url);
+    let data = ollama_request_json(model_name, prompt, sampling_parameters, false)?;
+    let req = client.post(&url)
+        .headers(ollama_headers(&bearer))
+        .body(data.to_string())
+        .send()
+        .await;
+    let resp = req.map_err(|e| format!("{}", e))?;
+    let status_code = resp.status().as_u16();
+    let response_txt = resp.text().await.map_err(|e|
+        format!("reading from socket {}: {}", url, e)
+    )?;
+    // ollama answers 400 and 404 (no such model) with {"error": "..."}, that is reported at the level higher
+    if status_code != 200 && status_code != 400 && status_code != 404 {
+        return Err(format!("{} status={} text {}", url, status_code, response_txt));
+    }
+    let parsed_json: serde_json::Value = match serde_json::from_str(&response_txt) {
+        Ok(json) => json,
+        Err(e) => return Err(format!("Failed to parse JSON response: {}\n{}", e, response_txt)),
+    };
+    Ok(ollama_response_to_openai(&parsed_json))
+}
+
+pub async fn forward_to_ollama_style_endpoint_streaming(
+    save_url: &mut String,
+    bearer: String,
+    model_name: &str,
+    prompt: &str,
+    client: &reqwest::Client,
+    endpoint_template: &str,
+    endpoint_chat_passthrough: &str,
+    sampling_parameters: &SamplingParameters,
+) -> Result<OllamaEventSource, String> {
+    let is_passthrough = prompt.starts_with("PASSTHROUGH ");
+    let url = if !is_passthrough { endpoint_template.replace("$MODEL", model_name) } else { endpoint_chat_passthrough.to_string() };
+    save_url.clone_from(&url);
+    let data = ollama_request_json(model_name, prompt, sampling_parameters, true)?;
+    let resp = client.post(&url)
+        .headers(ollama_headers(&bearer))
+        .body(data.to_string())
+        .send()
+        .await
+        .map_err(|e| format!("can't stream from {}: {}", url, e))?;
+    let status_code = resp.status().as_u16();
+    if status_code != 200 {
+        let response_txt = resp.text().await.unwrap_or_default();
+        return Err(format!("{} status={} text {}", url, status_code, response_txt));
+    }
+    let mut bytes_stream = resp.bytes_stream();
+    let lines = stream! {
+        let mut buf: Vec<u8> = vec![];
+        while let Some(bytes_maybe) = bytes_stream.next().await {
+            match bytes_maybe {
+                Ok(bytes) => buf.extend_from_slice(&bytes),
+                Err(e) => {
+                    yield Err(reqwest_eventsource::Error::Transport(e));
+                    return;
+                }
+            }
+            while let Some(pos) = buf.iter().position(|b| *b == b'\n') {
+                let line: Vec<u8> = buf.drain(..=pos).collect();
+                if let Some(event) = line_event(&line) {
+                    yield Ok(event);
+                }
+            }
+        }
+        if let Some(event) = line_event(&buf) {
+            yield Ok(event);
+        }
+        yield Err(reqwest_eventsource::Error::StreamEnded);
+    };
+    Ok(OllamaEventSource { lines: Box::pin(lines), is_closed: false })
+}
+
+#[derive(Debug, Clone)]
+pub struct LocalModelInfo {
+    pub name: String,
+    pub n_ctx: usize,
+    pub supports_tools: bool,
+    pub supports_chat: bool,
+    pub supports_completion: bool,
+}
+
+pub async fn discover_ollama_models(
+    client: &reqwest::Client,
+    base_url: &str,
+) -> Result<Vec<LocalModelInfo>, String> {
+    let base_url = base_url.trim_end_matches('/');
+    let tags_url = format!("{}/api/tags", base_url);
+    let tags: serde_json::Value = client.get(&tags_url).send().await
+        .map_err(|e| format!("{}: {}", tags_url, e))?
+        .json().await
+        .map_err(|e| format!("{}: {}", tags_url, e))?;
+    let mut result = vec![];
+    for model in tags["models"].as_array().unwrap_or(&vec![]) {
+        let name = match model["name"].as_str() {
+            Some(name) => name.to_string(),
+            None => continue,
+        };
+        let mut info = LocalModelInfo { name: name.clone(), n_ctx: 0, supports_tools: false, supports_chat: true, supports_completion: true };
+        let show_url = format!("{}/api/show", base_url);
+        match client.post(&show_url).json(&json!({"name": name})).send().await {
+            Ok(resp) => {
+                let show: serde_json::Value = resp.json().await.unwrap_or_default();
+                // "completion" means it generates text at all (embedding models don't), base models have the
+                // template "{{ .Prompt }}" and instruct models render .Messages or at least .System
+                let capabilities = show["capabilities"].as_array().cloned().unwrap_or_default();
+                let generates = capabilities.is_empty() || capabilities.iter().any(|c| c == "completion");
+                let template = show["template"].as_str().unwrap_or_default();
+                info.supports_tools = capabilities.iter().any(|c| c == "tools");
+                info.supports_chat = generates && (template.contains(".Messages") || template.contains(".System"));
+                info.supports_completion = generates;
+                if let Some(model_info) = show["model_info"].as_object() {
+                    info.n_ctx = model_info.iter()
+                        .find(|(k, _)| k.ends_with(".context_length"))
+                        .and_then(|(_, v)| v.as_u64())
+                        .unwrap_or(0) as usize;
+                }
+            }
+            Err(e) => warn!("{}: {}", show_url, e),
+        }
+        result.push(info);
+    }
+    info!("ollama at {} reports {} models", base_url, result.len());
+    Ok(result)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ollama_chat_stream_to_openai() {
+        let mut converter = OllamaStreamConverter::new();
+        let chunk1 = converter.chunk_to_openai(&json!({
+            "model": "llama3.1", "message": {"role": "assistant", "content": "", "tool_calls": [
+                {"function": {"name": "tree", "arguments": {"path": "src"}}}
+            ]}, "done": false
+        }));
+        assert_eq!(chunk1["choices"][0]["delta"]["tool_calls"][0]["function"]["arguments"], "{\"path\":\"src\"}");
+        assert!(chunk1["choices"][0]["finish_reason"].is_null());
+        let chunk2 = converter.chunk_to_openai(&json!({
+            "model": "llama3.1", "message": {"role": "assistant", "content": ""}, "done": true,
+            "done_reason": "stop", "prompt_eval_count": 20, "eval_count": 4
+        }));
+        assert_eq!(chunk2["choices"][0]["finish_reason"], "tool_calls");
+        assert_eq!(chunk2["usage"]["total_tokens"], 24);
+    }
+
+    #[test]
+    fn test_ollama_generate_to_openai() {
+        let converted = ollama_response_to_openai(&json!({
+            "model": "starcoder2:3b", "response": "print(1)", "done": true, "done_reason": "length"
+        }));
+        assert_eq!(converted["choices"][0]["text"], "print(1)");
+        assert_eq!(converted["choices"][0]["finish_reason"], "length");
+    }
+
+    #[test]
+    fn test_ollama_request_passthrough() {
+        let prompt = format!("PASSTHROUGH {}", json!({"messages": [
+            {"role": "assistant", "content": "", "tool_calls": [{"id": "call_1", "type": "function", "function": {"name": "cat", "arguments": "{\"paths\":\"a.py\"}"}}]},
+            {"role": "tool", "content": "print(1)", "tool_call_id": "call_1"},
+        ]}));
+        let data = ollama_request_json("llama3.1", &prompt, &SamplingParameters::default(), true).unwrap();
+        assert_eq!(data["messages"][0]["tool_calls"][0]["function"]["arguments"]["paths"], "a.py");
+        assert_eq!(data["messages"][1]["role"], "tool");
+        assert!(data.get("prompt").is_none());
+    }
+}
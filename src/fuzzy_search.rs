@@ -77,12 +77,13 @@ mod tests {
     use super::*;
     use std::path::PathBuf;
     use crate::files_in_workspace::retrieve_files_in_workspace_folders;
+    use crate::file_filter::IndexingConfig;
 
     async fn get_candidates_from_workspace_files() -> Vec<String> {
         let proj_folders = vec![PathBuf::from(".").canonicalize().unwrap()];
         let proj_folder = &proj_folders[0];
 
-        let workspace_files = retrieve_files_in_workspace_folders(proj_folders.clone()).await;
+        let (workspace_files, _) = retrieve_files_in_workspace_folders(proj_folders.clone(), &IndexingConfig::default()).await;
 
         workspace_files
             .iter()
@@ -20,8 +20,10 @@ use crate::caps::CodeAssistantCaps;
 use crate::completion_cache::CompletionCache;
 use crate::custom_error::ScratchError;
 use crate::files_in_workspace::DocumentsState;
+use crate::integrations::integr_mcp::McpServerTools;
 use crate::integrations::sessions::IntegrationSession;
 use crate::privacy::PrivacySettings;
+use crate::file_filter::IndexingConfig;
 use crate::telemetry::telemetry_structs;
 use crate::vecdb::vdb_highlev::VecDb;
 
@@ -46,6 +48,8 @@ pub struct CommandLine {
     pub lsp_port: u16,
     #[structopt(long, default_value="0", help="Act as an LSP server, use stdin stdout for communication. This is compatible with having an HTTP server at the same time. But it's not compatible with LSP port.")]
     pub lsp_stdin_stdout: u16,
+    #[structopt(long, help="Act as a Model Context Protocol server on stdin stdout, exposing AST, vecdb and @-commands as tools for other agents. Not compatible with LSP stdin stdout.")]
+    pub mcp_stdio: bool,
 
     #[structopt(long, default_value="", help="End-user client version, such as version of VS Code plugin.")]
     pub enduser_client_version: String,
@@ -71,15 +75,24 @@ pub struct CommandLine {
     pub vecdb_max_files: usize,
     #[structopt(long, default_value="", help="Set VecDB storage path manually.")]
     pub vecdb_force_path: String,
+    #[structopt(long, default_value="", help="Search in a shared index instead of vectorizing files locally, give it /v1/vdb-search URL of another refact-lsp. Overrides vecdb_remote_endpoint in caps.")]
+    pub vecdb_remote: String,
+    #[structopt(long, default_value="", help="API key for --vecdb-remote, the default is the same as --api-key.")]
+    pub vecdb_remote_apikey: String,
+    #[structopt(long, default_value="", help="Workspace folder on the --vecdb-remote server, to translate its paths to local ones.")]
+    pub vecdb_remote_root: String,
 
     #[structopt(long, short="f", default_value="", help="A path to jsonl file with {\"path\": ...} on each line, files will immediately go to VecDB and AST.")]
     pub files_jsonl_path: String,
     #[structopt(long, short="w", default_value="", help="Workspace folder to find all the files. An LSP or HTTP request can override this later.")]
     pub workspace_folder: String,
 
-    #[structopt(long, help="create manually bring-your-own-key.yaml, integrations.yaml, customization.yaml and privacy.yaml and EXIT")]
+    #[structopt(long, help="create manually bring-your-own-key.yaml, integrations.yaml, customization.yaml, privacy.yaml and indexing.yaml and EXIT")]
     pub only_create_yaml_configs: bool,
 
+    #[structopt(long, default_value="0", help="How many rotated audit log files (10MB each) to keep in the cache dir, zero keeps them all.")]
+    pub audit_log_keep: usize,
+
     #[structopt(long, help="Enable experimental features, such as new integrations.")]
     pub experimental: bool,
 }
@@ -142,7 +155,10 @@ pub struct GlobalContext {
     pub documents_state: DocumentsState,
     pub at_commands_preview_cache: Arc<AMutex<AtCommandsPreviewCache>>,
     pub privacy_settings: Arc<PrivacySettings>,
+    pub indexing_config: Arc<IndexingConfig>,
     pub integration_sessions: HashMap<String, Arc<AMutex<Box<dyn IntegrationSession>>>>,
+    pub mcp_servers: Arc<StdMutex<HashMap<String, McpServerTools>>>,
+    pub lsp_client: Arc<StdMutex<Option<tower_lsp::Client>>>,  // to publish diagnostics outside of LSP requests
 }
 
 pub type SharedGlobalContext = Arc<ARwLock<GlobalContext>>;  // TODO: remove this type alias, confusing
@@ -320,7 +336,10 @@ pub async fn create_global_context(
         documents_state: DocumentsState::new(workspace_dirs).await,
         at_commands_preview_cache: Arc::new(AMutex::new(AtCommandsPreviewCache::new())),
         privacy_settings: Arc::new(PrivacySettings::default()),
+        indexing_config: Arc::new(IndexingConfig::default()),
         integration_sessions: HashMap::new(),
+        mcp_servers: Arc::new(StdMutex::new(HashMap::new())),
+        lsp_client: Arc::new(StdMutex::new(None)),
     };
     let gcx = Arc::new(ARwLock::new(cx));
     {
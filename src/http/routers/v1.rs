@@ -35,6 +35,7 @@ use crate::http::routers::v1::gui_help_handlers::handle_v1_fullpath;
 use crate::http::routers::v1::handlers_memdb::{handle_mem_query, handle_mem_add, handle_mem_erase, handle_mem_update_used, handle_mem_block_until_vectorized, handle_mem_list, handle_ongoing_update_or_create, handle_ongoing_dump};
 use crate::http::routers::v1::patch::handle_v1_patch_single_file_from_ticket;
 use crate::http::routers::v1::subchat::{handle_v1_subchat, handle_v1_subchat_single};
+use crate::http::routers::v1::audit_log::handle_v1_audit_log;
 
 use crate::http::utils::telemetry_wrapper;
 
@@ -58,6 +59,7 @@ pub mod handlers_memdb;
 mod subchat;
 mod gui_help_handlers;
 mod patch;
+mod audit_log;
 
 pub fn make_v1_router() -> Router {
     Router::new()
@@ -69,6 +71,7 @@ pub fn make_v1_router() -> Router {
         .route("/chat", telemetry_post!(handle_v1_chat))
         .route("/chat/completions", telemetry_post!(handle_v1_chat_completions))  // standard
         .route("/telemetry-network", telemetry_post!(handle_v1_telemetry_network))
+        .route("/audit-log", telemetry_post!(handle_v1_audit_log))
         .route("/snippet-accepted", telemetry_post!(handle_v1_snippet_accepted))
 
         .route("/caps", telemetry_get!(handle_v1_caps))
@@ -17,9 +17,10 @@ use crate::at_commands::at_commands::AtCommandsContext;
 use crate::at_commands::execute_at::{execute_at_commands_in_query, parse_words_from_line};
 use crate::custom_error::ScratchError;
 use crate::global_context::GlobalContext;
-use crate::call_validation::{ChatMessage, ContextEnum};
+use crate::call_validation::{ChatContent, ChatMessage, ContextEnum};
 use crate::at_commands::at_commands::filter_only_context_file_from_context_tool;
 use crate::postprocessing::pp_context_files::postprocess_context_files;
+use crate::privacy_secrets::redact_context_files;
 use crate::scratchpads::scratchpad_utils::max_tokens_for_rag_chat;
 
 
@@ -163,7 +164,7 @@ pub async fn handle_v1_command_preview(
         pp_settings.max_files_n = crate::http::routers::v1::chat::CHAT_TOP_N;
     }
 
-    let processed = postprocess_context_files(
+    let mut processed = postprocess_context_files(
         global_context.clone(),
         &mut filter_only_context_file_from_context_tool(&messages_for_postprocessing),
         tokenizer_arc.clone(),
@@ -172,10 +173,12 @@ pub async fn handle_v1_command_preview(
         &pp_settings,
     ).await;
 
+    redact_context_files(&mut processed);  // preview shows exactly what the model will get
+
     if !processed.is_empty() {
         let message = ChatMessage {
             role: "context_file".to_string(),
-            content: serde_json::to_string(&processed).unwrap(),
+            content: ChatContent::SimpleText(serde_json::to_string(&processed).unwrap()),
             tool_calls: None,
             tool_call_id: "".to_string(),
             ..Default::default()
@@ -23,10 +23,11 @@ pub async fn handle_v1_tools(
     Extension(gcx): Extension<Arc<ARwLock<GlobalContext>>>,
     _: hyper::body::Bytes,
 )  -> axum::response::Result<Response<Body>, ScratchError> {
-    let turned_on = crate::tools::tools_description::tools_merged_and_filtered(gcx.clone()).await.keys().cloned().collect::<Vec<_>>();
+    let all_tools = tools_merged_and_filtered(gcx.clone()).await;
+    let turned_on = all_tools.keys().cloned().collect::<Vec<_>>();
     let allow_experimental = gcx.read().await.cmdline.experimental;
 
-    let tool_desclist = tool_description_list_from_yaml(&turned_on, allow_experimental).unwrap_or_else(|e|{
+    let tool_desclist = tool_description_list_from_yaml(all_tools, &turned_on, allow_experimental).await.unwrap_or_else(|e|{
         tracing::error!("Error loading compiled_in_tools: {:?}", e);
         vec![]
     });
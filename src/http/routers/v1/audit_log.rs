@@ -0,0 +1,30 @@
+use axum::Extension;
+use axum::response::Result;
+use hyper::{Body, Response, StatusCode};
+use serde_json::json;
+
+use crate::audit_log::{AuditLogFilter, audit_log_read};
+use crate::custom_error::ScratchError;
+use crate::global_context::SharedGlobalContext;
+
+
+pub async fn handle_v1_audit_log(
+    Extension(_gcx): Extension<SharedGlobalContext>,
+    body_bytes: hyper::body::Bytes,
+) -> Result<Response<Body>, ScratchError> {
+    let filter = if body_bytes.is_empty() {
+        AuditLogFilter::default()
+    } else {
+        serde_json::from_slice::<AuditLogFilter>(&body_bytes).map_err(|e| {
+            ScratchError::new(StatusCode::BAD_REQUEST, format!("JSON problem: {}", e))
+        })?
+    };
+    let records = tokio::task::spawn_blocking(move || audit_log_read(&filter)).await
+        .map_err(|e| ScratchError::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .map_err(|e| ScratchError::new(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header("Content-Type", "application/json")
+        .body(Body::from(serde_json::to_string_pretty(&json!({"records": records})).unwrap()))
+        .unwrap())
+}
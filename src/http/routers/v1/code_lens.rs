@@ -1,13 +1,24 @@
 use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
 use std::sync::Arc;
 use axum::Extension;
 use axum::response::Result;
 use hyper::{Body, Response, StatusCode};
+use tokio::sync::{Mutex as AMutex, RwLock as ARwLock};
 use url::Url;
 
-use crate::global_context::SharedGlobalContext;
+use crate::at_commands::at_commands::AtCommandsContext;
+use crate::call_validation::{ChatContent, ChatMessage};
+use crate::files_in_workspace::get_file_text_from_memory_or_disk;
+use crate::global_context::{GlobalContext, SharedGlobalContext, try_load_caps_quickly_if_not_present};
 use crate::ast::ast_structs::AstDefinition;
 use crate::custom_error::ScratchError;
+use crate::subchat::subchat_single;
+use crate::yaml_configs::customization_loader::load_customization;
+
+
+const CODE_LENS_N_CTX: usize = 4096;
+const CODE_LENS_TOP_N: usize = 7;
 
 
 #[derive(Deserialize)]
@@ -22,10 +33,70 @@ struct CodeLensResponse {
 }
 
 #[derive(Serialize)]
-struct CodeLensOutput {
-    spath: String,
+pub struct CodeLensOutput {
+    pub spath: String,
+    pub line1: usize,
+    pub line2: usize,
+}
+
+pub fn code_lens_from_defs(defs: &[Arc<AstDefinition>]) -> Vec<CodeLensOutput> {
+    let mut output: Vec<CodeLensOutput> = Vec::new();
+    for def in defs.iter() {
+        let line1 = def.full_range.start_point.row + 1;
+        let line2 = def.full_range.end_point.row + 1;
+        if line2 > line1 {
+            output.push(CodeLensOutput {
+                spath: def.path_drop0(),
+                line1,
+                line2,
+            });
+        }
+    }
+    output
+}
+
+pub async fn code_lens_execute(
+    gcx: Arc<ARwLock<GlobalContext>>,
+    cpath: &PathBuf,
     line1: usize,
     line2: usize,
+    lens_name: &str,
+) -> Result<Vec<ChatMessage>, String> {
+    // IDE plugins do this on their side: take the command messages, put the code in, run as a chat
+    let tconfig = load_customization(gcx.clone(), true).await?;
+    let lens = tconfig.code_lens.get(lens_name).ok_or(format!("no code lens {:?} in customization", lens_name))?;
+    if lens.messages.is_empty() {
+        return Err(format!("code lens {:?} has no messages, it only works in IDE", lens_name));
+    }
+    let text = get_file_text_from_memory_or_disk(gcx.clone(), cpath).await?;
+    let code_selection = text.lines().skip(line1.saturating_sub(1)).take((line2 + 1).saturating_sub(line1)).collect::<Vec<_>>().join("\n");
+    let messages: Vec<ChatMessage> = lens.messages.iter().map(|msg| ChatMessage {
+        role: msg.role.clone(),
+        content: ChatContent::SimpleText(msg.content.content_text_only().replace("%CODE_SELECTION%", &code_selection)),
+        ..Default::default()
+    }).collect();
+    let model_name = try_load_caps_quickly_if_not_present(gcx.clone(), 0).await
+        .map_err(|e| format!("no caps: {:?}", e))?
+        .read().unwrap().code_chat_default_model.clone();
+    let ccx: Arc<AMutex<AtCommandsContext>> = Arc::new(AMutex::new(
+        AtCommandsContext::new(gcx.clone(), CODE_LENS_N_CTX, CODE_LENS_TOP_N, false, messages.clone(), "".to_string()).await)
+    );
+    let choices = subchat_single(
+        ccx.clone(),
+        &model_name,
+        messages.clone(),
+        vec![],
+        None,
+        false,
+        None,
+        None,
+        1,
+        None,
+        None,
+        None,
+    ).await?;
+    let new_messages = choices.into_iter().next().unwrap_or_default().into_iter().skip(messages.len()).collect();
+    Ok(new_messages)
 }
 
 pub async fn handle_v1_code_lens(
@@ -52,22 +123,9 @@ pub async fn handle_v1_code_lens(
             .unwrap())
     };
 
-    let mut output: Vec<CodeLensOutput> = Vec::new();
-    for def in defs.iter() {
-        let line1 = def.full_range.start_point.row + 1;
-        let line2 = def.full_range.end_point.row + 1;
-        if line2 > line1 {
-            output.push(CodeLensOutput {
-                spath: def.path_drop0(),
-                line1,
-                line2,
-            });
-        }
-    }
-
     let response = CodeLensResponse {
         success: 1,
-        code_lens: output,
+        code_lens: code_lens_from_defs(&defs),
     };
 
     Ok(Response::builder()
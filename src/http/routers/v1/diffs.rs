@@ -176,7 +176,7 @@ async fn _sync_documents_ast_vecdb(gcx: Arc<ARwLock<GlobalContext>>, docs: Vec<D
     let vecdb_enqueued = if let Some(vservice) = {
         let gcx_locked = gcx.write().await;
         let vec_db_guard = gcx_locked.vec_db.lock().await;
-        vec_db_guard.as_ref().map(|v| v.vectorizer_service.clone())
+        vec_db_guard.as_ref().and_then(|v| v.vectorizer_service.clone())
     } {
         vectorizer_enqueue_files(vservice, &docs, true).await;
         true
@@ -5,6 +5,7 @@ use serde::Serialize;
 
 use crate::ast::ast_structs::AstStatus;
 use crate::custom_error::ScratchError;
+use crate::file_filter::SkippedFilesReport;
 use crate::global_context::SharedGlobalContext;
 use crate::vecdb::vdb_structs::VecDbStatus;
 
@@ -16,15 +17,16 @@ struct RagStatus {
     vecdb: Option<VecDbStatus>,
     vecdb_alive: String,
     vec_db_error: String,
+    skipped: SkippedFilesReport,  // reason => count and some examples
 }
 
 pub async fn handle_v1_rag_status(
     Extension(gcx): Extension<SharedGlobalContext>,
     _: hyper::body::Bytes,
 ) -> Result<Response<Body>, ScratchError> {
-    let (vec_db_module, vec_db_error, ast_module) = {
+    let (vec_db_module, vec_db_error, ast_module, indexing_skipped) = {
         let gcx_locked = gcx.write().await;
-        (gcx_locked.vec_db.clone(), gcx_locked.vec_db_error.clone(), gcx_locked.ast_service.clone())
+        (gcx_locked.vec_db.clone(), gcx_locked.vec_db_error.clone(), gcx_locked.ast_service.clone(), gcx_locked.documents_state.indexing_skipped.clone())
     };
 
     let (maybe_vecdb_status, vecdb_message) = match crate::vecdb::vdb_highlev::get_status(vec_db_module).await {
@@ -47,6 +49,7 @@ pub async fn handle_v1_rag_status(
         vecdb: maybe_vecdb_status,
         vecdb_alive: vecdb_message,
         vec_db_error,
+        skipped: indexing_skipped.lock().unwrap().report(),
     };
     let json_string = serde_json::to_string_pretty(&status).map_err(|e| {
         ScratchError::new(StatusCode::INTERNAL_SERVER_ERROR, format!("JSON serialization problem: {}", e))
@@ -1,7 +1,7 @@
 use crate::caps::get_custom_embedding_api_key;
 use crate::custom_error::ScratchError;
 use crate::global_context::SharedGlobalContext;
-use crate::vecdb::vdb_structs::VecdbSearch;
+use crate::vecdb::vdb_structs::{lexical_weight_default, VecdbSearch};
 use axum::response::Result;
 use axum::Extension;
 use hyper::{Body, Response, StatusCode};
@@ -10,6 +10,10 @@ use serde::{Deserialize, Serialize};
 struct VecDBPost {
     query: String,
     top_n: usize,
+    #[serde(default)]
+    scope: Option<String>,  // a file path, or a directory path ending with a separator
+    #[serde(default)]
+    lexical_weight: Option<f32>,  // depends on the query if not given, see lexical_weight_default
 }
 
 const NO_VECDB: &str = "Vector db is not running, check if you have --vecdb parameter and a vectorization model is running on server side.";
@@ -27,7 +31,7 @@ pub async fn handle_v1_vecdb_search(
     let cx_locked = global_context.read().await;
 
     let search_res = match *cx_locked.vec_db.lock().await {
-        Some(ref db) => db.vecdb_search(post.query.to_string(), post.top_n, None, &api_key).await,
+        Some(ref db) => db.vecdb_search(post.query.to_string(), post.top_n, post.scope, post.lexical_weight.unwrap_or_else(|| lexical_weight_default(&post.query)), &api_key).await,
         None => {
             return Err(ScratchError::new(
                 StatusCode::INTERNAL_SERVER_ERROR, NO_VECDB.to_string(),
@@ -0,0 +1,20 @@
+pub const COMPILED_IN_INITIAL_INDEXING_YAML: &str = r#"#
+# This config file determines which workspace files get indexed by AST and vecdb.
+#
+# Put the same keys into <workspace>/.refact/indexing.yaml to override them for one workspace.
+# Files excluded by .gitignore are never indexed, .refactignore (same syntax) excludes from indexing only.
+#
+# If you have a syntax error in this file, the built-in defaults are used.
+
+# extensions: ["py", "rs", ...]         # replaces the built-in list of source file extensions
+additional_extensions: []               # added to the built-in list, for example ["proto", "tf"]
+min_file_size: 5                        # bytes
+
+ast:
+    max_file_size: 184320               # 180k
+    disabled_languages: []              # "cpp", "python", "java", "javascript", "rust", "go", "typescript", "typescriptreact", or an extension like "proto"
+
+vecdb:
+    max_file_size: 184320
+    disabled_languages: []
+"#;
@@ -0,0 +1,382 @@
+use std::sync::Arc;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use regex::Regex;
+use serde_json::Value;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::sync::Mutex as AMutex;
+use tokio::process::Command;
+use tokio::time::{timeout, Duration};
+use async_trait::async_trait;
+use tracing::{error, info};
+use serde::{Deserialize, Serialize};
+
+use crate::at_commands::at_commands::AtCommandsContext;
+use crate::call_validation::{ChatContent, ContextEnum, ChatMessage};
+use crate::tools::tools_description::{AtParamDict, Tool, ToolDict};
+
+// Tools declared by the user in `cmdline_tools` of integrations.yaml: a command template, %param% gets replaced
+// with the argument the model gives. The template is split into words before substitution, so arguments never
+// go through a shell.
+
+fn default_timeout() -> u64 {
+    120
+}
+
+fn default_limit_lines() -> usize {
+    100
+}
+
+fn default_limit_chars() -> usize {
+    10000
+}
+
+fn default_valuable_top_or_bottom() -> String {
+    "bottom".to_string()
+}
+
+fn default_grep_context_lines() -> usize {
+    5
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct CmdlineOutputFilter {
+    #[serde(default = "default_limit_lines")]
+    pub limit_lines: usize,
+    #[serde(default = "default_limit_chars")]
+    pub limit_chars: usize,
+    #[serde(default = "default_valuable_top_or_bottom")]
+    pub valuable_top_or_bottom: String,  // which end to keep if the output is too long
+    #[serde(default)]
+    pub grep: String,  // regex, keep only the matching lines and some context around them
+    #[serde(default = "default_grep_context_lines")]
+    pub grep_context_lines: usize,
+}
+
+impl Default for CmdlineOutputFilter {
+    fn default() -> Self {
+        CmdlineOutputFilter {
+            limit_lines: default_limit_lines(),
+            limit_chars: default_limit_chars(),
+            valuable_top_or_bottom: default_valuable_top_or_bottom(),
+            grep: String::new(),
+            grep_context_lines: default_grep_context_lines(),
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct IntegrationCmdline {
+    pub description: String,
+    #[serde(default)]
+    pub parameters: Vec<AtParamDict>,
+    #[serde(default)]
+    pub parameters_required: Option<Vec<String>>,  // all of them if not given
+    pub command: String,
+    #[serde(default)]
+    pub workdir: String,  // no %param% here, the model doesn't pick the directory; the default is the first workspace folder
+    #[serde(default = "default_timeout")]
+    pub timeout: u64,
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    #[serde(default)]
+    pub output_filter: CmdlineOutputFilter,
+}
+
+pub struct ToolCmdline {
+    name: String,
+    cfg: IntegrationCmdline,
+}
+
+lazy_static::lazy_static! {
+    static ref PARAM_RE: Regex = Regex::new(r"%([A-Za-z0-9_]+)%").unwrap();
+}
+
+pub fn cmdline_tools_if_configured(integrations_value: &serde_yaml::Value) -> Vec<(String, ToolCmdline)> {
+    let tools_value = match integrations_value.get("cmdline_tools") {
+        Some(x) => x.clone(),
+        None => return vec![],
+    };
+    let tools = match serde_yaml::from_value::<indexmap::IndexMap<String, IntegrationCmdline>>(tools_value) {
+        Ok(x) => x,
+        Err(e) => {
+            error!("Failed to parse integration cmdline_tools: {:?}", e);
+            return vec![];
+        }
+    };
+    tools.into_iter().map(|(name, cfg)| (name.clone(), ToolCmdline { name, cfg })).collect()
+}
+
+fn replace_params(template: &str, args: &HashMap<String, Value>) -> (String, bool) {
+    // one pass over the template, the values aren't looked into, so "%x%" given by the model stays as is;
+    // returns true if some placeholder had no argument, it becomes empty
+    let mut has_unset = false;
+    let result = PARAM_RE.replace_all(template, |caps: &regex::Captures| {
+        match args.get(&caps[1]) {
+            Some(Value::String(s)) => s.clone(),
+            Some(other) => other.to_string(),
+            None => {
+                has_unset = true;
+                String::new()
+            }
+        }
+    }).to_string();
+    (result, has_unset)
+}
+
+#[cfg(unix)]
+pub fn kill_process_group(pid: u32, signal: libc::c_int) {
+    unsafe { libc::killpg(pid as libc::pid_t, signal); }
+}
+
+async fn read_all<R: AsyncRead + Unpin>(reader: Option<&mut R>) -> Vec<u8> {
+    let mut buf = vec![];
+    if let Some(reader) = reader {
+        let _ = reader.read_to_end(&mut buf).await;
+    }
+    buf
+}
+
+pub async fn output_in_process_group(cmd: &mut Command, timeout_secs: u64) -> Result<std::process::Output, String> {
+    // the command gets its own process group, on timeout the whole group is killed, not only the direct child
+    cmd.stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .kill_on_drop(true);
+    #[cfg(unix)]
+    cmd.process_group(0);
+    let mut child = cmd.spawn().map_err(|e| format!("failed to start {}: {}", cmd.as_std().get_program().to_string_lossy(), e))?;
+    let pid = child.id();
+    let mut stdout = child.stdout.take();
+    let mut stderr = child.stderr.take();
+    let result = timeout(Duration::from_secs(timeout_secs), async {
+        let (status, stdout, stderr) = tokio::join!(child.wait(), read_all(stdout.as_mut()), read_all(stderr.as_mut()));
+        status.map(|status| std::process::Output { status, stdout, stderr }).map_err(|e| e.to_string())
+    }).await;
+    match result {
+        Ok(output) => output,
+        Err(_) => {
+            #[cfg(unix)]
+            if let Some(pid) = pid {
+                kill_process_group(pid, libc::SIGKILL);
+            }
+            let _ = child.kill().await;
+            Err(format!("timed out after {}s, the process group was killed", timeout_secs))
+        }
+    }
+}
+
+impl ToolCmdline {
+    fn command_args(&self, args: &HashMap<String, Value>) -> Result<Vec<String>, String> {
+        for param in self.cfg.parameters_required.as_ref().unwrap_or(&self.cfg.parameters.iter().map(|p| p.name.clone()).collect()) {
+            if !args.contains_key(param) {
+                return Err(format!("Missing argument `{}`", param));
+            }
+        }
+        let words = shell_words::split(&self.cfg.command).map_err(|e| format!("{}: bad command template: {}", self.name, e))?;
+        // optional parameters that weren't given disappear, together with the word if nothing else is left in it
+        let command_args: Vec<String> = words.iter()
+            .map(|w| replace_params(w, args))
+            .filter(|(w, has_unset)| !(w.is_empty() && *has_unset))
+            .map(|(w, _)| w)
+            .collect();
+        if command_args.is_empty() {
+            return Err(format!("{}: command is empty", self.name));
+        }
+        Ok(command_args)
+    }
+}
+
+pub fn filter_output(output: &str, filter: &CmdlineOutputFilter) -> String {
+    let mut lines: Vec<&str> = output.lines().collect();
+    if !filter.grep.is_empty() {
+        match Regex::new(&filter.grep) {
+            Ok(re) => {
+                let mut keep = vec![false; lines.len()];
+                for (i, line) in lines.iter().enumerate() {
+                    if re.is_match(line) {
+                        let from = i.saturating_sub(filter.grep_context_lines);
+                        let to = (i + filter.grep_context_lines + 1).min(lines.len());
+                        keep[from..to].iter_mut().for_each(|k| *k = true);
+                    }
+                }
+                if keep.iter().any(|k| *k) {
+                    lines = lines.into_iter().zip(keep).filter(|(_, k)| *k).map(|(line, _)| line).collect();
+                }
+            }
+            Err(e) => error!("output_filter grep {:?}: {}", filter.grep, e),
+        }
+    }
+
+    let from_bottom = filter.valuable_top_or_bottom == "bottom";
+    let mut skipped_lines = lines.len().saturating_sub(filter.limit_lines);
+    let mut kept: Vec<&str> = if from_bottom {
+        lines[skipped_lines..].to_vec()
+    } else {
+        lines[..lines.len() - skipped_lines].to_vec()
+    };
+    let mut chars = 0;
+    let mut fits = kept.len();
+    for (n, line) in (0..kept.len()).map(|i| if from_bottom { kept[kept.len() - 1 - i] } else { kept[i] }).enumerate() {
+        chars += line.len() + 1;
+        if chars > filter.limit_chars {
+            fits = n;
+            break;
+        }
+    }
+    skipped_lines += kept.len() - fits;
+    if from_bottom {
+        kept.drain(..kept.len() - fits);
+    } else {
+        kept.truncate(fits);
+    }
+
+    let mut result = kept.join("\n");
+    if skipped_lines > 0 {
+        let note = format!("... {} lines skipped ...", skipped_lines);
+        result = if from_bottom { format!("{}\n{}", note, result) } else { format!("{}\n{}", result, note) };
+    }
+    result
+}
+
+#[async_trait]
+impl Tool for ToolCmdline {
+    async fn tool_execute(
+        &mut self,
+        ccx: Arc<AMutex<AtCommandsContext>>,
+        tool_call_id: &String,
+        args: &HashMap<String, Value>,
+    ) -> Result<(bool, Vec<ContextEnum>), String> {
+        let command_args = self.command_args(args)?;
+        let workdir = if !self.cfg.workdir.is_empty() {
+            PathBuf::from(&self.cfg.workdir)
+        } else {
+            let gcx = ccx.lock().await.global_context.clone();
+            let workspace_folders = gcx.read().await.documents_state.workspace_folders.lock().unwrap().clone();
+            workspace_folders.first().cloned().unwrap_or_else(|| PathBuf::from("."))
+        };
+
+        info!("{}: running {:?} in {}", self.name, command_args, workdir.display());
+        let t0 = std::time::Instant::now();
+        let output = output_in_process_group(
+            Command::new(&command_args[0]).args(&command_args[1..]).current_dir(&workdir).envs(&self.cfg.env),
+            self.cfg.timeout,
+        ).await.map_err(|e| format!("{}: {}", self.name, e))?;
+
+        let stdout = filter_output(&String::from_utf8_lossy(&output.stdout), &self.cfg.output_filter);
+        let stderr = filter_output(&String::from_utf8_lossy(&output.stderr), &self.cfg.output_filter);
+        let mut out = format!("{} finished in {:.1}s, exit code {}\n", shell_words::join(&command_args), t0.elapsed().as_secs_f64(),
+            output.status.code().map(|x| x.to_string()).unwrap_or("unknown".to_string()));
+        if !stdout.is_empty() {
+            out.push_str(&format!("stdout:\n{}\n", stdout));
+        }
+        if !stderr.is_empty() {
+            out.push_str(&format!("stderr:\n{}\n", stderr));
+        }
+
+        Ok((false, vec![
+            ContextEnum::ChatMessage(ChatMessage {
+                role: "tool".to_string(),
+                content: ChatContent::SimpleText(out),
+                tool_calls: None,
+                tool_call_id: tool_call_id.clone(),
+                ..Default::default()
+            })
+        ]))
+    }
+
+    fn command_to_match_against_confirm_deny(
+        &self,
+        args: &HashMap<String, Value>,
+    ) -> Result<String, String> {
+        Ok(shell_words::join(self.command_args(args)?))
+    }
+
+    fn tool_description(&self) -> Option<ToolDict> {
+        Some(ToolDict {
+            name: self.name.clone(),
+            agentic: true,
+            experimental: false,
+            description: self.cfg.description.clone(),
+            parameters: self.cfg.parameters.clone(),
+            parameters_required: self.cfg.parameters_required.clone().unwrap_or(self.cfg.parameters.iter().map(|p| p.name.clone()).collect()),
+            input_schema: None,
+        })
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cmdline_tool() {
+        let integrations_value: serde_yaml::Value = serde_yaml::from_str(r#"
+cmdline_tools:
+  run_migration:
+    description: "Run a database migration"
+    parameters:
+      - name: "migration"
+        type: "string"
+        description: "Migration name"
+      - name: "dry_run"
+        type: "string"
+        description: "Pass --dry-run here to only print SQL"
+    parameters_required: ["migration"]
+    command: "./scripts/run_migration.sh %migration% %dry_run%"
+"#).unwrap();
+        let tools = cmdline_tools_if_configured(&integrations_value);
+        assert_eq!(tools.len(), 1);
+        let tool = &tools[0].1;
+        let args = HashMap::from([("migration".to_string(), Value::String("0042 add users; rm -rf /".to_string()))]);
+        assert_eq!(tool.command_args(&args).unwrap(), vec!["./scripts/run_migration.sh", "0042 add users; rm -rf /"]);
+        assert_eq!(tool.command_to_match_against_confirm_deny(&args).unwrap(), "./scripts/run_migration.sh '0042 add users; rm -rf /'");
+        assert!(tool.command_args(&HashMap::new()).is_err());
+        // a value is never substituted again, and unset placeholders go only from the template
+        let args = HashMap::from([
+            ("migration".to_string(), Value::String("%dry_run%".to_string())),
+            ("dry_run".to_string(), Value::String("--dry-run".to_string())),
+        ]);
+        assert_eq!(tool.command_args(&args).unwrap(), vec!["./scripts/run_migration.sh", "%dry_run%", "--dry-run"]);
+        let args = HashMap::from([("migration".to_string(), Value::String("50%off%x%".to_string()))]);
+        assert_eq!(tool.command_args(&args).unwrap(), vec!["./scripts/run_migration.sh", "50%off%x%"]);
+        let args = HashMap::from([("migration".to_string(), Value::String("".to_string()))]);
+        assert_eq!(tool.command_args(&args).unwrap(), vec!["./scripts/run_migration.sh", ""]);
+        assert_eq!(tool.tool_description().unwrap().parameters_required, vec!["migration".to_string()]);
+    }
+
+    #[test]
+    fn test_filter_output() {
+        let output = (1..=10).map(|i| format!("line {}", i)).collect::<Vec<_>>().join("\n");
+        let filter = CmdlineOutputFilter { limit_lines: 3, ..Default::default() };
+        assert_eq!(filter_output(&output, &filter), "... 7 lines skipped ...\nline 8\nline 9\nline 10");
+        let filter = CmdlineOutputFilter { limit_lines: 3, valuable_top_or_bottom: "top".to_string(), ..Default::default() };
+        assert_eq!(filter_output(&output, &filter), "line 1\nline 2\nline 3\n... 7 lines skipped ...");
+        let filter = CmdlineOutputFilter { grep: "line 5$".to_string(), grep_context_lines: 1, ..Default::default() };
+        assert_eq!(filter_output(&output, &filter), "line 4\nline 5\nline 6");
+        let filter = CmdlineOutputFilter { limit_chars: 16, ..Default::default() };
+        assert_eq!(filter_output(&output, &filter), "... 8 lines skipped ...\nline 9\nline 10");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_timeout_kills_process_group() {
+        let tmp = tempfile::tempdir().unwrap();
+        let pid_file = tmp.path().join("pid");
+        let output = output_in_process_group(Command::new("sh").arg("-c").arg("echo hello; echo oops >&2"), 10).await.unwrap();
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "hello\n");
+        assert_eq!(String::from_utf8_lossy(&output.stderr), "oops\n");
+
+        // the grandchild keeps stdout open, killing only the direct child would not be enough
+        let script = format!("sleep 60 & echo $! > {}; wait", pid_file.display());
+        let t0 = std::time::Instant::now();
+        let err = output_in_process_group(Command::new("sh").arg("-c").arg(&script), 1).await.unwrap_err();
+        assert!(err.contains("timed out"), "{}", err);
+        assert!(t0.elapsed() < Duration::from_secs(10));
+        let pid = std::fs::read_to_string(&pid_file).unwrap().trim().to_string();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        let alive = std::fs::read_to_string(format!("/proc/{}/stat", pid)).map(|stat| !stat.contains(") Z ")).unwrap_or(false);
+        assert!(!alive, "sleep {} survived the timeout", pid);
+    }
+}
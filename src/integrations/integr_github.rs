@@ -7,7 +7,7 @@ use tracing::{error, info};
 use serde::{Deserialize, Serialize};
 
 use crate::at_commands::at_commands::AtCommandsContext;
-use crate::call_validation::{ContextEnum, ChatMessage};
+use crate::call_validation::{ChatContent, ContextEnum, ChatMessage};
 
 use crate::tools::tools_description::Tool;
 use serde_json::Value;
@@ -85,7 +85,7 @@ impl Tool for ToolGithub {
         let mut results = vec![];
         results.push(ContextEnum::ChatMessage(ChatMessage {
             role: "tool".to_string(),
-            content: content,
+            content: ChatContent::SimpleText(content),
             tool_calls: None,
             tool_call_id: tool_call_id.clone(),
             ..Default::default()
@@ -0,0 +1,648 @@
+use std::any::Any;
+use std::sync::{Arc, Mutex as StdMutex};
+use std::collections::HashMap;
+use std::time::SystemTime;
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::sync::{mpsc, Mutex as AMutex, RwLock as ARwLock};
+use tokio::process::{Command, Child, ChildStdin, ChildStdout};
+use tokio::task::JoinHandle;
+use tokio::time::{timeout, Duration};
+use async_trait::async_trait;
+use futures::StreamExt;
+use tracing::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::at_commands::at_commands::AtCommandsContext;
+use crate::call_validation::{ChatContent, ContextEnum, ChatMessage};
+#[cfg(unix)]
+use crate::integrations::integr_cmdline::kill_process_group;
+use crate::integrations::sessions::{IntegrationSession, get_session_hashmap_key};
+use crate::global_context::GlobalContext;
+use crate::tools::tools_description::{Tool, ToolDict};
+
+// Model Context Protocol client: each server from `mcp_servers` in integrations.yaml is a process (or a local SSE
+// endpoint) speaking JSON-RPC, every tool it lists becomes a tool named mcp_<server>_<tool>.
+// Tools lists are gathered in the background and cached in gcx.mcp_servers, tool calls use a session per chat.
+
+const SESSION_TIMEOUT_AFTER_INACTIVITY: Duration = Duration::from_secs(30 * 60);
+pub const MCP_PROTOCOL_VERSION: &str = "2024-11-05";
+const MCP_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+const MCP_RETRY_BACKOFF_MIN: u64 = 10;   // seconds, doubles with every failure to list the tools
+const MCP_RETRY_BACKOFF_MAX: u64 = 600;
+const MCP_TOOL_NAME_MAX_LEN: usize = 64;
+
+fn default_timeout() -> u64 {
+    60
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct IntegrationMcpServer {
+    #[serde(default)]
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    #[serde(default)]
+    pub url: String,  // SSE endpoint, instead of command
+    #[serde(default = "default_timeout")]
+    pub timeout: u64,  // seconds, for one tool call
+}
+
+#[derive(Clone, Deserialize, Debug)]
+pub struct McpToolInfo {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default, rename = "inputSchema")]
+    pub input_schema: Value,
+}
+
+struct McpServerProcess {
+    process: Child,
+}
+
+impl Drop for McpServerProcess {
+    fn drop(&mut self) {
+        // npx and uvx start the real server as a grandchild, kill_on_drop alone would leave it orphaned;
+        // the child is never waited for, so the process group id is still ours
+        #[cfg(unix)]
+        if let Some(pid) = self.process.id() {
+            kill_process_group(pid, libc::SIGKILL);
+        }
+    }
+}
+
+enum McpTransport {
+    Stdio {
+        _process: Box<McpServerProcess>,
+        stdin: ChildStdin,
+        stdout: BufReader<ChildStdout>,
+    },
+    Sse {
+        client: reqwest::Client,
+        post_url: String,
+        events: mpsc::UnboundedReceiver<(String, String)>,
+        reader: JoinHandle<()>,
+    },
+}
+
+impl Drop for McpTransport {
+    fn drop(&mut self) {
+        if let McpTransport::Sse { reader, .. } = self {
+            reader.abort();
+        }
+    }
+}
+
+impl McpTransport {
+    async fn send(&mut self, message: &Value) -> Result<(), String> {
+        match self {
+            McpTransport::Stdio { stdin, .. } => {
+                stdin.write_all(format!("{}\n", message).as_bytes()).await.map_err(|e| e.to_string())?;
+                stdin.flush().await.map_err(|e| e.to_string())
+            }
+            McpTransport::Sse { client, post_url, .. } => {
+                let response = client.post(post_url.as_str()).json(message).send().await.map_err(|e| e.to_string())?;
+                if !response.status().is_success() {
+                    return Err(format!("{} returned {}", post_url, response.status()));
+                }
+                Ok(())
+            }
+        }
+    }
+
+    async fn recv(&mut self) -> Result<Value, String> {
+        loop {
+            let text = match self {
+                McpTransport::Stdio { stdout, .. } => {
+                    let mut line = String::new();
+                    if stdout.read_line(&mut line).await.map_err(|e| e.to_string())? == 0 {
+                        return Err("MCP server closed stdout".to_string());
+                    }
+                    line
+                }
+                McpTransport::Sse { events, .. } => {
+                    match events.recv().await {
+                        Some((event, data)) if event == "message" || event.is_empty() => data,
+                        Some(_) => continue,
+                        None => return Err("MCP server closed the event stream".to_string()),
+                    }
+                }
+            };
+            if text.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str::<Value>(&text) {
+                Ok(message) => return Ok(message),
+                Err(_) => info!("MCP server says: {}", text.trim_end()),  // some servers log to stdout
+            }
+        }
+    }
+}
+
+pub struct McpSession {
+    config: IntegrationMcpServer,
+    transport: Option<McpTransport>,  // connected on first use, dropped after an error
+    next_id: u64,
+    last_usage_ts: u64,
+}
+
+#[derive(Default)]
+pub struct McpServerTools {
+    // tools list of one server, gathered in the background, chats never wait for a server
+    config: Option<IntegrationMcpServer>,
+    tools: Option<Vec<McpToolInfo>>,
+    listing: bool,
+    failures: u32,
+    retry_after_ts: u64,
+}
+
+impl IntegrationSession for McpSession
+{
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn is_expired(&self) -> bool {
+        let current_time = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs();
+        self.last_usage_ts + SESSION_TIMEOUT_AFTER_INACTIVITY.as_secs() < current_time
+    }
+}
+
+async fn sse_connect(url: &str) -> Result<McpTransport, String> {
+    let client = reqwest::Client::new();
+    let response = client.get(url).header("Accept", "text/event-stream").send().await.map_err(|e| e.to_string())?;
+    if !response.status().is_success() {
+        return Err(format!("{} returned {}", url, response.status()));
+    }
+    let (tx, mut events) = mpsc::unbounded_channel::<(String, String)>();
+    let reader = tokio::spawn(async move {
+        let mut bytes_stream = response.bytes_stream();
+        let mut buffer = String::new();
+        let (mut event, mut data) = (String::new(), String::new());
+        while let Some(Ok(bytes)) = bytes_stream.next().await {
+            buffer.push_str(&String::from_utf8_lossy(&bytes));
+            while let Some(pos) = buffer.find('\n') {
+                let line = buffer[..pos].trim_end_matches('\r').to_string();
+                buffer.drain(..=pos);
+                if line.is_empty() {
+                    if !data.is_empty() && tx.send((std::mem::take(&mut event), std::mem::take(&mut data))).is_err() {
+                        return;
+                    }
+                    event.clear();
+                } else if let Some(x) = line.strip_prefix("event:") {
+                    event = x.trim().to_string();
+                } else if let Some(x) = line.strip_prefix("data:") {
+                    if !data.is_empty() {
+                        data.push('\n');
+                    }
+                    data.push_str(x.trim_start());
+                }
+            }
+        }
+    });
+    // the first event tells where to post the requests
+    let endpoint = loop {
+        match events.recv().await {
+            Some((event, data)) if event == "endpoint" => break data,
+            Some(_) => continue,
+            None => return Err(format!("{} closed the stream before sending the endpoint", url)),
+        }
+    };
+    let post_url = url::Url::parse(url).and_then(|base| base.join(&endpoint)).map_err(|e| e.to_string())?;
+    Ok(McpTransport::Sse { client, post_url: post_url.to_string(), events, reader })
+}
+
+fn stdio_connect(config: &IntegrationMcpServer) -> Result<McpTransport, String> {
+    let mut cmd = Command::new(&config.command);
+    cmd.args(&config.args)
+        .envs(&config.env)
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .kill_on_drop(true);
+    #[cfg(unix)]
+    cmd.process_group(0);  // see McpServerProcess::drop
+    let mut process = cmd.spawn().map_err(|e| format!("failed to start {}: {}", config.command, e))?;
+    let stdin = process.stdin.take().ok_or("Failed to open stdin for MCP server")?;
+    let stdout = BufReader::new(process.stdout.take().ok_or("Failed to open stdout for MCP server")?);
+    if let Some(stderr) = process.stderr.take() {
+        let command = config.command.clone();
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(stderr).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                info!("MCP {} stderr: {}", command, line);
+            }
+        });
+    }
+    Ok(McpTransport::Stdio { _process: Box::new(McpServerProcess { process }), stdin, stdout })
+}
+
+impl McpSession {
+    fn new(config: &IntegrationMcpServer) -> Self {
+        McpSession {
+            config: config.clone(),
+            transport: None,
+            next_id: 1,
+            last_usage_ts: 0,
+        }
+    }
+
+    async fn connect(&mut self) -> Result<(), String> {
+        if self.config.url.is_empty() && self.config.command.is_empty() {
+            return Err("MCP server needs either `command` or `url`".to_string());
+        }
+        let result = timeout(MCP_HANDSHAKE_TIMEOUT, async {
+            self.transport = Some(if !self.config.url.is_empty() {
+                sse_connect(&self.config.url).await?
+            } else {
+                stdio_connect(&self.config)?
+            });
+            self.handshake().await
+        }).await.unwrap_or_else(|_| Err(format!("MCP handshake timed out after {}s", MCP_HANDSHAKE_TIMEOUT.as_secs())));
+        if result.is_err() {
+            self.transport = None;
+        }
+        result
+    }
+
+    async fn connect_if_needed(&mut self, config: &IntegrationMcpServer) -> Result<(), String> {
+        if self.config != *config {
+            self.transport = None;
+            self.config = config.clone();
+        }
+        if self.transport.is_none() {
+            self.connect().await?;
+        }
+        Ok(())
+    }
+
+    async fn handshake(&mut self) -> Result<(), String> {
+        let init = self.request("initialize", json!({
+            "protocolVersion": MCP_PROTOCOL_VERSION,
+            "capabilities": {},
+            "clientInfo": {"name": "refact-lsp", "version": crate::version::build_info::PKG_VERSION},
+        })).await?;
+        info!("MCP server {} initialized", init["serverInfo"]["name"].as_str().unwrap_or("unknown"));
+        self.transport_mut()?.send(&json!({"jsonrpc": "2.0", "method": "notifications/initialized"})).await?;
+        self.touch();
+        Ok(())
+    }
+
+    async fn list_tools(&mut self) -> Result<Vec<McpToolInfo>, String> {
+        let mut tools = vec![];
+        let mut cursor: Option<String> = None;
+        loop {
+            let params = match &cursor {
+                Some(c) => json!({"cursor": c}),
+                None => json!({}),
+            };
+            let result = self.request("tools/list", params).await?;
+            let page: Vec<McpToolInfo> = serde_json::from_value(result["tools"].clone()).map_err(|e| format!("tools/list: {}", e))?;
+            tools.extend(page);
+            cursor = result["nextCursor"].as_str().map(|x| x.to_string());
+            if cursor.is_none() {
+                break;
+            }
+        }
+        Ok(tools)
+    }
+
+    fn transport_mut(&mut self) -> Result<&mut McpTransport, String> {
+        self.transport.as_mut().ok_or("MCP server is not connected".to_string())
+    }
+
+    fn touch(&mut self) {
+        self.last_usage_ts = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs();
+    }
+
+    async fn request(&mut self, method: &str, params: Value) -> Result<Value, String> {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.transport_mut()?.send(&json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params})).await?;
+        loop {
+            let message = self.transport_mut()?.recv().await?;
+            if let Some(server_method) = message.get("method").and_then(|x| x.as_str()) {
+                // server to client: notifications are ignored, requests other than ping aren't supported
+                if let Some(server_id) = message.get("id") {
+                    let reply = if server_method == "ping" {
+                        json!({"jsonrpc": "2.0", "id": server_id, "result": {}})
+                    } else {
+                        json!({"jsonrpc": "2.0", "id": server_id, "error": {"code": -32601, "message": format!("{} is not supported", server_method)}})
+                    };
+                    self.transport_mut()?.send(&reply).await?;
+                }
+                continue;
+            }
+            if message.get("id").and_then(|x| x.as_u64()) != Some(id) {
+                continue;
+            }
+            if let Some(error) = message.get("error") {
+                return Err(format!("{} failed: {}", method, error["message"].as_str().unwrap_or(&error.to_string())));
+            }
+            return Ok(message.get("result").cloned().unwrap_or(Value::Null));
+        }
+    }
+}
+
+pub fn mcp_tool_name(server_name: &str, tool_name: &str) -> String {
+    // OpenAI function names: ^[a-zA-Z0-9_-]{1,64}$; a long name is cut and gets a hash of the whole name,
+    // so that two tools starting the same way stay two tools
+    let full_name = format!("mcp_{}_{}", server_name, tool_name);
+    let name: String = full_name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '_' || c == '-' { c } else { '_' })
+        .collect();
+    if name.len() <= MCP_TOOL_NAME_MAX_LEN {
+        return name;
+    }
+    let hash = format!("{:x}", Sha256::digest(full_name.as_bytes()));
+    format!("{}_{}", &name[..MCP_TOOL_NAME_MAX_LEN - 9], &hash[..8])
+}
+
+fn mcp_tool_dict(name: &str, tool: &McpToolInfo) -> ToolDict {
+    // the schema goes to the model as the server wrote it: nested objects, enums, formats
+    let input_schema = match &tool.input_schema {
+        Value::Null => json!({"type": "object", "properties": {}}),
+        schema => schema.clone(),
+    };
+    ToolDict {
+        name: name.to_string(),
+        agentic: true,
+        experimental: false,
+        description: tool.description.clone(),
+        parameters: vec![],
+        parameters_required: vec![],
+        input_schema: Some(input_schema),
+    }
+}
+
+fn mcp_result_to_text(result: &Value) -> String {
+    let mut parts = vec![];
+    for item in result["content"].as_array().unwrap_or(&vec![]) {
+        match item["type"].as_str().unwrap_or_default() {
+            "text" => parts.push(item["text"].as_str().unwrap_or_default().to_string()),
+            "resource" => {
+                let resource = &item["resource"];
+                parts.push(format!("{}\n{}", resource["uri"].as_str().unwrap_or_default(), resource["text"].as_str().unwrap_or_default()));
+            }
+            other => parts.push(format!("[{} content is not supported]", other)),
+        }
+    }
+    parts.join("\n")
+}
+
+async fn mcp_session_get(
+    gcx: Arc<ARwLock<GlobalContext>>,
+    session_hashmap_key: &String,
+    config: &IntegrationMcpServer,
+) -> Arc<AMutex<Box<dyn IntegrationSession>>> {
+    // a single entry() under the write lock, two tool calls at once share one session, it connects on first use
+    gcx.write().await.integration_sessions.entry(session_hashmap_key.clone())
+        .or_insert_with(|| Arc::new(AMutex::new(Box::new(McpSession::new(config)) as Box<dyn IntegrationSession>)))
+        .clone()
+}
+
+fn mcp_retry_backoff(failures: u32) -> u64 {
+    (MCP_RETRY_BACKOFF_MIN << failures.saturating_sub(1).min(10)).min(MCP_RETRY_BACKOFF_MAX)
+}
+
+async fn mcp_list_tools_in_background(
+    servers_arc: Arc<StdMutex<HashMap<String, McpServerTools>>>,
+    server_name: String,
+    config: IntegrationMcpServer,
+) {
+    info!("MCP server {}: listing tools", server_name);
+    let result = {
+        let mut session = McpSession::new(&config);
+        match session.connect().await {
+            Ok(()) => timeout(MCP_HANDSHAKE_TIMEOUT, session.list_tools()).await
+                .unwrap_or_else(|_| Err(format!("tools/list timed out after {}s", MCP_HANDSHAKE_TIMEOUT.as_secs()))),
+            Err(e) => Err(e),
+        }
+        // the session goes away here, chats start their own
+    };
+    let mut servers_locked = servers_arc.lock().unwrap();
+    let state = match servers_locked.get_mut(&server_name) {
+        Some(state) if state.config.as_ref() == Some(&config) => state,
+        _ => return,  // config changed in the meantime, the new one gets its own attempt
+    };
+    state.listing = false;
+    match result {
+        Ok(tools) => {
+            info!("MCP server {}: {} tools", server_name, tools.len());
+            state.tools = Some(tools);
+            state.failures = 0;
+        }
+        Err(e) => {
+            state.failures += 1;
+            let backoff = mcp_retry_backoff(state.failures);
+            state.retry_after_ts = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs() + backoff;
+            warn!("MCP server {}: {}, next attempt in {}s", server_name, e, backoff);
+        }
+    }
+}
+
+pub struct ToolMcp {
+    server_name: String,
+    config: IntegrationMcpServer,
+    tool: McpToolInfo,
+    tool_name: String,
+}
+
+pub async fn mcp_tools_if_configured(
+    gcx: Arc<ARwLock<GlobalContext>>,
+    integrations_value: &serde_yaml::Value,
+) -> Vec<(String, ToolMcp)> {
+    let servers_value = match integrations_value.get("mcp_servers") {
+        Some(x) => x.clone(),
+        None => return vec![],
+    };
+    let servers = match serde_yaml::from_value::<HashMap<String, IntegrationMcpServer>>(servers_value) {
+        Ok(x) => x,
+        Err(e) => {
+            error!("Failed to parse integration mcp_servers: {:?}", e);
+            return vec![];
+        }
+    };
+    let servers_arc = gcx.read().await.mcp_servers.clone();
+    let current_time = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs();
+    let mut result = vec![];
+    let mut to_list = vec![];
+    {
+        let mut servers_locked = servers_arc.lock().unwrap();
+        servers_locked.retain(|server_name, _| servers.contains_key(server_name));
+        for (server_name, config) in servers {
+            let state = servers_locked.entry(server_name.clone()).or_default();
+            if state.config.as_ref() != Some(&config) {
+                *state = McpServerTools { config: Some(config.clone()), ..Default::default() };
+            }
+            if state.tools.is_none() && !state.listing && state.retry_after_ts <= current_time {
+                state.listing = true;
+                to_list.push((server_name.clone(), config.clone()));
+            }
+            for tool in state.tools.iter().flatten() {
+                let tool_name = mcp_tool_name(&server_name, &tool.name);
+                if result.iter().any(|(name, _)| name == &tool_name) {
+                    warn!("MCP {}: tool {:?} is {} after replacing the characters OpenAI doesn't allow, same as another tool, skipped", server_name, tool.name, tool_name);
+                    continue;
+                }
+                result.push((tool_name.clone(), ToolMcp { server_name: server_name.clone(), config: config.clone(), tool: tool.clone(), tool_name }));
+            }
+        }
+    }
+    for (server_name, config) in to_list {
+        tokio::spawn(mcp_list_tools_in_background(servers_arc.clone(), server_name, config));
+    }
+    result
+}
+
+#[async_trait]
+impl Tool for ToolMcp {
+    async fn tool_execute(
+        &mut self,
+        ccx: Arc<AMutex<AtCommandsContext>>,
+        tool_call_id: &String,
+        args: &HashMap<String, Value>,
+    ) -> Result<(bool, Vec<ContextEnum>), String> {
+        let (gcx, chat_id) = {
+            let ccx_lock = ccx.lock().await;
+            (ccx_lock.global_context.clone(), ccx_lock.chat_id.clone())
+        };
+        let session_hashmap_key = get_session_hashmap_key(&format!("mcp_{}", self.server_name), &chat_id);
+        let session = mcp_session_get(gcx.clone(), &session_hashmap_key, &self.config).await;
+
+        let result = {
+            let mut session_locked = session.lock().await;
+            let mcp_session = session_locked.as_any_mut().downcast_mut::<McpSession>().ok_or("Failed to downcast to McpSession")?;
+            let call_result = async {
+                mcp_session.connect_if_needed(&self.config).await?;
+                timeout(
+                    Duration::from_secs(self.config.timeout),
+                    mcp_session.request("tools/call", json!({"name": self.tool.name, "arguments": args})),
+                ).await.unwrap_or_else(|_| Err(format!("no answer in {}s", self.config.timeout)))
+            }.await;
+            mcp_session.touch();
+            match call_result {
+                Ok(result) => result,
+                Err(e) => {
+                    // the connection is in unknown state, the next call starts over
+                    mcp_session.transport = None;
+                    return Err(format!("MCP {}: {}", self.tool_name, e));
+                }
+            }
+        };
+        let mut output = mcp_result_to_text(&result);
+        if result["isError"].as_bool().unwrap_or(false) {
+            return Err(output);
+        }
+        if output.is_empty() {
+            output = "Success, no output".to_string();
+        }
+
+        Ok((false, vec![
+            ContextEnum::ChatMessage(ChatMessage {
+                role: "tool".to_string(),
+                content: ChatContent::SimpleText(output),
+                tool_calls: None,
+                tool_call_id: tool_call_id.clone(),
+                ..Default::default()
+            })
+        ]))
+    }
+
+    fn command_to_match_against_confirm_deny(
+        &self,
+        args: &HashMap<String, Value>,
+    ) -> Result<String, String> {
+        // rules like "mcp_jira_delete_*" match the tool, arguments are there for finer rules
+        Ok(format!("{} {}", self.tool_name, serde_json::to_string(args).unwrap_or_default()))
+    }
+
+    fn tool_description(&self) -> Option<ToolDict> {
+        Some(mcp_tool_dict(&self.tool_name, &self.tool))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mcp_tool_dict() {
+        let tool: McpToolInfo = serde_json::from_value(json!({
+            "name": "search.issues",
+            "description": "Search issues with JQL",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "jql": {"type": "string", "description": "JQL query"},
+                    "limit": {"type": ["integer", "null"]},
+                    "order": {"type": "string", "enum": ["asc", "desc"]},
+                    "filter": {
+                        "type": "object",
+                        "properties": {"assignee": {"type": "string"}, "labels": {"type": "array", "items": {"type": "string"}}},
+                        "required": ["assignee"],
+                    },
+                },
+                "required": ["jql"],
+                "additionalProperties": false,
+            },
+        })).unwrap();
+        let name = mcp_tool_name("jira server", &tool.name);
+        assert_eq!(name, "mcp_jira_server_search_issues");
+        let long1 = mcp_tool_name("jira", &format!("{}_one", "x".repeat(70)));
+        let long2 = mcp_tool_name("jira", &format!("{}_two", "x".repeat(70)));
+        assert_eq!((long1.len(), long2.len()), (64, 64));
+        assert_ne!(long1, long2);
+        assert_eq!(long1, mcp_tool_name("jira", &format!("{}_one", "x".repeat(70))));
+        let openai = mcp_tool_dict(&name, &tool).into_openai_style();
+        assert_eq!(openai["function"]["name"], json!("mcp_jira_server_search_issues"));
+        assert_eq!(openai["function"]["parameters"], tool.input_schema);
+
+        let no_schema: McpToolInfo = serde_json::from_value(json!({"name": "ping"})).unwrap();
+        let openai = mcp_tool_dict("mcp_x_ping", &no_schema).into_openai_style();
+        assert_eq!(openai["function"]["parameters"], json!({"type": "object", "properties": {}}));
+
+        let result = json!({"content": [{"type": "text", "text": "PROJ-1 open"}, {"type": "image", "data": ""}]});
+        assert_eq!(mcp_result_to_text(&result), "PROJ-1 open\n[image content is not supported]");
+    }
+
+    #[test]
+    fn test_mcp_retry_backoff() {
+        let backoffs: Vec<u64> = (1..=8).map(mcp_retry_backoff).collect();
+        assert_eq!(backoffs, vec![10, 20, 40, 80, 160, 320, 600, 600]);
+        assert_eq!(mcp_retry_backoff(u32::MAX), 600);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_mcp_stdio_kills_process_group() {
+        let tmp = tempfile::tempdir().unwrap();
+        let pid_file = tmp.path().join("pid");
+        let config = IntegrationMcpServer {
+            command: "sh".to_string(),
+            args: vec!["-c".to_string(), format!("sleep 60 & echo $! > {}; wait", pid_file.display())],
+            env: HashMap::new(),
+            url: "".to_string(),
+            timeout: 10,
+        };
+        let transport = stdio_connect(&config).unwrap();
+        let mut pid = String::new();
+        for _ in 0..50 {
+            pid = std::fs::read_to_string(&pid_file).unwrap_or_default().trim().to_string();
+            if !pid.is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        assert!(!pid.is_empty());
+        drop(transport);
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        let alive = std::fs::read_to_string(format!("/proc/{}/stat", pid)).map(|stat| !stat.contains(") Z ")).unwrap_or(false);
+        assert!(!alive, "sleep {} outlived the MCP server", pid);
+    }
+}
@@ -0,0 +1,485 @@
+use std::any::Any;
+use std::sync::{Arc, Mutex as StdMutex};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::SystemTime;
+use regex::Regex;
+use serde_json::Value;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::sync::{Mutex as AMutex, RwLock as ARwLock};
+use tokio::process::{Command, Child, ChildStdin};
+use tokio::time::{sleep, Duration, Instant};
+use async_trait::async_trait;
+use tracing::{error, info};
+use serde::{Deserialize, Serialize};
+
+use crate::at_commands::at_commands::AtCommandsContext;
+use crate::call_validation::{ChatContent, ContextEnum, ChatMessage};
+use crate::integrations::integr_cmdline::{filter_output, CmdlineOutputFilter};
+#[cfg(unix)]
+use crate::integrations::integr_cmdline::kill_process_group;
+use crate::integrations::sessions::{IntegrationSession, get_session_hashmap_key};
+use crate::global_context::GlobalContext;
+use crate::tools::tools_description::Tool;
+
+const SESSION_TIMEOUT_AFTER_INACTIVITY: Duration = Duration::from_secs(30 * 60);
+const OUTPUT_BUFFER_MAX_CHARS: usize = 1_000_000;  // not read by the model for a long time, older output goes away
+const WAIT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+const START_SETTLE_TIME: Duration = Duration::from_millis(1000);
+const WRITE_SETTLE_TIME: Duration = Duration::from_millis(500);
+const WAIT_DEFAULT_TIMEOUT_SECS: u64 = 30;
+const WAIT_MAX_TIMEOUT_SECS: u64 = 300;  // the chat waits for the tool, don't let it hang for hours
+const KILL_GRACE_PERIOD: Duration = Duration::from_secs(2);
+
+#[derive(Clone, Serialize, Deserialize, Debug, Default)]
+pub struct IntegrationProcess {
+    #[serde(default)]
+    pub env: HashMap<String, String>,  // added to the environment of every process started
+}
+
+pub struct ToolProcess {
+    integration_process: IntegrationProcess,
+}
+
+#[derive(Default)]
+struct ProcessOutput {
+    unread: String,  // stdout and stderr as they come
+    dropped_chars: usize,
+}
+
+pub struct ProcessSession {
+    process: Option<Child>,  // None once the group is killed
+    pid: Option<u32>,
+    reaped: bool,  // the process group id could belong to someone else by now, don't signal it
+    stdin: Option<ChildStdin>,
+    output: Arc<StdMutex<ProcessOutput>>,
+    command: String,
+    last_usage_ts: u64,
+}
+
+impl IntegrationSession for ProcessSession
+{
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn is_expired(&self) -> bool {
+        let current_time = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs();
+        self.last_usage_ts + SESSION_TIMEOUT_AFTER_INACTIVITY.as_secs() < current_time
+    }
+
+    fn shutdown(&mut self) {
+        #[cfg(unix)]
+        if let (Some(pid), false) = (self.pid, self.reaped) {
+            kill_process_group(pid, libc::SIGTERM);
+            let deadline = std::time::Instant::now() + KILL_GRACE_PERIOD;
+            while std::time::Instant::now() < deadline && matches!(self.exit_code(), Ok(None)) {
+                std::thread::sleep(WAIT_POLL_INTERVAL);
+            }
+            if !self.reaped {
+                kill_process_group(pid, libc::SIGKILL);
+            }
+        }
+        self.process = None;  // nothing left for Drop
+    }
+}
+
+impl Drop for ProcessSession {
+    fn drop(&mut self) {
+        // the direct child could be gone while the servers it spawned still run, so the whole group:
+        // SIGTERM to let them clean up, SIGKILL after a grace period for those that ignore it
+        #[cfg(unix)]
+        if let (Some(pid), Some(process), false) = (self.pid, self.process.take(), self.reaped) {
+            kill_process_group(pid, libc::SIGTERM);
+            std::thread::spawn(move || {
+                std::thread::sleep(KILL_GRACE_PERIOD);
+                kill_process_group(pid, libc::SIGKILL);
+                drop(process);  // only now it can be reaped, and the group id reused
+            });
+        }
+        info!("process session {:?} is over", self.command);
+    }
+}
+
+impl ProcessSession {
+    fn touch(&mut self) {
+        self.last_usage_ts = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs();
+    }
+
+    // Ok(None) while running, exit code if it exited normally. On linux the child isn't reaped until the group
+    // is killed, its zombie keeps the process group id from going to another group
+    #[cfg(target_os = "linux")]
+    fn exit_code(&mut self) -> Result<Option<Option<i32>>, String> {
+        let Some(pid) = self.pid.filter(|_| self.process.is_some()) else { return Ok(Some(None)) };
+        let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+        if unsafe { libc::waitid(libc::P_PID, pid as libc::id_t, &mut info, libc::WEXITED | libc::WNOHANG | libc::WNOWAIT) } != 0 {
+            return Err(std::io::Error::last_os_error().to_string());
+        }
+        if unsafe { info.si_pid() } == 0 {
+            return Ok(None);
+        }
+        Ok(Some(if info.si_code == libc::CLD_EXITED { Some(unsafe { info.si_status() }) } else { None }))
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn exit_code(&mut self) -> Result<Option<Option<i32>>, String> {
+        let Some(process) = self.process.as_mut() else { return Ok(Some(None)) };
+        match process.try_wait() {
+            Ok(Some(status)) => {
+                self.reaped = true;
+                Ok(Some(status.code()))
+            }
+            Ok(None) => Ok(None),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    fn status(&mut self) -> String {
+        match self.exit_code() {
+            Ok(None) => "running".to_string(),
+            Ok(Some(code)) => format!("exited with code {}", code.map(|x| x.to_string()).unwrap_or("unknown".to_string())),
+            Err(e) => format!("unknown: {}", e),
+        }
+    }
+
+    fn take_output(&mut self) -> String {
+        let mut output = self.output.lock().unwrap();
+        let mut text = std::mem::take(&mut output.unread);
+        if output.dropped_chars > 0 {
+            text = format!("... {} chars dropped, read more often ...\n{}", output.dropped_chars, text);
+            output.dropped_chars = 0;
+        }
+        filter_output(&text, &CmdlineOutputFilter::default())
+    }
+
+    fn report(&mut self, name: &str) -> String {
+        let output = self.take_output();
+        let status = self.status();
+        if output.is_empty() {
+            format!("process {:?} {}, no new output", name, status)
+        } else {
+            format!("process {:?} {}, new output:\n{}", name, status, output)
+        }
+    }
+
+    async fn wait_for(&mut self, re: &Regex, wait_timeout: Duration) -> bool {
+        let deadline = Instant::now() + wait_timeout;
+        loop {
+            if re.is_match(&self.output.lock().unwrap().unread) {
+                return true;
+            }
+            if matches!(self.exit_code(), Ok(Some(_))) || Instant::now() >= deadline {
+                // one last look, the output could arrive after the exit
+                sleep(WAIT_POLL_INTERVAL).await;
+                return re.is_match(&self.output.lock().unwrap().unread);
+            }
+            sleep(WAIT_POLL_INTERVAL).await;
+        }
+    }
+}
+
+async fn pump_output<R: AsyncRead + Unpin>(mut reader: R, output: Arc<StdMutex<ProcessOutput>>) {
+    let mut buf = [0u8; 4096];
+    loop {
+        let n = match reader.read(&mut buf).await {
+            Ok(0) | Err(_) => break,
+            Ok(n) => n,
+        };
+        let mut output_locked = output.lock().unwrap();
+        output_locked.unread.push_str(&String::from_utf8_lossy(&buf[..n]));
+        if output_locked.unread.len() > OUTPUT_BUFFER_MAX_CHARS {
+            let mut cut = output_locked.unread.len() - OUTPUT_BUFFER_MAX_CHARS;
+            while !output_locked.unread.is_char_boundary(cut) {
+                cut += 1;
+            }
+            output_locked.unread.drain(..cut);
+            output_locked.dropped_chars += cut;
+        }
+    }
+}
+
+impl ToolProcess {
+    pub fn new_if_configured(integrations_value: &serde_yaml::Value) -> Option<Self> {
+        let integration_process_value = integrations_value.get("process")?;
+
+        let integration_process = match integration_process_value {
+            serde_yaml::Value::Null => IntegrationProcess::default(),  // just `process:` to turn it on
+            v => serde_yaml::from_value::<IntegrationProcess>(v.clone()).map_err(|e| {
+                error!("Failed to parse integration process: {:?}", e);
+                e
+            }).ok()?,
+        };
+
+        Some(Self { integration_process })
+    }
+
+    async fn start(&self, gcx: Arc<ARwLock<GlobalContext>>, session_hashmap_key: &String, name: &str, command: &str, workdir: &str) -> Result<ProcessSession, String> {
+        let command_args = shell_words::split(command).map_err(|e| e.to_string())?;
+        if command_args.is_empty() {
+            return Err("Parsed command is empty".to_string());
+        }
+        if let Some(existing) = gcx.read().await.integration_sessions.get(session_hashmap_key).cloned() {
+            let mut existing_locked = existing.lock().await;
+            if let Some(session) = existing_locked.as_any_mut().downcast_mut::<ProcessSession>() {
+                if !matches!(session.exit_code(), Ok(Some(_))) {
+                    return Err(format!("process {:?} is already running {:?}, kill it first or choose another name", name, session.command));
+                }
+            }
+        }
+        let workdir = if !workdir.is_empty() {
+            PathBuf::from(workdir)
+        } else {
+            let workspace_folders = gcx.read().await.documents_state.workspace_folders.lock().unwrap().clone();
+            workspace_folders.first().cloned().unwrap_or_else(|| PathBuf::from("."))
+        };
+
+        info!("starting process {:?}: {:?} in {}", name, command_args, workdir.display());
+        spawn_process_session(command, &command_args, &workdir, &self.integration_process.env)
+    }
+}
+
+fn spawn_process_session(command: &str, command_args: &[String], workdir: &PathBuf, env: &HashMap<String, String>) -> Result<ProcessSession, String> {
+    let mut cmd = Command::new(&command_args[0]);
+    cmd.args(&command_args[1..])
+        .current_dir(workdir)
+        .envs(env)
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped());
+    #[cfg(unix)]
+    cmd.process_group(0);  // so the whole group can be killed, see Drop
+    #[cfg(not(unix))]
+    cmd.kill_on_drop(true);
+    #[cfg(target_os = "linux")]
+    {
+        // if refact-lsp crashes, Drop never runs, the kernel kills the child instead
+        let parent_pid = std::process::id();
+        unsafe {
+            cmd.pre_exec(move || {
+                if libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGKILL) != 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                if libc::getppid() != parent_pid as libc::pid_t {
+                    return Err(std::io::Error::other("refact-lsp exited before the process started"));
+                }
+                Ok(())
+            });
+        }
+    }
+    let mut process = cmd.spawn().map_err(|e| format!("failed to start {}: {}", command_args[0], e))?;
+
+    let output = Arc::new(StdMutex::new(ProcessOutput::default()));
+    if let Some(stdout) = process.stdout.take() {
+        tokio::spawn(pump_output(stdout, output.clone()));
+    }
+    if let Some(stderr) = process.stderr.take() {
+        tokio::spawn(pump_output(stderr, output.clone()));
+    }
+    let mut session = ProcessSession {
+        pid: process.id(),
+        reaped: false,
+        stdin: process.stdin.take(),
+        process: Some(process),
+        output,
+        command: command.to_string(),
+        last_usage_ts: 0,
+    };
+    session.touch();
+    Ok(session)
+}
+
+fn string_arg<'a>(args: &'a HashMap<String, Value>, name: &str) -> Result<Option<&'a str>, String> {
+    match args.get(name) {
+        Some(Value::String(s)) => Ok(Some(s.as_str())),
+        Some(Value::Null) | None => Ok(None),
+        Some(v) => Err(format!("argument `{}` is not a string: {:?}", name, v)),
+    }
+}
+
+#[async_trait]
+impl Tool for ToolProcess {
+    async fn tool_execute(
+        &mut self,
+        ccx: Arc<AMutex<AtCommandsContext>>,
+        tool_call_id: &String,
+        args: &HashMap<String, Value>,
+    ) -> Result<(bool, Vec<ContextEnum>), String> {
+        let action = string_arg(args, "action")?.ok_or("Missing argument `action`")?;
+        let name = string_arg(args, "name")?.unwrap_or("default");
+        let (gcx, chat_id) = {
+            let ccx_lock = ccx.lock().await;
+            (ccx_lock.global_context.clone(), ccx_lock.chat_id.clone())
+        };
+        let key_prefix = get_session_hashmap_key("process", &format!("{}/", chat_id));
+        let session_hashmap_key = format!("{}{}", key_prefix, name);
+        let wait_timeout = Duration::from_secs(match args.get("timeout") {
+            Some(Value::Number(n)) => n.as_u64().unwrap_or(WAIT_DEFAULT_TIMEOUT_SECS),
+            Some(Value::String(s)) => s.parse::<u64>().map_err(|e| format!("argument `timeout`: {}", e))?,
+            _ => WAIT_DEFAULT_TIMEOUT_SECS,
+        }.min(WAIT_MAX_TIMEOUT_SECS));
+        let pattern = string_arg(args, "pattern")?.map(|p| Regex::new(p).map_err(|e| format!("argument `pattern`: {}", e))).transpose()?;
+
+        let output = if action == "list" {
+            let sessions = gcx.read().await.integration_sessions.iter()
+                .filter(|(key, _)| key.starts_with(&key_prefix))
+                .map(|(key, session)| (key[key_prefix.len()..].to_string(), session.clone()))
+                .collect::<Vec<_>>();
+            let mut lines = vec![];
+            for (process_name, session) in sessions {
+                let mut session_locked = session.lock().await;
+                if let Some(s) = session_locked.as_any_mut().downcast_mut::<ProcessSession>() {
+                    let status = s.status();
+                    lines.push(format!("{:?} {:?} {}", process_name, s.command, status));
+                }
+            }
+            if lines.is_empty() { "no processes in this chat".to_string() } else { lines.join("\n") }
+
+        } else if action == "start" {
+            let command = string_arg(args, "command")?.ok_or("Missing argument `command`")?;
+            let workdir = string_arg(args, "workdir")?.unwrap_or_default();
+            let mut session = self.start(gcx.clone(), &session_hashmap_key, name, command, workdir).await?;
+            let found = match &pattern {
+                Some(re) => Some(session.wait_for(re, wait_timeout).await),
+                None => { sleep(START_SETTLE_TIME).await; None }
+            };
+            let mut out = session.report(name);
+            if found == Some(false) {
+                out = format!("pattern not found in {}s\n{}", wait_timeout.as_secs(), out);
+            }
+            let session: Box<dyn IntegrationSession> = Box::new(session);
+            gcx.write().await.integration_sessions.insert(session_hashmap_key, Arc::new(AMutex::new(session)));
+            out
+
+        } else {
+            let session = gcx.read().await.integration_sessions.get(&session_hashmap_key).cloned()
+                .ok_or(format!("no process {:?} in this chat, start it first", name))?;
+            if action == "kill" {
+                let out = {
+                    let mut session_locked = session.lock().await;
+                    let s = session_locked.as_any_mut().downcast_mut::<ProcessSession>().ok_or("Failed to downcast to ProcessSession")?;
+                    s.take_output()
+                };
+                gcx.write().await.integration_sessions.remove(&session_hashmap_key);
+                drop(session);  // the last reference, Drop kills the process group
+                format!("process {:?} killed, last output:\n{}", name, out)
+            } else {
+                let mut session_locked = session.lock().await;
+                let s = session_locked.as_any_mut().downcast_mut::<ProcessSession>().ok_or("Failed to downcast to ProcessSession")?;
+                s.touch();
+                match action {
+                    "read" => s.report(name),
+                    "write" => {
+                        let input = string_arg(args, "input")?.ok_or("Missing argument `input`")?;
+                        let stdin = s.stdin.as_mut().ok_or("stdin is closed")?;
+                        stdin.write_all(format!("{}\n", input).as_bytes()).await.map_err(|e| e.to_string())?;
+                        stdin.flush().await.map_err(|e| e.to_string())?;
+                        sleep(WRITE_SETTLE_TIME).await;
+                        s.report(name)
+                    }
+                    "wait" => {
+                        let re = pattern.ok_or("Missing argument `pattern`")?;
+                        let found = s.wait_for(&re, wait_timeout).await;
+                        let out = s.report(name);
+                        if found { out } else { format!("pattern not found in {}s\n{}", wait_timeout.as_secs(), out) }
+                    }
+                    _ => return Err(format!("unknown action {:?}, use start, read, write, wait, kill or list", action)),
+                }
+            }
+        };
+
+        Ok((false, vec![
+            ContextEnum::ChatMessage(ChatMessage {
+                role: "tool".to_string(),
+                content: ChatContent::SimpleText(output),
+                tool_calls: None,
+                tool_call_id: tool_call_id.clone(),
+                ..Default::default()
+            })
+        ]))
+    }
+
+    fn command_to_match_against_confirm_deny(
+        &self,
+        args: &HashMap<String, Value>,
+    ) -> Result<String, String> {
+        // whatever goes to stdin can be a shell command too, deny rules apply to it the same way
+        match (string_arg(args, "action")?, string_arg(args, "command")?, string_arg(args, "input")?) {
+            (Some("start"), Some(command), _) => Ok(command.to_string()),
+            (Some("write"), _, Some(input)) => Ok(input.to_string()),
+            _ => Ok("".to_string()),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_process_session() {
+        let command = "sh -c 'echo starting; sleep 0.2; echo ready on port 8080; cat'";
+        let command_args = shell_words::split(command).unwrap();
+        let mut session = spawn_process_session(command, &command_args, &PathBuf::from("."), &HashMap::new()).unwrap();
+        assert!(session.wait_for(&Regex::new("ready on port \\d+").unwrap(), Duration::from_secs(5)).await);
+        assert_eq!(session.report("server"), "process \"server\" running, new output:\nstarting\nready on port 8080");
+        assert_eq!(session.report("server"), "process \"server\" running, no new output");
+
+        let stdin = session.stdin.as_mut().unwrap();
+        stdin.write_all(b"hello\n").await.unwrap();
+        stdin.flush().await.unwrap();
+        assert!(session.wait_for(&Regex::new("hello").unwrap(), Duration::from_secs(5)).await);
+        assert!(!session.wait_for(&Regex::new("never").unwrap(), Duration::from_millis(300)).await);
+        assert_eq!(session.take_output(), "hello");
+
+        session.stdin = None;  // cat gets EOF
+        assert!(!session.wait_for(&Regex::new("never").unwrap(), Duration::from_secs(5)).await);
+        assert_eq!(session.status(), "exited with code 0");
+    }
+
+    #[test]
+    fn test_process_confirm_deny() {
+        let tool = ToolProcess { integration_process: IntegrationProcess::default() };
+        let args = |pairs: &[(&str, &str)]| pairs.iter().map(|(k, v)| (k.to_string(), Value::String(v.to_string()))).collect::<HashMap<_, _>>();
+        assert_eq!(tool.command_to_match_against_confirm_deny(&args(&[("action", "start"), ("command", "npm run dev")])).unwrap(), "npm run dev");
+        assert_eq!(tool.command_to_match_against_confirm_deny(&args(&[("action", "write"), ("name", "shell"), ("input", "rm -rf /")])).unwrap(), "rm -rf /");
+        assert_eq!(tool.command_to_match_against_confirm_deny(&args(&[("action", "read")])).unwrap(), "");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_process_session_drop_kills_group() {
+        // the grandchild ignores SIGTERM and outlives its parent, it still goes with the group
+        let command = "sh -c 'trap \"\" TERM; sleep 60 & echo $!'";
+        let command_args = shell_words::split(command).unwrap();
+        let mut session = spawn_process_session(command, &command_args, &PathBuf::from("."), &HashMap::new()).unwrap();
+        assert!(session.wait_for(&Regex::new("\\d+\n").unwrap(), Duration::from_secs(5)).await);
+        let pid = session.take_output().trim().to_string();
+        let is_alive = || std::fs::read_to_string(format!("/proc/{}/stat", pid)).map(|stat| !stat.contains(") Z ")).unwrap_or(false);
+        assert!(is_alive());
+        drop(session);
+        sleep(Duration::from_millis(500)).await;
+        assert!(is_alive(), "killed before the grace period");
+        sleep(KILL_GRACE_PERIOD + Duration::from_millis(500)).await;
+        assert!(!is_alive(), "sleep {} survived the session", pid);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_process_session_shutdown() {
+        // at exit there's no time for the background SIGKILL, shutdown() waits for it
+        let command = "sh -c 'trap \"\" TERM; sleep 60 & echo $!; wait'";
+        let command_args = shell_words::split(command).unwrap();
+        let mut session = spawn_process_session(command, &command_args, &PathBuf::from("."), &HashMap::new()).unwrap();
+        assert!(session.wait_for(&Regex::new("\\d+\n").unwrap(), Duration::from_secs(5)).await);
+        let pid = session.take_output().trim().to_string();
+        let t0 = std::time::Instant::now();
+        session.shutdown();
+        assert!(t0.elapsed() < KILL_GRACE_PERIOD + Duration::from_secs(1));
+        sleep(Duration::from_millis(200)).await;
+        let alive = std::fs::read_to_string(format!("/proc/{}/stat", pid)).map(|stat| !stat.contains(") Z ")).unwrap_or(false);
+        assert!(!alive, "sleep {} survived shutdown", pid);
+        assert_eq!(session.status(), "exited with code unknown");
+    }
+}
@@ -0,0 +1,641 @@
+use std::sync::Arc;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use quick_xml::events::Event;
+use regex::Regex;
+use serde_json::Value;
+use tokio::sync::{Mutex as AMutex, RwLock as ARwLock};
+use tokio::process::Command;
+use async_trait::async_trait;
+use tracing::{error, info};
+use serde::{Deserialize, Serialize};
+
+use crate::at_commands::at_commands::AtCommandsContext;
+use crate::call_validation::{ChatContent, ContextEnum, ChatMessage, ContextFile};
+use crate::files_correction::correct_to_nearest_filename;
+use crate::global_context::GlobalContext;
+use crate::integrations::integr_cmdline::{filter_output, output_in_process_group, CmdlineOutputFilter};
+use crate::tools::tools_description::Tool;
+
+// Runs tests and reads the machine readable report of the framework, so the model gets a list of failures with
+// the assertion locations instead of the raw log.
+
+const SUMMARY_MAX_FAILURES: usize = 20;
+const SUMMARY_MESSAGE_MAX_LINES: usize = 8;
+const CONTEXT_FILES_MAX: usize = 10;
+const CONTEXT_LINES_AROUND: usize = 10;
+
+fn default_timeout() -> u64 {
+    600
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, Default)]
+pub struct IntegrationTest {
+    #[serde(default)]
+    pub framework: String,  // cargo, pytest, jest, go; guessed from the files in workdir if empty
+    #[serde(default)]
+    pub command: String,  // instead of the default "cargo test", "python -m pytest", "npx jest", "go test"
+    #[serde(default)]
+    pub workdir: String,
+    #[serde(default = "default_timeout")]
+    pub timeout: u64,
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+}
+
+pub struct ToolTest {
+    integration_test: IntegrationTest,
+    workspace_folder: PathBuf,  // the workdir if it's not configured
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TestStatus {
+    Passed,
+    Failed,
+    Skipped,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct TestRecord {
+    pub name: String,
+    pub status: TestStatus,
+    pub file: String,  // as the framework reports it, often relative
+    pub line: usize,  // zero if unknown
+    pub message: String,
+}
+
+impl TestRecord {
+    fn new(name: &str, status: TestStatus) -> Self {
+        TestRecord { name: name.to_string(), status, file: String::new(), line: 0, message: String::new() }
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref ANSI_RE: Regex = Regex::new(r"\x1b\[[0-9;]*m").unwrap();
+    static ref RUST_PANIC_RE: Regex = Regex::new(r"panicked at (?:'.*', )?([^\s:']+):(\d+):\d+").unwrap();
+    static ref PYTHON_LOCATION_RE: Regex = Regex::new(r"(?m)^([^\s:]+\.py):(\d+): ").unwrap();
+    static ref JS_STACK_RE: Regex = Regex::new(r"\(?([^\s()]+\.[jt]sx?):(\d+):\d+\)?").unwrap();
+    static ref GO_LOCATION_RE: Regex = Regex::new(r"^\s+([\w\-./]+\.go):(\d+):").unwrap();
+}
+
+fn first_lines(text: &str, n: usize) -> String {
+    text.lines().filter(|l| !l.trim().is_empty()).take(n).collect::<Vec<_>>().join("\n")
+}
+
+pub fn parse_libtest_json(output: &str) -> Vec<TestRecord> {
+    let mut records = vec![];
+    for line in output.lines() {
+        let event = match serde_json::from_str::<Value>(line) {
+            Ok(x) if x["type"] == "test" => x,
+            _ => continue,
+        };
+        let status = match event["event"].as_str().unwrap_or_default() {
+            "ok" => TestStatus::Passed,
+            "failed" | "timeout" => TestStatus::Failed,
+            "ignored" => TestStatus::Skipped,
+            _ => continue,  // started
+        };
+        let mut record = TestRecord::new(event["name"].as_str().unwrap_or_default(), status);
+        if status == TestStatus::Failed {
+            let stdout = event["stdout"].as_str().unwrap_or_default();
+            if let Some(caps) = RUST_PANIC_RE.captures(stdout) {
+                record.file = caps[1].to_string();
+                record.line = caps[2].parse().unwrap_or(0);
+            }
+            let message = stdout.lines().filter(|l| !l.starts_with("note: run with `RUST_BACKTRACE")).collect::<Vec<_>>().join("\n");
+            record.message = first_lines(&message, SUMMARY_MESSAGE_MAX_LINES);
+        }
+        records.push(record);
+    }
+    records
+}
+
+pub fn parse_pytest_junit(xml: &str) -> Result<Vec<TestRecord>, String> {
+    let mut reader = quick_xml::Reader::from_str(xml);
+    let mut records = vec![];
+    let mut current: Option<TestRecord> = None;
+    let mut failure_text: Option<String> = None;
+    loop {
+        let (e, is_empty) = match reader.read_event().map_err(|e| format!("junit xml: {}", e))? {
+            Event::Start(e) => (e, false),
+            Event::Empty(e) => (e, true),
+            Event::Text(t) => {
+                if let Some(text) = failure_text.as_mut() {
+                    text.push_str(&t.unescape().unwrap_or_default());
+                }
+                continue;
+            }
+            Event::CData(c) => {
+                if let Some(text) = failure_text.as_mut() {
+                    text.push_str(&String::from_utf8_lossy(&c.into_inner()));
+                }
+                continue;
+            }
+            Event::End(e) => {
+                match e.name().as_ref() {
+                    b"testcase" => records.extend(current.take()),
+                    b"failure" | b"error" => {
+                        if let (Some(record), Some(text)) = (current.as_mut(), failure_text.take()) {
+                            if let Some(caps) = PYTHON_LOCATION_RE.captures_iter(&text).last() {
+                                record.file = caps[1].to_string();
+                                record.line = caps[2].parse().unwrap_or(0);
+                            }
+                            let explanation = text.lines().filter(|l| l.starts_with("E ")).collect::<Vec<_>>().join("\n");
+                            if !explanation.is_empty() {
+                                record.message = first_lines(&explanation, SUMMARY_MESSAGE_MAX_LINES);
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+                continue;
+            }
+            Event::Eof => break,
+            _ => continue,
+        };
+        let attrs: HashMap<String, String> = e.attributes().flatten()
+            .map(|a| (String::from_utf8_lossy(a.key.as_ref()).to_string(), a.unescape_value().map(|v| v.to_string()).unwrap_or_default()))
+            .collect();
+        let attr = |k: &str| attrs.get(k).cloned().unwrap_or_default();
+        match e.name().as_ref() {
+            b"testcase" => {
+                let file = attr("file");
+                let name = if file.is_empty() { format!("{}::{}", attr("classname"), attr("name")) } else { format!("{}::{}", file, attr("name")) };
+                let mut record = TestRecord::new(&name, TestStatus::Passed);
+                record.file = file;
+                record.line = attr("line").parse::<usize>().map(|x| x + 1).unwrap_or(0);  // zero based
+                if is_empty {
+                    records.push(record);
+                } else {
+                    current = Some(record);
+                }
+            }
+            b"failure" | b"error" | b"skipped" => {
+                if let Some(record) = current.as_mut() {
+                    record.status = if e.name().as_ref() == b"skipped" { TestStatus::Skipped } else { TestStatus::Failed };
+                    record.message = attr("message");
+                    if !is_empty && record.status == TestStatus::Failed {
+                        failure_text = Some(String::new());
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(records)
+}
+
+pub fn parse_jest_json(report: &str) -> Result<Vec<TestRecord>, String> {
+    let report: Value = serde_json::from_str(report).map_err(|e| format!("jest json: {}", e))?;
+    let mut records = vec![];
+    for suite in report["testResults"].as_array().unwrap_or(&vec![]) {
+        let file = suite["name"].as_str().unwrap_or_default();
+        let assertions = suite["assertionResults"].as_array().cloned().unwrap_or_default();
+        if assertions.is_empty() && suite["status"] == "failed" {
+            // the file didn't even run: syntax error, missing import
+            let mut record = TestRecord::new(file, TestStatus::Failed);
+            record.file = file.to_string();
+            record.message = first_lines(&ANSI_RE.replace_all(suite["message"].as_str().unwrap_or_default(), ""), SUMMARY_MESSAGE_MAX_LINES);
+            records.push(record);
+            continue;
+        }
+        for a in assertions {
+            let status = match a["status"].as_str().unwrap_or_default() {
+                "passed" => TestStatus::Passed,
+                "failed" => TestStatus::Failed,
+                _ => TestStatus::Skipped,  // pending, skipped, todo, disabled
+            };
+            let mut record = TestRecord::new(a["fullName"].as_str().unwrap_or_default(), status);
+            record.file = file.to_string();
+            record.line = a["location"]["line"].as_u64().unwrap_or(0) as usize;
+            if status == TestStatus::Failed {
+                let messages = a["failureMessages"].as_array().unwrap_or(&vec![]).iter()
+                    .filter_map(|m| m.as_str()).map(|m| ANSI_RE.replace_all(m, "").to_string()).collect::<Vec<_>>().join("\n");
+                // the frame in the test file itself is where the assertion is
+                if let Some(caps) = JS_STACK_RE.captures_iter(&messages).find(|c| c[1] == *file) {
+                    record.line = caps[2].parse().unwrap_or(record.line);
+                }
+                let explanation = messages.lines().filter(|l| !l.trim_start().starts_with("at ")).collect::<Vec<_>>().join("\n");
+                record.message = first_lines(&explanation, SUMMARY_MESSAGE_MAX_LINES);
+            }
+            records.push(record);
+        }
+    }
+    Ok(records)
+}
+
+pub fn parse_go_test_json(output: &str) -> Vec<TestRecord> {
+    let mut records = vec![];
+    let mut outputs: HashMap<(String, String), Vec<String>> = HashMap::new();
+    let mut package_has_test_results: HashMap<String, bool> = HashMap::new();
+    for line in output.lines() {
+        let event = match serde_json::from_str::<Value>(line) {
+            Ok(x) => x,
+            Err(_) => continue,
+        };
+        let package = event["Package"].as_str().unwrap_or_default().to_string();
+        let test = event["Test"].as_str().unwrap_or_default().to_string();
+        let action = event["Action"].as_str().unwrap_or_default();
+        if action == "output" {
+            outputs.entry((package, test)).or_default().push(event["Output"].as_str().unwrap_or_default().trim_end().to_string());
+            continue;
+        }
+        let status = match action {
+            "pass" => TestStatus::Passed,
+            "fail" => TestStatus::Failed,
+            "skip" => TestStatus::Skipped,
+            _ => continue,
+        };
+        let test_output = outputs.remove(&(package.clone(), test.clone())).unwrap_or_default();
+        if test.is_empty() {
+            // package result, it's interesting only if no test inside says why it failed, like a build error
+            if status == TestStatus::Failed && !package_has_test_results.get(&package).copied().unwrap_or(false) {
+                let mut record = TestRecord::new(&package, status);
+                record.message = first_lines(&test_output.join("\n"), SUMMARY_MESSAGE_MAX_LINES);
+                records.push(record);
+            }
+            continue;
+        }
+        *package_has_test_results.entry(package.clone()).or_default() |= status == TestStatus::Failed;
+        let mut record = TestRecord::new(&format!("{}.{}", package, test), status);
+        if status == TestStatus::Failed {
+            let useful: Vec<&String> = test_output.iter()
+                .filter(|l| !l.starts_with("=== ") && !l.starts_with("--- FAIL"))
+                .collect();
+            if let Some(caps) = useful.iter().find_map(|l| GO_LOCATION_RE.captures(l)) {
+                record.file = caps[1].to_string();
+                record.line = caps[2].parse().unwrap_or(0);
+            }
+            record.message = first_lines(&useful.iter().map(|l| l.trim()).collect::<Vec<_>>().join("\n"), SUMMARY_MESSAGE_MAX_LINES);
+        }
+        records.push(record);
+    }
+    records
+}
+
+fn guess_framework(workdir: &Path) -> Option<&'static str> {
+    if workdir.join("Cargo.toml").exists() {
+        Some("cargo")
+    } else if workdir.join("go.mod").exists() {
+        Some("go")
+    } else if workdir.join("package.json").exists() {
+        Some("jest")
+    } else if ["pyproject.toml", "setup.py", "setup.cfg", "pytest.ini", "tox.ini"].iter().any(|f| workdir.join(f).exists()) {
+        Some("pytest")
+    } else {
+        None
+    }
+}
+
+fn cargo_filter_from_path(path: &str) -> Vec<String> {
+    // tests/integration.rs => --test integration, src/ast/parser.rs => ast::parser
+    let p = Path::new(path);
+    let stem = p.file_stem().map(|x| x.to_string_lossy().to_string()).unwrap_or_default();
+    let components: Vec<String> = p.components().map(|c| c.as_os_str().to_string_lossy().to_string()).collect();
+    if let Some(tests_at) = components.iter().position(|c| c == "tests") {
+        if tests_at + 2 == components.len() && path.ends_with(".rs") {
+            return vec!["--test".to_string(), stem];
+        }
+    }
+    let after_src = match components.iter().position(|c| c == "src") {
+        Some(src_at) => components[src_at + 1..].to_vec(),
+        None => components,
+    };
+    let module = after_src.iter()
+        .map(|c| c.trim_end_matches(".rs").to_string())
+        .filter(|c| !["lib", "main", "mod"].contains(&c.as_str()))
+        .collect::<Vec<_>>()
+        .join("::");
+    if module.is_empty() { vec![] } else { vec![module] }
+}
+
+fn test_command(framework: &str, base_command: &[String], path: &str, test_id: &str, report_file: &Path) -> (Vec<String>, HashMap<String, String>) {
+    let mut args: Vec<String> = base_command.to_vec();
+    let mut env = HashMap::new();
+    let report = report_file.to_string_lossy().to_string();
+    match framework {
+        "cargo" => {
+            if !test_id.is_empty() {
+                args.push(test_id.to_string());
+            } else if !path.is_empty() {
+                args.extend(cargo_filter_from_path(path));
+            }
+            args.extend(["--", "-Z", "unstable-options", "--format", "json"].iter().map(|x| x.to_string()));
+            env.insert("RUSTC_BOOTSTRAP".to_string(), "1".to_string());  // json output is unstable in libtest
+        }
+        "pytest" => {
+            if !test_id.is_empty() {
+                args.push(test_id.to_string());  // tests/test_x.py::test_y
+            } else if !path.is_empty() {
+                args.push(path.to_string());
+            }
+            args.extend(["-q", "-o", "junit_family=xunit1", "--junitxml"].iter().map(|x| x.to_string()));
+            args.push(report);
+        }
+        "jest" => {
+            if !path.is_empty() {
+                args.push(path.to_string());
+            }
+            if !test_id.is_empty() {
+                args.extend(["-t".to_string(), test_id.to_string()]);
+            }
+            args.extend(["--json", "--testLocationInResults", "--outputFile"].iter().map(|x| x.to_string()));
+            args.push(report);
+        }
+        "go" => {
+            args.push("-json".to_string());
+            if !test_id.is_empty() {
+                args.extend(["-run".to_string(), format!("^{}$", test_id)]);
+            }
+            let package = match Path::new(path) {
+                _ if path.is_empty() => "./...".to_string(),
+                p if p.extension().is_some() => format!("./{}", p.parent().map(|x| x.to_string_lossy().to_string()).unwrap_or_default()),
+                p => format!("./{}/...", p.to_string_lossy().trim_end_matches('/')),
+            };
+            args.push(package.replace("//", "/"));
+        }
+        _ => {}
+    }
+    (args, env)
+}
+
+fn default_base_command(framework: &str) -> Vec<String> {
+    match framework {
+        "cargo" => vec!["cargo", "test"],
+        "pytest" => vec!["python", "-m", "pytest"],
+        "jest" => vec!["npx", "jest"],
+        "go" => vec!["go", "test"],
+        _ => vec![],
+    }.into_iter().map(|x| x.to_string()).collect()
+}
+
+pub fn summarize(framework: &str, records: &[TestRecord], elapsed_secs: f64) -> String {
+    let count = |s: TestStatus| records.iter().filter(|r| r.status == s).count();
+    let mut summary = format!("{}: {} passed, {} failed, {} skipped in {:.1}s\n",
+        framework, count(TestStatus::Passed), count(TestStatus::Failed), count(TestStatus::Skipped), elapsed_secs);
+    let failed: Vec<&TestRecord> = records.iter().filter(|r| r.status == TestStatus::Failed).collect();
+    for r in failed.iter().take(SUMMARY_MAX_FAILURES) {
+        if r.line > 0 {
+            summary.push_str(&format!("\nFAILED {} at {}:{}\n", r.name, r.file, r.line));
+        } else {
+            summary.push_str(&format!("\nFAILED {}\n", r.name));
+        }
+        if !r.message.is_empty() {
+            summary.push_str(&format!("{}\n", r.message));
+        }
+    }
+    if failed.len() > SUMMARY_MAX_FAILURES {
+        summary.push_str(&format!("\n... and {} more failed\n", failed.len() - SUMMARY_MAX_FAILURES));
+    }
+    summary
+}
+
+async fn resolve_file(gcx: Arc<ARwLock<GlobalContext>>, workdir: &Path, file: &str) -> Option<String> {
+    let candidates = [PathBuf::from(file), workdir.join(file)];
+    if let Some(p) = candidates.iter().find(|p| p.is_absolute() && p.is_file()) {
+        return Some(crate::files_correction::canonical_path(&p.to_string_lossy().to_string()).to_string_lossy().to_string());
+    }
+    correct_to_nearest_filename(gcx, &file.to_string(), false, 1).await.into_iter().next()
+}
+
+impl ToolTest {
+    pub fn new_if_configured(integrations_value: &serde_yaml::Value, workspace_folder: Option<PathBuf>) -> Option<Self> {
+        let integration_test_value = integrations_value.get("test")?;
+
+        let integration_test = match integration_test_value {
+            serde_yaml::Value::Null => IntegrationTest { timeout: default_timeout(), ..Default::default() },
+            v => serde_yaml::from_value::<IntegrationTest>(v.clone()).map_err(|e| {
+                error!("Failed to parse integration test: {:?}", e);
+                e
+            }).ok()?,
+        };
+
+        Some(Self { integration_test, workspace_folder: workspace_folder.unwrap_or_else(|| PathBuf::from(".")) })
+    }
+
+    fn workdir(&self) -> PathBuf {
+        if !self.integration_test.workdir.is_empty() {
+            PathBuf::from(&self.integration_test.workdir)
+        } else {
+            self.workspace_folder.clone()
+        }
+    }
+
+    fn framework(&self, workdir: &Path) -> Result<String, String> {
+        if !self.integration_test.framework.is_empty() {
+            Ok(self.integration_test.framework.clone())
+        } else {
+            guess_framework(workdir).map(|x| x.to_string())
+                .ok_or(format!("cannot tell the test framework in {}, set `framework` for the test integration", workdir.display()))
+        }
+    }
+
+    fn base_command(&self, framework: &str) -> Result<Vec<String>, String> {
+        if self.integration_test.command.is_empty() {
+            Ok(default_base_command(framework))
+        } else {
+            shell_words::split(&self.integration_test.command).map_err(|e| e.to_string())
+        }
+    }
+}
+
+fn selection_args(args: &HashMap<String, Value>) -> Result<(String, String), String> {
+    let get = |name: &str| match args.get(name) {
+        // would be taken for an option of the test runner
+        Some(Value::String(s)) if s.starts_with('-') => Err(format!("argument `{}` can't start with '-': {:?}", name, s)),
+        Some(Value::String(s)) => Ok(s.clone()),
+        Some(Value::Null) | None => Ok(String::new()),
+        Some(v) => Err(format!("argument `{}` is not a string: {:?}", name, v)),
+    };
+    Ok((get("path")?, get("test_id")?))
+}
+
+#[async_trait]
+impl Tool for ToolTest {
+    async fn tool_execute(
+        &mut self,
+        ccx: Arc<AMutex<AtCommandsContext>>,
+        tool_call_id: &String,
+        args: &HashMap<String, Value>,
+    ) -> Result<(bool, Vec<ContextEnum>), String> {
+        let (path, test_id) = selection_args(args)?;
+        let gcx = ccx.lock().await.global_context.clone();
+        let workdir = self.workdir();
+        let framework = self.framework(&workdir)?;
+        let path = match Path::new(&path).strip_prefix(&workdir) {
+            Ok(relative) => relative.to_string_lossy().to_string(),
+            Err(_) => path,
+        };
+
+        let report_dir = tempfile::tempdir().map_err(|e| e.to_string())?;
+        let report_file = report_dir.path().join("report");
+        let (command_args, mut env) = test_command(&framework, &self.base_command(&framework)?, &path, &test_id, &report_file);
+        if command_args.is_empty() {
+            return Err(format!("unknown test framework {:?}, use cargo, pytest, jest or go", framework));
+        }
+        env.extend(self.integration_test.env.clone());
+
+        info!("test: running {:?} in {}", command_args, workdir.display());
+        let t0 = std::time::Instant::now();
+        let output = output_in_process_group(
+            Command::new(&command_args[0]).args(&command_args[1..]).current_dir(&workdir).envs(&env),
+            self.integration_test.timeout,
+        ).await.map_err(|e| format!("tests: {}", e))?;
+        let elapsed = t0.elapsed().as_secs_f64();
+        let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+        let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+
+        let records = match framework.as_str() {
+            "cargo" => Ok(parse_libtest_json(&stdout)),
+            "go" => Ok(parse_go_test_json(&stdout)),
+            "pytest" => std::fs::read_to_string(&report_file).map_err(|e| e.to_string()).and_then(|x| parse_pytest_junit(&x)),
+            "jest" => std::fs::read_to_string(&report_file).map_err(|e| e.to_string()).and_then(|x| parse_jest_json(&x)),
+            _ => Ok(vec![]),
+        }.unwrap_or_else(|e| {
+            info!("test: no report: {}", e);
+            vec![]
+        });
+
+        let mut summary = summarize(&framework, &records, elapsed);
+        if records.is_empty() || (records.iter().all(|r| r.status != TestStatus::Failed) && !output.status.success()) {
+            // compilation errors, collection errors, crashes: the raw output is all there is
+            let filter = CmdlineOutputFilter { limit_lines: 50, ..Default::default() };
+            summary.push_str(&format!("\n{} exit code {}, output:\n{}\n{}", shell_words::join(&command_args),
+                output.status.code().map(|x| x.to_string()).unwrap_or("unknown".to_string()),
+                filter_output(&stdout.lines().filter(|l| !l.starts_with('{')).collect::<Vec<_>>().join("\n"), &filter),
+                filter_output(&stderr, &filter)));
+        }
+
+        let mut results = vec![ContextEnum::ChatMessage(ChatMessage {
+            role: "tool".to_string(),
+            content: ChatContent::SimpleText(summary),
+            tool_calls: None,
+            tool_call_id: tool_call_id.clone(),
+            ..Default::default()
+        })];
+        let mut seen = vec![];
+        for r in records.iter().filter(|r| r.status == TestStatus::Failed && r.line > 0) {
+            if seen.len() >= CONTEXT_FILES_MAX || seen.contains(&(r.file.clone(), r.line)) {
+                continue;
+            }
+            seen.push((r.file.clone(), r.line));
+            if let Some(file_name) = resolve_file(gcx.clone(), &workdir, &r.file).await {
+                results.push(ContextEnum::ContextFile(ContextFile {
+                    file_name,
+                    file_content: "".to_string(),
+                    line1: r.line.saturating_sub(CONTEXT_LINES_AROUND).max(1),
+                    line2: r.line + CONTEXT_LINES_AROUND,
+                    symbols: vec![],
+                    gradient_type: -1,
+                    usefulness: 100.0,
+                }));
+            }
+        }
+        Ok((false, results))
+    }
+
+    fn command_to_match_against_confirm_deny(
+        &self,
+        args: &HashMap<String, Value>,
+    ) -> Result<String, String> {
+        let framework = self.framework(&self.workdir())?;
+        let (path, test_id) = selection_args(args)?;
+        let (command_args, _) = test_command(&framework, &self.base_command(&framework)?, &path, &test_id, Path::new("report"));
+        Ok(shell_words::join(command_args))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_reports() {
+        let libtest = r#"{ "type": "suite", "event": "started", "test_count": 3 }
+{ "type": "test", "event": "started", "name": "ast::tests::test_a" }
+{ "type": "test", "name": "ast::tests::test_a", "event": "ok" }
+{ "type": "test", "name": "ast::tests::test_b", "event": "failed", "stdout": "thread 'ast::tests::test_b' panicked at src/ast/mod.rs:42:9:\nassertion `left == right` failed\n  left: 1\n right: 2\nnote: run with `RUST_BACKTRACE=1` environment variable to display a backtrace\n" }
+{ "type": "test", "name": "ast::tests::test_c", "event": "ignored" }
+{ "type": "suite", "event": "failed", "passed": 1, "failed": 1, "ignored": 1 }"#;
+        let records = parse_libtest_json(libtest);
+        assert_eq!(records.len(), 3);
+        assert_eq!((records[1].file.as_str(), records[1].line), ("src/ast/mod.rs", 42));
+        assert_eq!(records[1].message, "thread 'ast::tests::test_b' panicked at src/ast/mod.rs:42:9:\nassertion `left == right` failed\n  left: 1\n right: 2");
+        assert!(summarize("cargo", &records, 1.0).starts_with("cargo: 1 passed, 1 failed, 1 skipped in 1.0s\n\nFAILED ast::tests::test_b at src/ast/mod.rs:42\n"));
+
+        let junit = r#"<?xml version="1.0" encoding="utf-8"?><testsuites><testsuite name="pytest" errors="0" failures="1" skipped="1" tests="3">
+<testcase classname="tests.test_calc" file="tests/test_calc.py" line="2" name="test_add" time="0.001" />
+<testcase classname="tests.test_calc" file="tests/test_calc.py" line="5" name="test_sub" time="0.001"><failure message="assert 1 == 2">def test_sub():
+&gt;       assert sub(3, 1) == 1
+E       assert 2 == 1
+E        +  where 2 = sub(3, 1)
+
+tests/test_calc.py:7: AssertionError</failure></testcase>
+<testcase classname="tests.test_calc" file="tests/test_calc.py" line="9" name="test_mul" time="0"><skipped type="pytest.skip" message="not ready">skipped</skipped></testcase>
+</testsuite></testsuites>"#;
+        let records = parse_pytest_junit(junit).unwrap();
+        let statuses: Vec<TestStatus> = records.iter().map(|r| r.status).collect();
+        assert_eq!(statuses, vec![TestStatus::Passed, TestStatus::Failed, TestStatus::Skipped]);
+        assert_eq!(records[1].name, "tests/test_calc.py::test_sub");
+        assert_eq!((records[1].file.as_str(), records[1].line), ("tests/test_calc.py", 7));
+        assert_eq!(records[1].message, "E       assert 2 == 1\nE        +  where 2 = sub(3, 1)");
+
+        let jest = r#"{"numFailedTests": 1, "testResults": [{"name": "/proj/sum.test.js", "status": "failed", "assertionResults": [
+            {"fullName": "sum adds", "status": "passed", "failureMessages": [], "location": {"line": 3, "column": 1}},
+            {"fullName": "sum subtracts", "status": "failed", "location": {"line": 7, "column": 1}, "failureMessages": ["Error: \u001b[2mexpect(\u001b[22mreceived\u001b[2m).toBe(\u001b[22mexpected\u001b[2m)\nExpected: 1\nReceived: 2\n    at Object.<anonymous> (/proj/sum.test.js:8:22)\n    at Promise.then.completed (/proj/node_modules/jest-circus/build/utils.js:298:28)"]}
+        ]}, {"name": "/proj/broken.test.js", "status": "failed", "message": "SyntaxError: Unexpected token", "assertionResults": []}]}"#;
+        let records = parse_jest_json(jest).unwrap();
+        assert_eq!(records.len(), 3);
+        assert_eq!((records[1].file.as_str(), records[1].line), ("/proj/sum.test.js", 8));
+        assert_eq!(records[1].message, "Error: expect(received).toBe(expected)\nExpected: 1\nReceived: 2");
+        assert_eq!((records[2].name.as_str(), records[2].status), ("/proj/broken.test.js", TestStatus::Failed));
+
+        let go = r#"{"Action":"run","Package":"example.com/calc","Test":"TestAdd"}
+{"Action":"output","Package":"example.com/calc","Test":"TestAdd","Output":"=== RUN   TestAdd\n"}
+{"Action":"output","Package":"example.com/calc","Test":"TestAdd","Output":"    calc_test.go:12: expected 3, got 4\n"}
+{"Action":"output","Package":"example.com/calc","Test":"TestAdd","Output":"--- FAIL: TestAdd (0.00s)\n"}
+{"Action":"fail","Package":"example.com/calc","Test":"TestAdd","Elapsed":0}
+{"Action":"pass","Package":"example.com/calc","Test":"TestSub","Elapsed":0}
+{"Action":"fail","Package":"example.com/calc","Elapsed":0.01}
+{"Action":"output","Package":"example.com/broken","Output":"broken.go:3:1: syntax error\n"}
+{"Action":"fail","Package":"example.com/broken","Elapsed":0}"#;
+        let records = parse_go_test_json(go);
+        assert_eq!(records.len(), 3);
+        assert_eq!((records[0].file.as_str(), records[0].line), ("calc_test.go", 12));
+        assert_eq!(records[0].message, "calc_test.go:12: expected 3, got 4");
+        assert_eq!((records[2].name.as_str(), records[2].message.as_str()), ("example.com/broken", "broken.go:3:1: syntax error"));
+    }
+
+    #[test]
+    fn test_test_command() {
+        let report = Path::new("/tmp/report");
+        let (cmd, env) = test_command("cargo", &default_base_command("cargo"), "src/ast/parser.rs", "", report);
+        assert_eq!(shell_words::join(&cmd), "cargo test ast::parser -- -Z unstable-options --format json");
+        assert_eq!(env.get("RUSTC_BOOTSTRAP").unwrap(), "1");
+        let (cmd, _) = test_command("cargo", &default_base_command("cargo"), "tests/integration.rs", "", report);
+        assert_eq!(cmd[2..4], ["--test".to_string(), "integration".to_string()]);
+        let (cmd, _) = test_command("pytest", &default_base_command("pytest"), "tests/test_calc.py", "tests/test_calc.py::test_sub", report);
+        assert_eq!(shell_words::join(&cmd), "python -m pytest tests/test_calc.py::test_sub -q -o 'junit_family=xunit1' --junitxml /tmp/report");
+        let (cmd, _) = test_command("jest", &default_base_command("jest"), "src/sum.test.js", "sum adds", report);
+        assert_eq!(shell_words::join(&cmd), "npx jest src/sum.test.js -t 'sum adds' --json --testLocationInResults --outputFile /tmp/report");
+        let (cmd, _) = test_command("go", &default_base_command("go"), "calc/calc_test.go", "TestAdd", report);
+        assert_eq!(shell_words::join(&cmd), "go test -json -run '^TestAdd$' ./calc");
+        let (cmd, _) = test_command("go", &default_base_command("go"), "", "", report);
+        assert_eq!(shell_words::join(&cmd), "go test -json ./...");
+    }
+
+    #[test]
+    fn test_test_confirm_deny() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::fs::write(tmp.path().join("Cargo.toml"), "[package]\n").unwrap();
+        let tool = ToolTest::new_if_configured(&serde_yaml::from_str("test:").unwrap(), Some(tmp.path().to_path_buf())).unwrap();
+        let args = HashMap::from([("path".to_string(), Value::String("src/ast/parser.rs".to_string()))]);
+        assert_eq!(tool.command_to_match_against_confirm_deny(&args).unwrap(), "cargo test ast::parser -- -Z unstable-options --format json");
+        let args = HashMap::from([("test_id".to_string(), Value::String("--config=evil.toml".to_string()))]);
+        assert!(tool.command_to_match_against_confirm_deny(&args).is_err());
+        let tool = ToolTest::new_if_configured(&serde_yaml::from_str("test:").unwrap(), Some(tmp.path().join("nothing"))).unwrap();
+        assert!(tool.command_to_match_against_confirm_deny(&HashMap::new()).is_err());
+    }
+}
@@ -1,5 +1,9 @@
 pub mod integr_github;
 pub mod integr_pdb;
+pub mod integr_process;
+pub mod integr_mcp;
+pub mod integr_cmdline;
+pub mod integr_test;
 pub mod sessions;
 
 pub const INTEGRATIONS_DEFAULT_YAML: &str = r#"# This file is used to configure integrations in Refact Agent.
@@ -25,4 +29,66 @@ commands_deny:
 #pdb:
 #  python_path: "/opt/homebrew/bin/python3"  # Uncomment to set a custom python path, defaults to "python3"
 
+
+# --- Long-running processes ---
+# Lets the model start dev servers or watchers and keep them running in the chat. Commands to start go through
+# the rules above. Processes are killed with "kill", or after 30 minutes of not being used.
+#process:
+#  env:                                    # added to the environment of every process
+#    PORT: "8080"
+
+
+# --- Test runner ---
+# Runs tests and gives the model failures with their locations. The framework is guessed from the files in
+# workdir (Cargo.toml, go.mod, package.json, pyproject.toml...) if not set. "test: {}" is enough to turn it on.
+#test:
+#  framework: "pytest"                     # cargo, pytest, jest or go
+#  command: "poetry run pytest"            # instead of "cargo test", "python -m pytest", "npx jest", "go test"
+#  workdir: "/home/user/projects/backend"  # the first workspace folder if not given
+#  timeout: 600                            # seconds, the tests are killed after that
+#  env:
+#    DATABASE_URL: "postgres://localhost/test"
+
+
+# --- MCP servers ---
+# Tools from Model Context Protocol servers appear as mcp_<server>_<tool>, for example "mcp_jira_delete_*" in
+# commands_need_confirmation above will match them. A server is started either as a process talking over stdio,
+# or connected to using a local SSE url.
+#mcp_servers:
+#  jira:
+#    command: "npx"
+#    args: ["-y", "mcp-server-jira"]
+#    env:
+#      JIRA_TOKEN: "xxx"
+#    timeout: 60                           # seconds for one tool call
+#  docs:
+#    url: "http://127.0.0.1:8931/sse"
+
+
+# --- Command line tools ---
+# Your own commands as tools, %param% in command is replaced with the argument, workdir is used as written. The command is not
+# run by a shell, so write "sh -c '...'" if you need pipes. Rules above match the command after substitution.
+#cmdline_tools:
+#  make_lint:
+#    description: "Run linters on the whole project"
+#    command: "make lint"
+#    timeout: 300                          # seconds, the process is killed after that
+#  run_migration:
+#    description: "Apply a database migration"
+#    parameters:
+#      - name: "migration"
+#        type: "string"
+#        description: "Migration name, like 0042_add_users"
+#    parameters_required: ["migration"]   # all parameters are required if not given
+#    command: "./scripts/run_migration.sh %migration%"
+#    workdir: "/home/user/projects/backend"  # the first workspace folder if not given
+#    env:
+#      DATABASE_URL: "postgres://localhost/dev"
+#    output_filter:
+#      limit_lines: 100
+#      limit_chars: 10000
+#      valuable_top_or_bottom: "bottom"
+#      grep: "(?i)error|warning"           # keep only the lines that match and some lines around them
+#      grep_context_lines: 5
+
 "#;
@@ -9,6 +9,9 @@ pub trait IntegrationSession: Any + Send + Sync
     fn as_any_mut(&mut self) -> &mut dyn Any;
 
     fn is_expired(&self) -> bool;
+
+    // called at exit, blocking is fine; Drop may leave work to background threads that won't get to run
+    fn shutdown(&mut self) {}
 }
 
 pub fn get_session_hashmap_key(integration_name: &str, base_key: &str) -> String {
@@ -40,6 +43,14 @@ async fn remove_expired_sessions(gcx: Arc<ARwLock<GlobalContext>>) {
     // sessions still keeps a reference on all sessions, just in case a destructor is called in the block above
 }
 
+pub async fn stop_all_sessions(gcx: Arc<ARwLock<GlobalContext>>) {
+    // sessions own processes in their own process groups, they would outlive us otherwise
+    let sessions = std::mem::take(&mut gcx.write().await.integration_sessions);
+    join_all(sessions.into_values().map(|session| {
+        tokio::task::spawn_blocking(move || session.blocking_lock().shutdown())
+    })).await;
+}
+
 pub async fn remove_expired_sessions_background_task(
     gcx: Arc<ARwLock<GlobalContext>>,
 ) {
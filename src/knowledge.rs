@@ -400,6 +400,8 @@ pub async fn vectorize_dirty_memories(
             texts,
             api_key,
             1,
+            &[],
+            None,
         ).await?;
         for (chunk_save, x) in chunk.iter_mut().zip(embedding_mb.iter()) {
             chunk_save.vector = Some(x.clone());  // <-- this will make the rest of todo[].vector appear
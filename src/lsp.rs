@@ -1,26 +1,36 @@
 use std::collections::HashMap;
 use std::fmt::Display;
 use std::path::PathBuf;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex as StdMutex};
 use std::io::Write;
 
+use ropey::Rope;
 use serde::{Deserialize, Serialize};
+use serde_json::json;
 use tokio::net::TcpListener;
 use tokio::sync::RwLock as ARwLock;
 use tokio::task::JoinHandle;
+use tokio::time::Duration;
+use tower::ServiceBuilder;
+use tower::util::MapResponse;
 use tower_lsp::{ClientSocket, LanguageServer, LspService};
-use tower_lsp::jsonrpc::{Error, Result};
+use tower_lsp::jsonrpc::{Error, Response, Result};
 use tower_lsp::lsp_types::*;
 use tracing::{error, info};
 
+use crate::ast::{ast_diagnostics, ast_navigation};
 use crate::call_validation::{CodeCompletionInputs, CodeCompletionPost, CursorPosition, SamplingParameters};
 use crate::files_in_workspace;
-use crate::files_in_workspace::{on_did_change, on_did_delete};
+use crate::files_in_workspace::{get_file_text_from_memory_or_disk, lsp_position_to_char_idx, on_did_change_incremental, on_did_delete, DocumentEdit};
 use crate::global_context::{CommandLine, GlobalContext};
 use crate::http::routers::v1::code_completion::handle_v1_code_completion;
+use crate::http::routers::v1::code_lens::{code_lens_execute, code_lens_from_defs};
 use crate::telemetry::snippets_collection;
+use crate::yaml_configs::customization_loader::load_customization;
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
+const CODE_LENS_COMMAND: &str = "refact.runCodeLens";
+const DID_CHANGE_DIAGNOSTICS_DEBOUNCE: Duration = Duration::from_millis(500);
 
 
 #[derive(Debug, Deserialize)]
@@ -38,6 +48,7 @@ impl Display for APIError {
 pub struct LspBackend {
     pub gcx: Arc<ARwLock<GlobalContext>>,
     pub client: tower_lsp::Client,
+    pub diagnostics_pending: Arc<StdMutex<HashMap<PathBuf, u64>>>,  // didChange counter per document, for the debounce
 }
 
 
@@ -61,6 +72,42 @@ pub struct CompletionParams1 {
     // pub model: String,
 }
 
+// LSP 3.18 inline completion, lsp-types we use doesn't have it yet
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InlineCompletionParams {
+    #[serde(flatten)]
+    pub text_document_position: TextDocumentPositionParams,
+    pub context: InlineCompletionContext,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InlineCompletionContext {
+    pub trigger_kind: u32,  // 1 = invoked explicitly, 2 = automatic
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InlineCompletionItem {
+    pub insert_text: String,
+    pub range: Range,
+}
+
+#[derive(Debug, Serialize, Default)]
+pub struct InlineCompletionList {
+    pub items: Vec<InlineCompletionItem>,
+}
+
+// Travels in CodeLens.data and then in the command arguments
+#[derive(Debug, Deserialize, Serialize)]
+pub struct CodeLensData {
+    pub uri: Url,
+    pub line1: usize,
+    pub line2: usize,
+    pub lens: String,
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct SnippetAcceptedParams {
     snippet_telemetry_id: u64,
@@ -118,46 +165,98 @@ pub struct SuccessRes {
     pub success: bool,
 }
 
+fn code_completion_post(
+    path_string: &str,
+    txt: &str,
+    line: i32,
+    character: i32,
+    multiline: bool,
+    parameters: SamplingParameters,
+) -> CodeCompletionPost {
+    CodeCompletionPost {
+        inputs: CodeCompletionInputs {
+            sources: HashMap::from([(path_string.to_string(), txt.to_string())]),
+            cursor: CursorPosition {
+                file: path_string.to_string(),
+                line,
+                character,
+            },
+            multiline,
+        },
+        parameters,
+        model: "".to_string(),
+        scratchpad: "".to_string(),
+        stream: false,
+        no_cache: false,
+        use_ast: false,
+        use_vecdb: false,
+        rag_tokens_n: 0,
+    }
+}
+
 impl LspBackend {
-    async fn flat_params_to_code_completion_post(&self, params: &CompletionParams1) -> Result<CodeCompletionPost> {
-        let path = crate::files_correction::canonical_path(&params.text_document_position.text_document.uri.to_file_path().unwrap_or_default().display().to_string());
+    async fn publish_document_diagnostics(&self, uri: Url, path: &PathBuf, text: &str) {
+        let diagnostics = ast_diagnostics::document_diagnostics(path, &Rope::from_str(text));
+        self.client.publish_diagnostics(uri, diagnostics, None).await;
+    }
+
+    fn publish_document_diagnostics_debounced(&self, uri: Url, path: PathBuf) {
+        // parsing and linting the whole document on every keystroke is too much, wait until typing stops
+        let generation = {
+            let mut pending_locked = self.diagnostics_pending.lock().unwrap();
+            let generation = pending_locked.entry(path.clone()).or_insert(0);
+            *generation += 1;
+            *generation
+        };
+        let (gcx, client, pending) = (self.gcx.clone(), self.client.clone(), self.diagnostics_pending.clone());
+        tokio::spawn(async move {
+            tokio::time::sleep(DID_CHANGE_DIAGNOSTICS_DEBOUNCE).await;
+            {
+                let mut pending_locked = pending.lock().unwrap();
+                if pending_locked.get(&path) != Some(&generation) {
+                    return;  // more edits came, the last one will publish
+                }
+                pending_locked.remove(&path);
+            }
+            let Ok(text) = get_file_text_from_memory_or_disk(gcx, &path).await else { return };
+            let diagnostics = tokio::task::spawn_blocking(move || {
+                ast_diagnostics::document_diagnostics(&path, &Rope::from_str(&text))
+            }).await.unwrap_or_default();
+            client.publish_diagnostics(uri, diagnostics, None).await;
+        });
+    }
+
+    async fn document_text(&self, uri: &Url) -> Result<String> {
+        let path = crate::files_correction::canonical_path(&uri.to_file_path().unwrap_or_default().display().to_string());
         let txt = match self.gcx.read().await.documents_state.memory_document_map.get(&path) {
             Some(doc) => doc.read().await.clone().get_text_or_read_from_disk(self.gcx.clone()).await.unwrap_or_default(),
             None => return Err(internal_error("document not found"))
         };
+        Ok(txt)
+    }
+
+    async fn flat_params_to_code_completion_post(&self, params: &CompletionParams1) -> Result<CodeCompletionPost> {
+        let txt = self.document_text(&params.text_document_position.text_document.uri).await?;
         // url -> String method should be the same as in telemetry::snippets_collection::sources_changed
         let path_string = params.text_document_position.text_document.uri.to_file_path().unwrap_or_default().to_string_lossy().to_string();
-        Ok(CodeCompletionPost {
-            inputs: CodeCompletionInputs {
-                sources: HashMap::from([(path_string.clone(), (&txt).to_string())]),
-                cursor: CursorPosition {
-                    file: path_string.clone(),
-                    line: params.text_document_position.position.line as i32,
-                    character: params.text_document_position.position.character as i32,
-                },
-                multiline: params.multiline,
-            },
-            parameters: SamplingParameters {
+        Ok(code_completion_post(
+            &path_string,
+            &txt,
+            params.text_document_position.position.line as i32,
+            params.text_document_position.position.character as i32,
+            params.multiline,
+            SamplingParameters {
                 max_new_tokens: params.parameters.max_new_tokens as usize,
                 temperature: Option::from(params.parameters.temperature),
                 top_p: None,
                 stop: vec![],
                 n: None,
             },
-            model: "".to_string(),
-            scratchpad: "".to_string(),
-            stream: false,
-            no_cache: false,
-            use_ast: false,
-            use_vecdb: false,
-            rag_tokens_n: 0,
-        })
+        ))
     }
 
-    pub async fn get_completions(&self, params: CompletionParams1) -> Result<CompletionRes> {
-        let mut post = self.flat_params_to_code_completion_post(&params).await?;
-
-        let res = handle_v1_code_completion(self.gcx.clone(), &mut post)
+    async fn run_code_completion(&self, post: &mut CodeCompletionPost) -> Result<CompletionRes> {
+        let res = handle_v1_code_completion(self.gcx.clone(), post)
             .await.map_err(|e| internal_error(e))?;
 
         let body_bytes = hyper::body::to_bytes(res.into_body()).await.map_err(|e| internal_error(e))?;
@@ -168,6 +267,62 @@ impl LspBackend {
         Ok(value)
     }
 
+    pub async fn get_completions(&self, params: CompletionParams1) -> Result<CompletionRes> {
+        let mut post = self.flat_params_to_code_completion_post(&params).await?;
+        self.run_code_completion(&mut post).await
+    }
+
+    // textDocument/inlineCompletion, for editors without the plugin. On $/cancelRequest tower-lsp drops this
+    // future, that also drops the request to the model in flight.
+    pub async fn inline_completion(&self, params: InlineCompletionParams) -> Result<InlineCompletionList> {
+        let txt = self.document_text(&params.text_document_position.text_document.uri).await?;
+        let path_string = params.text_document_position.text_document.uri.to_file_path().unwrap_or_default().to_string_lossy().to_string();
+        let position = params.text_document_position.position;
+        let rope = Rope::from_str(&txt);
+        let line_n = position.line as usize;
+        if line_n >= rope.len_lines() {
+            return Ok(InlineCompletionList::default());
+        }
+        // scratchpad counts chars, LSP counts UTF-16 code units
+        let line_start = rope.line_to_char(line_n);
+        let col = lsp_position_to_char_idx(&rope, line_n, position.character as usize) - line_start;
+        let line_text = rope.line(line_n).to_string();
+        let line_text = line_text.trim_end_matches(['\r', '\n']);
+        let text_left: String = line_text.chars().take(col).collect();
+        // same rule as in the IDE plugins: nothing but indent on the left means the user expects a block of code
+        let multiline = text_left.trim().is_empty();
+        let mut post = code_completion_post(
+            &path_string,
+            &txt,
+            position.line as i32,
+            col as i32,
+            multiline,
+            SamplingParameters {
+                max_new_tokens: 0,
+                temperature: None,
+                top_p: None,
+                stop: vec![],
+                n: None,
+            },
+        );
+        let res = self.run_code_completion(&mut post).await?;
+        // single line completion is generated without the rest of the line, so it replaces it
+        let range_end = if multiline {
+            position
+        } else {
+            Position::new(position.line, line_text.encode_utf16().count() as u32)
+        };
+        let items = res.choices.into_iter()
+            .filter(|x| !x.code_completion.is_empty())
+            .map(|x| InlineCompletionItem {
+                insert_text: x.code_completion,
+                range: Range::new(position, range_end),
+            })
+            .collect();
+        info!("inline completion trigger_kind={} multiline={} snippet_telemetry_id={}", params.context.trigger_kind, multiline, res.snippet_telemetry_id);
+        Ok(InlineCompletionList { items })
+    }
+
     pub async fn accept_snippet(&self, params: SnippetAcceptedParams) -> Result<SuccessRes> {
         let success = snippets_collection::snippet_accepted(self.gcx.clone(), params.snippet_telemetry_id).await;
         Ok(SuccessRes { success })
@@ -228,9 +383,18 @@ impl LanguageServer for LspBackend {
             }),
             capabilities: ServerCapabilities {
                 text_document_sync: Some(TextDocumentSyncCapability::Kind(
-                    TextDocumentSyncKind::FULL,
+                    TextDocumentSyncKind::INCREMENTAL,
                 )),
                 completion_provider: Some(completion_options),
+                definition_provider: Some(OneOf::Left(true)),
+                references_provider: Some(OneOf::Left(true)),
+                document_symbol_provider: Some(OneOf::Left(true)),
+                workspace_symbol_provider: Some(OneOf::Left(true)),
+                code_lens_provider: Some(CodeLensOptions { resolve_provider: Some(true) }),
+                execute_command_provider: Some(ExecuteCommandOptions {
+                    commands: vec![CODE_LENS_COMMAND.to_string()],
+                    work_done_progress_options: WorkDoneProgressOptions { work_done_progress: Some(false) },
+                }),
                 workspace: Some(WorkspaceServerCapabilities {
                     workspace_folders: Some(WorkspaceFoldersServerCapabilities {
                         supported: Some(true),
@@ -264,7 +428,8 @@ impl LanguageServer for LspBackend {
             &cpath,
             &params.text_document.text,
             &params.text_document.language_id
-        ).await
+        ).await;
+        self.publish_document_diagnostics(params.text_document.uri, &cpath, &params.text_document.text).await;
     }
 
     async fn did_close(&self, params: DidCloseTextDocumentParams) {
@@ -276,15 +441,22 @@ impl LanguageServer for LspBackend {
             self.gcx.clone(),
             &cpath,
         ).await;
+        self.client.publish_diagnostics(params.text_document.uri, vec![], None).await;
     }
 
     async fn did_change(&self, params: DidChangeTextDocumentParams) {
         let path = crate::files_correction::canonical_path(&params.text_document.uri.to_file_path().unwrap_or_default().display().to_string());
-        on_did_change(
-            self.gcx.clone(),
-            &path,
-            &params.content_changes[0].text  // TODO: This text could be just a part of the whole file (if range is not none)
-        ).await
+        let edits: Vec<DocumentEdit> = params.content_changes.into_iter().map(|change| DocumentEdit {
+            range: change.range.map(|r| (
+                (r.start.line as usize, r.start.character as usize),
+                (r.end.line as usize, r.end.character as usize),
+            )),
+            text: change.text,
+        }).collect();
+        match on_did_change_incremental(self.gcx.clone(), &path, &edits).await {
+            Ok(_) => self.publish_document_diagnostics_debounced(params.text_document.uri, path),
+            Err(e) => error!("did_change {}: {}", path.display(), e),
+        }
     }
 
     async fn did_save(&self, params: DidSaveTextDocumentParams) {
@@ -293,6 +465,9 @@ impl LanguageServer for LspBackend {
             .log_message(MessageType::INFO, "{refact-lsp} file saved")
             .await;
         info!("{} saved", path.display());
+        if let Ok(text) = self.document_text(&params.text_document.uri).await {
+            self.publish_document_diagnostics(params.text_document.uri, &path, &text).await;
+        }
     }
 
     async fn shutdown(&self) -> Result<()> {
@@ -305,6 +480,90 @@ impl LanguageServer for LspBackend {
         Ok(Some(CompletionResponse::Array(vec![])))
     }
 
+    async fn goto_definition(&self, params: GotoDefinitionParams) -> Result<Option<GotoDefinitionResponse>> {
+        let cpath = crate::files_correction::canonical_path(&params.text_document_position_params.text_document.uri.to_file_path().unwrap_or_default().display().to_string());
+        let locations = ast_navigation::goto_definition(self.gcx.clone(), &cpath, &params.text_document_position_params.position).await;
+        Ok(if locations.is_empty() { None } else { Some(GotoDefinitionResponse::Array(locations)) })
+    }
+
+    async fn references(&self, params: ReferenceParams) -> Result<Option<Vec<Location>>> {
+        let cpath = crate::files_correction::canonical_path(&params.text_document_position.text_document.uri.to_file_path().unwrap_or_default().display().to_string());
+        let locations = ast_navigation::references(self.gcx.clone(), &cpath, &params.text_document_position.position, params.context.include_declaration).await;
+        Ok(Some(locations))
+    }
+
+    async fn document_symbol(&self, params: DocumentSymbolParams) -> Result<Option<DocumentSymbolResponse>> {
+        let cpath = crate::files_correction::canonical_path(&params.text_document.uri.to_file_path().unwrap_or_default().display().to_string());
+        let symbols = ast_navigation::document_symbols(self.gcx.clone(), &cpath).await;
+        Ok(Some(DocumentSymbolResponse::Flat(symbols)))
+    }
+
+    async fn symbol(&self, params: WorkspaceSymbolParams) -> Result<Option<Vec<SymbolInformation>>> {
+        Ok(Some(ast_navigation::workspace_symbols(self.gcx.clone(), &params.query).await))
+    }
+
+    async fn prepare_type_hierarchy(&self, params: TypeHierarchyPrepareParams) -> Result<Option<Vec<TypeHierarchyItem>>> {
+        let cpath = crate::files_correction::canonical_path(&params.text_document_position_params.text_document.uri.to_file_path().unwrap_or_default().display().to_string());
+        let items = ast_navigation::prepare_type_hierarchy(self.gcx.clone(), &cpath, &params.text_document_position_params.position).await;
+        Ok(if items.is_empty() { None } else { Some(items) })
+    }
+
+    async fn supertypes(&self, params: TypeHierarchySupertypesParams) -> Result<Option<Vec<TypeHierarchyItem>>> {
+        Ok(Some(ast_navigation::supertypes(self.gcx.clone(), &params.item).await))
+    }
+
+    async fn subtypes(&self, params: TypeHierarchySubtypesParams) -> Result<Option<Vec<TypeHierarchyItem>>> {
+        Ok(Some(ast_navigation::subtypes(self.gcx.clone(), &params.item).await))
+    }
+
+    async fn code_lens(&self, params: CodeLensParams) -> Result<Option<Vec<CodeLens>>> {
+        let cpath = crate::files_correction::canonical_path(&params.text_document.uri.to_file_path().unwrap_or_default().display().to_string());
+        let Some(ast_index) = ast_navigation::ast_index_maybe(self.gcx.clone()).await else { return Ok(None) };
+        let defs = crate::ast::ast_db::doc_defs(ast_index, &cpath.to_string_lossy().to_string()).await;
+        // lenses without messages (like "Open Chat") only make sense inside the IDE plugin
+        let lens_names: Vec<String> = load_customization(self.gcx.clone(), true).await.map_err(internal_error)?
+            .code_lens.into_iter().filter(|(_, lens)| !lens.messages.is_empty()).map(|(name, _)| name).collect();
+        let mut result = vec![];
+        for output in code_lens_from_defs(&defs) {
+            for lens_name in lens_names.iter() {
+                let line = output.line1 as u32 - 1;
+                result.push(CodeLens {
+                    range: Range::new(Position::new(line, 0), Position::new(line, 0)),
+                    command: None,
+                    data: Some(json!(CodeLensData {
+                        uri: params.text_document.uri.clone(),
+                        line1: output.line1,
+                        line2: output.line2,
+                        lens: lens_name.clone(),
+                    })),
+                });
+            }
+        }
+        Ok(Some(result))
+    }
+
+    async fn code_lens_resolve(&self, params: CodeLens) -> Result<CodeLens> {
+        let data: CodeLensData = serde_json::from_value(params.data.clone().unwrap_or_default()).map_err(internal_error)?;
+        let tconfig = load_customization(self.gcx.clone(), true).await.map_err(internal_error)?;
+        let label = tconfig.code_lens.get(&data.lens).map(|x| x.label.clone()).unwrap_or(data.lens.clone());
+        Ok(CodeLens {
+            command: Some(Command::new(label, CODE_LENS_COMMAND.to_string(), Some(vec![json!(data)]))),
+            ..params
+        })
+    }
+
+    async fn execute_command(&self, params: ExecuteCommandParams) -> Result<Option<serde_json::Value>> {
+        if params.command != CODE_LENS_COMMAND {
+            return Err(Error::method_not_found());
+        }
+        let data: CodeLensData = serde_json::from_value(params.arguments.into_iter().next().unwrap_or_default()).map_err(internal_error)?;
+        let cpath = crate::files_correction::canonical_path(&data.uri.to_file_path().unwrap_or_default().display().to_string());
+        let new_messages = code_lens_execute(self.gcx.clone(), &cpath, data.line1, data.line2, &data.lens).await.map_err(internal_error)?;
+        let answer = new_messages.iter().filter(|x| x.role == "assistant").map(|x| x.content.content_text_only()).collect::<Vec<_>>().join("\n\n");
+        self.client.show_message(MessageType::INFO, &answer).await;
+        Ok(Some(json!(new_messages)))
+    }
+
     async fn did_change_workspace_folders(&self, params: DidChangeWorkspaceFoldersParams) {
         for add_folder in params.event.added {
             info!("UNCLEAR LSP EVENT: did_change_workspace_folders/add {}", add_folder.name);
@@ -330,17 +589,56 @@ impl LanguageServer for LspBackend {
     }
 }
 
+// ServerCapabilities in lsp-types we use has no inlineCompletionProvider and typeHierarchyProvider, add them to the initialize response
+fn add_missing_capabilities(response: Option<Response>) -> Option<Response> {
+    let (id, result) = response?.into_parts();
+    match result {
+        Ok(mut value) => {
+            if let Some(capabilities) = value.get_mut("capabilities").and_then(|x| x.as_object_mut()) {
+                capabilities.insert("inlineCompletionProvider".to_string(), json!({}));
+                capabilities.insert("typeHierarchyProvider".to_string(), json!(true));
+            }
+            Some(Response::from_ok(id, value))
+        }
+        Err(e) => Some(Response::from_error(id, e)),
+    }
+}
+
+// The document's own diagnostics plus `extra`, the next edit of the document publishes without them
+pub async fn publish_diagnostics_with_extra(gcx: Arc<ARwLock<GlobalContext>>, path: &PathBuf, extra: Vec<Diagnostic>) {
+    let client = gcx.read().await.lsp_client.lock().unwrap().clone();
+    let (Some(client), Ok(uri)) = (client, Url::from_file_path(path)) else { return };
+    let text = get_file_text_from_memory_or_disk(gcx.clone(), path).await.unwrap_or_default();
+    let path = path.clone();
+    let mut diagnostics = tokio::task::spawn_blocking(move || {
+        ast_diagnostics::document_diagnostics(&path, &Rope::from_str(&text))
+    }).await.unwrap_or_default();
+    diagnostics.extend(extra);
+    client.publish_diagnostics(uri, diagnostics, None).await;
+}
+
+type LspServiceWithCapabilities = MapResponse<LspService<LspBackend>, fn(Option<Response>) -> Option<Response>>;
+
 async fn build_lsp_service(
     gcx: Arc<ARwLock<GlobalContext>>,
-) -> (LspService::<LspBackend>, ClientSocket) {
-    let (lsp_service, socket) = LspService::build(|client| LspBackend {
-        gcx,
-        client,
+) -> (LspServiceWithCapabilities, ClientSocket) {
+    let lsp_client = gcx.read().await.lsp_client.clone();
+    let (lsp_service, socket) = LspService::build(|client| {
+        *lsp_client.lock().unwrap() = Some(client.clone());
+        LspBackend {
+            gcx,
+            client,
+            diagnostics_pending: Arc::new(StdMutex::new(HashMap::new())),
+        }
     })
         .custom_method("refact/getCompletions", LspBackend::get_completions)
         .custom_method("refact/acceptCompletion", LspBackend::accept_snippet)
         .custom_method("refact/setActiveDocument", LspBackend::set_active_document)
+        .custom_method("textDocument/inlineCompletion", LspBackend::inline_completion)
         .finish();
+    let lsp_service = ServiceBuilder::new()
+        .map_response(add_missing_capabilities as fn(Option<Response>) -> Option<Response>)
+        .service(lsp_service);
     (lsp_service, socket)
 }
 
@@ -45,7 +45,10 @@ mod scratchpad_abstract;
 mod scratchpads;
 
 mod fetch_embedding;
+mod forward_to_anthropic_endpoint;
 mod forward_to_hf_endpoint;
+mod forward_to_llama_cpp_endpoint;
+mod forward_to_ollama_endpoint;
 mod forward_to_openai_endpoint;
 mod restream;
 
@@ -57,6 +60,10 @@ mod http;
 mod integrations;
 mod privacy;
 mod privacy_compiled_in;
+mod indexing_compiled_in;
+mod privacy_secrets;
+mod audit_log;
+mod mcp_server;
 
 
 #[tokio::main]
@@ -90,7 +97,7 @@ async fn main() {
         writer_is_stderr,
         if cmdline.verbose { Level::DEBUG } else { Level::INFO },
         Level::ERROR,
-        cmdline.lsp_stdin_stdout == 0
+        cmdline.lsp_stdin_stdout == 0 && !cmdline.mcp_stdio
     );
     let _tracing = tracing_subscriber::registry()
         .with(my_layer)
@@ -113,6 +120,7 @@ async fn main() {
             if arg_v == "--api-key" { api_key_at = arg_n + 1; }
         }
     }
+    audit_log::audit_log_init(cache_dir.join("audit"), cmdline.audit_log_keep);
 
     let byok_config_path = yaml_configs_try_create_all(gcx.clone()).await;
     if cmdline.only_create_yaml_configs {
@@ -139,8 +147,8 @@ async fn main() {
     // vector db will spontaneously start if the downloaded caps and command line parameters are right
 
     let should_start_http = cmdline.http_port != 0;
-    let should_start_lsp = (cmdline.lsp_port == 0 && cmdline.lsp_stdin_stdout == 1) ||
-        (cmdline.lsp_port != 0 && cmdline.lsp_stdin_stdout == 0);
+    let should_start_lsp = !cmdline.mcp_stdio && ((cmdline.lsp_port == 0 && cmdline.lsp_stdin_stdout == 1) ||
+        (cmdline.lsp_port != 0 && cmdline.lsp_stdin_stdout == 0));
 
     let mut main_handle: Option<JoinHandle<()>> = None;
     if should_start_http {
@@ -154,11 +162,16 @@ async fn main() {
             background_tasks.push_back(spawn_lsp_task(gcx.clone(), cmdline.clone()).await.unwrap())
         }
     }
-    if main_handle.is_some() {
+    if cmdline.mcp_stdio {
+        // runs until stdin closes, an HTTP server if any stops with it
+        mcp_server::mcp_stdio_main(gcx.clone()).await;
+    } else if main_handle.is_some() {
         let _ = main_handle.unwrap().await;
     }
 
     background_tasks.abort().await;
+    integrations::sessions::stop_all_sessions(gcx.clone()).await;
+    audit_log::audit_log_shutdown();
     info!("saving telemetry without sending, so should be quick");
     basic_transmit::basic_telemetry_compress(gcx.clone()).await;
     info!("bb\n");
@@ -0,0 +1,317 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::RwLock as StdRwLock;
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::sync::{mpsc, Mutex as AMutex, RwLock as ARwLock};
+use tokenizers::Tokenizer;
+use tracing::{error, info};
+
+use crate::at_commands::at_commands::{at_commands_dict, filter_only_context_file_from_context_tool, AtCommandsContext};
+use crate::at_commands::execute_at::execute_at_commands_in_query;
+use crate::cached_tokenizers;
+use crate::call_validation::{ChatContent, ChatMessage, ChatToolCall, ChatToolFunction, ContextEnum, ContextFile};
+use crate::files_correction::canonical_path;
+use crate::files_in_workspace::get_file_text_from_memory_or_disk;
+use crate::global_context::GlobalContext;
+use crate::http::routers::v1::chat::CHAT_TOP_N;
+use crate::integrations::integr_mcp::MCP_PROTOCOL_VERSION;
+use crate::postprocessing::pp_context_files::postprocess_context_files;
+use crate::privacy::{check_file_privacy, load_privacy_if_needed, FilePrivacyLevel};
+use crate::privacy_secrets::{log_redactions, redact_context_files, redact_secrets};
+use crate::scratchpads::scratchpad_utils::{max_tokens_for_rag_chat, HasRagResults};
+use crate::tools::tools_description::{tool_description_list_from_yaml, tools_merged_and_filtered, AtParamDict, ToolDict};
+use crate::tools::tools_execute::run_tools;
+
+// --mcp-stdio: the same workspace index, but for other agents. Model Context Protocol is JSON-RPC, one message
+// per line on stdin and stdout, so nothing else may print to stdout in this mode.
+
+const MCP_EXPOSED_TOOLS: &[&str] = &["definition", "references", "search", "tree", "cat", "locate", "knowledge"];
+const MCP_AT_COMMAND_TOOL: &str = "at_command";
+const MCP_MAXGEN: usize = 1024;  // there's no generation here, it only decides how much context fits
+const MCP_CHAT_ID: &str = "mcp-stdio";
+
+
+async fn chat_n_ctx_and_tokenizer(gcx: Arc<ARwLock<GlobalContext>>) -> Result<(usize, Arc<StdRwLock<Tokenizer>>), String> {
+    let caps = crate::global_context::try_load_caps_quickly_if_not_present(gcx.clone(), 0).await.map_err(|e| e.message)?;
+    let (model_name, n_ctx) = {
+        let caps_locked = caps.read().unwrap();
+        let (model_name, model_record) = crate::caps::which_model_to_use(
+            &caps_locked.code_chat_models,
+            "",
+            &caps_locked.code_chat_default_model,
+        )?;
+        (model_name, model_record.n_ctx)
+    };
+    let tokenizer = cached_tokenizers::cached_tokenizer(caps.clone(), gcx.clone(), model_name).await?;
+    Ok((n_ctx, tokenizer))
+}
+
+async fn mcp_tools_list(gcx: Arc<ARwLock<GlobalContext>>) -> Result<Value, String> {
+    let all_tools = tools_merged_and_filtered(gcx.clone()).await;
+    let turned_on = all_tools.keys().filter(|x| MCP_EXPOSED_TOOLS.contains(&x.as_str())).cloned().collect::<Vec<_>>();
+    let allow_experimental = gcx.read().await.cmdline.experimental;
+    let mut tool_desclist = tool_description_list_from_yaml(all_tools, &turned_on, allow_experimental).await?;
+
+    let mut at_commands = at_commands_dict(gcx.clone()).await.keys().cloned().collect::<Vec<_>>();
+    at_commands.sort();
+    tool_desclist.push(ToolDict {
+        name: MCP_AT_COMMAND_TOOL.to_string(),
+        agentic: false,
+        experimental: false,
+        description: format!("Run @-commands the same way the chat input does and get the context they produce. Available: {}", at_commands.join(", ")),
+        parameters: vec![AtParamDict {
+            name: "query".to_string(),
+            param_type: "string".to_string(),
+            description: "One or more commands, one per line, for example: @file src/main.rs".to_string(),
+        }],
+        parameters_required: vec!["query".to_string()],
+        input_schema: None,
+    });
+
+    let tools = tool_desclist.into_iter().map(|x| {
+        let openai_style = x.into_openai_style();
+        json!({
+            "name": openai_style["function"]["name"],
+            "description": openai_style["function"]["description"],
+            "inputSchema": openai_style["function"]["parameters"],
+        })
+    }).collect::<Vec<_>>();
+    Ok(json!({"tools": tools}))
+}
+
+fn mcp_content_from_context_files(context_files: &[ContextFile]) -> Vec<Value> {
+    context_files.iter().map(|cf| {
+        let mut uri = url::Url::from_file_path(&cf.file_name).map(|x| x.to_string()).unwrap_or(cf.file_name.clone());
+        if cf.line1 > 0 {
+            uri = format!("{}#L{}-L{}", uri, cf.line1, cf.line2);
+        }
+        json!({"type": "resource", "resource": {"uri": uri, "mimeType": "text/plain", "text": cf.file_content}})
+    }).collect()
+}
+
+fn mcp_content_from_messages(messages: &[ChatMessage]) -> Vec<Value> {
+    let mut content = vec![];
+    for msg in messages {
+        let text = msg.content.content_text_only();
+        if msg.role == "context_file" {
+            match serde_json::from_str::<Vec<ContextFile>>(&text) {
+                Ok(context_files) => content.extend(mcp_content_from_context_files(&context_files)),
+                Err(e) => error!("MCP: cannot parse context files: {}", e),
+            }
+        } else if !text.is_empty() {
+            content.push(json!({"type": "text", "text": text}));
+        }
+    }
+    content
+}
+
+async fn mcp_call_tool(gcx: Arc<ARwLock<GlobalContext>>, name: &str, arguments: &Value) -> Result<Vec<Value>, String> {
+    let (n_ctx, tokenizer) = chat_n_ctx_and_tokenizer(gcx.clone()).await?;
+    let ccx = Arc::new(AMutex::new(AtCommandsContext::new(
+        gcx.clone(),
+        n_ctx,
+        CHAT_TOP_N,
+        false,
+        vec![],
+        MCP_CHAT_ID.to_string(),
+    ).await));
+
+    if name == MCP_AT_COMMAND_TOOL {
+        let mut query = arguments["query"].as_str().ok_or("Missing argument `query`")?.to_string();
+        let (messages_for_postprocessing, highlights) = execute_at_commands_in_query(ccx.clone(), &mut query).await;
+        let mut content = vec![];
+        for h in highlights.iter().filter(|h| !h.ok) {
+            content.push(json!({"type": "text", "text": format!("{}: {}", h.kind, h.reason.clone().unwrap_or_default())}));
+        }
+        for exec_result in messages_for_postprocessing.iter() {
+            if let ContextEnum::ChatMessage(msg) = exec_result {
+                let mut redacted = vec![];
+                let text = redact_secrets(&msg.content.content_text_only(), &format!("@-command {}", msg.role), &mut redacted);
+                log_redactions(&redacted);
+                content.push(json!({"type": "text", "text": text}));
+            }
+        }
+        let mut pp_settings = ccx.lock().await.postprocess_parameters.clone();
+        if pp_settings.max_files_n == 0 {
+            pp_settings.max_files_n = CHAT_TOP_N;
+        }
+        let mut processed = postprocess_context_files(
+            gcx.clone(),
+            &mut filter_only_context_file_from_context_tool(&messages_for_postprocessing),
+            tokenizer.clone(),
+            max_tokens_for_rag_chat(n_ctx, MCP_MAXGEN),
+            false,
+            &pp_settings,
+        ).await;
+        redact_context_files(&mut processed);
+        content.extend(mcp_content_from_context_files(&processed));
+        return Ok(content);
+    }
+
+    if !MCP_EXPOSED_TOOLS.contains(&name) || !ccx.lock().await.at_tools.contains_key(name) {
+        return Err(format!("Unknown tool: {}", name));
+    }
+    let messages = vec![ChatMessage {
+        role: "assistant".to_string(),
+        content: ChatContent::SimpleText("".to_string()),
+        tool_calls: Some(vec![ChatToolCall {
+            id: format!("{}-call", MCP_CHAT_ID),
+            function: ChatToolFunction { name: name.to_string(), arguments: arguments.to_string() },
+            tool_type: "function".to_string(),
+        }]),
+        ..Default::default()
+    }];
+    // goes through the same deny rules, postprocessing and secrets redaction as the chat
+    let (all_messages, _) = run_tools(ccx.clone(), tokenizer, MCP_MAXGEN, &messages, &mut HasRagResults::new()).await;
+    Ok(mcp_content_from_messages(&all_messages[messages.len()..]))
+}
+
+fn mcp_resource_path(uri: &str, workspace_folders: &[PathBuf]) -> Result<PathBuf, String> {
+    // ".." and symlinks are resolved first, only files inside the workspace are served
+    let path = url::Url::parse(uri).ok().and_then(|x| x.to_file_path().ok()).ok_or(format!("not a file:// uri: {}", uri))?;
+    let path = canonical_path(&path.to_string_lossy().to_string());
+    if !workspace_folders.iter().any(|folder| path.starts_with(canonical_path(&folder.to_string_lossy().to_string()))) {
+        return Err(format!("{} is outside of the workspace", path.display()));
+    }
+    Ok(path)
+}
+
+async fn mcp_read_resource(gcx: Arc<ARwLock<GlobalContext>>, uri: &str) -> Result<Value, String> {
+    let workspace_folders = gcx.read().await.documents_state.workspace_folders.lock().unwrap().clone();
+    let path = mcp_resource_path(uri, &workspace_folders)?;
+    check_file_privacy(load_privacy_if_needed(gcx.clone()).await, &path, &FilePrivacyLevel::AllowToSendAnywhere)?;
+    let text = get_file_text_from_memory_or_disk(gcx.clone(), &path).await?;
+    let mut redacted = vec![];
+    let text = redact_secrets(&text, &path.to_string_lossy(), &mut redacted);
+    log_redactions(&redacted);
+    Ok(json!({"contents": [{"uri": uri, "mimeType": "text/plain", "text": text}]}))
+}
+
+async fn mcp_handle_request(gcx: Arc<ARwLock<GlobalContext>>, method: &str, params: &Value) -> Result<Value, (i64, String)> {
+    let internal = |e: String| (-32603, e);
+    match method {
+        "initialize" => Ok(json!({
+            "protocolVersion": MCP_PROTOCOL_VERSION,
+            "capabilities": {"tools": {}, "resources": {}},
+            "serverInfo": {"name": "refact-lsp", "version": crate::version::build_info::PKG_VERSION},
+        })),
+        "ping" => Ok(json!({})),
+        "tools/list" => mcp_tools_list(gcx).await.map_err(internal),
+        "tools/call" => {
+            let name = params["name"].as_str().ok_or((-32602, "missing tool name".to_string()))?;
+            let arguments = if params["arguments"].is_object() { params["arguments"].clone() } else { json!({}) };
+            info!("MCP tools/call {}({})", name, arguments);
+            Ok(match mcp_call_tool(gcx, name, &arguments).await {
+                Ok(content) => json!({"content": content, "isError": false}),
+                Err(e) => json!({"content": [{"type": "text", "text": e}], "isError": true}),
+            })
+        }
+        "resources/list" => Ok(json!({"resources": []})),
+        "resources/templates/list" => Ok(json!({"resourceTemplates": [
+            {"uriTemplate": "file:///{path}", "name": "Workspace file", "mimeType": "text/plain"},
+        ]})),
+        "resources/read" => {
+            let uri = params["uri"].as_str().ok_or((-32602, "missing uri".to_string()))?;
+            mcp_read_resource(gcx, uri).await.map_err(internal)
+        }
+        _ => Err((-32601, format!("method {} not found", method))),
+    }
+}
+
+pub async fn mcp_stdio_main(gcx: Arc<ARwLock<GlobalContext>>) {
+    info!("MCP server on stdin/stdout");
+    let (tx, mut rx) = mpsc::unbounded_channel::<Value>();
+    let writer = tokio::spawn(async move {
+        let mut stdout = tokio::io::stdout();
+        while let Some(message) = rx.recv().await {
+            if stdout.write_all(format!("{}\n", message).as_bytes()).await.is_err() || stdout.flush().await.is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let message = match serde_json::from_str::<Value>(&line) {
+            Ok(x) => x,
+            Err(e) => {
+                let _ = tx.send(json!({"jsonrpc": "2.0", "id": null, "error": {"code": -32700, "message": e.to_string()}}));
+                continue;
+            }
+        };
+        let (id, method) = match (message.get("id"), message["method"].as_str()) {
+            (Some(id), Some(method)) => (id.clone(), method.to_string()),
+            _ => continue,  // notifications and responses, nothing to answer
+        };
+        // tools can take a while, ping and the rest should not wait for them
+        let gcx = gcx.clone();
+        let tx = tx.clone();
+        tokio::spawn(async move {
+            let reply = match mcp_handle_request(gcx, &method, &message["params"]).await {
+                Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+                Err((code, e)) => {
+                    error!("MCP {}: {}", method, e);
+                    json!({"jsonrpc": "2.0", "id": id, "error": {"code": code, "message": e}})
+                }
+            };
+            let _ = tx.send(reply);
+        });
+    }
+    info!("MCP stdin closed, exiting");
+    drop(tx);
+    let _ = writer.await;
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mcp_content_from_messages() {
+        let context_files = vec![ContextFile {
+            file_name: "/home/user/proj/src/main.rs".to_string(),
+            file_content: "fn main() {}\n".to_string(),
+            line1: 1,
+            line2: 1,
+            symbols: vec![],
+            gradient_type: -1,
+            usefulness: 0.0,
+        }];
+        let messages = vec![
+            ChatMessage::new("tool".to_string(), "Definition of main found".to_string()),
+            ChatMessage::new("context_file".to_string(), serde_json::to_string(&context_files).unwrap()),
+        ];
+        let content = mcp_content_from_messages(&messages);
+        assert_eq!(content.len(), 2);
+        assert_eq!(content[0], json!({"type": "text", "text": "Definition of main found"}));
+        assert_eq!(content[1]["resource"]["uri"], "file:///home/user/proj/src/main.rs#L1-L1");
+        assert_eq!(content[1]["resource"]["text"], "fn main() {}\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_mcp_resource_path() {
+        let tmp = tempfile::tempdir().unwrap();
+        let workspace = tmp.path().join("proj");
+        std::fs::create_dir_all(workspace.join("src")).unwrap();
+        std::fs::write(workspace.join("src/main.rs"), "fn main() {}\n").unwrap();
+        std::fs::write(tmp.path().join("secret.txt"), "password\n").unwrap();
+        std::os::unix::fs::symlink(tmp.path().join("secret.txt"), workspace.join("src/link.txt")).unwrap();
+        let workspace_folders = vec![workspace.clone()];
+        let uri = |p: &std::path::Path| url::Url::from_file_path(p).unwrap().to_string();
+
+        let path = mcp_resource_path(&uri(&workspace.join("src/main.rs")), &workspace_folders).unwrap();
+        assert!(path.ends_with("proj/src/main.rs"), "{}", path.display());
+        assert!(mcp_resource_path(&uri(&tmp.path().join("secret.txt")), &workspace_folders).is_err());
+        assert!(mcp_resource_path(&format!("file://{}/src/../../secret.txt", workspace.display()), &workspace_folders).is_err());
+        assert!(mcp_resource_path(&uri(&workspace.join("src/link.txt")), &workspace_folders).is_err());
+        assert!(mcp_resource_path("file:///etc/passwd", &workspace_folders).is_err());
+        assert!(mcp_resource_path("https://example.com/a.rs", &workspace_folders).is_err());
+        assert!(mcp_resource_path(&uri(&workspace.join("src/main.rs")), &[]).is_err());
+    }
+}
@@ -2,7 +2,7 @@ use std::sync::Arc;
 use std::sync::RwLock;
 use tokenizers::Tokenizer;
 
-use crate::call_validation::ChatMessage;
+use crate::call_validation::{ChatContent, ChatMessage};
 use crate::scratchpads::scratchpad_utils::count_tokens;
 
 
@@ -15,7 +15,7 @@ pub async fn postprocess_plain_text(
         return (vec![], tokens_limit);
     }
     let mut messages_sorted = messages.clone();
-    messages_sorted.sort_by(|a, b| a.content.len().cmp(&b.content.len()));
+    messages_sorted.sort_by_key(|m| m.content.content_text_only().len());
 
     let mut tok_used_global = 0;
     let mut tok_per_m = tokens_limit / messages_sorted.len();
@@ -25,7 +25,8 @@ pub async fn postprocess_plain_text(
     for (idx, msg) in messages_sorted.iter().cloned().enumerate() {
         let mut out = vec![];
         let mut tok_used = 0;
-        for line in msg.content.lines() {
+        let text = msg.content.content_text_only();
+        for line in text.lines() {
             let line_tokens = count_tokens(&tokenizer_guard, &line);
             if tok_used + line_tokens > tok_per_m {
                 if out.is_empty() {
@@ -43,7 +44,7 @@ pub async fn postprocess_plain_text(
         }
         tok_used_global += tok_used;
         let mut m_cloned = msg.clone();
-        m_cloned.content = out.join("\n");
+        m_cloned.content = ChatContent::SimpleText(out.join("\n"));
 
         // TODO: find a good way to tell the model how much lines were omitted
 
@@ -5,9 +5,10 @@ use tokio::sync::RwLock as ARwLock;
 use tokio::time::Duration;
 use tokio::fs;
 use tracing::error;
-use glob::Pattern;
+use glob::{MatchOptions, Pattern};
 use std::time::SystemTime;
 
+use crate::audit_log::AuditFile;
 use crate::global_context::GlobalContext;
 
 
@@ -22,6 +23,8 @@ pub enum FilePrivacyLevel {
 pub struct PrivacySettings {
     pub privacy_rules: FilePrivacySettings,
     #[serde(default)]
+    pub trusted_endpoints: Vec<String>,  // the servers I control, host or URL globs
+    #[serde(default)]
     pub loaded_ts: u64,
 }
 
@@ -39,6 +42,7 @@ impl Default for PrivacySettings {
                 blocked: vec!["*".to_string()],
                 only_send_to_servers_I_control: vec![],
             },
+            trusted_endpoints: vec![],
             loaded_ts: 0,
         }
     }
@@ -116,6 +120,56 @@ pub fn check_file_privacy(privacy_settings: Arc<PrivacySettings>, path: &Path, m
     Ok(())
 }
 
+fn is_endpoint_trusted(privacy_settings: &PrivacySettings, endpoint_url: &str) -> bool
+{
+    // globs match the host ("*.mycompany.com"), host with port ("10.0.0.5:8008") or, if they have "://", the whole URL
+    // ("https://mycompany.com/refact/**"); in URL globs `*` doesn't cross "/", otherwise "https://api.openai.com/x.mycompany.com" would do
+    let url = match url::Url::parse(endpoint_url) {
+        Ok(url) => url,
+        Err(_) => return false,
+    };
+    let host = url.host_str().unwrap_or_default();
+    let host_port = match url.port() {
+        Some(port) => format!("{}:{}", host, port),
+        None => host.to_string(),
+    };
+    privacy_settings.trusted_endpoints.iter().any(|glob| {
+        match Pattern::new(glob) {
+            Ok(pattern) if glob.contains("://") => pattern.matches_with(url.as_str(), MatchOptions { require_literal_separator: true, ..MatchOptions::new() }),
+            Ok(pattern) => pattern.matches(host) || pattern.matches(&host_port),
+            Err(e) => {
+                error!("trusted_endpoints: invalid pattern {:?}: {}", glob, e);
+                false
+            }
+        }
+    })
+}
+
+pub fn endpoint_min_allowed_privacy_level(privacy_settings: Arc<PrivacySettings>, endpoint_url: &str) -> FilePrivacyLevel
+{
+    if is_endpoint_trusted(&privacy_settings, endpoint_url) {
+        FilePrivacyLevel::OnlySendToServersIControl
+    } else {
+        FilePrivacyLevel::AllowToSendAnywhere
+    }
+}
+
+pub fn check_file_privacy_for_endpoint(privacy_settings: Arc<PrivacySettings>, path: &Path, endpoint_url: &str) -> Result<(), String>
+{
+    let min_allowed_privacy_level = endpoint_min_allowed_privacy_level(privacy_settings.clone(), endpoint_url);
+    check_file_privacy(privacy_settings, path, &min_allowed_privacy_level)
+        .map_err(|e| format!("{}, not allowed to send to {}", e, endpoint_url))
+}
+
+pub fn check_files_privacy_for_endpoint(privacy_settings: Arc<PrivacySettings>, files: &[AuditFile], endpoint_url: &str) -> Result<(), String>
+{
+    for f in files {
+        check_file_privacy_for_endpoint(privacy_settings.clone(), Path::new(&f.file_name), endpoint_url)
+            .map_err(|e| format!("{}: {}", f.file_name, e))?;
+    }
+    Ok(())
+}
+
 
 #[cfg(test)]
 mod tests {
@@ -129,6 +183,7 @@ mod tests {
                     only_send_to_servers_I_control: Vec::new(),
                     blocked: Vec::new(),
                 },
+                trusted_endpoints: Vec::new(),
                 loaded_ts: 0,
             }
         }
@@ -142,6 +197,7 @@ mod tests {
                 only_send_to_servers_I_control: vec!["*.pem".to_string(), "*/semi_private_dir/*.md".to_string()],
                 blocked: vec!["*.pem".to_string(), "*/secret_dir/*".to_string(), "secret_passwords.txt".to_string()],
             },
+            trusted_endpoints: vec![],
             loaded_ts: 0,
         });
 
@@ -181,6 +237,7 @@ mod tests {
                 only_send_to_servers_I_control: vec!["*.cat.txt".to_string(), "*.md".to_string(), "*/.venv/*".to_string(), "**/tests_dir/**/*".to_string()],
                 blocked: vec!["*/make.png".to_string(), "*.txt".to_string()],
             },
+            trusted_endpoints: vec![],
             loaded_ts: 0,
         });
 
@@ -214,12 +271,40 @@ mod tests {
             }
         }
     }
-}
-
-
-
-
-
-
 
+    #[test]
+    fn test_privacy_trusted_endpoints() {
+        let privacy_settings = Arc::new(PrivacySettings {
+            privacy_rules: FilePrivacySettings {
+                only_send_to_servers_I_control: vec!["*/semi_private_dir/*".to_string()],
+                blocked: vec!["*.pem".to_string()],
+            },
+            trusted_endpoints: vec!["*.mycompany.com".to_string(), "10.0.0.5:8008".to_string(), "http://localhost/refact/**".to_string()],
+            loaded_ts: 0,
+        });
 
+        let current_dir = std::env::current_dir().unwrap();
+        let semi_private = current_dir.join("semi_private_dir/notes.md");
+        let public = current_dir.join("main.rs");
+        let blocked = current_dir.join("secret.pem");
+
+        let cases: Vec<(&str, &PathBuf, bool)> = vec![
+            ("https://inference.mycompany.com/v1/completions", &semi_private, true),
+            ("http://10.0.0.5:8008/v1/embeddings", &semi_private, true),
+            ("http://10.0.0.5:8009/v1/embeddings", &semi_private, false),
+            ("http://localhost/refact/v1/chat", &semi_private, true),
+            ("http://localhost/other/v1/chat", &semi_private, false),
+            ("https://api.openai.com/v1/chat/completions", &semi_private, false),
+            ("https://mycompany.com.evil.com/v1/completions", &semi_private, false),
+            ("https://api.openai.com/v1/x.mycompany.com", &semi_private, false),
+            ("http://localhost.evil.com/refact/v1/chat", &semi_private, false),
+            ("https://api.openai.com/v1/chat/completions", &public, true),
+            ("https://inference.mycompany.com/v1/completions", &blocked, false),
+            ("not a url", &semi_private, false),
+        ];
+        for (endpoint, path, expected_ok) in cases {
+            let result = check_file_privacy_for_endpoint(privacy_settings.clone(), path, endpoint);
+            assert_eq!(result.is_ok(), expected_ok, "{} {} -> {:?}", endpoint, path.display(), result);
+        }
+    }
+}
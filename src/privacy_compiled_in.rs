@@ -13,9 +13,14 @@ privacy_rules:
         - "*/secret_project2/*.txt"
         - "*.pem"
 
-    only_send_to_servers_I_control:       # You can set up which ones you control in bring-your-own-key.yaml, otherwise you control none
+    only_send_to_servers_I_control:       # List the servers you control in trusted_endpoints below, otherwise you control none
         - "secret_passwords.txt"
 
+trusted_endpoints:                        # Host or URL globs: completion, chat and embedding servers you control
+    # - "*.mycompany.com"
+    # - "10.0.0.5:8008"
+    # - "http://localhost/refact/**"          # URL globs need "://", "*" stops at "/"
+
 
 # See unit tests in privacy.rs for more examples.
 "#;
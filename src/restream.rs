@@ -5,19 +5,24 @@ use tokio::sync::mpsc;
 use async_stream::stream;
 use futures::StreamExt;
 use hyper::{Body, Response, StatusCode};
-use reqwest_eventsource::Event;
+use reqwest_eventsource::{Event, EventSource};
 use serde_json::json;
 use tracing::{error, info};
 
-use crate::call_validation::SamplingParameters;
+use crate::call_validation::{ChatMessage, SamplingParameters};
 use crate::custom_error::ScratchError;
+use crate::forward_to_anthropic_endpoint;
 use crate::forward_to_hf_endpoint;
+use crate::forward_to_llama_cpp_endpoint;
+use crate::forward_to_ollama_endpoint;
 use crate::forward_to_openai_endpoint;
 use crate::nicer_logs;
 use crate::scratchpad_abstract::ScratchpadAbstract;
 use crate::telemetry::telemetry_structs;
 use crate::at_commands::at_commands::AtCommandsContext;
 use crate::caps::get_api_key;
+use crate::audit_log::{AuditFile, AuditRecord, audit_files_from_messages, audit_log_write, merge_audit_files};
+use crate::privacy::{check_files_privacy_for_endpoint, load_privacy_if_needed};
 
 
 async fn _get_endpoint_and_stuff_from_model_name(
@@ -71,6 +76,46 @@ async fn _get_endpoint_and_stuff_from_model_name(
     )
 }
 
+fn _prompt_files(messages: &[ChatMessage], audit_files: &[AuditFile]) -> Vec<AuditFile> {
+    let mut files = audit_files_from_messages(messages);
+    merge_audit_files(&mut files, audit_files.to_vec());
+    files
+}
+
+async fn _audit_record(
+    ccx: Arc<AMutex<AtCommandsContext>>,
+    scope: &str,
+    url: &str,
+    model_name: &str,
+    prompt: &str,
+) -> AuditRecord {
+    let files = {
+        let ccx_locked = ccx.lock().await;
+        _prompt_files(&ccx_locked.messages, &ccx_locked.audit_files)
+    };
+    AuditRecord::new(scope, url, model_name, files, prompt.len())
+}
+
+fn _endpoint_url(endpoint_template: &str, endpoint_chat_passthrough: &str, model_name: &str) -> String {
+    if !endpoint_chat_passthrough.is_empty() {
+        return endpoint_chat_passthrough.to_string();
+    }
+    endpoint_template.replace("$MODEL", model_name)
+}
+
+async fn _check_prompt_privacy(
+    ccx: Arc<AMutex<AtCommandsContext>>,
+    endpoint_url: &str,
+) -> Result<(), String> {
+    // every file that went into the prompt (@file, cat, context files, FIM rag) is checked against where the prompt goes
+    let (gcx, files) = {
+        let ccx_locked = ccx.lock().await;
+        (ccx_locked.global_context.clone(), _prompt_files(&ccx_locked.messages, &ccx_locked.audit_files))
+    };
+    let privacy_settings = load_privacy_if_needed(gcx).await;
+    check_files_privacy_for_endpoint(privacy_settings, &files, endpoint_url)
+}
+
 pub async fn scratchpad_interaction_not_stream_json(
     ccx: Arc<AMutex<AtCommandsContext>>,
     scratchpad: &mut Box<dyn ScratchpadAbstract>,
@@ -100,9 +145,15 @@ pub async fn scratchpad_interaction_not_stream_json(
         endpoint_chat_passthrough,
     ) = _get_endpoint_and_stuff_from_model_name(gcx.clone(), caps.clone(), model_name.clone()).await;
 
+    if !only_deterministic_messages {
+        let endpoint_url = _endpoint_url(&endpoint_template, &endpoint_chat_passthrough, &model_name);
+        _check_prompt_privacy(ccx.clone(), &endpoint_url).await
+            .map_err(|e| ScratchError::new(StatusCode::UNPROCESSABLE_ENTITY, e))?;
+    }
+
     let mut save_url: String = String::new();
     let _ = slowdown_arc.acquire().await;
-    let mut model_says = if only_deterministic_messages {
+    let model_says = if only_deterministic_messages {
         save_url = "only-det-messages".to_string();
         Ok(serde_json::Value::Object(serde_json::Map::new()))
     } else if endpoint_style == "hf" {
@@ -115,6 +166,37 @@ pub async fn scratchpad_interaction_not_stream_json(
             &endpoint_template,
             &parameters,
         ).await
+    } else if endpoint_style == "anthropic" {
+        forward_to_anthropic_endpoint::forward_to_anthropic_style_endpoint(
+            &mut save_url,
+            bearer.clone(),
+            &model_name,
+            prompt,
+            &client,
+            &endpoint_chat_passthrough,
+            parameters,
+        ).await
+    } else if endpoint_style == "ollama" {
+        forward_to_ollama_endpoint::forward_to_ollama_style_endpoint(
+            &mut save_url,
+            bearer.clone(),
+            &model_name,
+            prompt,
+            &client,
+            &endpoint_template,
+            &endpoint_chat_passthrough,
+            parameters,
+        ).await
+    } else if endpoint_style == "llama.cpp" {
+        forward_to_llama_cpp_endpoint::forward_to_llama_cpp_style_endpoint(
+            &mut save_url,
+            bearer.clone(),
+            &model_name,
+            prompt,
+            &client,
+            &endpoint_template,
+            parameters,
+        ).await
     } else {
         forward_to_openai_endpoint::forward_to_openai_style_endpoint(
             &mut save_url,
@@ -126,7 +208,16 @@ pub async fn scratchpad_interaction_not_stream_json(
             &endpoint_chat_passthrough,
             &parameters,  // includes n
         ).await
-    }.map_err(|e| {
+    };
+    if !only_deterministic_messages {
+        let mut audit_record = _audit_record(ccx.clone(), &scope, &save_url, &model_name, prompt).await;
+        if let Ok(model_says) = &model_says {
+            audit_record = audit_record.with_usage(model_says);
+            audit_record.success = true;
+        }
+        audit_log_write(&audit_record);
+    }
+    let mut model_says = model_says.map_err(|e| {
         tele_storage.write().unwrap().tele_net.push(telemetry_structs::TelemetryNetwork::new(
                 save_url.clone(),
                 scope.clone(),
@@ -231,6 +322,10 @@ pub async fn scratchpad_interaction_not_stream(
         only_deterministic_messages,
     ).await?;
     scratchpad_response_json["created"] = json!(t2.duration_since(std::time::UNIX_EPOCH).unwrap().as_millis() as f64 / 1000.0);
+    let secrets_redacted = ccx.lock().await.secrets_redacted.clone();
+    if !secrets_redacted.is_empty() {
+        scratchpad_response_json["secrets_redacted"] = json!(secrets_redacted);
+    }
 
     try_insert_usage(&mut scratchpad_response_json);
 
@@ -325,6 +420,18 @@ pub async fn scratchpad_interaction_stream(
             }
         }
         info!("scratchpad_interaction_stream prompt {:?}", t0.elapsed());
+        // the prompt is ready, nothing else gets redacted, the report goes with the final chunk
+        let mut secrets_redacted = my_ccx.lock().await.secrets_redacted.clone();
+
+        if !only_deterministic_messages {
+            let endpoint_url = _endpoint_url(&endpoint_template, &endpoint_chat_passthrough, &model_name);
+            if let Err(e) = _check_prompt_privacy(my_ccx.clone(), &endpoint_url).await {
+                tracing::warn!("not sending the prompt: {}", e);
+                let value_str = format!("data: {}\n\n", serde_json::to_string(&json!({"detail": e})).unwrap());
+                yield Result::<_, String>::Ok(value_str);
+                return;
+            }
+        }
 
         let mut save_url: String = String::new();
         let _ = slowdown_arc.acquire().await;
@@ -346,7 +453,7 @@ pub async fn scratchpad_interaction_stream(
                 break;
             }
             // info!("prompt: {:?}", prompt);
-            let event_source_maybe = if endpoint_style == "hf" {
+            let event_source_maybe: Result<Box<dyn EndpointEventSource>, String> = if endpoint_style == "hf" {
                 forward_to_hf_endpoint::forward_to_hf_style_endpoint_streaming(
                     &mut save_url,
                     bearer.clone(),
@@ -355,7 +462,38 @@ pub async fn scratchpad_interaction_stream(
                     &client,
                     &endpoint_template,
                     &parameters,
-                ).await
+                ).await.map(|x| Box::new(x) as Box<dyn EndpointEventSource>)
+            } else if endpoint_style == "anthropic" {
+                forward_to_anthropic_endpoint::forward_to_anthropic_style_endpoint_streaming(
+                    &mut save_url,
+                    bearer.clone(),
+                    &model_name,
+                    prompt.as_str(),
+                    &client,
+                    &endpoint_chat_passthrough,
+                    &parameters,
+                ).await.map(|x| Box::new(x) as Box<dyn EndpointEventSource>)
+            } else if endpoint_style == "ollama" {
+                forward_to_ollama_endpoint::forward_to_ollama_style_endpoint_streaming(
+                    &mut save_url,
+                    bearer.clone(),
+                    &model_name,
+                    prompt.as_str(),
+                    &client,
+                    &endpoint_template,
+                    &endpoint_chat_passthrough,
+                    &parameters,
+                ).await.map(|x| Box::new(x) as Box<dyn EndpointEventSource>)
+            } else if endpoint_style == "llama.cpp" {
+                forward_to_llama_cpp_endpoint::forward_to_llama_cpp_style_endpoint_streaming(
+                    &mut save_url,
+                    bearer.clone(),
+                    &model_name,
+                    prompt.as_str(),
+                    &client,
+                    &endpoint_template,
+                    &parameters,
+                ).await.map(|x| Box::new(x) as Box<dyn EndpointEventSource>)
             } else {
                 forward_to_openai_endpoint::forward_to_openai_style_endpoint_streaming(
                     &mut save_url,
@@ -366,11 +504,13 @@ pub async fn scratchpad_interaction_stream(
                     &endpoint_template,
                     &endpoint_chat_passthrough,
                     &parameters,
-                ).await
+                ).await.map(|x| Box::new(x) as Box<dyn EndpointEventSource>)
             };
+            let mut audit_record = _audit_record(my_ccx.clone(), &scope, &save_url, &model_name, &prompt).await;
             let mut event_source = match event_source_maybe {
                 Ok(event_source) => event_source,
                 Err(e) => {
+                    audit_log_write(&audit_record);
                     let e_str = format!("forward_to_endpoint: {:?}", e);
                     tele_storage.write().unwrap().tele_net.push(telemetry_structs::TelemetryNetwork::new(
                         save_url.clone(),
@@ -387,6 +527,8 @@ pub async fn scratchpad_interaction_stream(
             let mut finished: bool = false;
             let mut problem_reported = false;
             let mut was_correct_output_even_if_error = false;
+            let mut anthropic_converter = forward_to_anthropic_endpoint::AnthropicStreamConverter::new();
+            let mut ollama_converter = forward_to_ollama_endpoint::OllamaStreamConverter::new();
             // let mut test_countdown = 250;
             while let Some(event) = event_source.next().await {
                 match event {
@@ -396,8 +538,27 @@ pub async fn scratchpad_interaction_stream(
                         if message.data.starts_with("[DONE]") {
                             break;
                         }
-                        let json = serde_json::from_str::<serde_json::Value>(&message.data).unwrap();
+                        let mut json = serde_json::from_str::<serde_json::Value>(&message.data).unwrap();
+                        if endpoint_style == "ollama" {
+                            // local servers just close the connection after the last chunk, there's no [DONE]
+                            was_correct_output_even_if_error |= json["done"] == true;
+                            json = ollama_converter.chunk_to_openai(&json);
+                        } else if endpoint_style == "llama.cpp" {
+                            was_correct_output_even_if_error |= json["stop"] == true;
+                            json = forward_to_llama_cpp_endpoint::llama_cpp_chunk_to_openai(&json);
+                        } else if endpoint_style == "anthropic" {
+                            // messages api events -> openai-style deltas, the rest of the loop is the same
+                            let chunk_maybe = anthropic_converter.event_to_openai_chunk(&json);
+                            if anthropic_converter.message_stopped {
+                                break;
+                            }
+                            json = match chunk_maybe {
+                                Some(chunk) => chunk,
+                                None => continue,
+                            };
+                        }
                         crate::global_context::look_for_piggyback_fields(gcx.clone(), &json).await;
+                        audit_record = audit_record.with_usage(&json);
                         let value_maybe = _push_streaming_json_into_scratchpad(
                             my_scratchpad,
                             &json,
@@ -407,6 +568,9 @@ pub async fn scratchpad_interaction_stream(
                         );
                         if let Ok(mut value) = value_maybe {
                             try_insert_usage(&mut value);
+                            if finished && !secrets_redacted.is_empty() {
+                                value["secrets_redacted"] = json!(std::mem::take(&mut secrets_redacted));
+                            }
                             value["created"] = json!(t1.duration_since(std::time::UNIX_EPOCH).unwrap().as_millis() as f64 / 1000.0);
                             let value_str = format!("data: {}\n\n", serde_json::to_string(&value).unwrap());
                             let last_60_chars: String = crate::nicer_logs::first_n_chars(&value_str, 60);
@@ -444,6 +608,8 @@ pub async fn scratchpad_interaction_stream(
                     },
                 }
             }
+            audit_record.success = !problem_reported;
+            audit_log_write(&audit_record);
             if problem_reported {
                 return;
             } else if !finished {
@@ -451,6 +617,9 @@ pub async fn scratchpad_interaction_stream(
                 (value, _) = my_scratchpad.response_streaming("".to_string(), false, true).unwrap();
                 value["created"] = json!(t1.duration_since(std::time::UNIX_EPOCH).unwrap().as_millis() as f64 / 1000.0);
                 value["model"] = json!(model_name.clone());
+                if !secrets_redacted.is_empty() {
+                    value["secrets_redacted"] = json!(secrets_redacted);
+                }
                 let value_str = format!("data: {}\n\n", serde_json::to_string(&value).unwrap());
                 info!("yield final: {:?}", value_str);
                 yield Result::<_, String>::Ok(value_str);
@@ -474,6 +643,23 @@ pub async fn scratchpad_interaction_stream(
     return Ok(response);
 }
 
+// ollama streams json lines, not SSE, but gives the same events
+pub trait EndpointEventSource: futures::Stream<Item = Result<Event, reqwest_eventsource::Error>> + Send + Unpin {
+    fn close(&mut self);
+}
+
+impl EndpointEventSource for EventSource {
+    fn close(&mut self) {
+        EventSource::close(self)
+    }
+}
+
+impl EndpointEventSource for forward_to_ollama_endpoint::OllamaEventSource {
+    fn close(&mut self) {
+        forward_to_ollama_endpoint::OllamaEventSource::close(self)
+    }
+}
+
 pub fn try_insert_usage(msg_value: &mut serde_json::Value) -> bool {
     let map = match msg_value.as_object() {
         Some(map) => map,
@@ -587,3 +773,65 @@ pub async fn cached_stream(
        .unwrap();
     return Ok(response);
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audit_log::audit_files_from_context_files;
+    use crate::call_validation::{ChatContent, ContextFile};
+    use crate::privacy::{FilePrivacySettings, PrivacySettings};
+
+    #[test]
+    fn test_blocked_file_is_not_sent_to_chat() {
+        let privacy_settings = Arc::new(PrivacySettings {
+            privacy_rules: FilePrivacySettings {
+                only_send_to_servers_I_control: vec!["*/internal/*".to_string()],
+                blocked: vec!["*.pem".to_string()],
+            },
+            trusted_endpoints: vec!["inference.mycompany.com".to_string()],
+            loaded_ts: 0,
+        });
+        let context_file = |file_name: &str| ContextFile {
+            file_name: file_name.to_string(),
+            file_content: "...".to_string(),
+            line1: 1,
+            line2: 3,
+            symbols: vec![],
+            gradient_type: -1,
+            usefulness: 100.0,
+        };
+        // @file and cat results stay in the chat as context_file messages, tools and pp_context_files also land in ccx.audit_files
+        let messages = vec![
+            ChatMessage::new("user".to_string(), "look at this".to_string()),
+            ChatMessage {
+                role: "context_file".to_string(),
+                content: ChatContent::SimpleText(serde_json::to_string(&vec![context_file("/project/main.rs")]).unwrap()),
+                ..Default::default()
+            },
+        ];
+        let openai = _endpoint_url("https://api.openai.com/v1/chat/completions", "", "gpt-4o");
+        let own = _endpoint_url("https://inference.mycompany.com/v1/$MODEL", "", "llama");
+        assert_eq!(own, "https://inference.mycompany.com/v1/llama");
+
+        let files = _prompt_files(&messages, &[]);
+        assert!(check_files_privacy_for_endpoint(privacy_settings.clone(), &files, &openai).is_ok());
+
+        let files = _prompt_files(&messages, &audit_files_from_context_files(&[context_file("/project/internal/notes.md")]));
+        assert!(check_files_privacy_for_endpoint(privacy_settings.clone(), &files, &own).is_ok());
+        let err = check_files_privacy_for_endpoint(privacy_settings.clone(), &files, &openai).unwrap_err();
+        assert!(err.contains("notes.md"), "{}", err);
+
+        let mut messages_with_pem = messages.clone();
+        messages_with_pem.push(ChatMessage {
+            role: "context_file".to_string(),
+            content: ChatContent::SimpleText(serde_json::to_string(&vec![context_file("/project/keys/server.pem")]).unwrap()),
+            ..Default::default()
+        });
+        let files = _prompt_files(&messages_with_pem, &[]);
+        for endpoint in [&openai, &own] {
+            let err = check_files_privacy_for_endpoint(privacy_settings.clone(), &files, endpoint).unwrap_err();
+            assert!(err.contains("server.pem") && err.contains("Blocked"), "{}", err);
+        }
+    }
+}
@@ -10,7 +10,7 @@ use tracing::{info, error};
 
 use crate::at_commands::execute_at::run_at_commands;
 use crate::at_commands::at_commands::AtCommandsContext;
-use crate::call_validation::{ChatMessage, ChatPost, ContextFile, SamplingParameters};
+use crate::call_validation::{ChatContent, ChatMessage, ChatPost, ContextFile, SamplingParameters};
 use crate::global_context::GlobalContext;
 use crate::scratchpad_abstract::HasTokenizerAndEot;
 use crate::scratchpad_abstract::ScratchpadAbstract;
@@ -114,7 +114,7 @@ impl ScratchpadAbstract for GenericChatScratchpad {
         // };
         if let Some(first_msg) = limited_msgs.first_mut() {
             if first_msg.role == "system" {
-                first_msg.content = system_prompt_add_workspace_info(gcx.clone(), &first_msg.content).await;
+                first_msg.content = ChatContent::SimpleText(system_prompt_add_workspace_info(gcx.clone(), &first_msg.content.content_text_only()).await);
             }
         }
         sampling_parameters_to_patch.stop = self.dd.stop_list.clone();
@@ -125,22 +125,22 @@ impl ScratchpadAbstract for GenericChatScratchpad {
             prompt.push_str(self.token_esc.as_str());
             if msg.role == "system" {
                 prompt.push_str(self.keyword_syst.as_str());
-                prompt.push_str(msg.content.as_str());
+                prompt.push_str(msg.content.content_text_only().as_str());
                 prompt.push_str("\n");
             } else if msg.role == "user" {
                 prompt.push_str(self.keyword_user.as_str());
-                prompt.push_str(msg.content.as_str());
+                prompt.push_str(msg.content.content_text_only().as_str());
                 prompt.push_str("\n");
             } else if msg.role == "cd_instruction" {
                 prompt.push_str(self.keyword_user.as_str());
-                prompt.push_str(msg.content.as_str());
+                prompt.push_str(msg.content.content_text_only().as_str());
                 prompt.push_str("\n");
             } else if msg.role == "assistant" {
                 prompt.push_str(self.keyword_asst.as_str());
-                prompt.push_str(msg.content.as_str());
+                prompt.push_str(msg.content.content_text_only().as_str());
                 prompt.push_str("\n");
             } else if msg.role == "context_file" {
-                let vector_of_context_files: Vec<ContextFile> = serde_json::from_str(&msg.content).map_err(|e|error!("parsing context_files has failed: {}; content: {}", e, &msg.content)).unwrap_or(vec![]);
+                let vector_of_context_files: Vec<ContextFile> = serde_json::from_str(&msg.content.content_text_only()).map_err(|e|error!("parsing context_files has failed: {}; content: {}", e, &msg.content.content_text_only())).unwrap_or(vec![]);
                 for context_file in vector_of_context_files {
                     prompt.push_str(format!("{}\n```\n{}```\n\n", context_file.file_name, context_file.file_content).as_str());
                 }
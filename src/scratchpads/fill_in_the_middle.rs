@@ -20,6 +20,8 @@ use crate::completion_cache;
 use crate::scratchpad_abstract::HasTokenizerAndEot;
 use crate::scratchpad_abstract::ScratchpadAbstract;
 use crate::postprocessing::pp_context_files::postprocess_context_files;
+use crate::privacy_secrets::{log_redactions, merge_redactions, redact_secrets};
+use crate::audit_log::{AuditFile, audit_files_from_context_files, merge_audit_files};
 use crate::telemetry::snippets_collection;
 use crate::telemetry::telemetry_structs;
 
@@ -268,6 +270,11 @@ impl ScratchpadAbstract for FillInTheMiddleScratchpad {
             return Err(format!("order \"{}\" not recognized", self.order));
         }
         let fim_ms = fim_t0.elapsed().as_millis() as i32;
+        let mut audit_files = vec![AuditFile {
+            file_name: cpath.to_string_lossy().to_string(),
+            line1: if fim_line1 != i32::MAX { (fim_line1 + 1) as usize } else { (pos.line + 1) as usize },
+            line2: if fim_line2 != i32::MIN { (fim_line2 + 1) as usize } else { (pos.line + 1) as usize },
+        }];
 
         if use_rag && rag_tokens_n > 0 {
             info!(" -- rag search starts --");
@@ -316,6 +323,7 @@ impl ScratchpadAbstract for FillInTheMiddleScratchpad {
             ).await;
 
             prompt = add_context_to_prompt(&self.t.context_format, &prompt, &postprocessed_messages);
+            merge_audit_files(&mut audit_files, audit_files_from_context_files(&postprocessed_messages));
             let rag_ms = rag_t0.elapsed().as_millis() as i32;
             let post_ms = post_t0.elapsed().as_millis() as i32;
             info!(" -- /post fim {}ms, buckets {}ms, post {}ms -- ",
@@ -340,6 +348,15 @@ impl ScratchpadAbstract for FillInTheMiddleScratchpad {
             self.context_used["rag_tokens_limit".to_string()] = Value::from(rag_tokens_n as i64);
         }
 
+        let mut redacted = vec![];
+        let prompt = redact_secrets(&prompt, &self.post.inputs.cursor.file, &mut redacted);
+        log_redactions(&redacted);
+        {
+            let mut ccx_locked = ccx.lock().await;
+            merge_redactions(&mut ccx_locked.secrets_redacted, redacted);
+            merge_audit_files(&mut ccx_locked.audit_files, audit_files);
+        }
+
         if DEBUG {
             info!("cursor position\n{:?}", self.post.inputs.cursor);
             info!("prompt\n{}", prompt);
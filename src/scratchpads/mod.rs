@@ -10,6 +10,7 @@ pub mod chat_passthrough;
 pub mod chat_utils_deltadelta;
 pub mod chat_utils_limit_history;
 pub mod chat_utils_prompts;
+pub mod multimodality;
 pub mod scratchpad_utils;
 
 use crate::ast::ast_indexer_thread::AstIndexService;
@@ -0,0 +1,115 @@
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+
+use crate::call_validation::{ChatContent, ChatImageUrl};
+
+
+// https://platform.openai.com/docs/guides/vision/calculating-costs
+const IMAGE_TOKENS_BASE: usize = 85;
+const IMAGE_TOKENS_PER_TILE: usize = 170;
+const IMAGE_TILE_SIZE: usize = 512;
+// size unknown (remote url or unsupported format), assume a typical 1024x1024 picture
+const IMAGE_TOKENS_UNKNOWN: usize = IMAGE_TOKENS_BASE + 4 * IMAGE_TOKENS_PER_TILE;
+
+
+pub fn split_data_url(url: &str) -> Option<(&str, &str)> {
+    // data:image/png;base64,iVBORw0... => ("image/png", "iVBORw0...")
+    let rest = url.strip_prefix("data:")?;
+    let (header, data) = rest.split_once(',')?;
+    let mime = header.strip_suffix(";base64")?;
+    Some((mime, data.trim()))
+}
+
+pub fn parse_data_url(url: &str) -> Option<(String, Vec<u8>)> {
+    let (mime, data) = split_data_url(url)?;
+    let bytes = STANDARD.decode(data).ok()?;
+    Some((mime.to_string(), bytes))
+}
+
+pub fn image_dimensions(bytes: &[u8]) -> Option<(usize, usize)> {
+    if bytes.len() >= 24 && bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        let w = u32::from_be_bytes(bytes[16..20].try_into().ok()?) as usize;
+        let h = u32::from_be_bytes(bytes[20..24].try_into().ok()?) as usize;
+        return Some((w, h));
+    }
+    if bytes.len() >= 10 && (bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a")) {
+        let w = u16::from_le_bytes([bytes[6], bytes[7]]) as usize;
+        let h = u16::from_le_bytes([bytes[8], bytes[9]]) as usize;
+        return Some((w, h));
+    }
+    if bytes.len() >= 4 && bytes[0] == 0xFF && bytes[1] == 0xD8 {
+        // walk JPEG segments until a start-of-frame marker
+        let mut i = 2;
+        while i + 9 < bytes.len() {
+            if bytes[i] != 0xFF {
+                return None;
+            }
+            let marker = bytes[i + 1];
+            let seg_len = u16::from_be_bytes([bytes[i + 2], bytes[i + 3]]) as usize;
+            if (0xC0..=0xCF).contains(&marker) && marker != 0xC4 && marker != 0xC8 && marker != 0xCC {
+                let h = u16::from_be_bytes([bytes[i + 5], bytes[i + 6]]) as usize;
+                let w = u16::from_be_bytes([bytes[i + 7], bytes[i + 8]]) as usize;
+                return Some((w, h));
+            }
+            i += 2 + seg_len;
+        }
+    }
+    None
+}
+
+fn image_tokens_for_size(width: usize, height: usize) -> usize {
+    if width == 0 || height == 0 {
+        return IMAGE_TOKENS_UNKNOWN;
+    }
+    // fit into 2048x2048, then scale the shortest side down to 768
+    let (mut w, mut h) = (width as f64, height as f64);
+    let fit = (2048.0 / w.max(h)).min(1.0);
+    w *= fit;
+    h *= fit;
+    let shrink = (768.0 / w.min(h)).min(1.0);
+    w *= shrink;
+    h *= shrink;
+    let tiles_w = (w / IMAGE_TILE_SIZE as f64).ceil() as usize;
+    let tiles_h = (h / IMAGE_TILE_SIZE as f64).ceil() as usize;
+    IMAGE_TOKENS_BASE + IMAGE_TOKENS_PER_TILE * tiles_w * tiles_h
+}
+
+pub fn image_tokens(image: &ChatImageUrl) -> usize {
+    if image.detail.as_deref() == Some("low") {
+        return IMAGE_TOKENS_BASE;
+    }
+    parse_data_url(&image.url)
+        .and_then(|(_, bytes)| image_dimensions(&bytes))
+        .map(|(w, h)| image_tokens_for_size(w, h))
+        .unwrap_or(IMAGE_TOKENS_UNKNOWN)
+}
+
+pub fn content_image_tokens(content: &ChatContent) -> usize {
+    content.images().into_iter().map(image_tokens).sum()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_image_tokens_for_size() {
+        assert_eq!(image_tokens_for_size(1024, 1024), 85 + 170 * 4);
+        assert_eq!(image_tokens_for_size(2048, 4096), 85 + 170 * 6);
+        assert_eq!(image_tokens_for_size(100, 100), 85 + 170);
+    }
+
+    #[test]
+    fn test_png_dimensions_from_data_url() {
+        let mut png = b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0dIHDR".to_vec();
+        png.extend_from_slice(&640u32.to_be_bytes());
+        png.extend_from_slice(&480u32.to_be_bytes());
+        let url = format!("data:image/png;base64,{}", STANDARD.encode(&png));
+        let (mime, bytes) = parse_data_url(&url).unwrap();
+        assert_eq!(mime, "image/png");
+        assert_eq!(image_dimensions(&bytes), Some((640, 480)));
+        let image = ChatImageUrl { url, detail: None };
+        assert_eq!(image_tokens(&image), 85 + 170 * 2);
+    }
+}
@@ -6,7 +6,7 @@ use serde_json::Value;
 use tracing::{error, info, warn};
 use crate::tools::tools_description::{tools_merged_and_filtered, tool_description_list_from_yaml};
 use crate::at_commands::at_commands::AtCommandsContext;
-use crate::call_validation::{ChatMessage, ChatPost, ChatToolCall, ChatUsage, SamplingParameters, PostprocessSettings};
+use crate::call_validation::{ChatContent, ChatMessage, ChatPost, ChatToolCall, ChatUsage, SamplingParameters, PostprocessSettings};
 use crate::global_context::{GlobalContext, try_load_caps_quickly_if_not_present};
 use crate::http::routers::v1::chat::lookup_chat_scratchpad;
 use crate::scratchpad_abstract::ScratchpadAbstract;
@@ -164,7 +164,7 @@ async fn chat_interaction_non_stream(
         let mut ch_results = vec![];
         let msg = ChatMessage {
             role,
-            content,
+            content: ChatContent::SimpleText(content),
             tool_calls,
             tool_call_id,
             usage: usage_mb.clone(),
@@ -229,12 +229,13 @@ pub async fn subchat_single(
     let gcx = ccx.lock().await.global_context.clone();
 
     // this ignores customized tools
-    let tools_turned_on_by_cmdline = tools_merged_and_filtered(gcx.clone()).await.keys().cloned().collect::<Vec<_>>();
+    let all_tools = tools_merged_and_filtered(gcx.clone()).await;
+    let tools_turned_on_by_cmdline = all_tools.keys().cloned().collect::<Vec<_>>();
     let tools_turn_on_set: HashSet<String> = tools_subset.iter().cloned().collect();
     let tools_turned_on_by_cmdline_set: HashSet<String> = tools_turned_on_by_cmdline.into_iter().collect();
     let tools_on_intersection: Vec<String> = tools_turn_on_set.intersection(&tools_turned_on_by_cmdline_set).cloned().collect();
     let allow_experimental = gcx.read().await.cmdline.experimental;
-    let tools_desclist = tool_description_list_from_yaml(&tools_on_intersection, allow_experimental).unwrap_or_else(|e|{
+    let tools_desclist = tool_description_list_from_yaml(all_tools, &tools_on_intersection, allow_experimental).await.unwrap_or_else(|e|{
         error!("Error loading compiled_in_tools: {:?}", e);
         vec![]
     });
@@ -1,9 +1,9 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use rand::distributions::Alphanumeric;
 use rand::Rng;
 use ropey::Rope;
 
-use crate::ast::linters::lint;
+use crate::ast::linters::{lint, lint_problems, LintProblem};
 use crate::ast::treesitter::ast_instance_structs::SymbolInformation;
 use crate::files_in_workspace::Document;
 
@@ -42,10 +42,10 @@ pub async fn parse_and_get_error_symbols(
     Ok(error_symbols)
 }
 
-pub fn lint_and_get_error_messages(
-    path: &PathBuf,
+fn document_with_dummy_name(
+    path: &Path,
     file_text: &Rope,
-) -> Vec<String> {
+) -> Document {
     let dummy_filename = PathBuf::from(rand::thread_rng()
         .sample_iter(&Alphanumeric)
         .take(16)
@@ -54,9 +54,24 @@ pub fn lint_and_get_error_messages(
     let new_filename = dummy_filename.with_extension(
         path.extension().unwrap_or_default()
     );
-    let doc = Document { doc_path: new_filename.clone(), doc_text: Some(file_text.clone()) };
+    Document { doc_path: new_filename.clone(), doc_text: Some(file_text.clone()) }
+}
+
+pub fn lint_and_get_error_messages(
+    path: &Path,
+    file_text: &Rope,
+) -> Vec<String> {
+    let doc = document_with_dummy_name(path, file_text);
     match lint(&doc) {
         Ok(_) => vec![],
         Err(problems) => problems,
     }
 }
+
+pub fn lint_and_get_problems(
+    path: &Path,
+    file_text: &Rope,
+) -> Vec<LintProblem> {
+    let doc = document_with_dummy_name(path, file_text);
+    lint_problems(&doc)
+}
@@ -145,7 +145,7 @@ pub async fn execute_chat_model(
     let gcx = ccx.lock().await.global_context.clone();
     let privacy_settings = load_privacy_if_needed(gcx.clone()).await;
     for m in last_messages {
-        match DefaultToolPatch::parse_message(m.content.as_str(), privacy_settings.clone()).await {
+        match DefaultToolPatch::parse_message(m.content.content_text_only().as_str(), privacy_settings.clone()).await {
             Ok(chunks) => {
                 succ_chunks.push(chunks);
             }
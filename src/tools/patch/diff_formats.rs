@@ -5,13 +5,21 @@ use tokio::sync::{RwLock as ARwLock};
 use ropey::Rope;
 use tracing::warn;
 use crate::tools::patch::ast_interaction::{lint_and_get_error_messages, parse_and_get_error_symbols};
+use crate::ast::ast_diagnostics::patch_rejection_diagnostics;
 use crate::call_validation::DiffChunk;
 use crate::diffs::{apply_diff_chunks_to_text, correct_and_validate_chunks, unwrap_diff_apply_outputs};
 use crate::files_in_workspace::read_file_from_disk;
 use crate::global_context::GlobalContext;
+use crate::lsp::publish_diagnostics_with_extra;
 use crate::privacy::load_privacy_if_needed;
 
 
+async fn publish_patch_rejection(gcx: Arc<ARwLock<GlobalContext>>, path: &PathBuf, text_before: &Rope, text_after: &str) {
+    // so the user sees in the editor why the patch didn't go through
+    let diagnostics = patch_rejection_diagnostics(path, text_before, &Rope::from_str(text_after));
+    publish_diagnostics_with_extra(gcx, path, diagnostics).await;
+}
+
 pub async fn postprocess_diff_chunks_from_message(
     gcx: Arc<ARwLock<GlobalContext>>,
     chunks: &mut Vec<DiffChunk>,
@@ -103,12 +111,13 @@ pub async fn postprocess_diff_chunks_from_message(
                 "AST assessment has failed: the generated diff had introduced errors into the file `{:?}`: {} before errs < {} after errs",
                 path, before_error_symbols.len(), after_error_symbols.len()
             );
+            publish_patch_rejection(gcx.clone(), &path, &text_before, &text_after).await;
             return Err(message);
         }
 
         let before_lint_errors = lint_and_get_error_messages(
             &path,
-            &Rope::from_str(&text_after),
+            &text_before,
         );
         let after_lint_errors = lint_and_get_error_messages(
             &path,
@@ -119,6 +128,7 @@ pub async fn postprocess_diff_chunks_from_message(
                 "Linting has failed: the generated diff had introduced lint issues into the file `{:?}`: {} before errs < {} after errs",
                 path, before_lint_errors.len(), after_lint_errors.len()
             );
+            publish_patch_rejection(gcx.clone(), &path, &text_before, &text_after).await;
             return Err(message);
         }
     }
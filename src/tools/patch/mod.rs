@@ -1,7 +1,7 @@
 mod diff_formats;
 mod chat_interaction;
 pub mod tool_patch;
-mod ast_interaction;
+pub mod ast_interaction;
 mod unified_diff_format;
 pub mod tickets;
 mod ticket_to_chunks;
@@ -14,7 +14,7 @@ use crate::tools::patch::unified_diff_format::UnifiedDiffFormat;
 use crate::tools::patch::ticket_to_chunks::{add_to_file_diff, full_rewrite_diff, new_file_diff, retain_non_applied_tickets, rewrite_symbol_diff};
 use crate::tools::tools_execute::unwrap_subchat_params;
 use crate::tools::tools_description::Tool;
-use crate::call_validation::{ChatMessage, ChatUsage, ContextEnum, DiffChunk, SubchatParameters};
+use crate::call_validation::{ChatContent, ChatMessage, ChatUsage, ContextEnum, DiffChunk, SubchatParameters};
 use crate::global_context::GlobalContext;
 
 pub const N_CHOICES: usize = 16;
@@ -275,7 +275,7 @@ impl Tool for ToolPatch {
         let mut results = vec![];
         results.push(ChatMessage {
             role: "diff".to_string(),
-            content: json!(diff_chunks).to_string(),
+            content: ChatContent::SimpleText(json!(diff_chunks).to_string()),
             tool_calls: None,
             tool_call_id: tool_call_id.clone(),
             usage: Some(usage),
@@ -8,7 +8,7 @@ use async_trait::async_trait;
 
 use crate::at_commands::at_commands::AtCommandsContext;
 use crate::tools::tools_description::Tool;
-use crate::call_validation::{ChatMessage, ContextEnum};
+use crate::call_validation::{ChatContent, ChatMessage, ContextEnum};
 use crate::vecdb::vdb_highlev::memories_search;
 // use crate::vecdb::vdb_highlev::ongoing_find;
 
@@ -71,7 +71,7 @@ impl Tool for ToolGetKnowledge {
         let mut results = vec![];
         results.push(ContextEnum::ChatMessage(ChatMessage {
             role: "tool".to_string(),
-            content: memories_str,
+            content: ChatContent::SimpleText(memories_str),
             tool_calls: None,
             tool_call_id: tool_call_id.clone(),
             ..Default::default()
@@ -12,7 +12,7 @@ use crate::at_commands::at_file::{file_repair_candidates, return_one_candidate_o
 use crate::at_commands::at_search::execute_at_search;
 use crate::files_correction::{correct_to_nearest_dir_path, get_project_dirs};
 use crate::tools::tools_description::Tool;
-use crate::call_validation::{ChatMessage, ContextEnum, ContextFile};
+use crate::call_validation::{ChatContent, ChatMessage, ContextEnum, ContextFile};
 
 
 pub struct ToolSearch;
@@ -32,7 +32,7 @@ async fn execute_att_search(
     // maybe use this:
     // let dir_candidates = correct_to_nearest_dir_path(gcx.clone(), &path, false, 10).await;
 
-    let filter = if scope.ends_with('/') {
+    let scope_mb = if scope.ends_with('/') {
         let dir = return_one_candidate_or_a_good_error(
             gcx.clone(),
             scope,
@@ -40,7 +40,7 @@ async fn execute_att_search(
             &get_project_dirs(gcx.clone()).await,
             true
         ).await?;
-        format!("(scope LIKE '{}%')", dir)
+        if dir.ends_with(std::path::MAIN_SEPARATOR) { dir } else { format!("{}{}", dir, std::path::MAIN_SEPARATOR) }
     } else {
         let file_path = return_one_candidate_or_a_good_error(
             gcx.clone(),
@@ -49,11 +49,11 @@ async fn execute_att_search(
             &get_project_dirs(gcx.clone()).await,
             false
         ).await?;
-        format!("(scope = \"{}\")", file_path)
+        file_path
     };
 
-    info!("att-search: filter: {:?}", filter);
-    Ok(execute_at_search(ccx.clone(), &query, Some(filter)).await?)
+    info!("att-search: scope: {:?}", scope_mb);
+    execute_at_search(ccx.clone(), query, Some(scope_mb)).await
 }
 
 #[async_trait]
@@ -93,7 +93,11 @@ impl Tool for ToolSearch {
                 content.push_str(&format!("{}:\n", rec.file_name.clone()));
                 let file_recs = file_results_to_reqs.get(&rec.file_name).unwrap();
                 for file_req in file_recs.iter().sorted_by(|rec1, rec2| rec2.usefulness.total_cmp(&rec1.usefulness)) {
-                    content.push_str(&format!("    lines {}-{} score {:.1}%\n", file_req.line1, file_req.line2, file_req.usefulness));
+                    content.push_str(&format!("    lines {}-{} score {:.1}%", file_req.line1, file_req.line2, file_req.usefulness));
+                    if let Some(symbol_path) = file_req.symbols.first() {
+                        content.push_str(&format!(" in {}", symbol_path));
+                    }
+                    content.push('\n');
                 }
                 used_files.insert(rec.file_name.clone());
             }
@@ -102,7 +106,7 @@ impl Tool for ToolSearch {
         let mut results = vec_context_file_to_context_tools(vector_of_context_file.clone());
         results.push(ContextEnum::ChatMessage(ChatMessage {
             role: "tool".to_string(),
-            content,
+            content: ChatContent::SimpleText(content),
             tool_calls: None,
             tool_call_id: tool_call_id.clone(),
             ..Default::default()
@@ -7,7 +7,7 @@ use tokio::sync::Mutex as AMutex;
 use crate::at_commands::at_commands::AtCommandsContext;
 use crate::at_commands::at_web::execute_at_web;
 use crate::tools::tools_description::Tool;
-use crate::call_validation::{ChatMessage, ContextEnum};
+use crate::call_validation::{ChatContent, ChatMessage, ContextEnum};
 
 
 pub struct ToolWeb;
@@ -31,7 +31,7 @@ impl Tool for ToolWeb {
         let mut results = vec![];
         results.push(ContextEnum::ChatMessage(ChatMessage {
             role: "tool".to_string(),
-            content: text,
+            content: ChatContent::SimpleText(text),
             tool_calls: None,
             tool_call_id: tool_call_id.clone(),
             ..Default::default()
@@ -13,6 +13,10 @@ use crate::call_validation::{ChatUsage, ContextEnum};
 use crate::global_context::GlobalContext;
 use crate::integrations::integr_github::ToolGithub;
 use crate::integrations::integr_pdb::ToolPdb;
+use crate::integrations::integr_process::ToolProcess;
+use crate::integrations::integr_test::ToolTest;
+use crate::integrations::integr_mcp::mcp_tools_if_configured;
+use crate::integrations::integr_cmdline::cmdline_tools_if_configured;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct CommandsRequireConfimationConfig {
@@ -38,6 +42,8 @@ pub trait Tool: Send + Sync {
 
     fn tool_depends_on(&self) -> Vec<String> { vec![] }   // "ast", "vecdb"
 
+    fn tool_description(&self) -> Option<ToolDict> { None }   // for tools not in BUILT_IN_TOOLS, like the ones from MCP servers
+
     fn usage(&mut self) -> &mut Option<ChatUsage> {
         static mut DEFAULT_USAGE: Option<ChatUsage> = None;
         #[allow(static_mut_refs)]
@@ -87,6 +93,10 @@ pub async fn tools_merged_and_filtered(gcx: Arc<ARwLock<GlobalContext>>) -> Inde
         ("locate".to_string(), Arc::new(AMutex::new(Box::new(crate::tools::tool_locate_search::ToolLocateSearch{}) as Box<dyn Tool + Send>))),
     ]);
 
+    for (mcp_tool_name, mcp_tool) in mcp_tools_if_configured(gcx.clone(), &integrations_value).await {
+        tools_all.insert(mcp_tool_name, Arc::new(AMutex::new(Box::new(mcp_tool) as Box<dyn Tool + Send>)));
+    }
+
     if allow_experimental {
         // ("save_knowledge".to_string(), Arc::new(AMutex::new(Box::new(crate::tools::att_knowledge::ToolSaveKnowledge{}) as Box<dyn Tool + Send>))),
         // ("memorize_if_user_asks".to_string(), Arc::new(AMutex::new(Box::new(crate::tools::att_note_to_self::AtNoteToSelf{}) as Box<dyn AtTool + Send>))),
@@ -96,7 +106,21 @@ pub async fn tools_merged_and_filtered(gcx: Arc<ARwLock<GlobalContext>>) -> Inde
         if let Some(pdb_tool) = ToolPdb::new_if_configured(&integrations_value) {
             tools_all.insert("pdb".to_string(), Arc::new(AMutex::new(Box::new(pdb_tool) as Box<dyn Tool + Send>)));
         }
+        if let Some(process_tool) = ToolProcess::new_if_configured(&integrations_value) {
+            tools_all.insert("process".to_string(), Arc::new(AMutex::new(Box::new(process_tool) as Box<dyn Tool + Send>)));
+        }
+        let workspace_folder = gcx.read().await.documents_state.workspace_folders.lock().unwrap().first().cloned();
+        if let Some(test_tool) = ToolTest::new_if_configured(&integrations_value, workspace_folder) {
+            tools_all.insert("test".to_string(), Arc::new(AMutex::new(Box::new(test_tool) as Box<dyn Tool + Send>)));
+        }
         tools_all.insert("knowledge".to_string(), Arc::new(AMutex::new(Box::new(crate::tools::tool_knowledge::ToolGetKnowledge{}) as Box<dyn Tool + Send>)));
+        for (cmdline_tool_name, cmdline_tool) in cmdline_tools_if_configured(&integrations_value) {
+            if tools_all.contains_key(&cmdline_tool_name) {
+                warn!("cmdline_tools: {} is already taken by another tool, skipped", cmdline_tool_name);
+                continue;
+            }
+            tools_all.insert(cmdline_tool_name, Arc::new(AMutex::new(Box::new(cmdline_tool) as Box<dyn Tool + Send>)));
+        }
     }
 
     let mut filtered_tools = IndexMap::new();
@@ -127,11 +151,11 @@ pub async fn commands_require_confirmation_rules_from_integrations_yaml(gcx: Arc
 const BUILT_IN_TOOLS: &str = r####"
 tools:
   - name: "search"
-    description: "Find similar pieces of code or text using vector database"
+    description: "Find similar pieces of code or text using vector database, exact identifiers and messages are matched too"
     parameters:
       - name: "query"
         type: "string"
-        description: "Single line, paragraph or code sample to search for similar content."
+        description: "Single line, paragraph or code sample to search for similar content, or an exact identifier, error or log message."
       - name: "scope"
         type: "string"
         description: "'workspace' to search all files in workspace, 'dir/subdir/' to search in files within a directory, 'dir/file.ext' to search in a single file."
@@ -268,6 +292,49 @@ tools:
         description: "Examples:\npython -m pdb script.py\nbreak 10\ncontinue\nprint(variable_name)\nlist\nquit"
     parameters_required:
       - "command"
+
+  - name: "process"
+    agentic: true
+    experimental: true
+    description: "Start long-running processes like dev servers, watchers or test suites, they keep running between calls. Read what they printed since the last call, write to stdin, wait for a line to appear, kill."
+    parameters:
+      - name: "action"
+        type: "string"
+        description: "One of: start, read, write, wait, kill, list"
+      - name: "name"
+        type: "string"
+        description: "Your name for the process, to have several at once, for example: backend, frontend"
+      - name: "command"
+        type: "string"
+        description: "For start, the command to run, for example: npm run dev"
+      - name: "workdir"
+        type: "string"
+        description: "For start, optional, the first workspace folder if not given"
+      - name: "input"
+        type: "string"
+        description: "For write, a line to send to stdin"
+      - name: "pattern"
+        type: "string"
+        description: "For wait or start, a regex to wait for in the output, for example: Listening on port \\d+"
+      - name: "timeout"
+        type: "string"
+        description: "Seconds to wait for the pattern, 30 by default, 300 at most"
+    parameters_required:
+      - "action"
+      - "name"
+
+  - name: "test"
+    agentic: true
+    experimental: true
+    description: "Run tests and get the list of failures with the files and lines where they failed. Call without arguments to run everything."
+    parameters:
+      - name: "path"
+        type: "string"
+        description: "Optional, a test file or directory to run only the tests there, for example: tests/test_api.py"
+      - name: "test_id"
+        type: "string"
+        description: "Optional, one test: tests/test_api.py::test_login for pytest, a name filter for cargo, a test name for jest or go"
+    parameters_required: []
 "####;
 
 #[allow(dead_code)]
@@ -344,6 +411,8 @@ pub struct ToolDict {
     pub description: String,
     pub parameters: Vec<AtParamDict>,
     pub parameters_required: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input_schema: Option<Value>,  // JSON schema used as is instead of parameters, MCP servers have them
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
@@ -389,24 +458,38 @@ pub fn make_openai_tool_value(
 
 impl ToolDict {
     pub fn into_openai_style(self) -> Value {
-        make_openai_tool_value(
+        let mut tool_value = make_openai_tool_value(
             self.name,
             self.agentic,
             self.description,
             self.parameters_required,
             self.parameters,
-        )
+        );
+        if let Some(input_schema) = self.input_schema {
+            tool_value["function"]["parameters"] = input_schema;
+        }
+        tool_value
     }
 }
 
-pub fn tool_description_list_from_yaml(
+pub async fn tool_description_list_from_yaml(
+    tools: IndexMap<String, Arc<AMutex<Box<dyn Tool + Send>>>>,
     turned_on: &Vec<String>,
     allow_experimental: bool,
 ) -> Result<Vec<ToolDict>, String> {
     let at_dict: ToolDictDeserialize = serde_yaml::from_str(BUILT_IN_TOOLS)
         .map_err(|e|format!("Failed to parse BUILT_IN_TOOLS: {}", e))?;
-    Ok(at_dict.tools.iter()
+    let mut tool_desclist = at_dict.tools.iter()
         .filter(|x| turned_on.contains(&x.name) && (allow_experimental || !x.experimental))
         .cloned()
-        .collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    for (tool_name, tool_arc) in tools {
+        if !turned_on.contains(&tool_name) {
+            continue;
+        }
+        if let Some(tool_dict) = tool_arc.lock().await.tool_description() {
+            tool_desclist.push(tool_dict);
+        }
+    }
+    Ok(tool_desclist)
 }
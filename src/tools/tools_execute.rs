@@ -8,9 +8,11 @@ use tracing::{info, warn};
 
 use crate::at_commands::at_commands::AtCommandsContext;
 use crate::at_commands::execute_at::MIN_RAG_CONTEXT_LIMIT;
-use crate::call_validation::{ChatMessage, ContextEnum, ContextFile, SubchatParameters};
+use crate::call_validation::{ChatContent, ChatMessage, ContextEnum, ContextFile, SubchatParameters};
 use crate::postprocessing::pp_context_files::postprocess_context_files;
 use crate::postprocessing::pp_plain_text::postprocess_plain_text;
+use crate::privacy_secrets::{log_redactions, merge_redactions, redact_context_files, redact_secrets};
+use crate::audit_log::{audit_files_from_context_files, merge_audit_files};
 use crate::scratchpads::scratchpad_utils::{HasRagResults, max_tokens_for_rag_chat};
 use crate::tools::tools_description::commands_require_confirmation_rules_from_integrations_yaml;
 use crate::yaml_configs::customization_loader::load_customization;
@@ -78,7 +80,7 @@ pub async fn run_tools(
                 let tool_failed_message = tool_answer(
                     format!("tool use: function {:?} not found", &t_call.function.name), t_call.id.to_string()
                 );
-                warn!("{}", tool_failed_message.content);
+                warn!("{}", tool_failed_message.content.content_text_only());
                 generated_tool.push(tool_failed_message.clone());
                 continue;
             }
@@ -157,7 +159,13 @@ pub async fn run_tools(
         let mut have_answer = false;
         for msg in tool_execute_results {
             match msg {
-                ContextEnum::ChatMessage(m) => {
+                ContextEnum::ChatMessage(mut m) => {
+                    if let ChatContent::SimpleText(text) = &m.content {
+                        let mut redacted = vec![];
+                        m.content = ChatContent::SimpleText(redact_secrets(text, &format!("tool {}", t_call.function.name), &mut redacted));
+                        log_redactions(&redacted);
+                        merge_redactions(&mut ccx.lock().await.secrets_redacted, redacted);
+                    }
                     if (m.role == "tool" || m.role == "diff") && m.tool_call_id == t_call.id {
                         generated_tool.push(m);
                         have_answer = true;
@@ -259,7 +267,7 @@ async fn pp_run_tools(
             pp_settings.take_floor = 50.0;
         }
 
-        let context_file_vec = postprocess_context_files(
+        let mut context_file_vec = postprocess_context_files(
             gcx.clone(),
             context_files_for_pp,
             tokenizer.clone(),
@@ -267,6 +275,12 @@ async fn pp_run_tools(
             false,
             &pp_settings,
         ).await;
+        let redacted = redact_context_files(&mut context_file_vec);
+        {
+            let mut ccx_locked = ccx.lock().await;
+            merge_redactions(&mut ccx_locked.secrets_redacted, redacted);
+            merge_audit_files(&mut ccx_locked.audit_files, audit_files_from_context_files(&context_file_vec));
+        }
 
         if !context_file_vec.is_empty() {
             let json_vec = context_file_vec.iter().map(|p| json!(p)).collect::<Vec<_>>();
@@ -313,7 +327,7 @@ async fn pp_run_tools(
 fn tool_answer(content: String, tool_call_id: String) -> ChatMessage {
     ChatMessage {
         role: "tool".to_string(),
-        content,
+        content: ChatContent::SimpleText(content),
         tool_calls: None,
         tool_call_id,
         ..Default::default()
@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
 use std::sync::Arc;
 use std::sync::Mutex as StdMutex;
@@ -6,37 +6,43 @@ use indexmap::IndexMap;
 use tokio::sync::{Mutex as AMutex, RwLock as ARwLock};
 use tokio::task::JoinHandle;
 use async_trait::async_trait;
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
 use crate::background_tasks::BackgroundTasksHolder;
-use crate::caps::get_custom_embedding_api_key;
+use crate::caps::{get_custom_embedding_api_key, get_vecdb_remote_api_key};
 use crate::fetch_embedding;
 use crate::files_in_workspace::Document;
 use crate::global_context::{CommandLine, GlobalContext};
 use crate::knowledge::{lance_search, MemoriesDatabase};
 use crate::vecdb::vdb_cache::VecDBCache;
-use crate::vecdb::vdb_lance::VecDBHandler;
-use crate::vecdb::vdb_structs::{MemoRecord, MemoSearchResult, OngoingWork, SearchResult, VecDbStatus, VecdbConstants, VecdbSearch};
+use crate::vecdb::vdb_lance::{workspace_hash, VecDBHandler};
+use crate::vecdb::vdb_remote::VecDbRemote;
+use crate::vecdb::vdb_structs::{MemoRecord, MemoSearchResult, OngoingWork, SearchResult, VecDbStatus, VecdbConstants, VecdbRecord, VecdbSearch};
 use crate::vecdb::vdb_thread::{vectorizer_enqueue_dirty_memory, vectorizer_enqueue_files, FileVectorizerService};
 
 const VECDB_DISTANCE_REJECT_COMPLETELY: f32 = 0.25;  // XXX: it's actually a constant per embedding model, not universal for all models
+const VECDB_RRF_K: f32 = 60.0;  // the usual constant for reciprocal rank fusion, keeps the top ranks from dominating
+const VECDB_REMOTE_NO_LOCAL_DATA: &str = "vecdb searches in a remote index, there is no local index and no memories";
 
 
 pub struct VecDb {
-    pub memdb: Arc<AMutex<MemoriesDatabase>>,
+    // None in the remote mode: no local embeddings, index, vectorizer or memories, search goes to the index server
+    pub memdb: Option<Arc<AMutex<MemoriesDatabase>>>,
     vecdb_emb_client: Arc<AMutex<reqwest::Client>>,
-    vecdb_handler: Arc<AMutex<VecDBHandler>>,
-    pub vectorizer_service: Arc<AMutex<FileVectorizerService>>,
+    vecdb_handler: Option<Arc<AMutex<VecDBHandler>>>,
+    pub vectorizer_service: Option<Arc<AMutex<FileVectorizerService>>>,
     cmdline: CommandLine,  // TODO: take from command line what's needed, don't store a copy
     constants: VecdbConstants,
     pub mem_ongoing: Arc<StdMutex<HashMap<String, OngoingWork>>>,
+    remote: Option<VecDbRemote>,
+    workspace_hash: String,  // the index on disk belongs to these workspace folders
 }
 
 async fn vecdb_test_request(
     vecdb: &VecDb,
     api_key: &String,
 ) -> Result<(), String> {
-    let search_result = vecdb.vecdb_search("test query".to_string(), 3, None, api_key).await;
+    let search_result = vecdb.vecdb_search("test query".to_string(), 3, None, 0.0, api_key).await;
     match search_result {
         Ok(_) => {
             Ok(())
@@ -69,11 +75,22 @@ async fn _create_vecdb(
         "" => cache_dir,
         path => PathBuf::from(path),
     };
+    let workspace_folders = gcx.read().await.documents_state.workspace_folders.clone();
+    let workspace_hash = workspace_hash(&workspace_folders.lock().unwrap());
+    let remote = if constants.remote_endpoint.is_empty() {
+        None
+    } else {
+        let remote_api_key = get_vecdb_remote_api_key(gcx.clone()).await.map_err(|e| e.message)?;
+        info!("vecdb: search goes to {}, local files will not be vectorized", constants.remote_endpoint);
+        Some(VecDbRemote::new(&constants.remote_endpoint, &remote_api_key, &constants.remote_root, workspace_folders))
+    };
     let vec_db_mb = match VecDb::init(
         &base_dir,
         cmdline.clone(),
         constants,
-        &api_key
+        &api_key,
+        remote,
+        &workspace_hash,
     ).await {
         Ok(res) => Some(res),
         Err(err) => {
@@ -91,6 +108,10 @@ async fn _create_vecdb(
 
     match vecdb_test_request(&vec_db, &api_key).await {
         Ok(_) => {}
+        Err(e) if vec_db.remote.is_some() => {
+            // the index server might be down for a minute, searches retry on their own
+            warn!("vecdb: remote index doesn't answer, continue anyway: {}", e);
+        }
         Err(s) => { return Err(s); }
     }
     info!("vecdb: test request complete");
@@ -125,7 +146,7 @@ async fn do_i_need_to_reload_vecdb(
         }
     };
 
-    let vecdb_max_files = gcx.read().await.cmdline.vecdb_max_files;
+    let cmdline = gcx.read().await.cmdline.clone();
     let mut consts = {
         let caps_locked = caps.read().unwrap();
         let mut b = caps_locked.embedding_batch;
@@ -145,10 +166,18 @@ async fn do_i_need_to_reload_vecdb(
             endpoint_embeddings_template: caps_locked.endpoint_embeddings_template.clone(),
             endpoint_embeddings_style: caps_locked.endpoint_embeddings_style.clone(),
             splitter_window_size: caps_locked.embedding_n_ctx / 2,
-            vecdb_max_files: vecdb_max_files,
+            vecdb_max_files: cmdline.vecdb_max_files,
+            // command line wins over caps, so a laptop can opt out of the team index or into it
+            remote_endpoint: if cmdline.vecdb_remote.is_empty() { caps_locked.vecdb_remote_endpoint.clone() } else { cmdline.vecdb_remote.clone() },
+            remote_root: if cmdline.vecdb_remote_root.is_empty() { caps_locked.vecdb_remote_root.clone() } else { cmdline.vecdb_remote_root.clone() },
         }
     };
 
+    let current_workspace_hash = {
+        let workspace_folders = gcx.read().await.documents_state.workspace_folders.clone();
+        let workspace_folders_locked = workspace_folders.lock().unwrap();
+        workspace_hash(&workspace_folders_locked)
+    };
     let vec_db = gcx.write().await.vec_db.clone();
     match *vec_db.lock().await {
         None => {}
@@ -159,16 +188,24 @@ async fn do_i_need_to_reload_vecdb(
                 db.constants.endpoint_embeddings_style == consts.endpoint_embeddings_style &&
                 db.constants.splitter_window_size == consts.splitter_window_size &&
                 db.constants.embedding_batch == consts.embedding_batch &&
-                db.constants.embedding_size == consts.embedding_size
+                db.constants.embedding_size == consts.embedding_size &&
+                db.constants.remote_endpoint == consts.remote_endpoint &&
+                db.constants.remote_root == consts.remote_root &&
+                (db.remote.is_some() || db.workspace_hash == current_workspace_hash)
             {
                 return (false, None);
             }
         }
     }
 
+    if !consts.remote_endpoint.is_empty() {
+        // the index server has its own embedding model, nothing to load here
+        return (true, Some(consts));
+    }
+
     if consts.embedding_model.is_empty() || consts.endpoint_embeddings_template.is_empty() {
         error!("command line says to launch vecdb, but this will not happen: embedding_model.is_empty() || endpoint_embeddings_template.is_empty()");
-        return (true, None);
+        return (false, None);
     }
 
     let tokenizer_maybe = crate::cached_tokenizers::cached_tokenizer(
@@ -195,6 +232,8 @@ pub async fn vecdb_background_reload(
         let (need_reload, consts) = do_i_need_to_reload_vecdb(gcx.clone()).await;
         if need_reload {
             background_tasks.abort().await;
+            // the old handler holds the index lock, the new one might need the same directory
+            *gcx.read().await.vec_db.lock().await = None;
         }
         if need_reload && consts.is_some() {
             background_tasks = BackgroundTasksHolder::new(vec![]);
@@ -222,29 +261,38 @@ impl VecDb {
         cache_dir: &PathBuf,
         cmdline: CommandLine,
         constants: VecdbConstants,
-        api_key: &String
+        api_key: &str,
+        remote: Option<VecDbRemote>,
+        workspace_hash: &str,
     ) -> Result<VecDb, String> {
-        let handler = VecDBHandler::init(constants.embedding_size).await?;
-        let cache = VecDBCache::init(cache_dir, &constants.embedding_model, constants.embedding_size).await?;
-        let vecdb_handler = Arc::new(AMutex::new(handler));
-        let vecdb_cache = Arc::new(AMutex::new(cache));
-        let memdb = Arc::new(AMutex::new(MemoriesDatabase::init(cache_dir, &constants, cmdline.reset_memory).await?));
-
-        let vectorizer_service = Arc::new(AMutex::new(FileVectorizerService::new(
-            vecdb_handler.clone(),
-            vecdb_cache.clone(),
-            constants.clone(),
-            api_key.clone(),
-            memdb.clone(),
-        ).await));
+        let (memdb, vecdb_handler, vectorizer_service) = if remote.is_some() {
+            (None, None, None)
+        } else {
+            let handler = VecDBHandler::init(cache_dir, &constants.embedding_model, constants.embedding_size, &cmdline.get_prefix(), workspace_hash).await?;
+            let cache = VecDBCache::init(cache_dir, &constants.embedding_model, constants.embedding_size).await?;
+            let vecdb_handler = Arc::new(AMutex::new(handler));
+            let vecdb_cache = Arc::new(AMutex::new(cache));
+            let memdb = Arc::new(AMutex::new(MemoriesDatabase::init(cache_dir, &constants, cmdline.reset_memory).await?));
+
+            let vectorizer_service = Arc::new(AMutex::new(FileVectorizerService::new(
+                vecdb_handler.clone(),
+                vecdb_cache.clone(),
+                constants.clone(),
+                api_key.to_string(),
+                memdb.clone(),
+            ).await));
+            (Some(memdb), Some(vecdb_handler), Some(vectorizer_service))
+        };
         Ok(VecDb {
-            memdb: memdb.clone(),
+            memdb,
             vecdb_emb_client: Arc::new(AMutex::new(reqwest::Client::new())),
             vecdb_handler,
             vectorizer_service,
             cmdline: cmdline.clone(),
             constants: constants.clone(),
             mem_ongoing: Arc::new(StdMutex::new(HashMap::<String, OngoingWork>::new())),
+            remote,
+            workspace_hash: workspace_hash.to_string(),
         })
     }
 
@@ -252,10 +300,14 @@ impl VecDb {
         &self,
         gcx: Arc<ARwLock<GlobalContext>>,
     ) -> Vec<JoinHandle<()>> {
+        let vectorizer_service = match &self.vectorizer_service {
+            Some(vectorizer_service) => vectorizer_service.clone(),
+            None => return vec![],
+        };
         info!("vecdb: start_background_tasks");
-        vectorizer_enqueue_dirty_memory(self.vectorizer_service.clone()).await;
+        vectorizer_enqueue_dirty_memory(vectorizer_service.clone()).await;
         let my_tokenizer = self.constants.tokenizer.clone().unwrap();
-        return self.vectorizer_service.lock().await.vecdb_start_background_tasks(
+        return vectorizer_service.lock().await.vecdb_start_background_tasks(
             self.vecdb_emb_client.clone(),
             gcx.clone(),
             my_tokenizer.clone(),
@@ -263,11 +315,35 @@ impl VecDb {
     }
 
     pub async fn vectorizer_enqueue_files(&self, documents: &Vec<Document>, process_immediately: bool) {
-        vectorizer_enqueue_files(self.vectorizer_service.clone(), documents, process_immediately).await;
+        if let Some(vectorizer_service) = &self.vectorizer_service {
+            vectorizer_enqueue_files(vectorizer_service.clone(), documents, process_immediately).await;
+        }
+    }
+
+    pub async fn remove_files_not_in_workspace(&self, workspace_documents: &[Document]) {
+        // the index is on disk, it remembers files deleted while we were not running
+        let vecdb_handler = match &self.vecdb_handler {
+            Some(vecdb_handler) => vecdb_handler,
+            None => return,  // the index server has them
+        };
+        let workspace_scopes: HashSet<String> = workspace_documents.iter()
+            .map(|x| x.doc_path.to_string_lossy().to_string())
+            .collect();
+        let mut handler_locked = vecdb_handler.lock().await;
+        let gone: Vec<String> = handler_locked.indexed_files().into_iter()
+            .filter(|x| !workspace_scopes.contains(x))
+            .collect();
+        if !gone.is_empty() {
+            info!("vecdb: removing {} files that are not in the workspace anymore", gone.len());
+            handler_locked.vecdb_records_remove(gone).await;
+        }
     }
 
     pub async fn remove_file(&self, file_path: &PathBuf) {
-        let mut handler_locked = self.vecdb_handler.lock().await;
+        let mut handler_locked = match &self.vecdb_handler {
+            Some(vecdb_handler) => vecdb_handler.lock().await,
+            None => return,
+        };
         let file_path_str = file_path.to_string_lossy().to_string();
         handler_locked.vecdb_records_remove(vec![file_path_str]).await;
     }
@@ -283,7 +359,10 @@ pub async fn memories_add(
     let (memdb, vectorizer_service) = {
         let vec_db_guard = vec_db.lock().await;
         let vec_db = vec_db_guard.as_ref().ok_or("VecDb is not initialized")?;
-        (vec_db.memdb.clone(), vec_db.vectorizer_service.clone())
+        (
+            vec_db.memdb.clone().ok_or(VECDB_REMOTE_NO_LOCAL_DATA)?,
+            vec_db.vectorizer_service.clone().ok_or(VECDB_REMOTE_NO_LOCAL_DATA)?,
+        )
     };
 
     let memid = {
@@ -302,7 +381,10 @@ pub async fn memories_block_until_vectorized(
     let vectorizer_service = {
         let vec_db_guard = vec_db.lock().await;
         let vec_db = vec_db_guard.as_ref().ok_or("VecDb is not initialized")?;
-        vec_db.vectorizer_service.clone()
+        match &vec_db.vectorizer_service {
+            Some(vectorizer_service) => vectorizer_service.clone(),
+            None => return Ok(()),  // nothing is vectorized locally
+        }
     };
     let (vstatus, vstatus_notify) = {
         let service = vectorizer_service.lock().await;
@@ -325,7 +407,22 @@ pub async fn get_status(vec_db: Arc<AMutex<Option<VecDb>>>) -> Result<Option<Vec
     let vectorizer_service = {
         let vec_db_guard = vec_db.lock().await;
         let vec_db = vec_db_guard.as_ref().ok_or("VecDb is not initialized")?;
-        vec_db.vectorizer_service.clone()
+        match &vec_db.vectorizer_service {
+            Some(vectorizer_service) => vectorizer_service.clone(),
+            None => return Ok(Some(VecDbStatus {
+                files_unprocessed: 0,
+                files_total: 0,
+                requests_made_since_start: 0,
+                vectors_made_since_start: 0,
+                db_size: 0,
+                db_cache_size: 0,
+                state: "done".to_string(),
+                queue_additions: false,
+                vecdb_max_files_hit: false,
+                index_state: "remote".to_string(),
+                index_unindexed_rows: 0,
+            })),
+        }
     };
     let (vstatus, vecdb_handler, vecdb_cache) = {
         let vectorizer_locked = vectorizer_service.lock().await;
@@ -336,10 +433,22 @@ pub async fn get_status(vec_db: Arc<AMutex<Option<VecDb>>>) -> Result<Option<Vec
         )
     };
     let mut vstatus_copy = vstatus.lock().await.clone();
-    vstatus_copy.db_size = match vecdb_handler.lock().await.size().await {
-        Ok(res) => res,
-        Err(err) => return Err(err)
-    };
+    {
+        let mut handler_locked = vecdb_handler.lock().await;
+        vstatus_copy.db_size = handler_locked.size().await?;
+        if vstatus_copy.index_state != "building" {
+            match handler_locked.ann_index_unindexed_rows_cached().await? {
+                Some(unindexed) => {
+                    vstatus_copy.index_state = "ready".to_string();
+                    vstatus_copy.index_unindexed_rows = unindexed;
+                }
+                None => {
+                    vstatus_copy.index_state = "none".to_string();
+                    vstatus_copy.index_unindexed_rows = vstatus_copy.db_size;
+                }
+            }
+        }
+    }
     vstatus_copy.db_cache_size = match vecdb_cache.lock().await.size().await {
         Ok(res) => res,
         Err(err) => return Err(err.to_string())
@@ -356,7 +465,7 @@ pub async fn memories_select_all(
     let memdb = {
         let vec_db_guard = vec_db.lock().await;
         let vec_db = vec_db_guard.as_ref().ok_or("VecDb is not initialized")?;
-        vec_db.memdb.clone()
+        vec_db.memdb.clone().ok_or(VECDB_REMOTE_NO_LOCAL_DATA)?
     };
 
     let memdb_locked = memdb.lock().await;
@@ -371,7 +480,7 @@ pub async fn memories_erase(
     let memdb = {
         let vec_db_guard = vec_db.lock().await;
         let vec_db = vec_db_guard.as_ref().ok_or("VecDb is not initialized")?;
-        vec_db.memdb.clone()
+        vec_db.memdb.clone().ok_or(VECDB_REMOTE_NO_LOCAL_DATA)?
     };
 
     let memdb_locked = memdb.lock().await;
@@ -388,7 +497,7 @@ pub async fn memories_update(
     let memdb = {
         let vec_db_guard = vec_db.lock().await;
         let vec_db = vec_db_guard.as_ref().ok_or("VecDb is not initialized")?;
-        vec_db.memdb.clone()
+        vec_db.memdb.clone().ok_or(VECDB_REMOTE_NO_LOCAL_DATA)?
     };
 
     let memdb_locked = memdb.lock().await;
@@ -411,7 +520,7 @@ pub async fn memories_search(
         let vec_db_guard = vec_db.lock().await;
         let vec_db = vec_db_guard.as_ref().ok_or("VecDb is not initialized")?;
         (
-            vec_db.memdb.clone(),
+            vec_db.memdb.clone().ok_or(VECDB_REMOTE_NO_LOCAL_DATA)?,
             vec_db.vecdb_emb_client.clone(),
             vec_db.constants.clone(),
             vec_db.cmdline.clone(),
@@ -426,6 +535,8 @@ pub async fn memories_search(
         vec![query.clone()],
         &cmdline.api_key,
         5,
+        &[],
+        None,
     ).await?;
     if embedding.is_empty() {
         return Err("memdb_search: empty embedding".to_string());
@@ -534,16 +645,56 @@ pub async fn ongoing_dump(
     Ok(output)
 }
 
+fn reciprocal_rank_fusion(
+    vector_results: Vec<VecdbRecord>,
+    lexical_results: Vec<VecdbRecord>,
+    lexical_weight: f32,
+    top_n: usize,
+) -> Vec<VecdbRecord> {
+    // only the ranks matter, so cosine distances and bm25 scores don't need to be on the same scale
+    let mut fused: IndexMap<(PathBuf, u64, u64), (VecdbRecord, f32)> = IndexMap::new();
+    for (results, weight) in [(vector_results, 1.0 - lexical_weight), (lexical_results, lexical_weight)] {
+        for (rank, rec) in results.into_iter().enumerate() {
+            let score = weight / (VECDB_RRF_K + rank as f32 + 1.0);
+            fused.entry((rec.file_path.clone(), rec.start_line, rec.end_line))
+                .or_insert((rec, 0.0)).1 += score;
+        }
+    }
+    let mut fused: Vec<(VecdbRecord, f32)> = fused.into_values().collect();
+    fused.sort_by(|a, b| b.1.total_cmp(&a.1));
+    fused.truncate(top_n);
+    let score0 = fused.first().map(|x| x.1).unwrap_or(0.0);
+    fused.into_iter().map(|(mut rec, score)| {
+        rec.usefulness = 100.0 - 75.0 * (1.0 - score / score0).clamp(0.0, 1.0);
+        rec
+    }).collect()
+}
+
 #[async_trait]
 impl VecdbSearch for VecDb {
     async fn vecdb_search(
         &self,
         query: String,
         top_n: usize,
-        vecdb_scope_filter_mb: Option<String>,
+        scope_mb: Option<String>,
+        lexical_weight: f32,
         api_key: &String,
     ) -> Result<SearchResult, String> {
         // TODO: move out of struct, replace self with Arc
+        if let Some(remote) = &self.remote {
+            return remote.vecdb_search(query, top_n, scope_mb, lexical_weight, api_key).await;
+        }
+        let vecdb_handler = self.vecdb_handler.as_ref().ok_or(VECDB_REMOTE_NO_LOCAL_DATA)?;
+        let lexical_weight = lexical_weight.clamp(0.0, 1.0);
+        if lexical_weight >= 1.0 {
+            let t1 = std::time::Instant::now();
+            let results = vecdb_handler.lock().await.lexical_search(&query, top_n, scope_mb).await?;
+            info!("lexical search {:?} {:.3}s", query, t1.elapsed().as_secs_f64());
+            return Ok(SearchResult {
+                query_text: query,
+                results: reciprocal_rank_fusion(vec![], results, lexical_weight, top_n),
+            });
+        }
         let t0 = std::time::Instant::now();
         let embedding_mb = fetch_embedding::get_embedding_with_retry(
             self.vecdb_emb_client.clone(),
@@ -553,15 +704,17 @@ impl VecdbSearch for VecDb {
             vec![query.clone()],
             api_key,
             5,
+            &[],
+            None,
         ).await;
         if embedding_mb.is_err() {
             return Err(embedding_mb.unwrap_err().to_string());
         }
         info!("search query {:?}, it took {:.3}s to vectorize the query", query, t0.elapsed().as_secs_f64());
 
-        let mut handler_locked = self.vecdb_handler.lock().await;
+        let mut handler_locked = vecdb_handler.lock().await;
         let t1 = std::time::Instant::now();
-        let mut results = match handler_locked.vecdb_search(&embedding_mb.unwrap()[0], top_n, vecdb_scope_filter_mb).await {
+        let mut results = match handler_locked.vecdb_search(&embedding_mb.unwrap()[0], top_n, scope_mb.clone()).await {
             Ok(res) => res,
             Err(err) => { return Err(err.to_string()) }
         };
@@ -577,11 +730,17 @@ impl VecdbSearch for VecDb {
             if rec.distance.abs() >= VECDB_DISTANCE_REJECT_COMPLETELY {
                 info!("distance {:.3} -> dropped {}:{}-{}", rec.distance, last_35_chars, rec.start_line, rec.end_line);
             } else {
-                info!("distance {:.3} -> useful {:.1}, found {}:{}-{}", rec.distance, rec.usefulness, last_35_chars, rec.start_line, rec.end_line);
+                info!("distance {:.3} -> useful {:.1}, found {}:{}-{} {}", rec.distance, rec.usefulness, last_35_chars, rec.start_line, rec.end_line, rec.symbol_path);
                 filtered_results.push(rec.clone());
             }
         }
         results = filtered_results;
+        if lexical_weight > 0.0 {
+            let t2 = std::time::Instant::now();
+            let lexical_results = handler_locked.lexical_search(&query, top_n, scope_mb).await?;
+            info!("lexical search {:.3}s, {} results", t2.elapsed().as_secs_f64(), lexical_results.len());
+            results = reciprocal_rank_fusion(results, lexical_results, lexical_weight, top_n);
+        }
         Ok(
             SearchResult {
                 query_text: query,
@@ -591,3 +750,67 @@ impl VecdbSearch for VecDb {
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(file_name: &str, distance: f32) -> VecdbRecord {
+        VecdbRecord {
+            vector: None,
+            file_path: PathBuf::from(file_name),
+            start_line: 0,
+            end_line: 10,
+            distance,
+            usefulness: 0.0,
+            symbol_path: String::new(),
+            window_text: String::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_remote_mode_has_no_local_index() {
+        use structopt::StructOpt;
+        let cache_dir = tempfile::tempdir().unwrap();
+        let constants = VecdbConstants {
+            embedding_model: String::new(),
+            embedding_size: 0,
+            embedding_batch: 64,
+            tokenizer: None,
+            vectorizer_n_ctx: 0,
+            endpoint_embeddings_template: String::new(),
+            endpoint_embeddings_style: String::new(),
+            splitter_window_size: 0,
+            vecdb_max_files: 0,
+            remote_endpoint: "http://127.0.0.1:1/v1/vdb-search".to_string(),
+            remote_root: String::new(),
+        };
+        let remote = VecDbRemote::new(&constants.remote_endpoint, "", "", Arc::new(StdMutex::new(vec![])));
+        let cmdline = CommandLine::from_iter(["refact-lsp"]);
+        let vec_db = VecDb::init(&cache_dir.path().to_path_buf(), cmdline, constants, "", Some(remote), "ws").await.unwrap();
+        assert!(vec_db.memdb.is_none() && vec_db.vecdb_handler.is_none() && vec_db.vectorizer_service.is_none());
+        assert!(!cache_dir.path().join("refact_vecdb_cache").exists());
+
+        let vec_db_arc = Arc::new(AMutex::new(Some(vec_db)));
+        assert_eq!(get_status(vec_db_arc.clone()).await.unwrap().unwrap().index_state, "remote");
+        assert!(memories_select_all(vec_db_arc.clone()).await.is_err());
+        memories_block_until_vectorized(vec_db_arc.clone()).await.unwrap();
+    }
+
+    #[test]
+    fn test_reciprocal_rank_fusion() {
+        let vector_results = vec![record("a.rs", 0.1), record("b.rs", 0.15), record("c.rs", 0.2)];
+        let lexical_results = vec![record("c.rs", -1.0), record("d.rs", -1.0)];
+        let names = |results: &Vec<VecdbRecord>| results.iter().map(|x| x.file_path.to_string_lossy().to_string()).collect::<Vec<_>>();
+
+        let fused = reciprocal_rank_fusion(vector_results.clone(), lexical_results.clone(), 0.5, 10);
+        assert_eq!(names(&fused), vec!["c.rs", "a.rs", "b.rs", "d.rs"]);
+        assert_eq!(fused[0].usefulness, 100.0);
+        assert_eq!(fused[0].distance, 0.2);  // the vector side keeps its distance
+
+        let fused = reciprocal_rank_fusion(vector_results.clone(), lexical_results.clone(), 0.0, 2);
+        assert_eq!(names(&fused), vec!["a.rs", "b.rs"]);
+        let fused = reciprocal_rank_fusion(vector_results, lexical_results, 1.0, 2);
+        assert_eq!(names(&fused), vec!["c.rs", "d.rs"]);
+    }
+}
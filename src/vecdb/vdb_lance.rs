@@ -1,8 +1,10 @@
 use std::any::Any;
+use std::collections::HashMap;
 use itertools::Itertools;
 use std::fmt::{Debug, Formatter};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use arrow::array::ArrayData;
 use arrow::buffer::Buffer;
 use arrow::compute::concat_batches;
@@ -12,11 +14,19 @@ use arrow_array::types::{Float32Type, UInt64Type};
 use arrow_schema::{DataType, Field, Schema, SchemaRef};
 use futures_util::TryStreamExt;
 use lance::dataset::{WriteMode, WriteParams};
-use tempfile::{tempdir, TempDir};
+use lance::dataset::optimize::CompactionOptions;
+use lance::index::vector::pq::PQBuildParams;
+use lance_index::vector::ivf::IvfBuildParams;
+use sha2::{Digest, Sha256};
+use tokio::fs;
+use tokio_rusqlite::Connection;
+use tracing::{info, warn};
 use vectordb::database::Database;
+use vectordb::index::vector::IvfPQIndexBuilder;
 use vectordb::table::Table;
 
-use crate::vecdb::vdb_structs::VecdbRecord;
+use crate::files_correction::canonical_path;
+use crate::vecdb::vdb_structs::{lexical_word, VecdbRecord};
 
 
 impl Debug for VecDBHandler {
@@ -26,11 +36,39 @@ impl Debug for VecDBHandler {
 }
 
 pub struct VecDBHandler {
-    _data_database_temp_dir: TempDir,
     data_table: Table,
     schema: SchemaRef,
-    // data_table_hashes: HashSet<String>,
     embedding_size: i32,
+    // content hashes of the files that are completely in data_table, they survive restarts together with it
+    files_database: Connection,
+    files_hashes: HashMap<String, String>,
+    // held while the handler lives, two processes writing into one lance table corrupt it
+    _index_lock: std::fs::File,
+    ann_index_state_cache: Option<(Instant, Option<usize>)>,
+}
+
+const DATA_TABLE_NAME: &str = "data";
+const INDEX_LOCK_FILE_NAME: &str = "index.lock";
+const FILES_TABLE_NAME: &str = "files";
+// window texts for the lexical search, the same windows as in the data table
+const WINDOWS_TABLE_NAME: &str = "windows";
+const WINDOWS_FTS_TABLE_NAME: &str = "windows_fts";
+
+const ANN_INDEX_NAME: &str = "vector_idx";
+// below that a brute force scan is fast and exact, no index needed
+const ANN_INDEX_MIN_ROWS: usize = 10_000;
+// rows added after the index was built are scanned brute force, rebuild when there are too many of them
+const ANN_INDEX_MAX_UNINDEXED_PERCENT: usize = 20;
+const ANN_INDEX_NPROBES: usize = 32;
+// approximate distances from PQ are too rough for ranking, re-rank top_n * refine_factor candidates exactly
+const ANN_INDEX_REFINE_FACTOR: u32 = 10;
+// get_status is polled by the UI, load_indices reads the table manifest every time
+const ANN_INDEX_STATE_CACHE_TTL: Duration = Duration::from_secs(5);
+
+fn schemas_match(a: &SchemaRef, b: &SchemaRef) -> bool {
+    // metadata doesn't survive the round trip to disk, compare names and types only
+    a.fields().len() == b.fields().len() &&
+        a.fields().iter().zip(b.fields().iter()).all(|(x, y)| x.name() == y.name() && x.data_type() == y.data_type())
 }
 
 fn cosine_similarity(vec1: &Vec<f32>, vec2: &Vec<f32>) -> f32 {
@@ -45,17 +83,39 @@ pub fn cosine_distance(vec1: &Vec<f32>, vec2: &Vec<f32>) -> f32 {
 }
 
 
+pub fn workspace_hash(workspace_folders: &[PathBuf]) -> String {
+    // the same folders opened in a different order or via a symlink is the same workspace
+    let roots: Vec<String> = workspace_folders.iter()
+        .map(|x| canonical_path(&x.to_string_lossy().to_string()).to_string_lossy().to_string())
+        .sorted()
+        .dedup()
+        .collect();
+    let mut hasher = Sha256::new();
+    hasher.update(roots.join("\n"));
+    format!("{:x}", hasher.finalize())[..16].to_string()
+}
+
 impl VecDBHandler {
-    pub async fn init(embedding_size: i32) -> Result<VecDBHandler, String> {
-        let data_database_temp_dir = match tempdir() {
-            Ok(dir) => dir,
-            Err(_) => return Err(format!("{:?}", "Error creating temp dir")),
+    pub async fn init(cache_dir: &Path, model_name: &str, embedding_size: i32, prefix: &str, workspace_hash: &str) -> Result<VecDBHandler, String> {
+        // keyed like the embeddings cache, plus the account prefix: different accounts can see different files,
+        // plus the workspace: two IDE windows on different projects get two indexes
+        let index_dir = cache_dir.join("refact_vecdb_cache")
+            .join(format!("index_{}_ws_{}_model_{}_esize_{}", prefix, workspace_hash, model_name.replace("/", "_"), embedding_size));
+        if let Err(e) = fs::create_dir_all(&index_dir).await {
+            return Err(format!("{:?}", e));
+        }
+        let index_lock = match std::fs::OpenOptions::new().create(true).write(true).truncate(false).open(index_dir.join(INDEX_LOCK_FILE_NAME)) {
+            Ok(file) => file,
+            Err(err) => return Err(format!("{:?}", err))
         };
-        let data_database_temp_dir_str = match data_database_temp_dir.path().to_str() {
+        if let Err(err) = index_lock.try_lock() {
+            return Err(format!("vecdb index {:?} is in use by another process: {}", index_dir, err));
+        }
+        let index_dir_str = match index_dir.to_str() {
             Some(path) => path,
-            None => return Err(format!("{:?}", "Temp directory is not a valid path")),
+            None => return Err(format!("{:?}", "Index directory is not a valid path")),
         };
-        let temp_database = match Database::connect(data_database_temp_dir_str).await {
+        let database = match Database::connect(index_dir_str).await {
             Ok(db) => db,
             Err(err) => return Err(format!("{:?}", err))
         };
@@ -66,20 +126,103 @@ impl VecDBHandler {
             Field::new("scope", DataType::Utf8, true),
             Field::new("start_line", DataType::UInt64, true),
             Field::new("end_line", DataType::UInt64, true),
+            Field::new("symbol_path", DataType::Utf8, true),
         ]));
 
-        let batches_iter = RecordBatchIterator::new(vec![].into_iter().map(Ok), schema.clone());
-        let data_table = match temp_database.create_table("data", batches_iter, Option::from(WriteParams::default())).await {
-            Ok(table) => table,
+        let table_names = database.table_names().await.unwrap_or_default();
+        let mut data_table_mb = None;
+        if table_names.contains(&DATA_TABLE_NAME.to_string()) {
+            match database.open_table(DATA_TABLE_NAME).await {
+                Ok(table) if schemas_match(&table.schema(), &schema) => {
+                    data_table_mb = Some(table);
+                }
+                Ok(_) => {
+                    info!("vecdb index has invalid schema, recreating the index");
+                }
+                Err(err) => {
+                    warn!("vecdb index can't be opened, recreating the index: {:?}", err);
+                }
+            }
+            if data_table_mb.is_none() {
+                if let Err(err) = database.drop_table(DATA_TABLE_NAME).await {
+                    return Err(format!("{:?}", err));
+                }
+            }
+        }
+        let data_table_is_new = data_table_mb.is_none();
+        let data_table = match data_table_mb {
+            Some(table) => table,
+            None => {
+                let batches_iter = RecordBatchIterator::new(vec![].into_iter().map(Ok), schema.clone());
+                match database.create_table(DATA_TABLE_NAME, batches_iter, Option::from(WriteParams::default())).await {
+                    Ok(table) => table,
+                    Err(err) => return Err(format!("{:?}", err))
+                }
+            }
+        };
+
+        let files_database = match Connection::open(index_dir.join("files.sqlite")).await {
+            Ok(db) => db,
             Err(err) => return Err(format!("{:?}", err))
         };
+        let files_hashes = match files_database.call(move |conn| {
+            conn.execute(&format!(
+                "CREATE TABLE IF NOT EXISTS {FILES_TABLE_NAME} (
+                scope TEXT PRIMARY KEY,
+                content_hash TEXT NOT NULL
+            )"), [])?;
+            if data_table_is_new {
+                // a new data table might come with a new schema, windows follow it
+                conn.execute_batch(&format!(
+                    "DROP TABLE IF EXISTS {WINDOWS_FTS_TABLE_NAME}; DROP TABLE IF EXISTS {WINDOWS_TABLE_NAME};"
+                ))?;
+            }
+            let windows_table_exists = conn.query_row(
+                "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = ?1",
+                [WINDOWS_TABLE_NAME], |row| row.get::<_, i64>(0),
+            )? > 0;
+            // trigram tokenizer: identifiers and log messages match as substrings, not only as whole words
+            conn.execute_batch(&format!(
+                "CREATE TABLE IF NOT EXISTS {WINDOWS_TABLE_NAME} (
+                    id INTEGER PRIMARY KEY,
+                    scope TEXT NOT NULL,
+                    start_line INTEGER NOT NULL,
+                    end_line INTEGER NOT NULL,
+                    symbol_path TEXT NOT NULL,
+                    window_text TEXT NOT NULL
+                );
+                CREATE INDEX IF NOT EXISTS {WINDOWS_TABLE_NAME}_scope ON {WINDOWS_TABLE_NAME} (scope);
+                CREATE VIRTUAL TABLE IF NOT EXISTS {WINDOWS_FTS_TABLE_NAME} USING fts5(
+                    window_text, content='{WINDOWS_TABLE_NAME}', content_rowid='id', tokenize='trigram'
+                );
+                CREATE TRIGGER IF NOT EXISTS {WINDOWS_TABLE_NAME}_ai AFTER INSERT ON {WINDOWS_TABLE_NAME} BEGIN
+                    INSERT INTO {WINDOWS_FTS_TABLE_NAME} (rowid, window_text) VALUES (new.id, new.window_text);
+                END;
+                CREATE TRIGGER IF NOT EXISTS {WINDOWS_TABLE_NAME}_ad AFTER DELETE ON {WINDOWS_TABLE_NAME} BEGIN
+                    INSERT INTO {WINDOWS_FTS_TABLE_NAME} ({WINDOWS_FTS_TABLE_NAME}, rowid, window_text) VALUES ('delete', old.id, old.window_text);
+                END;"
+            ))?;
+            if data_table_is_new || !windows_table_exists {
+                // hashes are only good together with the records and windows they describe
+                conn.execute(&format!("DELETE FROM {FILES_TABLE_NAME}"), [])?;
+            }
+            let mut stmt = conn.prepare(&format!("SELECT scope, content_hash FROM {FILES_TABLE_NAME}"))?;
+            let rows = stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?;
+            Ok(rows.filter_map(|r| r.ok()).collect::<HashMap<_, _>>())
+        }).await {
+            Ok(hashes) => hashes,
+            Err(err) => return Err(format!("{:?}", err))
+        };
+        info!("vecdb index {:?} has {} files from the previous runs", index_dir, files_hashes.len());
 
         Ok(VecDBHandler {
-            _data_database_temp_dir: data_database_temp_dir,
             schema,
             data_table,
-            // data_table_hashes: HashSet::new(),
             embedding_size,
+            files_database,
+            files_hashes,
+            _index_lock: index_lock,
+            ann_index_state_cache: None,
         })
     }
 
@@ -90,6 +233,65 @@ impl VecDBHandler {
         }
     }
 
+    pub fn is_file_up_to_date(&self, scope: &String, content_hash: &String) -> bool {
+        self.files_hashes.get(scope) == Some(content_hash)
+    }
+
+    pub fn indexed_files(&self) -> Vec<String> {
+        self.files_hashes.keys().cloned().collect()
+    }
+
+    pub async fn set_files_hashes(&mut self, hashes: HashMap<String, String>) {
+        if hashes.is_empty() {
+            return;
+        }
+        let hashes_copy = hashes.clone();
+        if let Err(err) = self.files_database.call(move |conn| {
+            let transaction = conn.transaction()?;
+            for (scope, content_hash) in hashes_copy.iter() {
+                transaction.execute(
+                    &format!("INSERT OR REPLACE INTO {FILES_TABLE_NAME} (scope, content_hash) VALUES (?1, ?2)"),
+                    rusqlite::params![scope, content_hash],
+                )?;
+            }
+            transaction.commit()?;
+            Ok(())
+        }).await {
+            tracing::error!("Error saving file hashes: {:?}", err);
+            return;
+        }
+        self.files_hashes.extend(hashes);
+    }
+
+    async fn forget_files_hashes(&mut self, scopes: &[String]) {
+        let scopes: Vec<String> = scopes.iter().filter(|x| self.files_hashes.remove(*x).is_some()).cloned().collect();
+        if scopes.is_empty() {
+            return;
+        }
+        if let Err(err) = self.files_database.call(move |conn| {
+            let transaction = conn.transaction()?;
+            for scope in scopes.iter() {
+                transaction.execute(&format!("DELETE FROM {FILES_TABLE_NAME} WHERE scope = ?1"), rusqlite::params![scope])?;
+            }
+            transaction.commit()?;
+            Ok(())
+        }).await {
+            tracing::error!("Error removing file hashes: {:?}", err);
+        }
+    }
+
+    pub async fn compact(&mut self) {
+        // every add and delete is a new version on disk, an index that lives across restarts needs cleaning up
+        if let Err(err) = self.data_table.compact_files(CompactionOptions::default(), None).await {
+            warn!("vecdb compaction failed: {:?}", err);
+            return;
+        }
+        match self.data_table.cleanup_old_versions(chrono::Duration::minutes(10), Some(true)).await {
+            Ok(stats) => info!("vecdb compaction removed {} old versions, {} bytes", stats.old_versions, stats.bytes_removed),
+            Err(err) => warn!("vecdb cleanup of old versions failed: {:?}", err),
+        }
+    }
+
     pub async fn vecdb_records_add(&mut self, records: &Vec<VecdbRecord>)
     {
         fn make_emb_data(records: &Vec<VecdbRecord>, embedding_size: i32) -> Result<ArrayData, String> {
@@ -133,6 +335,7 @@ impl VecDBHandler {
         let scopes: Vec<String> = records.iter().map(|x| x.file_path.to_str().unwrap_or("No filename").to_string()).collect();
         let start_lines: Vec<u64> = records.iter().map(|x| x.start_line).collect();
         let end_lines: Vec<u64> = records.iter().map(|x| x.end_line).collect();
+        let symbol_paths: Vec<String> = records.iter().map(|x| x.symbol_path.clone()).collect();
         let data_batches_iter = RecordBatchIterator::new(
             vec![RecordBatch::try_new(
                 self.schema.clone(),
@@ -141,6 +344,7 @@ impl VecDBHandler {
                     Arc::new(StringArray::from(scopes.clone())),
                     Arc::new(UInt64Array::from(start_lines.clone())),
                     Arc::new(UInt64Array::from(end_lines.clone())),
+                    Arc::new(StringArray::from(symbol_paths)),
                 ],
             )],
             self.schema.clone(),
@@ -154,6 +358,24 @@ impl VecDBHandler {
             }),
         ).await {
             tracing::error!("{}", err);
+            return;
+        }
+
+        let windows: Vec<(String, u64, u64, String, String)> = records.iter().zip(scopes)
+            .map(|(x, scope)| (scope, x.start_line, x.end_line, x.symbol_path.clone(), x.window_text.clone()))
+            .collect();
+        if let Err(err) = self.files_database.call(move |conn| {
+            let transaction = conn.transaction()?;
+            for (scope, start_line, end_line, symbol_path, window_text) in windows.iter() {
+                transaction.execute(
+                    &format!("INSERT INTO {WINDOWS_TABLE_NAME} (scope, start_line, end_line, symbol_path, window_text) VALUES (?1, ?2, ?3, ?4, ?5)"),
+                    rusqlite::params![scope, start_line, end_line, symbol_path, window_text],
+                )?;
+            }
+            transaction.commit()?;
+            Ok(())
+        }).await {
+            tracing::error!("Error adding windows to the lexical index: {:?}", err);
         }
     }
 
@@ -161,6 +383,18 @@ impl VecDBHandler {
         &mut self,
         scopes_to_remove: Vec<String>
     ) {
+        self.forget_files_hashes(&scopes_to_remove).await;
+        let scopes_copy = scopes_to_remove.clone();
+        if let Err(err) = self.files_database.call(move |conn| {
+            let transaction = conn.transaction()?;
+            for scope in scopes_copy.iter() {
+                transaction.execute(&format!("DELETE FROM {WINDOWS_TABLE_NAME} WHERE scope = ?1"), rusqlite::params![scope])?;
+            }
+            transaction.commit()?;
+            Ok(())
+        }).await {
+            tracing::error!("Error removing windows from the lexical index: {:?}", err);
+        }
         let mut delete_queries = Vec::new();
 
         for chunk in &scopes_to_remove.iter().chunks(100) {
@@ -186,23 +420,54 @@ impl VecDBHandler {
         }
     }
 
-    // pub async fn create_index(&mut self) -> vectordb::error::Result<()> {
-    //     let size = self.size().await.unwrap_or(0);
-    //     if size == 0 {
-    //         return Ok(());
-    //     }
-    //     self.data_table.create_index(
-    //         IvfPQIndexBuilder::default()
-    //             .column("vector".to_owned())
-    //             .index_name("index".to_owned())
-    //             .metric_type(MetricType::Cosine)
-    //             .ivf_params(IvfBuildParams {
-    //                 num_partitions: min(size, 512),
-    //                 ..IvfBuildParams::default()
-    //             })
-    //             .replace(true)
-    //     ).await
-    // }
+    pub async fn ann_index_unindexed_rows(&self) -> Result<Option<usize>, String> {
+        // None if there's no index yet
+        let indices = self.data_table.load_indices().await.map_err(|e| format!("{:?}", e))?;
+        let index = match indices.iter().find(|x| x.index_name == ANN_INDEX_NAME) {
+            Some(index) => index,
+            None => return Ok(None),
+        };
+        self.data_table.count_unindexed_rows(&index.index_uuid).await.map_err(|e| format!("{:?}", e))
+    }
+
+    pub async fn ann_index_unindexed_rows_cached(&mut self) -> Result<Option<usize>, String> {
+        if let Some((checked_at, state)) = self.ann_index_state_cache {
+            if checked_at.elapsed() < ANN_INDEX_STATE_CACHE_TTL {
+                return Ok(state);
+            }
+        }
+        let state = self.ann_index_unindexed_rows().await?;
+        self.ann_index_state_cache = Some((Instant::now(), state));
+        Ok(state)
+    }
+
+    pub async fn ann_index_needs_rebuild(&self) -> bool {
+        let size = self.size().await.unwrap_or(0);
+        if size < ANN_INDEX_MIN_ROWS {
+            return false;
+        }
+        match self.ann_index_unindexed_rows().await {
+            Ok(None) => true,
+            Ok(Some(unindexed)) => unindexed * 100 > size * ANN_INDEX_MAX_UNINDEXED_PERCENT,
+            Err(err) => {
+                warn!("vecdb can't read the index state: {}", err);
+                false
+            }
+        }
+    }
+
+    pub fn ann_index_build_params(&self) -> (Table, i32) {
+        // the build takes a while, it works on its own copy of the table and doesn't block searches
+        (self.data_table.clone(), self.embedding_size)
+    }
+
+    pub async fn reload_table(&mut self) {
+        self.ann_index_state_cache = None;
+        match self.data_table.checkout_latest().await {
+            Ok(table) => self.data_table = table,
+            Err(err) => warn!("vecdb can't reload the table: {:?}", err),
+        }
+    }
 
     fn parse_table_iter(
         record_batch: RecordBatch,
@@ -241,6 +506,11 @@ impl VecDBHandler {
                     .value(idx),
                 distance,
                 usefulness: 0.0,
+                symbol_path: as_string_array(record_batch.column_by_name("symbol_path")
+                    .expect("Missing column 'symbol_path'"))
+                    .value(idx)
+                    .to_string(),
+                window_text: String::new(),
             })
         }).collect()
     }
@@ -249,9 +519,12 @@ impl VecDBHandler {
         &mut self,
         embedding: &Vec<f32>,
         top_n: usize,
-        vecdb_scope_filter_mb: Option<String>,
+        scope_mb: Option<String>,
     ) -> vectordb::error::Result<Vec<VecdbRecord>> {
+        let vecdb_scope_filter_mb = scope_mb.map(|x| lance_scope_filter(&x));
         let use_prefilter = vecdb_scope_filter_mb.is_some();
+        // the table and the index use the default L2 metric, distances in the result are cosine, recalculated
+        // in parse_table_iter, that's what VECDB_DISTANCE_REJECT_COMPLETELY is calibrated for
         let query = self
             .data_table
             .clone()
@@ -259,6 +532,8 @@ impl VecDBHandler {
             .prefilter(use_prefilter)
             .filter(vecdb_scope_filter_mb)
             .limit(top_n)
+            .nprobes(ANN_INDEX_NPROBES)
+            .refine_factor(Some(ANN_INDEX_REFINE_FACTOR))
             .use_index(true)
             .execute()
             .await?
@@ -281,4 +556,238 @@ impl VecDBHandler {
             Err(err) => Err(err),
         }
     }
+
+    pub async fn lexical_search(
+        &self,
+        query: &str,
+        top_n: usize,
+        scope_mb: Option<String>,
+    ) -> Result<Vec<VecdbRecord>, String> {
+        let fts_query = match fts_query_from_text(query) {
+            Some(q) => q,
+            None => return Ok(vec![]),  // trigrams need at least 3 characters
+        };
+        // a directory scope is a prefix, % and _ in the path are not wildcards
+        let (scope_condition, scope_param_mb) = match scope_mb {
+            Some(scope) if scope.ends_with('/') || scope.ends_with('\\') => {
+                (" AND w.scope LIKE ?3 ESCAPE '\\'", Some(format!("{}%", sqlite_like_escape(&scope))))
+            }
+            Some(scope) => (" AND w.scope = ?3", Some(scope)),
+            None => ("", None),
+        };
+        let sql = format!(
+            "SELECT w.scope, w.start_line, w.end_line, w.symbol_path FROM {WINDOWS_FTS_TABLE_NAME} \
+            JOIN {WINDOWS_TABLE_NAME} w ON w.id = {WINDOWS_FTS_TABLE_NAME}.rowid \
+            WHERE {WINDOWS_FTS_TABLE_NAME} MATCH ?1{scope_condition} \
+            ORDER BY bm25({WINDOWS_FTS_TABLE_NAME}) LIMIT ?2"
+        );
+        self.files_database.call(move |conn| {
+            let mut stmt = conn.prepare(&sql)?;
+            let mut params: Vec<&dyn rusqlite::ToSql> = vec![&fts_query, &top_n];
+            if let Some(scope_param) = &scope_param_mb {
+                params.push(scope_param);
+            }
+            let rows = stmt.query_map(params.as_slice(), |row| {
+                Ok(VecdbRecord {
+                    vector: None,
+                    file_path: PathBuf::from(row.get::<_, String>(0)?),
+                    start_line: row.get(1)?,
+                    end_line: row.get(2)?,
+                    distance: -1.0,
+                    usefulness: 0.0,
+                    symbol_path: row.get(3)?,
+                    window_text: String::new(),
+                })
+            })?;
+            Ok(rows.collect::<Result<Vec<_>, _>>()?)
+        }).await.map_err(|e| format!("lexical search: {:?}", e))
+    }
+}
+
+fn sqlite_like_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+}
+
+fn lance_scope_filter(scope: &str) -> String {
+    // lance can't do LIKE ... ESCAPE, a directory is a range of strings instead, no wildcards to escape
+    let escaped = scope.replace('\'', "''");
+    if scope.ends_with('/') || scope.ends_with('\\') {
+        format!("(scope >= '{}' AND scope < '{}{}')", escaped, escaped, char::MAX)
+    } else {
+        format!("(scope = '{}')", escaped)
+    }
+}
+
+pub fn fts_query_from_text(text: &str) -> Option<String> {
+    // the whole text as a phrase, or any identifier-like word in it; common words like "the" or "file"
+    // match almost every window, they don't go in alone
+    fn quoted(s: &str) -> String {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    }
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let mut terms: Vec<String> = words.iter().filter_map(|w| lexical_word(w)).unique().map(quoted).collect();
+    let phrase = words.join(" ");
+    if words.iter().any(|w| w.chars().count() >= 3) && (words.len() > 1 || terms.is_empty()) {
+        terms.push(quoted(&phrase));
+    }
+    if terms.is_empty() { None } else { Some(terms.join(" OR ")) }
+}
+
+fn pq_num_sub_vectors(embedding_size: usize) -> usize {
+    // sub-vectors must split the embedding evenly, 8 dimensions each is a good tradeoff between size and recall
+    (1..=(embedding_size / 8).max(1)).rev().find(|n| embedding_size.is_multiple_of(*n)).unwrap_or(1)
+}
+
+pub async fn ann_index_build(mut table: Table, embedding_size: i32) -> Result<usize, String> {
+    let size = table.count_rows().await.map_err(|e| format!("{:?}", e))?;
+    if size == 0 {
+        return Ok(0);
+    }
+    let num_partitions = ((size as f64).sqrt() as usize).clamp(16, 1024).min(size);
+    table.create_index(
+        IvfPQIndexBuilder::default()
+            .column("vector".to_owned())
+            .index_name(ANN_INDEX_NAME.to_owned())
+            .ivf_params(IvfBuildParams {
+                num_partitions,
+                ..IvfBuildParams::default()
+            })
+            .pq_params(PQBuildParams {
+                num_sub_vectors: pq_num_sub_vectors(embedding_size as usize),
+                ..PQBuildParams::default()
+            })
+            .replace(true)
+    ).await.map_err(|e| format!("{:?}", e))?;
+    Ok(size)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng};
+    use rand::rngs::StdRng;
+    use super::*;
+    use crate::vecdb::vdb_structs::{lexical_weight_default, VECDB_LEXICAL_WEIGHT_DEFAULT};
+
+    fn random_records(rng: &mut StdRng, count: usize, embedding_size: i32, file_name: &str) -> Vec<VecdbRecord> {
+        (0..count).map(|i| VecdbRecord {
+            vector: Some((0..embedding_size).map(|_| rng.gen_range(-1.0..1.0)).collect()),
+            file_path: PathBuf::from(format!("/tmp/{}_{}.py", file_name, i)),
+            start_line: 0,
+            end_line: 10,
+            distance: -1.0,
+            usefulness: 0.0,
+            symbol_path: format!("{}_{}", file_name, i),
+            window_text: format!("window {} of {}", i, file_name),
+        }).collect()
+    }
+
+    #[test]
+    fn test_pq_num_sub_vectors() {
+        assert_eq!(pq_num_sub_vectors(768), 96);
+        assert_eq!(pq_num_sub_vectors(1536), 192);
+        assert_eq!(pq_num_sub_vectors(100), 10);
+        assert_eq!(pq_num_sub_vectors(4), 1);
+    }
+
+    #[test]
+    fn test_fts_query_from_text() {
+        assert_eq!(fts_query_from_text("a b"), None);
+        assert_eq!(fts_query_from_text("handle_v1_vecdb_search"), Some("\"handle_v1_vecdb_search\"".to_string()));
+        assert_eq!(fts_query_from_text("goat"), Some("\"goat\"".to_string()));
+        assert_eq!(
+            fts_query_from_text("Failed to \"open\"  file"),
+            Some("\"Failed to \"\"open\"\" file\"".to_string())
+        );
+        assert_eq!(
+            fts_query_from_text("where is VecDb::search called for the query"),
+            Some("\"VecDb::search\" OR \"where is VecDb::search called for the query\"".to_string())
+        );
+        assert_eq!(lexical_weight_default("how are files split for the index"), 0.0);
+        assert_eq!(lexical_weight_default("where is fetchEmbedding retried"), VECDB_LEXICAL_WEIGHT_DEFAULT);
+        assert_eq!(lexical_weight_default("\"connection refused\""), VECDB_LEXICAL_WEIGHT_DEFAULT);
+    }
+
+    #[test]
+    fn test_workspace_hash() {
+        let a = PathBuf::from("/tmp/project_a");
+        let b = PathBuf::from("/tmp/project_b");
+        assert_eq!(workspace_hash(&[a.clone(), b.clone()]), workspace_hash(&[b.clone(), a.clone()]));
+        assert_ne!(workspace_hash(&[a.clone()]), workspace_hash(&[b.clone()]));
+        assert_eq!(workspace_hash(&[a.clone()]).len(), 16);
+    }
+
+    #[tokio::test]
+    async fn test_index_lock() {
+        let cache_dir = tempfile::tempdir().unwrap();
+        let handler = VecDBHandler::init(cache_dir.path(), "test-model", 8, "test", "ws").await.unwrap();
+        assert!(VecDBHandler::init(cache_dir.path(), "test-model", 8, "test", "ws").await.is_err());
+        assert!(VecDBHandler::init(cache_dir.path(), "test-model", 8, "test", "other_ws").await.is_ok());
+        drop(handler);
+        assert!(VecDBHandler::init(cache_dir.path(), "test-model", 8, "test", "ws").await.is_ok());
+    }
+
+    #[test]
+    fn test_lance_scope_filter() {
+        assert_eq!(lance_scope_filter("/home/user/it's/main.rs"), "(scope = '/home/user/it''s/main.rs')");
+        assert_eq!(lance_scope_filter("/home/user/my_project/"), format!("(scope >= '/home/user/my_project/' AND scope < '/home/user/my_project/{}')", char::MAX));
+        assert_eq!(sqlite_like_escape("/home/50%_off\\"), "/home/50\\%\\_off\\\\");
+    }
+
+    #[tokio::test]
+    async fn test_lexical_search() {
+        let cache_dir = tempfile::tempdir().unwrap();
+        let mut rng = StdRng::seed_from_u64(42);
+        let mut handler = VecDBHandler::init(cache_dir.path(), "test-model", 8, "test", "ws").await.unwrap();
+        let mut records = random_records(&mut rng, 3, 8, "lexical");
+        records[0].window_text = "fn main() {\n    println!(\"hello\");\n}".to_string();
+        records[1].window_text = "error!(\"vecdb index build failed: {}\", err);".to_string();
+        records[2].window_text = "let failed = build_index();".to_string();
+        handler.vecdb_records_add(&records).await;
+
+        // the phrase, not any window with "failed" in it
+        let found = handler.lexical_search("index build failed", 10, None).await.unwrap();
+        assert_eq!(found.iter().map(|x| x.file_path.clone()).collect::<Vec<_>>(), vec![records[1].file_path.clone()]);
+        assert_eq!(found[0].symbol_path, "lexical_1");
+        let found = handler.lexical_search("who calls build_index", 10, None).await.unwrap();
+        assert_eq!(found.iter().map(|x| x.file_path.clone()).collect::<Vec<_>>(), vec![records[2].file_path.clone()]);
+        let found = handler.lexical_search("println", 10, Some("/tmp/lexical_0.py".to_string())).await.unwrap();
+        assert_eq!(found.len(), 1);
+        let found = handler.lexical_search("println", 10, Some("/tmp/lexical_1.py".to_string())).await.unwrap();
+        assert!(found.is_empty());
+        let found = handler.lexical_search("println", 10, Some("/tmp/".to_string())).await.unwrap();
+        assert_eq!(found.len(), 1);
+        // _ is a LIKE wildcard, it must not match the 'm' in /tmp/
+        let found = handler.lexical_search("println", 10, Some("/t_p/".to_string())).await.unwrap();
+        assert!(found.is_empty());
+
+        handler.vecdb_records_remove(vec![records[1].file_path.to_string_lossy().to_string()]).await;
+        let found = handler.lexical_search("index build failed", 10, None).await.unwrap();
+        assert!(found.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_ann_index_build() {
+        let cache_dir = tempfile::tempdir().unwrap();
+        let embedding_size = 32;
+        let mut rng = StdRng::seed_from_u64(42);
+        let mut handler = VecDBHandler::init(cache_dir.path(), "test-model", embedding_size, "test", "ws").await.unwrap();
+        let records = random_records(&mut rng, 2000, embedding_size, "indexed");
+        handler.vecdb_records_add(&records).await;
+        assert_eq!(handler.ann_index_unindexed_rows().await.unwrap(), None);
+
+        let (table, embedding_size) = handler.ann_index_build_params();
+        assert_eq!(ann_index_build(table, embedding_size).await.unwrap(), 2000);
+        handler.reload_table().await;
+        assert_eq!(handler.ann_index_unindexed_rows().await.unwrap(), Some(0));
+
+        handler.vecdb_records_add(&random_records(&mut rng, 5, embedding_size, "new")).await;
+        assert_eq!(handler.ann_index_unindexed_rows().await.unwrap(), Some(5));
+
+        let query = records[7].vector.clone().unwrap();
+        let found = handler.vecdb_search(&query, 5, None).await.unwrap();
+        assert_eq!(found[0].file_path, records[7].file_path);
+        assert_eq!(found[0].symbol_path, "indexed_7");
+        assert!(found[0].distance.abs() < 1e-5);
+    }
 }
@@ -1,14 +1,86 @@
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex as StdMutex};
 use async_trait::async_trait;
-use reqwest::header::CONTENT_TYPE;
-use reqwest::header::HeaderMap;
-use reqwest::header::HeaderValue;
+use reqwest::header::{AUTHORIZATION, CONTENT_TYPE};
 use serde_json::json;
+use tracing::{info, warn};
 
 use crate::vecdb::vdb_structs::{SearchResult, VecdbSearch};
 
+const REMOTE_SEARCH_MAX_ATTEMPTS: usize = 3;
+const REMOTE_SEARCH_SLEEP_MS: u64 = 500;
 
+
+// Search in a shared index, built by another refact-lsp that serves /v1/vdb-search for the whole team.
+// That server has the repository checked out somewhere else, paths are translated both ways using remote_root.
 #[derive(Debug)]
-pub struct VecDbRemote {}
+pub struct VecDbRemote {
+    pub endpoint: String,
+    api_key: String,
+    remote_root: String,
+    workspace_folders: Arc<StdMutex<Vec<PathBuf>>>,
+    client: reqwest::Client,
+}
+
+fn scope_to_remote(scope: &str, workspace_folders: &[PathBuf], remote_root: &str) -> String {
+    if remote_root.is_empty() {
+        return scope.to_string();
+    }
+    for folder in workspace_folders {
+        if let Ok(relative) = Path::new(scope).strip_prefix(folder) {
+            let mut remote = PathBuf::from(remote_root).join(relative).to_string_lossy().to_string();
+            if scope.ends_with(std::path::MAIN_SEPARATOR) && !remote.ends_with('/') {
+                remote.push('/');  // still a directory scope
+            }
+            return remote;
+        }
+    }
+    scope.to_string()
+}
+
+fn path_to_local(path: &Path, workspace_folders: &[PathBuf], remote_root: &str) -> PathBuf {
+    if remote_root.is_empty() {
+        return path.to_path_buf();
+    }
+    let relative = match path.strip_prefix(remote_root) {
+        Ok(relative) => relative,
+        Err(_) => return path.to_path_buf(),
+    };
+    let candidates: Vec<PathBuf> = workspace_folders.iter().map(|x| x.join(relative)).collect();
+    candidates.iter().find(|x| x.exists()).or(candidates.first()).cloned().unwrap_or(path.to_path_buf())
+}
+
+impl VecDbRemote {
+    pub fn new(endpoint: &str, api_key: &str, remote_root: &str, workspace_folders: Arc<StdMutex<Vec<PathBuf>>>) -> Self {
+        VecDbRemote {
+            endpoint: endpoint.to_string(),
+            api_key: api_key.to_string(),
+            remote_root: remote_root.to_string(),
+            workspace_folders,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    async fn search_once(&self, body: &serde_json::Value) -> Result<SearchResult, (String, bool)> {
+        // the error comes with a flag: does it make sense to try again
+        let mut request = self.client.post(&self.endpoint)
+            .header(CONTENT_TYPE, "application/json")
+            .body(body.to_string());
+        if !self.api_key.is_empty() {
+            request = request.header(AUTHORIZATION, format!("Bearer {}", self.api_key));
+        }
+        let response = request.send().await
+            .map_err(|e| (format!("remote vecdb {}: {}", self.endpoint, e), true))?;
+        let status = response.status();
+        let text = response.text().await
+            .map_err(|e| (format!("remote vecdb {}: {}", self.endpoint, e), true))?;
+        if !status.is_success() {
+            return Err((format!("remote vecdb {} returned {}: {}", self.endpoint, status, text), status.is_server_error()));
+        }
+        serde_json::from_str::<SearchResult>(&text)
+            .map_err(|e| (format!("remote vecdb {} JSON problem: {}", self.endpoint, e), false))
+    }
+}
 
 #[async_trait]
 impl VecdbSearch for VecDbRemote {
@@ -16,34 +88,55 @@ impl VecdbSearch for VecDbRemote {
         &self,
         query: String,
         top_n: usize,
-        _vecdb_scope_filter_mb: Option<String>,
-        _api_key: &String,
+        scope_mb: Option<String>,
+        lexical_weight: f32,
+        _api_key: &String,  // that's for the embedding model, the index server has its own
     ) -> Result<SearchResult, String> {
-        let url = "http://127.0.0.1:8008/v1/vdb-search".to_string();
-        let mut headers = HeaderMap::new();
-        // headers.insert(AUTHORIZATION, HeaderValue::from_str(&format!("Bearer {}", self.token)).unwrap());
-        headers.insert(CONTENT_TYPE, HeaderValue::from_str("application/json").unwrap());
+        let workspace_folders = self.workspace_folders.lock().unwrap().clone();
         let body = json!({
-            "text": query,
-            "top_n": top_n
+            "query": query,
+            "top_n": top_n,
+            "scope": scope_mb.map(|x| scope_to_remote(&x, &workspace_folders, &self.remote_root)),
+            "lexical_weight": lexical_weight,
         });
-        let res = reqwest::Client::new()
-            .post(&url)
-            .headers(headers)
-            .body(body.to_string())
-            .send()
-            .await.map_err(|e| format!("Vecdb search HTTP error (1): {}", e))?;
-
-        let body = res.text().await.map_err(|e| format!("Vecdb search HTTP error (2): {}", e))?;
-        // info!("Vecdb search result: {:?}", &body);
-        let result: Vec<SearchResult> = serde_json::from_str(&body).map_err(|e| {
-            format!("vecdb JSON problem: {}", e)
-        })?;
-        if result.len() == 0 {
-            return Err("Vecdb search result is empty".to_string());
+        let t0 = std::time::Instant::now();
+        let mut attempt_n = 0;
+        let mut result = loop {
+            attempt_n += 1;
+            match self.search_once(&body).await {
+                Ok(result) => break result,
+                Err((err, retry)) => {
+                    if !retry || attempt_n >= REMOTE_SEARCH_MAX_ATTEMPTS {
+                        return Err(err);
+                    }
+                    warn!("{}, will retry", err);
+                    tokio::time::sleep(tokio::time::Duration::from_millis(REMOTE_SEARCH_SLEEP_MS * attempt_n as u64)).await;
+                }
+            }
+        };
+        info!("remote vecdb search {:?} {:.3}s, {} results", query, t0.elapsed().as_secs_f64(), result.results.len());
+        for rec in result.results.iter_mut() {
+            rec.file_path = path_to_local(&rec.file_path, &workspace_folders, &self.remote_root);
         }
-        let result0 = result[0].clone();
-        // info!("Vecdb search result: {:?}", &result0);
-        Ok(result0)
+        Ok(result)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_paths_mapping() {
+        let workspace_folders = vec![PathBuf::from("/home/user/monorepo")];
+        assert_eq!(scope_to_remote("/home/user/monorepo/src/main.rs", &workspace_folders, "/srv/index/monorepo"), "/srv/index/monorepo/src/main.rs");
+        assert_eq!(scope_to_remote("/home/user/monorepo/src/", &workspace_folders, "/srv/index/monorepo"), "/srv/index/monorepo/src/");
+        assert_eq!(scope_to_remote("/home/user/other/main.rs", &workspace_folders, "/srv/index/monorepo"), "/home/user/other/main.rs");
+        assert_eq!(scope_to_remote("/home/user/monorepo/src/", &workspace_folders, ""), "/home/user/monorepo/src/");
+
+        assert_eq!(path_to_local(Path::new("/srv/index/monorepo/src/main.rs"), &workspace_folders, "/srv/index/monorepo"), PathBuf::from("/home/user/monorepo/src/main.rs"));
+        assert_eq!(path_to_local(Path::new("/srv/other/main.rs"), &workspace_folders, "/srv/index/monorepo"), PathBuf::from("/srv/other/main.rs"));
+        assert_eq!(path_to_local(Path::new("/srv/index/monorepo/main.rs"), &workspace_folders, ""), PathBuf::from("/srv/index/monorepo/main.rs"));
     }
 }
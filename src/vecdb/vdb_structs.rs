@@ -8,13 +8,36 @@ use tokenizers::Tokenizer;
 use async_trait::async_trait;
 
 
+// how much the lexical ranking weighs against the vector one: 0.0 is vectors only, 1.0 is lexical only
+pub const VECDB_LEXICAL_WEIGHT_DEFAULT: f32 = 0.5;
+
+pub fn lexical_word(word: &str) -> Option<&str> {
+    // handle_v1_search, VecDb::search, fetchEmbedding, E0308, src/main.rs; not "the", "open" or "Failed"
+    let word = word.trim_matches(|c: char| !c.is_alphanumeric() && c != '_');
+    let identifier_like = word.chars().count() >= 3 && (
+        word.chars().any(|c| c.is_ascii_digit() || "_.:/()<>[]#$@".contains(c)) ||
+        word.chars().skip(1).any(|c| c.is_uppercase())
+    );
+    if identifier_like { Some(word) } else { None }
+}
+
+pub fn lexical_weight_default(query: &str) -> f32 {
+    // the lexical index finds identifiers and quoted strings, for prose it only adds filler
+    if query.contains('"') || query.split_whitespace().any(|w| lexical_word(w).is_some()) {
+        VECDB_LEXICAL_WEIGHT_DEFAULT
+    } else {
+        0.0
+    }
+}
+
 #[async_trait]
 pub trait VecdbSearch: Send {
     async fn vecdb_search(
         &self,
         query: String,
         top_n: usize,
-        filter_mb: Option<String>,
+        scope_mb: Option<String>,  // a file path, or a directory path ending with a separator
+        lexical_weight: f32,
         api_key: &String,
     ) -> Result<SearchResult, String>;
 }
@@ -31,6 +54,8 @@ pub struct VecdbConstants {
     pub endpoint_embeddings_style: String,
     pub splitter_window_size: usize,
     pub vecdb_max_files: usize,
+    pub remote_endpoint: String,  // search goes to a shared index server, files are not vectorized locally
+    pub remote_root: String,  // workspace root on that server, maps its paths to the local workspace
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -44,6 +69,8 @@ pub struct VecDbStatus {
     pub state: String,   // "starting", "parsing", "done"
     pub queue_additions: bool,
     pub vecdb_max_files_hit: bool,
+    pub index_state: String,  // "none", "building", "ready", "remote"
+    pub index_unindexed_rows: usize,
 }
 
 
@@ -55,6 +82,10 @@ pub struct VecdbRecord {
     pub end_line: u64,
     pub distance: f32,
     pub usefulness: f32,
+    #[serde(default)]
+    pub symbol_path: String,  // "Foo::bar" if the window is a symbol, empty for plain text windows
+    #[serde(skip)]
+    pub window_text: String,  // goes into the lexical index, not stored in the vector table
 }
 
 #[derive(Debug, Clone)]
@@ -10,12 +10,17 @@ use tokio::task::JoinHandle;
 use tracing::{info, warn};
 use tokenizers::Tokenizer;
 
+use crate::ast::chunk_utils::official_text_hashing_function;
 use crate::ast::file_splitter::AstBasedFileSplitter;
+use crate::audit_log::AuditFile;
 use crate::fetch_embedding::get_embedding_with_retry;
+use crate::privacy::{load_privacy_if_needed, PrivacySettings};
+use crate::privacy_secrets::{log_redactions, redact_secrets};
 use crate::files_in_workspace::Document;
+use crate::file_filter::{check_indexing_target, IndexingTarget};
 use crate::global_context::GlobalContext;
 use crate::knowledge::{MemoriesDatabase, vectorize_dirty_memories};
-use crate::vecdb::vdb_lance::VecDBHandler;
+use crate::vecdb::vdb_lance::{ann_index_build, VecDBHandler};
 use crate::vecdb::vdb_structs::{VecdbRecord, SplitResult, VecdbConstants, VecDbStatus, SimpleTextHashVector};
 use crate::vecdb::vdb_cache::VecDBCache;
 
@@ -93,20 +98,34 @@ async fn vectorize_batch_from_q(
     constants: &VecdbConstants,
     api_key: &String,
     vecdb_cache_arc: Arc<AMutex<VecDBCache>>,
+    privacy_settings: Arc<PrivacySettings>,
     #[allow(non_snake_case)]
     B: usize,
 ) -> Result<(), String> {
     let batch = run_actual_model_on_these.drain(..B.min(run_actual_model_on_these.len())).collect::<Vec<_>>();
     assert!(batch.len() > 0);
 
+    let mut redacted = vec![];
+    let texts = batch.iter()
+        .map(|x| redact_secrets(&x.window_text, &x.file_path.to_string_lossy(), &mut redacted))
+        .collect();
+    log_redactions(&redacted);
+    let audit_files: Vec<AuditFile> = batch.iter().map(|x| AuditFile {
+        file_name: x.file_path.to_string_lossy().to_string(),
+        line1: x.start_line as usize + 1,
+        line2: x.end_line as usize + 1,
+    }).collect();
+
     let batch_result = get_embedding_with_retry(
         client.clone(),
         &constants.endpoint_embeddings_style.clone(),
         &constants.embedding_model.clone(),
         &constants.endpoint_embeddings_template.clone(),
-        batch.iter().map(|x| x.window_text.clone()).collect(),
+        texts,
         api_key,
         10,
+        &audit_files,
+        Some(privacy_settings),
     ).await?;
 
     if batch_result.len() != batch.len() {
@@ -133,6 +152,8 @@ async fn vectorize_batch_from_q(
                 end_line: data_res.end_line,
                 distance: -1.0,
                 usefulness: 0.0,
+                symbol_path: data_res.symbol_path.clone(),
+                window_text: data_res.window_text.clone(),
             }
         );
         send_to_cache.push(
@@ -161,6 +182,7 @@ async fn vectorize_batch_from_q(
 async fn from_splits_to_vecdb_records_applying_cache(
     splits: &mut Vec<SplitResult>,
     ready_to_vecdb: &mut Vec<VecdbRecord>,
+    run_actual_model_on_these: &mut Vec<SplitResult>,
     vecdb_cache_arc: Arc<AMutex<VecDBCache>>,
     group_size: usize,
 ) {
@@ -173,6 +195,7 @@ async fn from_splits_to_vecdb_records_applying_cache(
             // info!("query cache {} records {:.3}s", batch.len(), t0.elapsed().as_secs_f32());
             for (split, maybe_vector) in batch.iter().zip(vectors.iter()) {
                 if maybe_vector.is_none() {
+                    run_actual_model_on_these.push(split.clone());
                     continue;
                 }
                 ready_to_vecdb.push(VecdbRecord {
@@ -182,6 +205,8 @@ async fn from_splits_to_vecdb_records_applying_cache(
                     end_line: split.end_line,
                     distance: -1.0,
                     usefulness: 0.0,
+                    symbol_path: split.symbol_path.clone(),
+                    window_text: split.window_text.clone(),
                 });
             }
         } else if let Err(err) = vecdb_cache_arc.lock().await.fetch_vectors_from_cache(&batch).await {
@@ -207,6 +232,11 @@ async fn vectorize_thread(
     let mut reported_unprocessed: usize = 0;
     let mut run_actual_model_on_these: Vec<SplitResult> = vec![];
     let mut ready_to_vecdb: Vec<VecdbRecord> = vec![];
+    // files are up to date in the index only after all their records are there, remember the hashes until then
+    let mut pending_files_hashes: HashMap<String, String> = HashMap::new();
+    // old records of a changed file go away once, its new records might come in several batches
+    let mut files_to_clear: HashSet<String> = HashSet::new();
+    let mut files_unchanged: usize = 0;
     // let mut delayed_cached_splits_q: Vec<SplitResult> = vec![];
 
     loop {
@@ -246,9 +276,12 @@ async fn vectorize_thread(
                     &constants,
                     &api_key,
                     vecdb_cache_arc.clone(),
+                    load_privacy_if_needed(gcx.clone()).await,
                     constants.embedding_batch,
                 ).await {
                     tracing::error!("{}", err);
+                    // some records are lost, don't let those files look up to date on the next start
+                    pending_files_hashes.clear();
                     continue;
                 }
             } else {
@@ -289,7 +322,15 @@ async fn vectorize_thread(
                     continue;
                 }
                 None => {
-                    _send_to_vecdb(vecdb_handler_arc.clone(), &mut ready_to_vecdb).await;
+                    _send_to_vecdb(vecdb_handler_arc.clone(), &mut ready_to_vecdb, &mut files_to_clear).await;
+                    {
+                        let mut handler_locked = vecdb_handler_arc.lock().await;
+                        if !files_to_clear.is_empty() {
+                            // changed files that have no records now
+                            handler_locked.vecdb_records_remove(files_to_clear.drain().collect()).await;
+                        }
+                        handler_locked.set_files_hashes(std::mem::take(&mut pending_files_hashes)).await;
+                    }
                     let reported_vecdb_complete = {
                         let mut vstatus_locked = vstatus.lock().await;
                         let done = vstatus_locked.state == "done";
@@ -298,19 +339,19 @@ async fn vectorize_thread(
                             vstatus_locked.files_total = 0;
                             vstatus_locked.state = "done".to_string();
                             info!(
-                                "vectorizer since start {} API calls, {} vectors",
-                                vstatus_locked.requests_made_since_start, vstatus_locked.vectors_made_since_start
+                                "vectorizer since start {} API calls, {} vectors, {} files unchanged since the last run",
+                                vstatus_locked.requests_made_since_start, vstatus_locked.vectors_made_since_start, files_unchanged
                             );
                         }
                         done
                     };
                     if !reported_vecdb_complete {
-                        // For now, we do not create index because it hurts the quality of retrieval
-                        // info!("VECDB Creating index");
-                        // match vecdb_handler_arc.lock().await.create_index().await {
-                        //     Ok(_) => info!("VECDB CREATED INDEX"),
-                        //     Err(err) => info!("VECDB Error creating index: {}", err)
-                        // }
+                        let index_building = vstatus.lock().await.index_state == "building";
+                        if !index_building {
+                            // compaction moves rows around, an index built at the same time would be thrown away
+                            vecdb_handler_arc.lock().await.compact().await;
+                            ann_index_rebuild_in_background(vecdb_handler_arc.clone(), vstatus.clone(), vstatus_notify.clone()).await;
+                        }
                         let _ = write!(std::io::stderr(), "VECDB COMPLETE\n");
                         info!("VECDB COMPLETE"); // you can see stderr "VECDB COMPLETE" sometimes faster vs logs
                         vstatus_notify.notify_waiters();
@@ -325,6 +366,12 @@ async fn vectorize_thread(
         };
         let last_30_chars = crate::nicer_logs::last_n_chars(&doc.doc_path.display().to_string(), 30);
 
+        if let Err(reason) = check_indexing_target(gcx.clone(), &doc.doc_path, IndexingTarget::Vecdb).await {
+            info!("{}: {}", last_30_chars, reason);
+            files_to_clear.insert(doc.doc_path.to_string_lossy().to_string());  // indexed earlier with other settings
+            continue;
+        }
+
         // Not from memory, vecdb works on files from disk
         if let Err(err) = doc.update_text_from_disk(gcx.clone()).await {
             info!("{}: {}", last_30_chars, err);
@@ -336,6 +383,17 @@ async fn vectorize_thread(
             continue;
         }
 
+        // the splitter settings change the records as much as the text does
+        let scope = doc.doc_path.to_string_lossy().to_string();
+        let content_hash = official_text_hashing_function(&format!("{}:{}\n{}",
+            constants.splitter_window_size, constants.vectorizer_n_ctx, doc.doc_text.as_ref().unwrap()));
+        if vecdb_handler_arc.lock().await.is_file_up_to_date(&scope, &content_hash) {
+            files_unchanged += 1;
+            continue;
+        }
+        files_to_clear.insert(scope.clone());
+        pending_files_hashes.insert(scope, content_hash);
+
         let file_splitter = AstBasedFileSplitter::new(constants.splitter_window_size);
         let mut splits = file_splitter.vectorization_split(&doc, None, gcx.clone(), constants.vectorizer_n_ctx).await.unwrap_or_else(|err| {
             info!("{}", err);
@@ -358,23 +416,58 @@ async fn vectorize_thread(
         from_splits_to_vecdb_records_applying_cache(
             &mut splits,
             &mut ready_to_vecdb,
+            &mut run_actual_model_on_these,
             vecdb_cache_arc.clone(),
             1024,
         ).await;
 
         if ready_to_vecdb.len() > 100 {
-            _send_to_vecdb(vecdb_handler_arc.clone(), &mut ready_to_vecdb).await;
+            _send_to_vecdb(vecdb_handler_arc.clone(), &mut ready_to_vecdb, &mut files_to_clear).await;
         }
     }
 }
 
+async fn ann_index_rebuild_in_background(
+    vecdb_handler_arc: Arc<AMutex<VecDBHandler>>,
+    vstatus: Arc<AMutex<VecDbStatus>>,
+    vstatus_notify: Arc<ANotify>,
+) {
+    let (table, embedding_size) = {
+        let handler_locked = vecdb_handler_arc.lock().await;
+        if !handler_locked.ann_index_needs_rebuild().await {
+            return;
+        }
+        handler_locked.ann_index_build_params()
+    };
+    vstatus.lock().await.index_state = "building".to_string();
+    vstatus_notify.notify_waiters();
+    tokio::spawn(async move {
+        let t0 = std::time::Instant::now();
+        let index_state = match ann_index_build(table, embedding_size).await {
+            Ok(rows) => {
+                info!("VECDB index built over {} rows in {:.1}s", rows, t0.elapsed().as_secs_f64());
+                "ready"
+            }
+            Err(err) => {
+                tracing::error!("VECDB index build failed: {}", err);
+                "none"
+            }
+        };
+        vecdb_handler_arc.lock().await.reload_table().await;
+        vstatus.lock().await.index_state = index_state.to_string();
+        vstatus_notify.notify_waiters();
+    });
+}
+
 async fn _send_to_vecdb(
     vecdb_handler_arc: Arc<AMutex<VecDBHandler>>,
     ready_to_vecdb: &mut Vec<VecdbRecord>,
+    files_to_clear: &mut HashSet<String>,
 ) {
     while !ready_to_vecdb.is_empty() {
         let unique_file_paths: HashSet<String> = ready_to_vecdb.iter()
             .map(|x| x.file_path.to_str().unwrap_or("No filename").to_string())
+            .filter(|x| files_to_clear.remove(x))
             .collect();
         let unique_file_paths_vec: Vec<String> = unique_file_paths.into_iter().collect();
         vecdb_handler_arc.lock().await.vecdb_records_remove(unique_file_paths_vec).await;
@@ -407,6 +500,8 @@ impl FileVectorizerService {
                 state: "starting".to_string(),
                 queue_additions: true,
                 vecdb_max_files_hit: false,
+                index_state: "none".to_string(),
+                index_unindexed_rows: 0,
             }
         ));
         FileVectorizerService {
@@ -19,6 +19,7 @@ pub async fn yaml_configs_try_create_all(gcx: Arc<ARwLock<GlobalContext>>) -> St
         ("bring-your-own-key.yaml", crate::caps::BRING_YOUR_OWN_KEY_SAMPLE),
         ("customization.yaml", crate::yaml_configs::customization_compiled_in::COMPILED_IN_INITIAL_USER_YAML),
         ("privacy.yaml", crate::privacy_compiled_in::COMPILED_IN_INITIAL_PRIVACY_YAML),
+        ("indexing.yaml", crate::indexing_compiled_in::COMPILED_IN_INITIAL_INDEXING_YAML),
         ("integrations.yaml", crate::integrations::INTEGRATIONS_DEFAULT_YAML),
     ];
     for (file_name, content) in files {
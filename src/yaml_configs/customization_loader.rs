@@ -5,7 +5,7 @@ use std::sync::Arc;
 use indexmap::IndexMap;
 use tokio::sync::RwLock as ARwLock;
 
-use crate::call_validation::{ChatMessage, SubchatParameters};
+use crate::call_validation::{ChatContent, ChatMessage, SubchatParameters};
 use crate::global_context::{GlobalContext, try_load_caps_quickly_if_not_present};
 use crate::yaml_configs::customization_compiled_in::COMPILED_IN_CUSTOMIZATION_YAML;
 
@@ -76,18 +76,22 @@ fn _replace_variables_in_text(text: &mut String, variables: &HashMap<String, Str
 fn _replace_variables_in_messages(config: &mut CustomizationYaml, variables: &HashMap<String, String>) {
     for command in config.toolbox_commands.values_mut() {
         for msg in command.messages.iter_mut() {
+            let mut text = msg.content.content_text_only();
             let mut replaced = true;
             while replaced {
-                replaced = _replace_variables_in_text(&mut msg.content, variables);
+                replaced = _replace_variables_in_text(&mut text, variables);
             }
+            msg.content = ChatContent::SimpleText(text);
         }
     }
     for command in config.code_lens.values_mut() {
         for msg in command.messages.iter_mut() {
+            let mut text = msg.content.content_text_only();
             let mut replaced = true;
             while replaced {
-                replaced = _replace_variables_in_text(&mut msg.content, variables);
+                replaced = _replace_variables_in_text(&mut text, variables);
             }
+            msg.content = ChatContent::SimpleText(text);
         }
     }
 }